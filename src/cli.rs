@@ -0,0 +1,291 @@
+use std::path::{Path, PathBuf};
+
+use log::LevelFilter;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, PartialEq)]
+pub enum Transport {
+    Stdio,
+    // Listens for a single client on this address
+    Tcp(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Serve,
+    // no_ignore also takes the files .gitignore and .ignore files ignore
+    Format {
+        dir: Option<PathBuf>,
+        no_ignore: bool,
+    },
+    Check {
+        dir: Option<PathBuf>,
+        fix: bool,
+        no_ignore: bool,
+    },
+    Parse {
+        file: PathBuf,
+    },
+    ModApi {
+        path: Option<PathBuf>,
+    },
+    ModApiSchema,
+    Bench {
+        dir: PathBuf,
+        iterations: usize,
+    },
+    // The subcommand to show the help of, if any
+    Help(Option<String>),
+    Version,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    pub log_level: Option<LevelFilter>,
+    pub log_file: Option<PathBuf>,
+    pub transport: Transport,
+}
+
+// The name, its arguments and what it does
+const SUBCOMMANDS: &[(&str, &str, &str)] = &[
+    ("serve", "", "Starts the language server, the default"),
+    (
+        "format",
+        " [DIR] [--no-ignore]",
+        "Formats every .grug file under DIR in place, --no-ignore includes the ones .gitignore ignores",
+    ),
+    (
+        "check",
+        " [DIR] [--fix] [--no-ignore]",
+        "Prints the lint diagnostics of every .grug file under DIR, --fix writes safe fixes first",
+    ),
+    ("parse", " <FILE>", "Prints the parse tree of FILE as JSON"),
+    (
+        "mod-api",
+        " [PATH | schema]",
+        "Validates a mod_api.json and prints its documentation as markdown, or prints the JSON Schema of mod_api.json",
+    ),
+    (
+        "bench",
+        " <DIR> [--iterations <N>]",
+        "Parses, lints and formats every .grug file under DIR N times, 5 by default, and prints how fast it went",
+    ),
+    ("help", " [COMMAND]", "Prints the help of a command"),
+];
+
+const OPTIONS: &[(&str, &str)] = &[
+    (
+        "--log-level <LEVEL>",
+        "off, error, warn, info, debug or trace",
+    ),
+    ("--log-file <PATH>", "Where the server writes its logs"),
+    (
+        "--stdio",
+        "Talks to the client over stdin and stdout, the default",
+    ),
+    (
+        "--listen <ADDRESS>",
+        "Waits for the client on a TCP address instead",
+    ),
+    ("-h, --help", "Prints this help"),
+    ("-V, --version", "Prints the version"),
+];
+
+pub fn help(subcommand: Option<&str>) -> String {
+    let mut out = format!("grug-ls {}\n\n", VERSION);
+
+    match SUBCOMMANDS
+        .iter()
+        .find(|(name, ..)| Some(*name) == subcommand)
+    {
+        Some((name, arguments, description)) => {
+            out.push_str(&format!(
+                "Usage: grug-ls [OPTIONS] {}{}\n\n{}\n",
+                name, arguments, description
+            ));
+        }
+        None => {
+            out.push_str("Usage: grug-ls [OPTIONS] [COMMAND]\n\nCommands:\n");
+            for (name, arguments, description) in SUBCOMMANDS {
+                let usage = format!("{}{}", name, arguments);
+                out.push_str(&format!("    {:<22}{}\n", usage, description));
+            }
+        }
+    }
+
+    out.push_str("\nOptions:\n");
+    for (flag, description) in OPTIONS {
+        out.push_str(&format!("    {:<22}{}\n", flag, description));
+    }
+    out
+}
+
+impl Cli {
+    // Takes the arguments without the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+        let mut args = args.into_iter();
+
+        let mut log_level = None;
+        let mut log_file = None;
+        let mut transport = Transport::Stdio;
+        let mut subcommand: Option<String> = None;
+        let mut positional = Vec::new();
+        let mut fix = false;
+        let mut no_ignore = false;
+        let mut iterations = 5;
+        let mut help_requested = false;
+        let mut version_requested = false;
+
+        while let Some(arg) = args.next() {
+            let mut value =
+                |flag: &str| args.next().ok_or_else(|| format!("{} needs a value", flag));
+
+            match arg.as_str() {
+                "-h" | "--help" => help_requested = true,
+                "-V" | "--version" => version_requested = true,
+                "--log-level" => {
+                    let level = value(&arg)?;
+                    log_level = Some(
+                        level
+                            .parse()
+                            .map_err(|_| format!("Unknown log level: {}", level))?,
+                    );
+                }
+                "--log-file" => log_file = Some(PathBuf::from(value(&arg)?)),
+                "--stdio" => transport = Transport::Stdio,
+                "--listen" => transport = Transport::Tcp(value(&arg)?),
+                "--fix" if subcommand.as_deref() == Some("check") => fix = true,
+                "--no-ignore" if matches!(subcommand.as_deref(), Some("check" | "format")) => {
+                    no_ignore = true
+                }
+                "--iterations" if subcommand.as_deref() == Some("bench") => {
+                    let value = value(&arg)?;
+                    iterations = match value.parse() {
+                        Ok(iterations) if iterations > 0 => iterations,
+                        _ => return Err(format!("Invalid amount of iterations: {}", value)),
+                    };
+                }
+                _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+                _ if subcommand.is_none() => {
+                    if !SUBCOMMANDS.iter().any(|(name, ..)| *name == arg) {
+                        return Err(format!("Unknown command: {}", arg));
+                    }
+                    subcommand = Some(arg);
+                }
+                _ => positional.push(PathBuf::from(arg)),
+            }
+        }
+
+        let command = if help_requested {
+            Command::Help(subcommand)
+        } else if version_requested {
+            Command::Version
+        } else {
+            let max_positional = match subcommand.as_deref() {
+                None | Some("serve") => 0,
+                _ => 1,
+            };
+            if positional.len() > max_positional {
+                return Err(format!(
+                    "Unexpected argument: {}",
+                    positional[max_positional].display()
+                ));
+            }
+            let mut positional = positional.into_iter();
+
+            match subcommand.as_deref() {
+                None | Some("serve") => Command::Serve,
+                Some("format") => Command::Format {
+                    dir: positional.next(),
+                    no_ignore,
+                },
+                Some("check") => Command::Check {
+                    dir: positional.next(),
+                    fix,
+                    no_ignore,
+                },
+                Some("parse") => Command::Parse {
+                    file: positional.next().ok_or("parse needs a file")?,
+                },
+                Some("mod-api") => match positional.next() {
+                    Some(path) if path == Path::new("schema") => Command::ModApiSchema,
+                    path => Command::ModApi { path },
+                },
+                Some("bench") => Command::Bench {
+                    dir: positional.next().ok_or("bench needs a directory")?,
+                    iterations,
+                },
+                Some(_) => Command::Help(positional.next().map(|arg| arg.display().to_string())),
+            }
+        };
+
+        Ok(Cli {
+            command,
+            log_level,
+            log_file,
+            transport,
+        })
+    }
+}
+
+#[test]
+fn cli_test() {
+    let parse = |args: &[&str]| Cli::parse(args.iter().map(|arg| arg.to_string()));
+
+    let cli = parse(&[]).unwrap();
+    assert_eq!(cli.command, Command::Serve);
+    assert_eq!(cli.transport, Transport::Stdio);
+
+    let cli = parse(&["--log-level", "debug", "check", "mods", "--fix"]).unwrap();
+    assert_eq!(cli.log_level, Some(LevelFilter::Debug));
+    assert_eq!(
+        cli.command,
+        Command::Check {
+            dir: Some(PathBuf::from("mods")),
+            fix: true,
+            no_ignore: false
+        }
+    );
+    assert_eq!(
+        parse(&["format", "--no-ignore"]).unwrap().command,
+        Command::Format {
+            dir: None,
+            no_ignore: true
+        }
+    );
+    assert!(parse(&["parse", "a.grug", "--no-ignore"]).is_err());
+
+    let cli = parse(&["serve", "--listen", "127.0.0.1:9257"]).unwrap();
+    assert_eq!(cli.transport, Transport::Tcp("127.0.0.1:9257".to_string()));
+
+    assert_eq!(
+        parse(&["help", "parse"]).unwrap().command,
+        Command::Help(Some("parse".to_string()))
+    );
+    assert_eq!(
+        parse(&["format", "--help"]).unwrap().command,
+        Command::Help(Some("format".to_string()))
+    );
+
+    assert_eq!(
+        parse(&["bench", "mods", "--iterations", "3"])
+            .unwrap()
+            .command,
+        Command::Bench {
+            dir: PathBuf::from("mods"),
+            iterations: 3
+        }
+    );
+
+    assert_eq!(
+        parse(&["mod-api", "schema"]).unwrap().command,
+        Command::ModApiSchema
+    );
+
+    assert!(parse(&["parse"]).is_err());
+    assert!(parse(&["bench", "mods", "--iterations", "0"]).is_err());
+    assert!(parse(&["format", "--fix"]).is_err());
+    assert!(parse(&["lint"]).is_err());
+}
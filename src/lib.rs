@@ -1 +1,5 @@
+pub mod cli;
+#[cfg(feature = "native")]
+pub mod logging;
 pub mod server;
+pub mod test_support;
@@ -0,0 +1,324 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use log::{
+    Level, LevelFilter,
+    kv::{Key, Value},
+};
+use structured_logger::{Writer, json::JSONWriter};
+
+use crate::server::spans::with_current_span;
+
+// The limits only arrive with the client's settings, after the logger already started
+static MAX_SIZE: AtomicU64 = AtomicU64::new(10 * 1024 * 1024);
+static MAX_FILES: AtomicUsize = AtomicUsize::new(3);
+
+pub fn set_limits(max_size: u64, max_files: usize) {
+    MAX_SIZE.store(max_size, Ordering::Relaxed);
+    MAX_FILES.store(max_files, Ordering::Relaxed);
+}
+
+// grug-ls-logs.json.1 is the newest rotated file
+fn rotated_path(path: &Path, idx: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", idx));
+    PathBuf::from(name)
+}
+
+// A log file that moves itself aside once it reaches the size limit
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    // Starts from an empty file, the logs of older sessions aren't kept
+    pub fn create(path: PathBuf) -> std::io::Result<RotatingFile> {
+        let file = File::create(&path)?;
+
+        Ok(RotatingFile {
+            path,
+            file,
+            size: 0,
+        })
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let max_files = MAX_FILES.load(Ordering::Relaxed);
+
+        if max_files > 0 {
+            let _ = std::fs::remove_file(rotated_path(&self.path, max_files));
+            for idx in (1..max_files).rev() {
+                let _ = std::fs::rename(
+                    rotated_path(&self.path, idx),
+                    rotated_path(&self.path, idx + 1),
+                );
+            }
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > MAX_SIZE.load(Ordering::Relaxed) {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+type ClientSink = Box<dyn Fn(Level, String) + Send>;
+
+static CLIENT_SINK: Mutex<Option<ClientSink>> = Mutex::new(None);
+
+// Warnings and errors also go to the client once it's connected, so they show up in the editor
+pub fn forward_to_client(sink: impl Fn(Level, String) + Send + 'static) {
+    *CLIENT_SINK.lock().unwrap() = Some(Box::new(sink));
+}
+
+// The client's sender has to be dropped for the connection to close
+pub fn stop_forwarding() {
+    if let Ok(mut sink) = CLIENT_SINK.lock() {
+        *sink = None;
+    }
+    drop(std::panic::take_hook());
+}
+
+// Info and warning messages past this many characters get cut, whole payloads are logged at debug
+const MAX_MESSAGE_LEN: usize = 4096;
+
+fn truncate(message: &str, max_len: usize) -> Cow<'_, str> {
+    match message.char_indices().nth(max_len) {
+        Some((idx, _)) => Cow::Owned(format!(
+            "{}... ({} more bytes)",
+            &message[..idx],
+            message.len() - idx
+        )),
+        None => Cow::Borrowed(message),
+    }
+}
+
+// GRUG_LS_LOG, like `warn,grug_ls::server::completion=trace`. A bare level is the default,
+// otherwise the longest target that matches decides
+#[derive(Debug, PartialEq)]
+pub struct EnvFilter {
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl EnvFilter {
+    pub fn parse(spec: &str) -> EnvFilter {
+        let mut filter = EnvFilter {
+            default: LevelFilter::Info,
+            targets: Vec::new(),
+        };
+
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    if let Ok(level) = level.trim().parse() {
+                        filter.targets.push((target.trim().to_string(), level));
+                    }
+                }
+                None => match directive.parse() {
+                    Ok(level) => filter.default = level,
+                    // A target on its own gets everything
+                    Err(_) => filter
+                        .targets
+                        .push((directive.to_string(), LevelFilter::Trace)),
+                },
+            }
+        }
+
+        filter
+    }
+
+    pub fn from_env() -> Option<EnvFilter> {
+        std::env::var("GRUG_LS_LOG")
+            .ok()
+            .map(|spec| EnvFilter::parse(&spec))
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    pub fn enabled(&self, target: &str, level: Level) -> bool {
+        level <= self.level_for(target)
+    }
+
+    // The logger has to let through everything some target wants
+    pub fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+pub struct LogWriter {
+    file: JSONWriter<RotatingFile>,
+    filter: Option<EnvFilter>,
+}
+
+impl LogWriter {
+    pub fn new(file: RotatingFile, filter: Option<EnvFilter>) -> LogWriter {
+        LogWriter {
+            file: JSONWriter::new(file),
+            filter,
+        }
+    }
+}
+
+impl Writer for LogWriter {
+    fn write_log(&self, value: &BTreeMap<Key, Value>) -> Result<(), std::io::Error> {
+        let level = value
+            .get(&Key::from("level"))
+            .and_then(|level| level.to_string().parse::<Level>().ok());
+
+        if let (Some(filter), Some(level), Some(target)) =
+            (&self.filter, level, value.get(&Key::from("target")))
+            && !filter.enabled(&target.to_string(), level)
+        {
+            return Ok(());
+        }
+
+        if let Some(level) = level
+            && level <= Level::Warn
+            && let Some(message) = value.get(&Key::from("message"))
+            // A panic while forwarding poisons the lock, the file still gets the log then
+            && let Ok(sink) = CLIENT_SINK.lock()
+            && let Some(sink) = sink.as_ref()
+        {
+            sink(level, message.to_string());
+        }
+
+        // Errors keep their backtraces
+        let truncated = match level {
+            Some(Level::Warn | Level::Info) => value
+                .get(&Key::from("message"))
+                .map(|message| message.to_string())
+                .and_then(|message| match truncate(&message, MAX_MESSAGE_LEN) {
+                    Cow::Owned(truncated) => Some(truncated),
+                    Cow::Borrowed(_) => None,
+                }),
+            _ => None,
+        };
+
+        with_current_span(|span| {
+            if truncated.is_none() && span.is_none() {
+                return self.file.write_log(value);
+            }
+
+            let mut value = value.clone();
+            if let Some(truncated) = &truncated {
+                value.insert(Key::from("message"), Value::from(truncated.as_str()));
+            }
+            if let Some(span) = span {
+                value.insert(Key::from("method"), Value::from(span.method.as_str()));
+                if let Some(request_id) = &span.request_id {
+                    value.insert(Key::from("requestId"), Value::from(request_id.as_str()));
+                }
+                if let Some(document) = &span.document {
+                    value.insert(Key::from("document"), Value::from(document.as_str()));
+                }
+            }
+            self.file.write_log(&value)
+        })
+    }
+}
+
+// Replaces the hook structured-logger installs, which leaves out the backtrace
+pub fn install_panic_hook(log_path: PathBuf, notify_user: impl Fn(String) + Send + Sync + 'static) {
+    std::panic::set_hook(Box::new(move |info| {
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        log::error!("{}\n{}", info, backtrace);
+
+        notify_user(format!(
+            "grug-ls crashed, the log with the details is at {}",
+            log_path.display()
+        ));
+        // The message is sent from another thread, which needs a moment before the process dies
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }));
+}
+
+#[test]
+fn truncate_test() {
+    assert_eq!(truncate("short", 10), "short");
+    assert_eq!(truncate("ééééé", 2), "éé... (6 more bytes)");
+}
+
+#[test]
+fn env_filter_test() {
+    let filter = EnvFilter::parse("warn, grug_ls::server=debug,grug_ls::server::completion=trace");
+    assert!(!filter.enabled("grug_ls::logging", Level::Info));
+    assert!(filter.enabled("grug_ls::server::hover", Level::Debug));
+    assert!(!filter.enabled("grug_ls::server::hover", Level::Trace));
+    assert!(filter.enabled("grug_ls::server::completion", Level::Trace));
+    assert!(!filter.enabled("grug_ls::serverless", Level::Debug));
+    assert_eq!(filter.max_level(), LevelFilter::Trace);
+
+    let filter = EnvFilter::parse("grug_ls::server::helper");
+    assert!(filter.enabled("grug_ls::server::helper", Level::Trace));
+    assert!(filter.enabled("grug_ls::server::hover", Level::Info));
+    assert!(!filter.enabled("grug_ls::server::hover", Level::Debug));
+}
+
+#[test]
+fn rotating_file_test() {
+    let dir = std::env::temp_dir().join(format!("grug-ls-rotation-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("logs.json");
+
+    set_limits(10, 2);
+    let mut file = RotatingFile::create(path.clone()).unwrap();
+    for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+        file.write_all(line.as_bytes()).unwrap();
+    }
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "fourth\n");
+    assert_eq!(
+        std::fs::read_to_string(rotated_path(&path, 1)).unwrap(),
+        "third\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(rotated_path(&path, 2)).unwrap(),
+        "second\n"
+    );
+    assert!(!rotated_path(&path, 3).exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
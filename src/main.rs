@@ -1,62 +1,147 @@
+use grug_ls::cli::{self, Cli, Command, Transport};
+use grug_ls::logging::{self, EnvFilter, LogWriter, RotatingFile};
 use grug_ls::server::Server;
 
 use log::error;
 use log::info;
-use lsp_server::{Connection, ErrorCode, Response};
-use lsp_types::HoverProviderCapability;
-use lsp_types::InitializeParams;
-use lsp_types::OneOf;
-use lsp_types::ServerCapabilities;
-use lsp_types::TextDocumentSyncCapability;
-use lsp_types::TextDocumentSyncKind;
-use lsp_types::{CompletionOptions, InitializeResult, ServerInfo};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Response};
+use lsp_types::notification::{LogMessage, Notification as _, ShowMessage};
+use lsp_types::{InitializeParams, LogMessageParams, MessageType, ShowMessageParams};
+use lsp_types::{InitializeResult, ServerInfo};
 use structured_logger::Builder;
-use structured_logger::json::new_writer;
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    if args.contains(&"--version".to_string()) {
-        println!("1.0.0");
-        return;
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::help(None));
+            std::process::exit(2);
+        }
+    };
+
+    let current_dir = || std::env::current_dir().unwrap();
+    match cli.command {
+        Command::Serve => {}
+        Command::Help(subcommand) => {
+            print!("{}", cli::help(subcommand.as_deref()));
+            return;
+        }
+        Command::Version => {
+            println!("{}", cli::VERSION);
+            return;
+        }
+        // These run without starting the server
+        Command::Format { dir, no_ignore } => {
+            let dir = dir.unwrap_or_else(current_dir);
+            let success = grug_ls::server::formatting::format_files(&dir, !no_ignore);
+            std::process::exit(if success { 0 } else { 1 });
+        }
+        Command::Check {
+            dir,
+            fix,
+            no_ignore,
+        } => {
+            let dir = dir.unwrap_or_else(current_dir);
+            let success = grug_ls::server::check::check_files(&dir, fix, !no_ignore);
+            std::process::exit(if success { 0 } else { 1 });
+        }
+        Command::Parse { file } => {
+            let success = grug_ls::server::syntax_tree::print_syntax_tree(&file);
+            std::process::exit(if success { 0 } else { 1 });
+        }
+        Command::Bench { dir, iterations } => {
+            let success = grug_ls::server::bench::bench_files(&dir, iterations);
+            std::process::exit(if success { 0 } else { 1 });
+        }
+        Command::ModApi { path } => {
+            let path = path.unwrap_or_else(|| current_dir().join("mod_api.json"));
+            let success = grug_ls::server::check::check_mod_api(&path);
+            std::process::exit(if success { 0 } else { 1 });
+        }
+        Command::ModApiSchema => {
+            let schema = grug_ls::server::mod_api::schema::mod_api_schema();
+            println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+            return;
+        }
     }
 
-    let log_file_path = std::env::temp_dir().join("grug-ls-logs.json");
+    let log_file_path = cli
+        .log_file
+        .clone()
+        .unwrap_or_else(|| std::env::temp_dir().join("grug-ls-logs.json"));
 
-    let file_writer = std::fs::File::options()
-        .create(true)
-        .append(true)
-        .open(log_file_path)
-        .unwrap();
+    let file_writer = RotatingFile::create(log_file_path.clone()).unwrap();
 
-    file_writer.set_len(0).unwrap();
+    // GRUG_LS_LOG wins over both the command line and the client's settings
+    let env_filter = EnvFilter::from_env();
+    let log_level = env_filter
+        .as_ref()
+        .map(EnvFilter::max_level)
+        .or(cli.log_level);
 
-    Builder::with_level("INFO")
-        .with_target_writer("*", new_writer(file_writer))
+    // The level gets lowered once the client's settings arrive
+    Builder::with_level("TRACE")
+        .with_target_writer("*", Box::new(LogWriter::new(file_writer, env_filter)))
         .init();
-
-    let (mut connection, io_threads) = Connection::stdio();
-
-    let server_capabilities = ServerCapabilities {
-        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
-        hover_provider: Some(HoverProviderCapability::Simple(true)),
-        completion_provider: Some(CompletionOptions::default()),
-        definition_provider: Some(lsp_types::OneOf::Left(true)),
-        rename_provider: Some(lsp_types::OneOf::Right(lsp_types::RenameOptions {
-            prepare_provider: Some(false),
-            work_done_progress_options: lsp_types::WorkDoneProgressOptions {
-                work_done_progress: None,
-            },
-        })),
-        document_formatting_provider: Some(OneOf::Left(true)),
-
-        ..Default::default()
+    log::set_max_level(log_level.unwrap_or(log::LevelFilter::Info));
+
+    let (connection, io_threads) = match &cli.transport {
+        Transport::Stdio => Connection::stdio(),
+        Transport::Tcp(address) => match Connection::listen(address) {
+            Ok(connection) => connection,
+            Err(err) => {
+                error!("Couldn't listen on {}: {}", address, err);
+                eprintln!("Couldn't listen on {}: {}", address, err);
+                std::process::exit(1);
+            }
+        },
     };
 
+    let sender = connection.sender.clone();
+    logging::forward_to_client(move |level, message| {
+        let typ = if level == log::Level::Error {
+            MessageType::ERROR
+        } else {
+            MessageType::WARNING
+        };
+        let params = LogMessageParams { typ, message };
+        let notification = Notification::new(LogMessage::METHOD.to_string(), params);
+        let _ = sender.send(Message::Notification(notification));
+    });
+
+    let sender = connection.sender.clone();
+    logging::install_panic_hook(log_file_path, move |message| {
+        let params = ShowMessageParams {
+            typ: MessageType::ERROR,
+            message,
+        };
+        let notification = Notification::new(ShowMessage::METHOD.to_string(), params);
+        let _ = sender.send(Message::Notification(notification));
+    });
+
     let (mut server, id) = match connection.initialize_start() {
         Ok((req_id, value)) => {
-            let params: InitializeParams = serde_json::from_value(value).unwrap();
+            let params: InitializeParams = match serde_json::from_value(value) {
+                Ok(params) => params,
+                Err(err) => {
+                    // There is nothing to serve without knowing the client
+                    error!("Malformed initialize params: {}", err);
+                    let res = Response::new_err(
+                        req_id,
+                        ErrorCode::InvalidParams as i32,
+                        format!("Invalid params for initialize: {}", err),
+                    );
+                    let _ = connection.sender.send(Message::Response(res));
+
+                    std::process::exit(1);
+                }
+            };
 
-            let server = Server::from_request(params);
+            let server = Server::from_request(params, connection.sender.clone());
+            // The command line wins over the client's settings
+            if let Some(level) = log_level {
+                log::set_max_level(level);
+            }
             match server {
                 Ok(server) => (server, req_id),
                 Err(err) => {
@@ -68,53 +153,75 @@ fn main() {
                     let res = serde_json::to_value(res).unwrap();
                     connection.initialize_finish(req_id, res).unwrap();
 
-                    panic!();
+                    std::process::exit(1);
                 }
             }
         }
         Err(err) => {
             error!("Init Start err: {}", err);
-            panic!()
+            std::process::exit(1);
         }
     };
 
     let init_data = InitializeResult {
-        capabilities: server_capabilities,
+        capabilities: server.get_capabilities(),
         server_info: Some(ServerInfo {
             name: "Grug-LS".to_string(),
-            version: Some("1.0.0".to_string()),
+            version: Some(cli::VERSION.to_string()),
         }),
-        ..Default::default()
     };
     let init_data = serde_json::to_value(init_data).unwrap();
 
     connection.initialize_finish(id, init_data).unwrap();
+    server.report_startup_problems();
 
     let mut parser = tree_sitter::Parser::new();
     parser
         .set_language(&tree_sitter_grug::LANGUAGE.into())
         .unwrap();
 
+    server.index_workspace();
+
     info!("LSP START");
-    info!("Got these arguments: {:?}", args);
+    info!("Got these arguments: {:?}", cli);
 
+    let receiver = connection.receiver.clone();
+    let analysis_results = server.analysis_results();
     loop {
-        let message: lsp_server::Message = connection.receiver.recv().unwrap();
-
-        if let lsp_server::Message::Request(ref req) = message {
-            if connection.handle_shutdown(req).unwrap_or(true) {
-                break;
+        // Wakes up once edits have settled down, to check the edited files
+        let timeout = match server.next_deadline() {
+            Some(deadline) => crossbeam_channel::at(deadline),
+            None => crossbeam_channel::never(),
+        };
+
+        crossbeam_channel::select! {
+            recv(receiver) -> message => {
+                // The client went away without saying goodbye
+                let Ok(message) = message else {
+                    break;
+                };
+                server.handle_message(message, &mut parser);
+            }
+            recv(analysis_results) -> result => {
+                if let Ok(result) = result {
+                    server.handle_analysis_result(result);
+                }
             }
+            recv(timeout) -> _ => server.analyze_due_documents(),
         }
 
-        server.handle_message(message, &mut connection, &mut parser);
-
         if server.should_exit {
             break;
         }
     }
     info!("Exiting lsp");
 
+    // The writer thread only stops once every sender is gone, the server has one too
+    let exit_code = server.exit_code();
+    drop(server);
+    logging::stop_forwarding();
     drop(connection);
     io_threads.join().unwrap();
+
+    std::process::exit(exit_code);
 }
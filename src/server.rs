@@ -1,26 +1,76 @@
-use lsp_server::{Connection, Message};
+use lsp_server::{ErrorCode, Message, RequestId, Response, ResponseError};
+use lsp_types::ClientCapabilities;
 use lsp_types::Uri;
-use lsp_types::{
-    ClientCapabilities, CompletionParams, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
-    GotoDefinitionParams, HoverParams,
-};
+use serde_json::Value;
 use tree_sitter::Parser;
-use vfs::{FileSystem, MemoryFS};
 
-use crate::server::{document::Document, helper::ServerUpdate, mod_api::ModApi};
-use std::{collections::HashMap, path::PathBuf, sync::mpsc::Receiver};
+#[cfg(not(feature = "native"))]
+use crate::server::helper::AnalysisWorker;
+use crate::server::{
+    document::Document,
+    helper::{AnalysisJob, AnalysisResult, ServerUpdate},
+    mod_api::{ModApi, ModApiError},
+    overlay_fs::OverlayFS,
+    settings::{Feature, Settings},
+    spans::Span,
+    stats::MessageStats,
+    utils::{Client, RequestResult, parse_params, request_error, send_message, uri_to_path},
+};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    str::FromStr,
+    sync::{Mutex, mpsc::Receiver},
+    time::Instant,
+};
 
+mod apply_edit;
+#[cfg(feature = "native")]
+pub mod bench;
+#[cfg(feature = "native")]
+pub mod check;
+mod client_capabilities;
+mod code_actions;
+mod code_lens;
+mod commands;
 mod completion;
+mod config_file;
+mod const_eval;
+mod diagnostics;
 mod document;
+mod document_links;
+mod entity_rename;
+mod file_rename;
+pub mod format_text;
+pub mod formatting;
+mod fuzzy;
 mod goto_definition;
 mod helper;
 mod hover;
+mod ignore;
+mod implementation;
 pub mod init;
-mod mod_api;
+mod inlay_hints;
+mod large_files;
+pub mod lint;
+pub mod mod_api;
+mod overlay_fs;
+pub mod ping;
+mod progress;
+mod references;
+mod rename;
+mod semantic_tokens;
+pub mod settings;
+mod signature_help;
+pub mod spans;
+pub mod stats;
+mod symbols;
+pub mod syntax_tree;
 mod text_sync;
+mod timeouts;
+mod type_check;
 mod utils;
-mod rename;
-mod formatting;
+mod workspace;
 
 use log::error;
 use log::info;
@@ -33,95 +83,410 @@ pub enum ServerFileElement {
 #[derive(Debug)]
 pub struct Server {
     pub should_exit: bool,
-    root_path: PathBuf,
+    // Exiting without a shutdown request first means something went wrong on the client's side
+    shutdown_requested: bool,
+    // None when the editor didn't open a workspace
+    root_path: Option<PathBuf>,
+    settings: Settings,
+    // What the editor sent, merged over .grug-ls.toml files deeper in the workspace
+    initialization_options: Option<Value>,
+    // The parsed .grug-ls.toml of each directory settings were looked up in, None where there is
+    // none. The watcher drops the ones that change. Behind a mutex since settings_for takes &self
+    config_files: Mutex<HashMap<PathBuf, Option<Value>>>,
     client_capabilities: ClientCapabilities,
     mod_api: ModApi,
-    file_system: MemoryFS,
+    // The editor's copy of mod_api.json while it's open
+    mod_api_buffer: Option<String>,
+    file_system: OverlayFS,
     document_map: HashMap<String, Document>,
+    // Closed documents dropped to stay under the cache size, read from disk again when needed
+    evicted_documents: HashSet<String>,
+    // Resident documents from the first loaded to the last, the front gets evicted first
+    load_order: VecDeque<String>,
+    open_documents: HashSet<String>,
     messages_chan: Receiver<ServerUpdate>,
+    // Used to give requests sent to the client unique ids
+    outgoing_requests: u32,
+    // workspace/applyEdit requests the client hasn't answered yet, with their labels
+    pending_edits: HashMap<RequestId, String>,
+    // Edited text that hasn't been parsed yet, keyed by path
+    unparsed_changes: HashMap<String, String>,
+    // How many times each open document was edited
+    generations: HashMap<String, u64>,
+    // The version the editor gave each open document, including mod_api.json
+    document_versions: HashMap<String, i32>,
+    analysis_jobs: crossbeam_channel::Sender<AnalysisJob>,
+    analysis_results: crossbeam_channel::Receiver<AnalysisResult>,
+    // Runs on the host's thread when there are no threads, see run_analysis_jobs
+    #[cfg(not(feature = "native"))]
+    analysis_worker: AnalysisWorker,
+    // When the request being answered should be done by, see past_deadline
+    request_deadline: Option<Instant>,
+    // When each edited document gets its diagnostics, pushed back by every edit
+    diagnostics_due: HashMap<String, Instant>,
+    // Why mod_api.json couldn't be loaded at startup, told to the user after initialize
+    mod_api_error: Option<ModApiError>,
+    // Why the workspace isn't watched, told to the user after initialize like mod_api_error
+    watcher_error: Option<String>,
+    // How many of each message came in and how long they took, for grug-ls/stats
+    message_stats: MessageStats,
+    client: Client,
+    // For grug-ls/ping
+    started: Instant,
+    // Whether the last attempt at loading mod_api.json worked
+    mod_api_loaded: bool,
+    watcher_stopped: bool,
 }
 
 impl Server {
-    pub fn get_document_by_uri<'a>(&'a self, uri: &Uri) -> Option<&'a Document> {
-        let uri = uri.as_str();
-        // We probably wont need to use this server on TCP
-        assert!(uri.starts_with("file://"));
+    pub fn exit_code(&self) -> i32 {
+        if self.shutdown_requested { 0 } else { 1 }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn mod_api_path(&self) -> Option<PathBuf> {
+        Some(self.root_path.as_ref()?.join(&self.settings.mod_api_path))
+    }
 
-        let path = &uri["file.//".len()..];
+    pub fn mod_api_uri(&self) -> Option<Uri> {
+        let path = self.mod_api_path()?;
+        Uri::from_str(&format!("file://{}", path.to_str()?)).ok()
+    }
 
-        if !self.file_system.exists(path).unwrap_or(false) {
+    pub fn get_document_by_uri<'a>(&'a self, uri: &Uri) -> Option<&'a Document> {
+        let path = uri_to_path(uri.as_str())?;
+
+        if !self.file_system.exists(path) {
             return None;
         }
 
         self.document_map.get(path)
+    }
 
+    pub fn handle_message(&mut self, message: Message, parser: &mut Parser) {
+        match message {
+            Message::Request(req) => {
+                let _span = Span::new(&req.method, Some(&req.id), &req.params).enter();
+                let response = match self.handle_request(&req.method, req.params, parser) {
+                    Ok(value) => Response::new_ok(req.id, value),
+                    Err(err) => Response {
+                        id: req.id,
+                        result: None,
+                        error: Some(err),
+                    },
+                };
+                send_message(&self.client, Message::Response(response));
+            }
+            Message::Notification(notif) => {
+                let _span = Span::new(&notif.method, None, &notif.params).enter();
+                self.handle_notification(&notif.method, notif.params, parser);
+            }
+            Message::Response(response) => self.handle_response(response),
+        }
     }
 
-    pub fn handle_message(
+    // Everything a message needs before it gets handled. Returns false when it shouldn't be
+    fn prepare_message(&mut self, method: &str, params: &Value, parser: &mut Parser) -> bool {
+        self.handle_worker_messages(parser);
+
+        // Requests need the latest text, the diagnostics can wait for the edits to settle down
+        if !matches!(method, "textDocument/didOpen" | "textDocument/didChange") {
+            let uri = params
+                .pointer("/textDocument/uri")
+                .and_then(|uri| uri.as_str())
+                .and_then(uri_to_path)
+                // Requests on mod_api.json can reach into every document
+                .filter(|path| !self.is_mod_api_path(path));
+            self.parse_pending_changes(parser, uri);
+        }
+
+        // After shutdown only exit is allowed
+        !self.shutdown_requested || method == "exit"
+    }
+
+    // Answers a request, without needing a connection to the client
+    pub fn handle_request(
         &mut self,
-        message: Message,
-        connection: &mut Connection,
+        method: &str,
+        params: Value,
         parser: &mut Parser,
-    ) {
-        self.handle_worker_messages();
+    ) -> RequestResult {
+        let started = Instant::now();
+        if !self.prepare_message(method, &params, parser) {
+            return Err(request_error(
+                ErrorCode::InvalidRequest,
+                "The server is shutting down".to_string(),
+            ));
+        }
 
-        let (id, method, params) = match message {
-            Message::Request(req) => (Some(req.id), req.method, req.params),
-            Message::Notification(notif) => (None, notif.method, notif.params),
-            _ => {
-                return;
-            }
+        let feature = match method {
+            "textDocument/hover" => Some(Feature::Hover),
+            "textDocument/completion" => Some(Feature::Completion),
+            "textDocument/definition" => Some(Feature::Definition),
+            "textDocument/implementation" => Some(Feature::Implementation),
+            "textDocument/rename" => Some(Feature::Rename),
+            "textDocument/formatting" => Some(Feature::Formatting),
+            "textDocument/references" => Some(Feature::References),
+            "textDocument/documentHighlight" => Some(Feature::DocumentHighlight),
+            "textDocument/semanticTokens/full" => Some(Feature::SemanticTokens),
+            "textDocument/codeAction" => Some(Feature::CodeActions),
+            "textDocument/signatureHelp" => Some(Feature::SignatureHelp),
+            "textDocument/inlayHint" | "inlayHint/resolve" => Some(Feature::InlayHints),
+            "textDocument/documentLink" | "documentLink/resolve" => Some(Feature::DocumentLinks),
+            "textDocument/codeLens" | "codeLens/resolve" => Some(Feature::CodeLens),
+            "workspace/symbol" => Some(Feature::WorkspaceSymbols),
+            _ => None,
         };
+        if let Some(feature) = feature
+            && !self.settings.is_enabled(feature)
+        {
+            info!("Ignoring {}, {:?} is disabled", method, feature);
+            return Ok(Value::Null);
+        }
 
-        match method.as_str() {
-            "textDocument/didOpen" => {
-                let did_open_notification: DidOpenTextDocumentParams =
-                    serde_json::from_value(params).unwrap();
+        self.start_deadline(started);
+        let result = self.dispatch_request(method, &params, parser);
+        self.end_deadline(method, started);
+        self.message_stats.record(method, started.elapsed());
 
-                self.handle_did_open(did_open_notification, parser);
-            }
-            "textDocument/didChange" => {
-                let did_change_notification: DidChangeTextDocumentParams =
-                    serde_json::from_value(params).unwrap();
+        result
+    }
 
-                self.handle_did_change(did_change_notification, parser);
-            }
-            "textDocument/didSave" => {
-                info!("Saved file");
-            }
+    fn dispatch_request(
+        &mut self,
+        method: &str,
+        params: &Value,
+        parser: &mut Parser,
+    ) -> RequestResult {
+        match method {
             "shutdown" => {
                 info!("Shutting down");
-                self.should_exit = true;
-            }
-            "textDocument/hover" => {
-                let req: HoverParams = serde_json::from_value(params).unwrap();
+                self.shutdown_requested = true;
 
-                self.handle_hover(req, connection, id.unwrap());
+                Ok(Value::Null)
             }
-            "textDocument/completion" => {
-                let req: CompletionParams = serde_json::from_value(params).unwrap();
-
-                self.handle_completion(req, connection, id.unwrap());
+            "textDocument/hover" => self.handle_hover(parse_params(method, params)?),
+            "textDocument/completion" => self.handle_completion(parse_params(method, params)?),
+            "textDocument/definition" => self.handle_goto_definition(parse_params(method, params)?),
+            "textDocument/implementation" => {
+                self.handle_implementation(parse_params(method, params)?)
             }
-            "textDocument/definition" => {
-                let params: GotoDefinitionParams = serde_json::from_value(params).unwrap();
-
-                self.handle_goto_definition(params, connection, id.unwrap());
+            "textDocument/rename" => self.rename(parse_params(method, params)?),
+            "textDocument/formatting" => self.formatting(parse_params(method, params)?),
+            "textDocument/references" => self.handle_references(parse_params(method, params)?),
+            "textDocument/documentHighlight" => {
+                self.handle_document_highlight(parse_params(method, params)?)
             }
-            "textDocument/rename" => {
-                let params: lsp_types::RenameParams = serde_json::from_value(params).unwrap();
-
-                self.rename(params, connection, id.unwrap());
+            "textDocument/semanticTokens/full" => {
+                self.handle_semantic_tokens(parse_params(method, params)?)
+            }
+            "textDocument/codeAction" => self.handle_code_action(parse_params(method, params)?),
+            "textDocument/signatureHelp" => {
+                self.handle_signature_help(parse_params(method, params)?)
+            }
+            "textDocument/inlayHint" => self.handle_inlay_hint(parse_params(method, params)?),
+            "inlayHint/resolve" => self.handle_inlay_hint_resolve(parse_params(method, params)?),
+            "textDocument/documentLink" => self.handle_document_link(parse_params(method, params)?),
+            "textDocument/codeLens" => self.handle_code_lens(parse_params(method, params)?),
+            "codeLens/resolve" => self.handle_code_lens_resolve(parse_params(method, params)?),
+            "documentLink/resolve" => {
+                self.handle_document_link_resolve(parse_params(method, params)?)
+            }
+            "workspace/symbol" => self.handle_workspace_symbol(parse_params(method, params)?),
+            "workspace/willRenameFiles" => {
+                self.handle_will_rename_files(parse_params(method, params)?)
+            }
+            "workspace/executeCommand" => {
+                self.handle_execute_command(parse_params(method, params)?, parser)
             }
-            "textDocument/formatting" => {
-                let params: lsp_types::DocumentFormattingParams = serde_json::from_value(params).unwrap();
+            "grug-ls/syntaxTree" => self.handle_syntax_tree(parse_params(method, params)?),
+            "grug-ls/formatText" => self.handle_format_text(parse_params(method, params)?, parser),
+            "grug-ls/modApi" => self.handle_mod_api_request(),
+            "grug-ls/modApiSchema" => self.handle_mod_api_schema_request(),
+            "grug-ls/stats" => self.handle_stats(),
+            "grug-ls/ping" => self.handle_ping(),
+            _ => {
+                error!("Unknown request method: {}", method);
+                Err(request_error(
+                    ErrorCode::MethodNotFound,
+                    format!("Unknown method: {}", method),
+                ))
+            }
+        }
+    }
+
+    pub fn handle_notification(&mut self, method: &str, params: Value, parser: &mut Parser) {
+        let started = Instant::now();
+        if !self.prepare_message(method, &params, parser) {
+            return;
+        }
 
-                self.formatting(params, connection, id.unwrap());
+        // There is nobody to answer, so a malformed notification only gets logged
+        if let Err(err) = self.dispatch_notification(method, &params, parser) {
+            error!("{}", err.message);
+        }
+        self.message_stats.record(method, started.elapsed());
+    }
+
+    fn dispatch_notification(
+        &mut self,
+        method: &str,
+        params: &Value,
+        parser: &mut Parser,
+    ) -> Result<(), ResponseError> {
+        match method {
+            "textDocument/didOpen" => self.handle_did_open(parse_params(method, params)?),
+            "textDocument/didChange" => self.handle_did_change(parse_params(method, params)?),
+            "textDocument/didClose" => self.handle_did_close(parse_params(method, params)?, parser),
+            "textDocument/didSave" => {
+                info!("Saved file");
+            }
+            "workspace/didRenameFiles" => {
+                self.handle_did_rename_files(parse_params(method, params)?, parser)
             }
             "exit" => {
                 self.should_exit = true;
             }
+            // Optional notifications like $/cancelRequest and $/setTrace can be ignored
+            _ if method.starts_with("$/") => {}
             _ => error!("Unknown message method: {}", method),
         }
+
+        Ok(())
     }
 }
+
+#[test]
+fn handle_request_test() {
+    use serde_json::json;
+
+    let (client, _messages) = crossbeam_channel::unbounded();
+    let mut server = Server::new(
+        None,
+        Settings::default(),
+        ClientCapabilities::default(),
+        ModApi::default(),
+        std::sync::mpsc::channel().1,
+        client,
+    );
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let uri = "file:///tmp/a-box.grug";
+    server.handle_notification(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": { "uri": uri, "languageId": "grug", "version": 1, "text": "x: i32 = 1\n" },
+        }),
+        &mut parser,
+    );
+
+    let position =
+        json!({ "textDocument": { "uri": uri }, "position": { "line": 0, "character": 0 } });
+    let malformed = server
+        .handle_request("textDocument/hover", json!({ "position": 3 }), &mut parser)
+        .unwrap_err();
+    assert_eq!(malformed.code, ErrorCode::InvalidParams as i32);
+    server.handle_notification("textDocument/didChange", json!([]), &mut parser);
+
+    let highlights = server
+        .handle_request(
+            "textDocument/documentHighlight",
+            position.clone(),
+            &mut parser,
+        )
+        .unwrap();
+    assert_eq!(highlights.as_array().unwrap().len(), 1);
+
+    let shutdown = server.handle_request("shutdown", Value::Null, &mut parser);
+    assert_eq!(shutdown.unwrap(), Value::Null);
+    let err = server
+        .handle_request("textDocument/hover", position, &mut parser)
+        .unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidRequest as i32);
+}
+
+#[test]
+fn untitled_uri_test() {
+    use lsp_types::{
+        DidOpenTextDocumentParams, HoverParams, TextDocumentIdentifier, TextDocumentItem,
+        TextDocumentPositionParams, notification::DidOpenTextDocument, request::HoverRequest,
+    };
+
+    use crate::test_support::TestClient;
+
+    let untitled = Uri::from_str("untitled:Untitled-1").unwrap();
+    let mut client = TestClient::new();
+    client.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem::new(
+            untitled.clone(),
+            "grug".to_string(),
+            1,
+            "x: i32 = 1\n".to_string(),
+        ),
+    });
+    assert!(client.server().get_document_by_uri(&untitled).is_none());
+
+    let err = client
+        .request::<HoverRequest>(HoverParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(untitled),
+                lsp_types::Position::new(0, 0),
+            ),
+            work_done_progress_params: Default::default(),
+        })
+        .unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidParams as i32);
+}
+
+#[test]
+fn shutdown_test() {
+    use lsp_types::{
+        DocumentSymbolParams, TextDocumentIdentifier, notification::Exit,
+        request::DocumentSymbolRequest,
+    };
+
+    use crate::test_support::{TestClient, uri};
+
+    // Exiting without a shutdown first is a failure
+    let mut client = TestClient::new();
+    client.notify::<Exit>(());
+    assert!(client.server().should_exit);
+    assert_eq!(client.server().exit_code(), 1);
+
+    let mut client = TestClient::new();
+    client.open("mods/foo/crate-box.grug", "on_spawn() {\n}\n");
+    assert_eq!(
+        client.request_json("shutdown", Value::Null).unwrap(),
+        Value::Null
+    );
+    assert!(!client.server().should_exit);
+
+    // Everything but exit is turned away once the server is shutting down
+    let err = client
+        .request::<DocumentSymbolRequest>(DocumentSymbolParams {
+            text_document: TextDocumentIdentifier::new(uri("mods/foo/crate-box.grug")),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })
+        .unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidRequest as i32);
+    let err = client.request_json("shutdown", Value::Null).unwrap_err();
+    assert_eq!(err.code, ErrorCode::InvalidRequest as i32);
+    client.open("mods/foo/tired-box.grug", "on_spawn() {\n}\n");
+    assert!(
+        !client
+            .server()
+            .open_documents
+            .contains("/grug-ls-fixture/mods/foo/tired-box.grug")
+    );
+
+    client.notify::<Exit>(());
+    assert!(client.server().should_exit);
+    assert_eq!(client.server().exit_code(), 0);
+}
@@ -0,0 +1,160 @@
+use lsp_server::Response;
+use lsp_types::{
+    ApplyWorkspaceEditParams, ApplyWorkspaceEditResponse, MessageType, WorkspaceEdit,
+    request::ApplyWorkspaceEdit,
+};
+
+use crate::server::{Server, utils::show_message};
+
+use log::{info, warn};
+
+impl Server {
+    fn supports_apply_edit(&self) -> bool {
+        self.client_capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.apply_edit)
+            .unwrap_or(false)
+    }
+
+    // Commands that change files hand the edit to the editor instead of writing the files,
+    // so it lands in unsaved buffers and can be undone. The editor's answer comes back
+    // in handle_response
+    pub fn apply_edit(&mut self, label: &str, edit: WorkspaceEdit) -> Result<(), String> {
+        if !self.supports_apply_edit() {
+            return Err("The editor can't apply edits sent by the server".to_string());
+        }
+
+        let id = self.send_request::<ApplyWorkspaceEdit>(ApplyWorkspaceEditParams {
+            label: Some(label.to_string()),
+            edit,
+        });
+        self.pending_edits.insert(id, label.to_string());
+
+        Ok(())
+    }
+
+    pub fn handle_response(&mut self, response: Response) {
+        let Some(label) = self.pending_edits.remove(&response.id) else {
+            return;
+        };
+
+        let failure = match (response.result, response.error) {
+            (_, Some(err)) => Some(err.message),
+            (Some(result), None) => {
+                match serde_json::from_value::<ApplyWorkspaceEditResponse>(result) {
+                    Ok(ApplyWorkspaceEditResponse { applied: true, .. }) => None,
+                    Ok(ApplyWorkspaceEditResponse { failure_reason, .. }) => Some(
+                        failure_reason.unwrap_or_else(|| "the editor refused the edit".to_string()),
+                    ),
+                    Err(err) => Some(format!("invalid response: {}", err)),
+                }
+            }
+            (None, None) => Some("the editor sent an empty response".to_string()),
+        };
+
+        match failure {
+            None => info!("The editor applied \"{}\"", label),
+            Some(reason) => {
+                warn!("The editor didn't apply \"{}\": {}", label, reason);
+                show_message(
+                    &self.client,
+                    MessageType::WARNING,
+                    format!("{} failed: {}", label, reason),
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn apply_edit_test() {
+    use lsp_types::{
+        ClientCapabilities, ExecuteCommandParams, ShowMessageParams, WorkspaceClientCapabilities,
+        notification::{Notification, ShowMessage},
+        request::{ExecuteCommand, Request},
+    };
+    use serde_json::json;
+
+    use crate::{
+        server::settings::Settings,
+        test_support::{TestClient, uri},
+    };
+
+    let rename = |client: &mut TestClient| {
+        client.open(
+            "mod_api.json",
+            r#"{"entities": {"box": {}}, "game_functions": {}}"#,
+        );
+        client.open("mods/foo/crate-box.grug", "on_spawn() {\n}\n");
+        client.request::<ExecuteCommand>(ExecuteCommandParams {
+            command: "grug.renameEntityType".to_string(),
+            arguments: vec![json!("box"), json!("crate_box")],
+            work_done_progress_params: Default::default(),
+        })
+    };
+
+    let mut client = TestClient::new();
+    assert!(rename(&mut client).is_err());
+    assert!(client.requests().is_empty());
+
+    let capabilities = ClientCapabilities {
+        workspace: Some(WorkspaceClientCapabilities {
+            apply_edit: Some(true),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let mut client = TestClient::with_capabilities(Settings::default(), capabilities);
+    rename(&mut client).unwrap();
+
+    let requests = client.requests();
+    let [request] = requests.as_slice() else {
+        panic!("{:?}", requests);
+    };
+    assert_eq!(request.method, ApplyWorkspaceEdit::METHOD);
+    let params: ApplyWorkspaceEditParams = serde_json::from_value(request.params.clone()).unwrap();
+    assert_eq!(params.label.as_deref(), Some("Rename box to crate_box"));
+    assert!(
+        params
+            .edit
+            .changes
+            .unwrap()
+            .contains_key(&uri("mod_api.json"))
+    );
+
+    client.notifications();
+    client.respond::<ApplyWorkspaceEdit>(
+        request,
+        ApplyWorkspaceEditResponse {
+            applied: false,
+            failure_reason: Some("mod_api.json is read-only".to_string()),
+            failed_change: None,
+        },
+    );
+    let messages: Vec<String> = client
+        .notifications()
+        .into_iter()
+        .filter(|notification| notification.method == ShowMessage::METHOD)
+        .map(|notification| {
+            serde_json::from_value::<ShowMessageParams>(notification.params)
+                .unwrap()
+                .message
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        ["Rename box to crate_box failed: mod_api.json is read-only"]
+    );
+
+    // Each answer is only expected once
+    client.respond::<ApplyWorkspaceEdit>(
+        request,
+        ApplyWorkspaceEditResponse {
+            applied: false,
+            failure_reason: None,
+            failed_change: None,
+        },
+    );
+    assert!(client.notifications().is_empty());
+}
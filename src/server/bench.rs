@@ -0,0 +1,153 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::mpsc::channel,
+    time::{Duration, Instant},
+};
+
+use lsp_types::{ClientCapabilities, Uri};
+use tree_sitter::Parser;
+
+use crate::server::{
+    Server,
+    document::Document,
+    formatting::FormatOptions,
+    lint::{LintContext, lint},
+    mod_api::ModApi,
+    settings::Settings,
+};
+
+// How long every file took in one of the phases, across all iterations
+struct Phase {
+    name: &'static str,
+    latencies: Vec<Duration>,
+}
+
+impl Phase {
+    fn new(name: &'static str) -> Phase {
+        Phase {
+            name,
+            latencies: Vec::new(),
+        }
+    }
+
+    fn time<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.latencies.push(started.elapsed());
+
+        result
+    }
+
+    fn report(&mut self, bytes: usize) -> String {
+        self.latencies.sort();
+        let total: Duration = self.latencies.iter().sum();
+        let seconds = total.as_secs_f64().max(f64::EPSILON);
+        let percentile = |p: f64| {
+            let idx = ((self.latencies.len() - 1) as f64 * p).round() as usize;
+            self.latencies[idx].as_secs_f64() * 1000.0
+        };
+
+        format!(
+            "{:<8} {:>10.1} files/s {:>8.2} MiB/s   p50 {:.3}ms   p90 {:.3}ms   max {:.3}ms",
+            self.name,
+            self.latencies.len() as f64 / seconds,
+            bytes as f64 / seconds / (1024.0 * 1024.0),
+            percentile(0.5),
+            percentile(0.9),
+            percentile(1.0),
+        )
+    }
+}
+
+// Backs `grug-ls bench`, runs on a single thread so the numbers are comparable between machines
+pub fn bench_files(dir: &Path, iterations: usize) -> bool {
+    let settings = Settings::default();
+
+    // Lints that need the mod API just find less without one
+    let mod_api = std::fs::read_to_string(dir.join(&settings.mod_api_path))
+        .ok()
+        .and_then(|json| ModApi::from_json(&json))
+        .unwrap_or_default();
+    let options = FormatOptions::from_settings(&settings);
+
+    let mut server = Server::new(
+        Some(dir.to_path_buf()),
+        settings,
+        ClientCapabilities::default(),
+        mod_api,
+        channel().1,
+        // Nothing is listening for messages to the editor
+        crossbeam_channel::unbounded().0,
+    );
+    server.index_workspace();
+    let entities = server.get_workspace_entities();
+
+    let mut files: Vec<(PathBuf, Vec<u8>)> = Vec::new();
+    for path in server.document_paths() {
+        match std::fs::read(&path) {
+            Ok(content) => files.push((PathBuf::from(path), content)),
+            Err(err) => eprintln!("Couldn't read {}: {}", path, err),
+        }
+    }
+    if files.is_empty() {
+        eprintln!("There are no grug files in {}", dir.display());
+        return false;
+    }
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let mut parse = Phase::new("parse");
+    let mut analyze = Phase::new("analyze");
+    let mut format = Phase::new("format");
+
+    for _ in 0..iterations {
+        for (path, content) in files.iter() {
+            let Some(path_str) = path.to_str() else {
+                continue;
+            };
+            let Ok(uri) = Uri::from_str(&format!("file://{}", path_str)) else {
+                continue;
+            };
+            let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
+
+            let Some(document) =
+                parse.time(|| Document::new(&mut parser, content.clone(), file_name, uri))
+            else {
+                continue;
+            };
+
+            let context = LintContext {
+                mod_api: &server.mod_api,
+                mod_name: server.get_mod_name(path),
+                entities: entities.clone(),
+                magic_number_threshold: server.settings.magic_number_threshold,
+            };
+            analyze.time(|| lint(&document, &context, &server.settings.lints));
+
+            format.time(|| {
+                Server::format_source(&options, &document.content, &document.tree.root_node())
+            });
+        }
+    }
+
+    let bytes: usize = files
+        .iter()
+        .map(|(_, content)| content.len())
+        .sum::<usize>()
+        * iterations;
+    println!(
+        "{} files, {} bytes, {} iterations",
+        files.len(),
+        bytes / iterations,
+        iterations
+    );
+    for phase in [&mut parse, &mut analyze, &mut format] {
+        println!("{}", phase.report(bytes));
+    }
+
+    true
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::mpsc::channel,
+};
+
+use lsp_types::{ClientCapabilities, Diagnostic, DiagnosticSeverity, Uri};
+use tree_sitter::Parser;
+
+use crate::server::{
+    Server,
+    config_file::cli_settings,
+    document::Document,
+    lint::{Fix, LintContext, LintLevel, apply_fixes, lint},
+    mod_api::ModApi,
+    settings::Settings,
+    workspace::{collect_grug_files_except, map_in_parallel},
+};
+
+fn severity_name(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "info",
+        _ => "hint",
+    }
+}
+
+// What checking one file found, printed once every file is done
+struct FileReport {
+    fixed: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// Lints the file, after applying the fixes if asked to
+fn check_file(
+    parser: &mut Parser,
+    path: &Path,
+    fix: bool,
+    context: &LintContext,
+    lints: &HashMap<String, LintLevel>,
+) -> Option<FileReport> {
+    let path_str = path.to_str()?;
+    let uri = Uri::from_str(&format!("file://{}", path_str)).ok()?;
+    let file_name = path.file_name()?.to_str()?.to_string();
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(err) => {
+            eprintln!("Couldn't read {}: {}", path.display(), err);
+            return None;
+        }
+    };
+
+    let document = Document::new(parser, content, file_name.clone(), uri.clone())?;
+    let diagnostics = lint(&document, context, lints);
+
+    let fixes: Vec<Fix> = diagnostics
+        .iter()
+        .filter_map(Fix::from_diagnostic)
+        .collect();
+    if !fix || fixes.is_empty() {
+        return Some(FileReport {
+            fixed: 0,
+            diagnostics,
+        });
+    }
+
+    let content = apply_fixes(&document.content, &fixes);
+    if let Err(err) = std::fs::write(path, &content) {
+        eprintln!("Couldn't write {}: {}", path.display(), err);
+        return Some(FileReport {
+            fixed: 0,
+            diagnostics,
+        });
+    }
+
+    let document = Document::new(parser, content, file_name, uri)?;
+    Some(FileReport {
+        fixed: fixes.len(),
+        diagnostics: lint(&document, context, lints),
+    })
+}
+
+// Prints where the file is broken, if it is
+fn read_mod_api(path: &Path) -> Option<ModApi> {
+    let json = match std::fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(err) => {
+            eprintln!("Couldn't read {}: {}", path.display(), err);
+            return None;
+        }
+    };
+
+    match ModApi::from_json_strict(&json) {
+        Ok(mod_api) => Some(mod_api),
+        Err(err) => {
+            eprintln!(
+                "{}:{}:{}: {}",
+                path.display(),
+                err.line.unwrap_or(1),
+                err.column.unwrap_or(1),
+                err.message
+            );
+            None
+        }
+    }
+}
+
+// grug-ls mod-api [path], validates the mod API and prints its docs
+pub fn check_mod_api(path: &Path) -> bool {
+    let Some(mod_api) = read_mod_api(path) else {
+        return false;
+    };
+
+    print!("{}", mod_api.to_markdown());
+    true
+}
+
+// Backs `grug-ls check`, returns false when any file has errors
+pub fn check_files(dir: &Path, fix: bool, respect_ignore_files: bool) -> bool {
+    let settings = cli_settings(dir, respect_ignore_files);
+
+    let mod_api_path = dir.join(&settings.mod_api_path);
+    let Some(mod_api) = read_mod_api(&mod_api_path) else {
+        return false;
+    };
+
+    let server = Server::new(
+        Some(dir.to_path_buf()),
+        settings,
+        ClientCapabilities::default(),
+        mod_api,
+        channel().1,
+        // Nothing is listening for messages to the editor
+        crossbeam_channel::unbounded().0,
+    );
+
+    // Each file is parsed once, by the worker that lints it, instead of indexing them first
+    let mut paths: Vec<PathBuf> = Vec::new();
+    collect_grug_files_except(
+        dir,
+        &|path| server.is_excluded(path),
+        server.settings.respect_ignore_files,
+        &mut paths,
+    );
+    let mod_names: HashMap<PathBuf, Option<String>> = paths
+        .iter()
+        .map(|path| (path.clone(), server.get_mod_name(path)))
+        .collect();
+    // Mods can turn lints on and off in their own .grug-ls.toml, like in the editor
+    let file_settings: HashMap<PathBuf, Settings> = paths
+        .iter()
+        .map(|path| {
+            let settings = server.settings_for(path.to_str().unwrap_or_default());
+            (path.clone(), settings.into_owned())
+        })
+        .collect();
+    let entities = server.entities_at(&paths);
+
+    let mut reports = Vec::new();
+    map_in_parallel(
+        paths,
+        |parser, path| {
+            let settings = &file_settings[path];
+            let context = LintContext {
+                mod_api: &server.mod_api,
+                mod_name: mod_names[path].clone(),
+                entities: entities.clone(),
+                magic_number_threshold: settings.magic_number_threshold,
+            };
+            check_file(parser, path, fix, &context, &settings.lints)
+        },
+        |path, report| {
+            if let Some(report) = report {
+                reports.push((path, report));
+            }
+        },
+    );
+    // Files finish in any order, but the output shouldn't change between runs
+    reports.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut errors = 0;
+    for (path, report) in reports {
+        if report.fixed > 0 {
+            println!("Fixed {} problem(s) in {}", report.fixed, path.display());
+        }
+
+        for diagnostic in report.diagnostics.iter() {
+            println!(
+                "{}:{}:{}: {}: {}",
+                path.display(),
+                diagnostic.range.start.line + 1,
+                diagnostic.range.start.character + 1,
+                severity_name(diagnostic.severity),
+                diagnostic.message
+            );
+        }
+
+        errors += report
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Some(DiagnosticSeverity::ERROR))
+            .count();
+    }
+
+    errors == 0
+}
+
+#[test]
+fn check_files_exclude_test() {
+    let root = std::env::temp_dir().join(format!("grug-ls-check-exclude-{}", std::process::id()));
+    let vendor = root.join("mods").join("vendor");
+    std::fs::create_dir_all(&vendor).unwrap();
+    std::fs::write(
+        root.join("mod_api.json"),
+        r#"{"entities": {"box": {}}, "game_functions": {}}"#,
+    )
+    .unwrap();
+    std::fs::write(
+        vendor.join("a-box.grug"),
+        "on_spawn() {\n    x: i32 = \n}\n",
+    )
+    .unwrap();
+
+    assert!(!check_files(&root, false, true));
+    std::fs::write(root.join(".grug-ls.toml"), "exclude = [\"mods/vendor\"]\n").unwrap();
+    assert!(check_files(&root, false, true));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn check_files_nested_config_test() {
+    let root = std::env::temp_dir().join(format!("grug-ls-check-nested-{}", std::process::id()));
+    let foo = root.join("mods").join("foo");
+    std::fs::create_dir_all(&foo).unwrap();
+    std::fs::write(
+        root.join("mod_api.json"),
+        r#"{"entities": {"box": {}}, "game_functions": {}}"#,
+    )
+    .unwrap();
+    std::fs::write(foo.join("a-box.grug"), "on_spawn() {\n    x: i32 = \n}\n").unwrap();
+
+    assert!(!check_files(&root, false, true));
+    std::fs::write(
+        foo.join(".grug-ls.toml"),
+        "[lints]\nsyntax-error = \"off\"\n",
+    )
+    .unwrap();
+    assert!(check_files(&root, false, true));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
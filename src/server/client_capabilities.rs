@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+use lsp_types::{
+    CompletionItem, CompletionTextEdit, DocumentChangeOperation, DocumentChanges, Documentation,
+    GotoDefinitionResponse, InsertReplaceEdit, InsertTextFormat, Location, MarkupKind, OneOf,
+    OptionalVersionedTextDocumentIdentifier, PositionEncodingKind, Range, RenameFile, ResourceOp,
+    ResourceOperationKind, TextDocumentEdit, TextEdit, Uri, WorkspaceEdit,
+};
+
+use crate::server::{
+    Server,
+    utils::{snippet_to_plain_text, uri_to_path},
+};
+
+// Picks the first format the client prefers that we can produce.
+// Clients that don't say anything only get plain text, as the spec requires
+fn preferred_markup_kind(formats: Option<&Vec<MarkupKind>>) -> MarkupKind {
+    formats
+        .and_then(|formats| {
+            formats
+                .iter()
+                .find(|kind| matches!(kind, MarkupKind::Markdown | MarkupKind::PlainText))
+        })
+        .cloned()
+        .unwrap_or(MarkupKind::PlainText)
+}
+
+impl Server {
+    // Edits to open documents carry the version they were made for, so the editor rejects them
+    // if the document changed in the meantime. Closed documents have no version to check
+    fn text_document_edits(&self, changes: HashMap<Uri, Vec<TextEdit>>) -> Vec<TextDocumentEdit> {
+        changes
+            .into_iter()
+            .map(|(uri, edits)| {
+                let version = uri_to_path(uri.as_str())
+                    .and_then(|path| self.document_versions.get(path))
+                    .copied();
+
+                TextDocumentEdit {
+                    text_document: OptionalVersionedTextDocumentIdentifier { uri, version },
+                    edits: edits.into_iter().map(OneOf::Left).collect(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn supports_snippets(&self) -> bool {
+        self.client_capabilities
+            .text_document
+            .as_ref()
+            .and_then(|text_document| text_document.completion.as_ref())
+            .and_then(|completion| completion.completion_item.as_ref())
+            .and_then(|item| item.snippet_support)
+            .unwrap_or(false)
+    }
+
+    pub fn supports_insert_replace(&self) -> bool {
+        self.client_capabilities
+            .text_document
+            .as_ref()
+            .and_then(|text_document| text_document.completion.as_ref())
+            .and_then(|completion| completion.completion_item.as_ref())
+            .and_then(|item| item.insert_replace_support)
+            .unwrap_or(false)
+    }
+
+    pub fn completion_markup_kind(&self) -> MarkupKind {
+        let formats = self
+            .client_capabilities
+            .text_document
+            .as_ref()
+            .and_then(|text_document| text_document.completion.as_ref())
+            .and_then(|completion| completion.completion_item.as_ref())
+            .and_then(|item| item.documentation_format.as_ref());
+
+        preferred_markup_kind(formats)
+    }
+
+    pub fn hover_markup_kind(&self) -> MarkupKind {
+        let formats = self
+            .client_capabilities
+            .text_document
+            .as_ref()
+            .and_then(|text_document| text_document.hover.as_ref())
+            .and_then(|hover| hover.content_format.as_ref());
+
+        preferred_markup_kind(formats)
+    }
+
+    // Columns are bytes everywhere in the server, like tree-sitter's, which is what UTF-8 means
+    pub fn supports_utf8_positions(&self) -> bool {
+        self.client_capabilities
+            .general
+            .as_ref()
+            .and_then(|general| general.position_encodings.as_ref())
+            .is_some_and(|encodings| encodings.contains(&PositionEncodingKind::UTF8))
+    }
+
+    pub fn supports_definition_links(&self) -> bool {
+        self.client_capabilities
+            .text_document
+            .as_ref()
+            .and_then(|text_document| text_document.definition.as_ref())
+            .and_then(|definition| definition.link_support)
+            .unwrap_or(false)
+    }
+
+    pub fn supports_document_changes(&self) -> bool {
+        self.client_capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.workspace_edit.as_ref())
+            .and_then(|workspace_edit| workspace_edit.document_changes)
+            .unwrap_or(false)
+    }
+
+    pub fn supports_file_renames(&self) -> bool {
+        self.supports_document_changes()
+            && self
+                .client_capabilities
+                .workspace
+                .as_ref()
+                .and_then(|workspace| workspace.workspace_edit.as_ref())
+                .and_then(|workspace_edit| workspace_edit.resource_operations.as_ref())
+                .is_some_and(|operations| operations.contains(&ResourceOperationKind::Rename))
+    }
+
+    pub fn make_workspace_edit(&self, changes: HashMap<Uri, Vec<TextEdit>>) -> WorkspaceEdit {
+        if !self.supports_document_changes() {
+            return WorkspaceEdit::new(changes);
+        }
+
+        WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Edits(self.text_document_edits(changes))),
+            ..Default::default()
+        }
+    }
+
+    // Files are renamed after all the edits, which refer to them by their old uri.
+    // Clients that can't rename files only get the edits
+    pub fn make_workspace_edit_with_renames(
+        &self,
+        changes: HashMap<Uri, Vec<TextEdit>>,
+        renames: Vec<RenameFile>,
+    ) -> WorkspaceEdit {
+        if renames.is_empty() || !self.supports_file_renames() {
+            return self.make_workspace_edit(changes);
+        }
+
+        let operations: Vec<DocumentChangeOperation> = self
+            .text_document_edits(changes)
+            .into_iter()
+            .map(DocumentChangeOperation::Edit)
+            .chain(
+                renames
+                    .into_iter()
+                    .map(|rename| DocumentChangeOperation::Op(ResourceOp::Rename(rename))),
+            )
+            .collect();
+
+        WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(operations)),
+            ..Default::default()
+        }
+    }
+
+    pub fn adapt_definition(&self, definition: GotoDefinitionResponse) -> GotoDefinitionResponse {
+        match definition {
+            GotoDefinitionResponse::Link(links) if !self.supports_definition_links() => {
+                let locations: Vec<Location> = links
+                    .into_iter()
+                    .map(|link| Location::new(link.target_uri, link.target_selection_range))
+                    .collect();
+
+                GotoDefinitionResponse::Array(locations)
+            }
+            definition => definition,
+        }
+    }
+
+    pub fn adapt_completion_item(&self, mut item: CompletionItem) -> CompletionItem {
+        if item.insert_text_format == Some(InsertTextFormat::SNIPPET) && !self.supports_snippets() {
+            item.insert_text = item.insert_text.as_deref().map(snippet_to_plain_text);
+            item.insert_text_format = Some(InsertTextFormat::PLAIN_TEXT);
+        }
+
+        if self.completion_markup_kind() == MarkupKind::PlainText
+            && let Some(Documentation::MarkupContent(markup)) = item.documentation
+        {
+            item.documentation = Some(Documentation::String(markup.value));
+        }
+
+        item
+    }
+
+    // Completing in the middle of a name: `insert` ends at the cursor, `replace` at the end of the name.
+    // Clients without insertReplaceSupport get the whole name replaced
+    pub fn adapt_completion_edit(
+        &self,
+        mut item: CompletionItem,
+        insert: Range,
+        replace: Range,
+    ) -> CompletionItem {
+        if item.text_edit.is_some() {
+            return item;
+        }
+
+        let new_text = item
+            .insert_text
+            .take()
+            .unwrap_or_else(|| item.label.clone());
+        item.text_edit = Some(if self.supports_insert_replace() {
+            CompletionTextEdit::InsertAndReplace(InsertReplaceEdit {
+                new_text,
+                insert,
+                replace,
+            })
+        } else {
+            CompletionTextEdit::Edit(TextEdit::new(replace, new_text))
+        });
+
+        item
+    }
+}
+
+#[test]
+fn versioned_edits_test() {
+    use lsp_types::{WorkspaceClientCapabilities, WorkspaceEditClientCapabilities};
+
+    use crate::{
+        server::settings::Settings,
+        test_support::{TestClient, uri},
+    };
+
+    let capabilities = lsp_types::ClientCapabilities {
+        workspace: Some(WorkspaceClientCapabilities {
+            workspace_edit: Some(WorkspaceEditClientCapabilities {
+                document_changes: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let mut client = TestClient::with_capabilities(Settings::default(), capabilities);
+    client.open("mods/foo/crate-box.grug", "on_spawn() {\n}\n");
+    client.change("mods/foo/crate-box.grug", "on_tick() {\n}\n");
+    client.open("mods/foo/tired-box.grug", "on_spawn() {\n}\n");
+    client.close("mods/foo/tired-box.grug");
+
+    let edit = |path: &str| {
+        (
+            uri(path),
+            vec![TextEdit::new(Range::default(), String::new())],
+        )
+    };
+    let workspace_edit = client.server().make_workspace_edit(HashMap::from([
+        edit("mods/foo/crate-box.grug"),
+        edit("mods/foo/tired-box.grug"),
+    ]));
+
+    let Some(DocumentChanges::Edits(edits)) = workspace_edit.document_changes else {
+        panic!("{:?}", workspace_edit);
+    };
+    let mut versions: Vec<(Uri, Option<i32>)> = edits
+        .into_iter()
+        .map(|edit| (edit.text_document.uri, edit.text_document.version))
+        .collect();
+    versions.sort_by_key(|(uri, _)| uri.to_string());
+    assert_eq!(
+        versions,
+        [
+            (uri("mods/foo/crate-box.grug"), Some(2)),
+            (uri("mods/foo/tired-box.grug"), None),
+        ]
+    );
+}
+
+#[test]
+fn position_encoding_test() {
+    use lsp_types::{
+        ClientCapabilities, GeneralClientCapabilities, TextDocumentSyncCapability,
+        TextDocumentSyncKind,
+    };
+
+    use crate::{server::settings::Settings, test_support::TestClient};
+
+    let sync_kind =
+        |client: &TestClient| match client.server().get_capabilities().text_document_sync {
+            Some(TextDocumentSyncCapability::Options(options)) => options.change,
+            _ => None,
+        };
+
+    // Edits from clients counting in UTF-16 come as the whole text, their columns would be
+    // read as bytes
+    let client = TestClient::new();
+    assert_eq!(client.server().get_capabilities().position_encoding, None);
+    assert_eq!(sync_kind(&client), Some(TextDocumentSyncKind::FULL));
+
+    let capabilities = ClientCapabilities {
+        general: Some(GeneralClientCapabilities {
+            position_encodings: Some(vec![
+                PositionEncodingKind::UTF16,
+                PositionEncodingKind::UTF8,
+            ]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let client = TestClient::with_capabilities(Settings::default(), capabilities);
+    assert_eq!(
+        client.server().get_capabilities().position_encoding,
+        Some(PositionEncodingKind::UTF8)
+    );
+    assert_eq!(sync_kind(&client), Some(TextDocumentSyncKind::INCREMENTAL));
+}
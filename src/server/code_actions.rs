@@ -0,0 +1,256 @@
+use std::{collections::HashMap, path::Path};
+
+use lsp_types::{CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams, Uri};
+
+use crate::server::{
+    Server,
+    document::Document,
+    lint::{Fix, combine_fixes},
+    utils::{RequestResult, ok, position_to_byte, unsupported_uri, uri_to_path},
+};
+
+mod boolean;
+mod helper_stub;
+mod invert_if;
+
+// Kinds are hierarchical, asking for `source` also gets `source.fixAll`
+fn wants_kind(only: Option<&[CodeActionKind]>, kind: &CodeActionKind) -> bool {
+    let Some(only) = only else {
+        return true;
+    };
+
+    only.iter().any(|requested| {
+        kind.as_str()
+            .strip_prefix(requested.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+impl Server {
+    // Every fix the lints have for the document at once, for editors that fix all on save.
+    // The document is linted again, since the client only sends the diagnostics in range
+    fn fix_all(&self, uri: &Uri, document: &Document) -> Option<CodeAction> {
+        let path = uri_to_path(uri.as_str())?;
+        if self.is_excluded(Path::new(path)) {
+            return None;
+        }
+
+        let fixes: Vec<Fix> = self
+            .lint_document(path, document)
+            .iter()
+            .filter_map(Fix::from_diagnostic)
+            .collect();
+        if fixes.is_empty() {
+            return None;
+        }
+
+        let edits = combine_fixes(&document.content, &fixes);
+        Some(CodeAction {
+            title: "Fix all auto-fixable problems".to_string(),
+            kind: Some(CodeActionKind::SOURCE_FIX_ALL),
+            edit: Some(self.make_workspace_edit(HashMap::from([(uri.clone(), edits)]))),
+            ..Default::default()
+        })
+    }
+
+    // The fixes lints attach to their diagnostics, offered as quick fixes.
+    // Only the kinds the client asks for are worked out, it asks for less on hover or save
+    pub fn handle_code_action(&self, params: CodeActionParams) -> RequestResult {
+        let uri = params.text_document.uri;
+        let Some(path) = uri_to_path(uri.as_str()) else {
+            return Err(unsupported_uri(uri.as_str()));
+        };
+        let only = params.context.only;
+        let wants = |kind: &CodeActionKind| wants_kind(only.as_deref(), kind);
+
+        let mut actions: Vec<CodeActionOrCommand> = Vec::new();
+        if wants(&CodeActionKind::QUICKFIX) {
+            actions.extend(
+                params
+                    .context
+                    .diagnostics
+                    .into_iter()
+                    // Other servers' diagnostics can carry data of their own
+                    .filter(|diagnostic| diagnostic.source.as_deref() == Some("grug-ls"))
+                    .filter_map(|diagnostic| {
+                        let fix = Fix::from_diagnostic(&diagnostic)?;
+                        let edit =
+                            self.make_workspace_edit(HashMap::from([(uri.clone(), fix.edits)]));
+
+                        Some(CodeActionOrCommand::CodeAction(CodeAction {
+                            title: fix.title,
+                            kind: Some(CodeActionKind::QUICKFIX),
+                            diagnostics: Some(vec![diagnostic]),
+                            edit: Some(edit),
+                            is_preferred: Some(true),
+                            ..Default::default()
+                        }))
+                    }),
+            );
+        }
+
+        // Rewrites of the code under the cursor, which aren't tied to a diagnostic
+        if let Some(document) = self.get_document_by_uri(&uri) {
+            let start = position_to_byte(&document.content, params.range.start);
+            let node = document
+                .tree
+                .root_node()
+                .descendant_for_byte_range(start, start);
+
+            let mut fixes = Vec::new();
+            if let Some(node) = node {
+                if wants(&CodeActionKind::QUICKFIX) {
+                    fixes.extend(
+                        boolean::simplify_boolean(document, &node)
+                            .into_iter()
+                            .map(|fix| (fix, CodeActionKind::QUICKFIX)),
+                    );
+
+                    let indent = " ".repeat(self.settings_for(path).formatter_width);
+                    fixes.extend(
+                        helper_stub::helper_stub(document, &self.mod_api, &node, &indent)
+                            .map(|fix| (fix, CodeActionKind::QUICKFIX)),
+                    );
+                }
+                if wants(&CodeActionKind::REFACTOR_REWRITE) {
+                    fixes.extend(
+                        invert_if::invert_if(document, &node)
+                            .map(|fix| (fix, CodeActionKind::REFACTOR_REWRITE)),
+                    );
+                }
+            }
+
+            for (fix, kind) in fixes {
+                let edit = self.make_workspace_edit(HashMap::from([(uri.clone(), fix.edits)]));
+
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: fix.title,
+                    kind: Some(kind),
+                    edit: Some(edit),
+                    ..Default::default()
+                }));
+            }
+
+            if wants(&CodeActionKind::SOURCE_FIX_ALL)
+                && let Some(action) = self.fix_all(&uri, document)
+            {
+                actions.push(CodeActionOrCommand::CodeAction(action));
+            }
+        }
+
+        ok(actions)
+    }
+}
+
+#[test]
+fn fix_all_test() {
+    use lsp_types::{CodeActionContext, Range, request::CodeActionRequest};
+
+    use crate::{
+        server::lint::apply_fixes,
+        test_support::{TestClient, uri},
+    };
+
+    let mut client = TestClient::new();
+    client.open(
+        "mod_api.json",
+        r#"{"entities": {}, "game_functions": {"set_speed": {"arguments": [{"name": "speed", "type": "f32"}]}}}"#,
+    );
+    let source = "on_spawn() {\n    x: i32 = 1\n    set_speed(2)\n}\n";
+    client.open("mods/foo/tired-box.grug", source);
+
+    let actions = client
+        .expect::<CodeActionRequest>(CodeActionParams {
+            text_document: lsp_types::TextDocumentIdentifier::new(uri("mods/foo/tired-box.grug")),
+            range: Range::default(),
+            context: CodeActionContext::default(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })
+        .unwrap();
+    let Some(CodeActionOrCommand::CodeAction(CodeAction {
+        edit: Some(edit), ..
+    })) = actions.into_iter().find(|action| {
+        matches!(action, CodeActionOrCommand::CodeAction(action)
+            if action.kind == Some(CodeActionKind::SOURCE_FIX_ALL))
+    })
+    else {
+        panic!();
+    };
+
+    let edits = edit
+        .changes
+        .unwrap()
+        .remove(&uri("mods/foo/tired-box.grug"))
+        .unwrap();
+    let fix = Fix {
+        title: String::new(),
+        edits,
+    };
+    assert_eq!(
+        String::from_utf8(apply_fixes(source.as_bytes(), &[fix])).unwrap(),
+        "on_spawn() {\n    _x: i32 = 1\n    set_speed(2.0)\n}\n"
+    );
+}
+
+#[test]
+fn code_action_kind_test() {
+    use lsp_types::{CodeActionContext, Diagnostic, Range, request::CodeActionRequest};
+
+    use crate::test_support::TestClient;
+
+    let mut client = TestClient::new();
+    client.open(
+        "mods/foo/tired-box.grug",
+        "on_spawn() {\n    y: i32 = 1\n    x: bool = true\n    if $0x == true {\n        print_string(\"a\")\n    } else {\n        print_string(\"b\")\n    }\n}\n",
+    );
+    let cursor = client.cursor("mods/foo/tired-box.grug");
+
+    // Fixes carried by another server's diagnostics aren't ours to offer
+    let mut foreign = Diagnostic {
+        range: Range::new(cursor.position, cursor.position),
+        source: Some("other-ls".to_string()),
+        ..Default::default()
+    };
+    Fix {
+        title: "Not ours".to_string(),
+        edits: Vec::new(),
+    }
+    .attach(&mut foreign);
+
+    let mut kinds = |only: Option<Vec<CodeActionKind>>| -> Vec<String> {
+        client
+            .expect::<CodeActionRequest>(CodeActionParams {
+                text_document: cursor.text_document.clone(),
+                range: Range::new(cursor.position, cursor.position),
+                context: CodeActionContext {
+                    diagnostics: vec![foreign.clone()],
+                    only,
+                    trigger_kind: None,
+                },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .unwrap()
+            .into_iter()
+            .map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => {
+                    action.kind.unwrap().as_str().to_string()
+                }
+                CodeActionOrCommand::Command(command) => panic!("{:?}", command),
+            })
+            .collect()
+    };
+
+    assert_eq!(
+        kinds(None),
+        ["quickfix", "refactor.rewrite", "source.fixAll"]
+    );
+    assert_eq!(kinds(Some(vec![CodeActionKind::QUICKFIX])), ["quickfix"]);
+    assert_eq!(
+        kinds(Some(vec![CodeActionKind::REFACTOR])),
+        ["refactor.rewrite"]
+    );
+    assert_eq!(kinds(Some(vec![CodeActionKind::SOURCE])), ["source.fixAll"]);
+    assert!(kinds(Some(vec![CodeActionKind::new("source.fix")])).is_empty());
+}
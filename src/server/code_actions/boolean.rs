@@ -0,0 +1,213 @@
+use lsp_types::TextEdit;
+use tree_sitter::Node;
+
+use crate::server::{
+    document::Document,
+    lint::Fix,
+    utils::{
+        needs_parentheses, operator_precedence, precedence, strip_parentheses,
+        treesitter_range_to_lsp,
+    },
+};
+
+// The comparison that is true exactly when this one is false
+fn flip_comparison(operator: &str) -> Option<&'static str> {
+    match operator {
+        "==" => Some("!="),
+        "!=" => Some("=="),
+        "<" => Some(">="),
+        ">=" => Some("<"),
+        ">" => Some("<="),
+        "<=" => Some(">"),
+        _ => None,
+    }
+}
+
+fn text<'a>(document: &'a Document, node: &Node) -> &'a str {
+    std::str::from_utf8(&document.content[node.byte_range()]).unwrap_or_default()
+}
+
+fn is_literal(document: &Document, node: &Node, value: &str) -> bool {
+    node.kind() == "identifier" && text(document, node) == value
+}
+
+// Comparisons get flipped and `not` is dropped instead of adding another one.
+// Returns the text along with its precedence, so the caller can tell if it needs parentheses
+pub fn negate(document: &Document, node: &Node) -> (String, u8) {
+    let inner = strip_parentheses(*node);
+
+    if inner.kind() == "binary_expression"
+        && let Some(operator) = inner.child_by_field_name("operator")
+        && let Some(flipped) = flip_comparison(operator.kind())
+        && let Some(left) = inner.child_by_field_name("left")
+        && let Some(right) = inner.child_by_field_name("right")
+    {
+        let text = format!(
+            "{} {} {}",
+            text(document, &left),
+            flipped,
+            text(document, &right)
+        );
+        return (text, operator_precedence(flipped));
+    }
+
+    if inner.kind() == "unary_expression"
+        && inner
+            .child_by_field_name("operator")
+            .is_some_and(|operator| operator.kind() == "not")
+        && let Some(operand) = inner.child_by_field_name("operand")
+    {
+        return (text(document, &operand).to_string(), precedence(&operand));
+    }
+
+    if precedence(node) < 7 {
+        (format!("not ({})", text(document, node)), 7)
+    } else {
+        (format!("not {}", text(document, node)), 7)
+    }
+}
+
+fn replace(node: &Node, (new_text, precedence): (String, u8)) -> Fix {
+    let new_text = if needs_parentheses(node, precedence) {
+        format!("({})", new_text)
+    } else {
+        new_text
+    };
+
+    Fix {
+        title: format!("Simplify to `{}`", new_text),
+        edits: vec![TextEdit::new(
+            treesitter_range_to_lsp(&node.range()),
+            new_text,
+        )],
+    }
+}
+
+// `x == true`, `x != false` and the like
+fn simplify_comparison(document: &Document, node: &Node) -> Option<Fix> {
+    let operator = node.child_by_field_name("operator")?.kind();
+    if !matches!(operator, "==" | "!=") {
+        return None;
+    }
+    let left = node.child_by_field_name("left")?;
+    let right = node.child_by_field_name("right")?;
+
+    let (other, literal) =
+        if is_literal(document, &right, "true") || is_literal(document, &right, "false") {
+            (left, right)
+        } else if is_literal(document, &left, "true") || is_literal(document, &left, "false") {
+            (right, left)
+        } else {
+            return None;
+        };
+
+    let keeps_value = (operator == "==") == is_literal(document, &literal, "true");
+    let simplified = if keeps_value {
+        (text(document, &other).to_string(), precedence(&other))
+    } else {
+        negate(document, &other)
+    };
+
+    Some(replace(node, simplified))
+}
+
+// `not (a == b)` and `not not x`
+fn simplify_not(document: &Document, node: &Node) -> Option<Fix> {
+    if node.child_by_field_name("operator")?.kind() != "not" {
+        return None;
+    }
+    let operand = node.child_by_field_name("operand")?;
+
+    let inner = strip_parentheses(operand);
+    let simplifies = match inner.kind() {
+        "binary_expression" => inner
+            .child_by_field_name("operator")
+            .is_some_and(|operator| flip_comparison(operator.kind()).is_some()),
+        "unary_expression" => inner
+            .child_by_field_name("operator")
+            .is_some_and(|operator| operator.kind() == "not"),
+        _ => false,
+    };
+    if !simplifies {
+        return None;
+    }
+
+    Some(replace(node, negate(document, &operand)))
+}
+
+// The simplifications for the expressions around the node, innermost first
+pub fn simplify_boolean(document: &Document, node: &Node) -> Vec<Fix> {
+    let mut fixes = Vec::new();
+
+    let mut current = Some(*node);
+    while let Some(node) = current {
+        let fix = match node.kind() {
+            "binary_expression" => simplify_comparison(document, &node),
+            "unary_expression" => simplify_not(document, &node),
+            _ => None,
+        };
+        fixes.extend(fix);
+
+        current = node.parent();
+    }
+
+    fixes
+}
+
+#[test]
+fn simplify_boolean_test() {
+    use std::str::FromStr;
+
+    use crate::server::lint::apply_fixes;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    // The first simplification around the `$0` in the expression, or its start
+    let mut simplify = |expression: &str| -> Option<String> {
+        let prefix = "on_spawn() {\n    x = ";
+        let cursor = prefix.len() + expression.find("$0").unwrap_or(0);
+        let source = format!("{}{}\n}}\n", prefix, expression.replace("$0", ""));
+        let document = Document::new(
+            &mut parser,
+            source.as_bytes().to_vec(),
+            "tired-box.grug".to_string(),
+            lsp_types::Uri::from_str("some_uri").unwrap(),
+        )
+        .unwrap();
+
+        let node = document
+            .tree
+            .root_node()
+            .descendant_for_byte_range(cursor, cursor)
+            .unwrap();
+        let fix = simplify_boolean(&document, &node).into_iter().next()?;
+
+        let fixed = String::from_utf8(apply_fixes(source.as_bytes(), &[fix])).unwrap();
+        Some(fixed[prefix.len()..fixed.len() - "\n}\n".len()].to_string())
+    };
+
+    assert_eq!(simplify("a == true").as_deref(), Some("a"));
+    assert_eq!(simplify("true == a").as_deref(), Some("a"));
+    assert_eq!(simplify("a != true").as_deref(), Some("not a"));
+    assert_eq!(simplify("a != false").as_deref(), Some("a"));
+    assert_eq!(simplify("a == false").as_deref(), Some("not a"));
+    assert_eq!(simplify("(a < b) == false").as_deref(), Some("a >= b"));
+    assert_eq!(
+        simplify("(a and b) == false").as_deref(),
+        Some("not (a and b)")
+    );
+    assert_eq!(simplify("not (a == b)").as_deref(), Some("a != b"));
+    assert_eq!(simplify("not (a <= b)").as_deref(), Some("a > b"));
+    assert_eq!(simplify("not not a").as_deref(), Some("a"));
+    assert_eq!(simplify("not (not a)").as_deref(), Some("a"));
+    // Still means the same once it's in a tighter expression
+    assert_eq!(
+        simplify("c == $0not (a == b)").as_deref(),
+        Some("c == (a != b)")
+    );
+    assert_eq!(simplify("not (a and b)"), None);
+    assert_eq!(simplify("a == b"), None);
+}
@@ -0,0 +1,155 @@
+use lsp_types::{Position, TextEdit};
+use tree_sitter::Node;
+
+use crate::server::{
+    document::{Document, Type},
+    lint::Fix,
+    mod_api::ModApi,
+    type_check::infer_type,
+    utils::get_inner_expression,
+};
+
+// Where the stub goes: below everything else in the file
+fn end_of_file(content: &[u8]) -> Position {
+    let line = content.iter().filter(|byte| **byte == b'\n').count();
+    let line_start = content
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |idx| idx + 1);
+
+    Position::new(line as u32, (content.len() - line_start) as u32)
+}
+
+// Variables passed along keep their name, anything else is numbered
+fn parameter_name(document: &Document, argument: &Node, idx: usize, taken: &[String]) -> String {
+    let base = get_inner_expression(argument)
+        .filter(|expression| expression.kind() == "identifier")
+        .map(|identifier| String::from_utf8_lossy(&document.content[identifier.byte_range()]))
+        .filter(|name| !matches!(name.as_ref(), "true" | "false"))
+        .map_or_else(|| format!("arg{}", idx + 1), |name| name.to_string());
+
+    let mut name = base.clone();
+    let mut suffix = 2;
+    while taken.contains(&name) {
+        name = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+    name
+}
+
+// Declares the helper the cursor is on a call to, when the file doesn't have it yet,
+// so it can be called before it's written
+pub fn helper_stub(
+    document: &Document,
+    mod_api: &ModApi,
+    node: &Node,
+    indent: &str,
+) -> Option<Fix> {
+    let mut current = Some(*node);
+    let call = loop {
+        let candidate = current?;
+        if candidate.kind() == "function_call" {
+            break candidate;
+        }
+        current = candidate.parent();
+    };
+
+    let name = call
+        .child_by_field_name("name")
+        .filter(|name| name.kind() == "helper_identifier")?;
+    let name = str::from_utf8(&document.content[name.byte_range()]).ok()?;
+    if document.get_helper(name.as_bytes()).is_some() {
+        return None;
+    }
+
+    let mut cursor = call.walk();
+    let mut params: Vec<String> = Vec::new();
+    let mut declarations: Vec<String> = Vec::new();
+    for (idx, argument) in call
+        .children_by_field_name("argument", &mut cursor)
+        .enumerate()
+    {
+        let param = parameter_name(document, &argument, idx, &params);
+        // The user fixes up what can't be inferred, i32 is the likeliest guess
+        let r#type = infer_type(document, mod_api, &argument).unwrap_or(Type::I32);
+
+        declarations.push(format!("{}: {}", param, r#type.as_str()));
+        params.push(param);
+    }
+
+    let separator = match document.content.last() {
+        None => "",
+        Some(b'\n') => "\n",
+        Some(_) => "\n\n",
+    };
+
+    let position = end_of_file(&document.content);
+    Some(Fix {
+        title: format!("Generate {}", name),
+        edits: vec![TextEdit {
+            range: lsp_types::Range::new(position, position),
+            new_text: format!(
+                "{}{}({}) {{\n{}# TODO\n}}\n",
+                separator,
+                name,
+                declarations.join(", "),
+                indent
+            ),
+        }],
+    })
+}
+
+#[test]
+fn helper_stub_test() {
+    use std::str::FromStr;
+
+    use crate::{server::lint::apply_fixes, test_support::parse_fixture};
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    let mod_api = ModApi::from_json(
+        r#"{"entities": {}, "game_functions": {"get_ammo": {"return_type": "i32"}}}"#,
+    )
+    .unwrap();
+
+    let mut stub = |fixture: &str| -> Option<String> {
+        let (source, cursor) = parse_fixture(fixture);
+        let document = Document::new(
+            &mut parser,
+            source.as_bytes().to_vec(),
+            "tired-box.grug".to_string(),
+            lsp_types::Uri::from_str("some_uri").unwrap(),
+        )
+        .unwrap();
+
+        let cursor = crate::server::utils::position_to_byte(&document.content, cursor.unwrap());
+        let node = document
+            .tree
+            .root_node()
+            .descendant_for_byte_range(cursor, cursor)
+            .unwrap();
+        let fix = helper_stub(&document, &mod_api, &node, "    ")?;
+
+        Some(String::from_utf8(apply_fixes(source.as_bytes(), &[fix])).unwrap())
+    };
+
+    assert_eq!(
+        stub(
+            "speed: f32 = 1.0\n\non_tick() {\n    helper_reload$0_gun(speed, get_ammo(), speed, \"fast\", 1 > 2)\n}\n"
+        )
+        .as_deref(),
+        Some(
+            "speed: f32 = 1.0\n\non_tick() {\n    helper_reload_gun(speed, get_ammo(), speed, \"fast\", 1 > 2)\n}\n\nhelper_reload_gun(speed: f32, arg2: i32, speed_2: f32, arg4: string, arg5: bool) {\n    # TODO\n}\n"
+        )
+    );
+    assert_eq!(
+        stub(
+            "on_tick() {\n    x: i32 = helper_get_x($0)\n}\n\nhelper_get_x() i32 {\n    return 1\n}"
+        )
+        .as_deref(),
+        None
+    );
+    assert_eq!(stub("on_tick() {\n    get_am$0mo()\n}\n").as_deref(), None);
+}
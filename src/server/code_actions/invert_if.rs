@@ -0,0 +1,103 @@
+use lsp_types::TextEdit;
+use tree_sitter::Node;
+
+use crate::server::{
+    code_actions::boolean::negate, document::Document, lint::Fix, utils::treesitter_range_to_lsp,
+};
+
+// Negates the condition and swaps the bodies of the if statement the cursor is on the condition of.
+// An `else if` would have to become a nested if, so only a plain `else` is swapped
+pub fn invert_if(document: &Document, node: &Node) -> Option<Fix> {
+    let mut current = Some(*node);
+    let (if_statement, body) = loop {
+        let candidate = current?;
+        if candidate.kind() == "if_statement"
+            && let Some(body) = candidate.child_by_field_name("body")
+            && node.start_byte() < body.start_byte()
+        {
+            break (candidate, body);
+        }
+        current = candidate.parent();
+    };
+
+    let condition = if_statement.child_by_field_name("condition")?;
+    let else_body = if_statement
+        .child_by_field_name("else")
+        .filter(|else_body| else_body.kind() == "body")?;
+
+    let text =
+        |node: &Node| String::from_utf8_lossy(&document.content[node.byte_range()]).to_string();
+    let (negated, _) = negate(document, &condition);
+
+    Some(Fix {
+        title: "Invert if condition".to_string(),
+        edits: vec![
+            TextEdit::new(treesitter_range_to_lsp(&condition.range()), negated),
+            TextEdit::new(treesitter_range_to_lsp(&body.range()), text(&else_body)),
+            TextEdit::new(treesitter_range_to_lsp(&else_body.range()), text(&body)),
+        ],
+    })
+}
+
+#[test]
+fn invert_if_test() {
+    use std::str::FromStr;
+
+    use crate::{server::lint::apply_fixes, test_support::parse_fixture};
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let mut invert = |fixture: &str| -> Option<String> {
+        let (source, cursor) = parse_fixture(fixture);
+        let document = Document::new(
+            &mut parser,
+            source.as_bytes().to_vec(),
+            "tired-box.grug".to_string(),
+            lsp_types::Uri::from_str("some_uri").unwrap(),
+        )
+        .unwrap();
+
+        let cursor = crate::server::utils::position_to_byte(&document.content, cursor.unwrap());
+        let node = document
+            .tree
+            .root_node()
+            .descendant_for_byte_range(cursor, cursor)
+            .unwrap();
+        let fix = invert_if(&document, &node)?;
+
+        Some(String::from_utf8(apply_fixes(source.as_bytes(), &[fix])).unwrap())
+    };
+
+    assert_eq!(
+        invert(
+            "on_tick() {\n    i$0f a < b {\n        x = 1\n    } else {\n        # none left\n        x = 2\n    }\n}\n"
+        )
+        .as_deref(),
+        Some(
+            "on_tick() {\n    if a >= b {\n        # none left\n        x = 2\n    } else {\n        x = 1\n    }\n}\n"
+        )
+    );
+    assert_eq!(
+        invert("on_tick() {\n    if not $0done {\n        x = 1\n    } else {\n        x = 2\n    }\n}\n")
+            .as_deref(),
+        Some("on_tick() {\n    if done {\n        x = 2\n    } else {\n        x = 1\n    }\n}\n")
+    );
+    // Not from inside the bodies, and not without a plain else
+    assert_eq!(
+        invert("on_tick() {\n    if a {\n        $0x = 1\n    } else {\n        x = 2\n    }\n}\n"),
+        None
+    );
+    assert_eq!(
+        invert("on_tick() {\n    if $0a {\n        x = 1\n    }\n}\n"),
+        None
+    );
+    assert_eq!(
+        invert(
+            "on_tick() {\n    if $0a {\n        x = 1\n    } else if b {\n        x = 2\n    }\n}\n"
+        ),
+        None
+    );
+}
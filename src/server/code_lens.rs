@@ -0,0 +1,184 @@
+use std::path::Path;
+
+use lsp_server::ResponseError;
+use lsp_types::{CodeLens, CodeLensParams, Command, Position, Range, Uri};
+use serde::{Deserialize, Serialize};
+
+use crate::server::{
+    Server,
+    document_links::argument_strings,
+    mod_api::GrugArgument,
+    references::find_helper_occurrences,
+    utils::{RequestResult, ok, parse_params, unsupported_uri, uri_to_path},
+};
+
+// What codeLens/resolve needs to count the references, so the lenses themselves stay cheap
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum LensData {
+    // The calls to a helper in its file
+    Helper { uri: Uri, name: String },
+    // The entity strings in the workspace that name the file's entity
+    Entity { uri: Uri },
+}
+
+fn references_title(count: usize) -> String {
+    match count {
+        1 => "1 reference".to_string(),
+        count => format!("{} references", count),
+    }
+}
+
+impl Server {
+    pub fn handle_code_lens(&self, params: CodeLensParams) -> RequestResult {
+        let uri = params.text_document.uri;
+        let Some(path) = uri_to_path(uri.as_str()) else {
+            return Err(unsupported_uri(uri.as_str()));
+        };
+        let Some(document) = self.get_document_by_uri(&uri) else {
+            return ok(None::<Vec<CodeLens>>);
+        };
+
+        let mut lenses: Vec<CodeLens> = Vec::new();
+        if self.get_entity_reference(Path::new(path)).is_some() {
+            lenses.push(CodeLens {
+                range: Range::default(),
+                command: None,
+                data: Some(serde_json::to_value(LensData::Entity { uri: uri.clone() }).unwrap()),
+            });
+        }
+
+        for helper in document.helpers.iter() {
+            let start = helper.range.start_point;
+            let start = Position::new(start.row as u32, start.column as u32);
+            let end = Position::new(start.line, start.character + helper.name.len() as u32);
+
+            lenses.push(CodeLens {
+                range: Range::new(start, end),
+                command: None,
+                data: Some(
+                    serde_json::to_value(LensData::Helper {
+                        uri: uri.clone(),
+                        name: helper.name.clone(),
+                    })
+                    .unwrap(),
+                ),
+            });
+        }
+
+        ok(lenses)
+    }
+
+    // Entity strings can be anywhere in the workspace, so this is what the lenses put off
+    fn count_entity_references(&self, path: &str) -> Result<usize, ResponseError> {
+        let Some(reference) = self.get_entity_reference(Path::new(path)) else {
+            return Ok(0);
+        };
+
+        let mut count = 0;
+        for other in self.document_paths() {
+            if self.past_deadline() {
+                return Err(self.timed_out("Counting the references to the entity"));
+            }
+
+            count += self
+                .with_document(&other, |document| {
+                    argument_strings(document, &self.mod_api)
+                        .into_iter()
+                        .filter(|(_, argument)| matches!(argument, GrugArgument::Entity { .. }))
+                        .filter(|(string, _)| {
+                            let text =
+                                String::from_utf8_lossy(&document.content[string.byte_range()]);
+                            self.entity_string_reference(Path::new(&other), text.trim_matches('"'))
+                                .is_some_and(|other_reference| other_reference == reference)
+                        })
+                        .count()
+                })
+                .unwrap_or(0);
+        }
+
+        Ok(count)
+    }
+
+    pub fn handle_code_lens_resolve(&self, mut lens: CodeLens) -> RequestResult {
+        let Some(data) = lens.data.take() else {
+            return ok(lens);
+        };
+
+        let count = match parse_params("codeLens/resolve", &data)? {
+            LensData::Helper { uri, name } => {
+                self.get_document_by_uri(&uri).map_or(0, |document| {
+                    find_helper_occurrences(document, name.as_bytes())
+                        .iter()
+                        .filter(|occurrence| !occurrence.is_declaration)
+                        .count()
+                })
+            }
+            LensData::Entity { uri } => match uri_to_path(uri.as_str()) {
+                Some(path) => self.count_entity_references(path)?,
+                None => 0,
+            },
+        };
+
+        // Only shows the count, there is nothing to run
+        lens.command = Some(Command {
+            title: references_title(count),
+            command: String::new(),
+            arguments: None,
+        });
+
+        ok(lens)
+    }
+}
+
+#[test]
+fn code_lens_test() {
+    use lsp_types::{
+        TextDocumentIdentifier,
+        request::{CodeLensRequest, CodeLensResolve},
+    };
+
+    use crate::test_support::{TestClient, uri};
+
+    let mut client = TestClient::new();
+    client.open(
+        "mod_api.json",
+        r#"{"entities": {"box": {}}, "game_functions": {
+            "spawn": {"arguments": [{"name": "what", "type": "entity", "entity_type": "box"}]},
+            "print_string": {"arguments": [{"name": "text", "type": "string"}]}
+        }}"#,
+    );
+    client.open(
+        "mods/foo/crate-box.grug",
+        "on_spawn() {\n    helper_open()\n    helper_open()\n}\n\nhelper_open() {\n}\n\nhelper_close() {\n}\n",
+    );
+    client.open(
+        "mods/foo/tired-box.grug",
+        "on_spawn() {\n    spawn(\"crate\")\n    spawn(\"foo:crate\")\n    spawn(\"bar:crate\")\n    print_string(\"crate\")\n}\n",
+    );
+
+    let lenses = client
+        .expect::<CodeLensRequest>(CodeLensParams {
+            text_document: TextDocumentIdentifier::new(uri("mods/foo/crate-box.grug")),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })
+        .unwrap();
+    assert!(lenses.iter().all(|lens| lens.command.is_none()));
+
+    let resolved: Vec<(u32, String)> = lenses
+        .into_iter()
+        .map(|lens| {
+            let lens = client.expect::<CodeLensResolve>(lens);
+            (lens.range.start.line, lens.command.unwrap().title)
+        })
+        .collect();
+    assert_eq!(
+        resolved,
+        [
+            (0, "2 references".to_string()),
+            (5, "2 references".to_string()),
+            (8, "0 references".to_string()),
+        ]
+    );
+}
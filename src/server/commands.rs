@@ -0,0 +1,98 @@
+use lsp_server::ErrorCode;
+use lsp_types::{Command, ExecuteCommandOptions, ExecuteCommandParams, WorkDoneProgressOptions};
+use serde_json::Value;
+use tree_sitter::Parser;
+
+use crate::server::{
+    Server,
+    utils::{RequestResult, request_error},
+};
+
+use log::error;
+use log::info;
+
+type CommandHandler = fn(&mut Server, &mut Parser, ExecuteCommandParams) -> Result<Value, String>;
+
+pub struct CommandDefinition {
+    // What clients bind to, namespaced like grug.formatWorkspace
+    pub name: &'static str,
+    pub title: &'static str,
+    handler: CommandHandler,
+}
+
+// Everything that can be run through workspace/executeCommand
+static COMMANDS: &[CommandDefinition] = &[
+    CommandDefinition {
+        name: "grug.evaluateSelection",
+        title: "Evaluate the selected constant expression",
+        handler: Server::evaluate_selection,
+    },
+    CommandDefinition {
+        name: "grug.formatWorkspace",
+        title: "Format every grug file in the workspace",
+        handler: Server::format_workspace,
+    },
+    CommandDefinition {
+        name: "grug.generateApiDocs",
+        title: "Generate Markdown docs for the mod API",
+        handler: Server::generate_api_docs,
+    },
+    CommandDefinition {
+        name: "grug.renameEntityType",
+        title: "Rename an entity type everywhere",
+        handler: Server::rename_entity_type,
+    },
+    CommandDefinition {
+        name: "grug.reloadModApi",
+        title: "Reload mod_api.json from disk",
+        handler: Server::reload_mod_api,
+    },
+];
+
+pub fn execute_command_options() -> ExecuteCommandOptions {
+    ExecuteCommandOptions {
+        commands: COMMANDS
+            .iter()
+            .map(|command| command.name.to_string())
+            .collect(),
+        work_done_progress_options: WorkDoneProgressOptions {
+            work_done_progress: None,
+        },
+    }
+}
+
+impl Server {
+    // For code actions and code lenses that want to run a command when picked
+    pub fn command(&self, name: &str, arguments: Vec<Value>) -> Option<Command> {
+        let definition = COMMANDS.iter().find(|command| command.name == name)?;
+
+        Some(Command {
+            title: definition.title.to_string(),
+            command: definition.name.to_string(),
+            arguments: (!arguments.is_empty()).then_some(arguments),
+        })
+    }
+
+    pub fn handle_execute_command(
+        &mut self,
+        params: ExecuteCommandParams,
+        parser: &mut Parser,
+    ) -> RequestResult {
+        match COMMANDS
+            .iter()
+            .find(|command| command.name == params.command)
+        {
+            Some(command) => {
+                info!("Executing command {}", command.name);
+                (command.handler)(self, parser, params).map_err(|err| {
+                    error!("Command {} failed: {}", command.name, err);
+                    request_error(ErrorCode::RequestFailed, err)
+                })
+            }
+            None => Err(request_error(
+                ErrorCode::InvalidParams,
+                format!("Unknown command: {}", params.command),
+            )),
+        }
+    }
+}
@@ -1,41 +1,224 @@
-use lsp_server::{Connection, Message, RequestId, Response};
+use std::path::Path;
+
 use lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionParams, Documentation, InsertTextFormat,
-    MarkupContent, MarkupKind,
+    CompletionItem, CompletionItemKind, CompletionParams, CompletionTextEdit, Documentation,
+    InsertTextFormat, MarkupContent, MarkupKind, Position, Range, TextEdit,
 };
 
 use crate::server::{
-    document::{Document, KEYWORDS, PRIMITIVE_TYPES, STATEMENT_SNIPPETS}, utils::{get_nearest_node, get_spot_info}, Server
+    Server,
+    document::{Document, KEYWORDS, PRIMITIVE_TYPES, STATEMENT_SNIPPETS, Type},
+    fuzzy::{filter_text, subword_match, typed_prefix},
+    mod_api::{GrugArgument, GrugEntity},
+    signature_help::open_calls,
+    utils::{
+        RequestResult, escape_snippet_text, get_call_argument, get_nearest_node, get_spot_info, ok,
+        position_to_byte, treesitter_range_to_lsp, unsupported_uri, uri_to_path,
+    },
 };
 
-use log::info;
+use log::{debug, trace};
+
+// Where each kind of item goes in the list, which clients sort by sort_text
+const LOCAL_TIER: u8 = 0;
+const PARAMETER_TIER: u8 = 1;
+const GLOBAL_TIER: u8 = 2;
+const HELPER_TIER: u8 = 3;
+const GAME_FUNCTION_TIER: u8 = 4;
+const KEYWORD_TIER: u8 = 5;
+const SNIPPET_TIER: u8 = 6;
+
+fn sort_text(tier: u8, label: &str) -> Option<String> {
+    Some(format!("{}{}", tier, label))
+}
+
+// `name(${1:first}, ${2:second})`, tabbing through the arguments
+fn call_snippet(name: &str, placeholders: impl Iterator<Item = String>) -> String {
+    let arguments: Vec<String> = placeholders
+        .enumerate()
+        .map(|(idx, placeholder)| format!("${{{}:{}}}", idx + 1, escape_snippet_text(&placeholder)))
+        .collect();
+
+    format!("{}({})", name, arguments.join(", "))
+}
+
+// Scaffolds every on_ function of the entity, used to start off an empty file
+fn entity_template(entity: &GrugEntity, with_todos: bool, as_snippet: bool) -> String {
+    let mut names: Vec<&String> = entity.on_functions.keys().collect();
+    names.sort();
+
+    let mut template = String::new();
+    for (idx, name) in names.iter().enumerate() {
+        let body = if with_todos {
+            let description = &entity.on_functions[*name].description;
+            if as_snippet {
+                format!("# TODO: {}", escape_snippet_text(description))
+            } else {
+                format!("# TODO: {}", description)
+            }
+        } else if as_snippet {
+            format!("${}", idx + 1)
+        } else {
+            String::new()
+        };
+
+        if idx > 0 {
+            template.push('\n');
+        }
+        template.push_str(&format!("{}() {{\n\t{}\n}}\n", name, body));
+    }
+
+    template
+}
 
 impl Server {
+    fn get_template_completion(&self, document: &Document) -> Vec<CompletionItem> {
+        let root = document.tree.root_node();
+        let mut cursor = root.walk();
+        let is_empty = root
+            .named_children(&mut cursor)
+            .all(|child| child.kind() == "comment");
+
+        let Some(entity) = self.mod_api.entities.get(&document.entity_type) else {
+            return Vec::new();
+        };
+        if !is_empty || entity.on_functions.is_empty() {
+            return Vec::new();
+        }
+
+        let as_snippet = self.supports_snippets();
+        let insert_text_format = if as_snippet {
+            InsertTextFormat::SNIPPET
+        } else {
+            InsertTextFormat::PLAIN_TEXT
+        };
+
+        [
+            ("entity template", false),
+            ("entity template with TODOs", true),
+        ]
+        .into_iter()
+        .map(|(label, with_todos)| CompletionItem {
+            label: label.to_string(),
+            detail: Some(format!("All on_ functions of {}", document.entity_type)),
+            kind: Some(CompletionItemKind::SNIPPET),
+            insert_text_format: Some(insert_text_format),
+            insert_text: Some(entity_template(entity, with_todos, as_snippet)),
+            sort_text: sort_text(SNIPPET_TIER, label),
+
+            ..Default::default()
+        })
+        .collect()
+    }
+
+    // Entity strings passed to game functions, with entities from the same mod first
+    fn get_entity_completion(
+        &self,
+        path: &str,
+        document: &Document,
+        string: &tree_sitter::Node<'_>,
+    ) -> Vec<CompletionItem> {
+        let Some((call, idx)) = get_call_argument(string) else {
+            return Vec::new();
+        };
+        let name = call.child_by_field_name("name").unwrap();
+        let Ok(name) = str::from_utf8(&document.content[name.byte_range()]) else {
+            return Vec::new();
+        };
+        let Some(GrugArgument::Entity { entity_type, .. }) = self
+            .mod_api
+            .game_functions
+            .get(name)
+            .and_then(|game_function| game_function.arguments.get(idx))
+        else {
+            return Vec::new();
+        };
+
+        let mod_name = self.get_mod_name(Path::new(path));
+
+        // Replaces everything between the quotes, so the mod name always ends up right
+        let mut range = treesitter_range_to_lsp(&string.range());
+        range.start.character += 1;
+        if string.end_position().column > string.start_position().column + 1 {
+            range.end.character -= 1;
+        }
+
+        self.get_workspace_entities()
+            .into_iter()
+            .filter(|entity| entity.entity_type == *entity_type)
+            .map(|entity| {
+                let reference = entity.reference();
+                let same_mod = mod_name.as_ref() == Some(&entity.mod_name);
+
+                CompletionItem {
+                    label: reference.clone(),
+                    detail: Some(entity.entity_type.clone()),
+                    kind: Some(CompletionItemKind::CLASS),
+                    sort_text: Some(format!("{}{}", if same_mod { 0 } else { 1 }, reference)),
+                    text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(range, reference))),
+
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
     pub fn get_completion(
         &self,
         document: &Document,
         node: &tree_sitter::Node<'_>,
     ) -> Vec<CompletionItem> {
-        let mut items: Vec<CompletionItem> = Vec::new();
+        let mut items: Vec<CompletionItem> = self.get_template_completion(document);
+
+        // The globals come first, then the scopes from the innermost one out
+        let variables = get_spot_info(document, node).variables;
+        let (globals, locals) = variables.split_at(document.global_vars.len());
+        let params: Vec<&tree_sitter::Range> = document
+            .on_functions
+            .iter()
+            .chain(document.helpers.iter())
+            .flat_map(|function| function.params.iter().map(|param| &param.range))
+            .collect();
 
-        let spot_info = get_spot_info(document, node);
+        for (idx, var) in locals.iter().chain(globals).enumerate() {
+            let tier = if idx >= locals.len() {
+                GLOBAL_TIER
+            } else if params.contains(&&var.range) {
+                PARAMETER_TIER
+            } else {
+                LOCAL_TIER
+            };
 
-        for var in spot_info.variables.iter() {
             items.push(CompletionItem {
                 label: var.name.clone(),
                 detail: Some(var.format()),
                 documentation: None,
                 kind: Some(CompletionItemKind::VARIABLE),
+                sort_text: sort_text(tier, &var.name),
 
                 ..Default::default()
             });
         }
         for helper in document.helpers.iter() {
+            // A half typed parameter list would give the snippet the wrong arguments
+            let (insert_text, insert_text_format) = if helper.params_parsed {
+                let placeholders = helper.params.iter().map(|param| param.name.clone());
+                (
+                    Some(call_snippet(&helper.name, placeholders)),
+                    Some(InsertTextFormat::SNIPPET),
+                )
+            } else {
+                (None, None)
+            };
+
             items.push(CompletionItem {
                 label: helper.name.clone(),
                 detail: Some(helper.format().clone()),
                 documentation: None,
-                kind: Some(CompletionItemKind::VARIABLE),
+                insert_text_format,
+                insert_text,
+                kind: Some(CompletionItemKind::FUNCTION),
+                sort_text: sort_text(HELPER_TIER, &helper.name),
 
                 ..Default::default()
             });
@@ -47,14 +230,8 @@ impl Server {
                 value: game_func.description.clone(),
             };
 
-            let mut snippet = format!("{}(", name);
-            for (idx, param) in game_func.arguments.iter().enumerate() {
-                snippet.push_str(&format!("${{{}:{}}}", idx + 1, param.get_name()));
-                if idx < game_func.arguments.len() - 1 {
-                    snippet.push_str(", ");
-                }
-            }
-            snippet.push(')');
+            let placeholders = game_func.arguments.iter().map(|arg| arg.get_placeholder());
+            let snippet = call_snippet(name, placeholders);
 
             items.push(CompletionItem {
                 label: name.clone(),
@@ -63,22 +240,55 @@ impl Server {
                 insert_text_format: Some(InsertTextFormat::SNIPPET),
                 insert_text: Some(snippet),
                 kind: Some(CompletionItemKind::FUNCTION),
+                sort_text: sort_text(GAME_FUNCTION_TIER, name),
 
                 ..Default::default()
             });
         }
 
-        for snippet in STATEMENT_SNIPPETS.values() {
+        // The user's snippets take the place of the game's with the same label, which take the place of built-in ones
+        let user = self.settings.snippets.iter().map(|(label, snippet)| {
+            (
+                label.as_str(),
+                snippet.body.as_str(),
+                snippet.description.as_str(),
+            )
+        });
+        let game = self
+            .mod_api
+            .snippets
+            .iter()
+            .map(|snippet| {
+                (
+                    snippet.label.as_str(),
+                    snippet.body.as_str(),
+                    snippet.description.as_str(),
+                )
+            })
+            .filter(|(label, ..)| !self.settings.snippets.contains_key(*label));
+        let built_in = STATEMENT_SNIPPETS
+            .values()
+            .map(|snippet| (snippet.label, snippet.snippet, snippet.doc))
+            .filter(|(label, ..)| {
+                !self.settings.snippets.contains_key(*label)
+                    && !self
+                        .mod_api
+                        .snippets
+                        .iter()
+                        .any(|snippet| snippet.label == *label)
+            });
+        for (label, snippet, doc) in built_in.chain(game).chain(user) {
             let markup = MarkupContent {
                 kind: MarkupKind::Markdown,
-                value: snippet.doc.to_string(),
+                value: doc.to_string(),
             };
             items.push(CompletionItem {
-                label: snippet.label.to_string(),
+                label: label.to_string(),
                 kind: Some(CompletionItemKind::SNIPPET),
                 insert_text_format: Some(InsertTextFormat::SNIPPET),
-                insert_text: Some(snippet.snippet.to_string()),
+                insert_text: Some(snippet.to_string()),
                 documentation: Some(Documentation::MarkupContent(markup)),
+                sort_text: sort_text(SNIPPET_TIER, label),
                 ..Default::default()
             })
         }
@@ -92,6 +302,7 @@ impl Server {
                 label: name.to_string(),
                 kind: Some(CompletionItemKind::KEYWORD),
                 documentation: Some(Documentation::MarkupContent(markup)),
+                sort_text: sort_text(KEYWORD_TIER, name),
                 ..Default::default()
             });
         }
@@ -101,61 +312,172 @@ impl Server {
                 .parent()
                 .map(|node| node.kind())
                 .unwrap_or("source_file")
+            && let Some(entity) = self.mod_api.entities.get(&document.entity_type)
         {
-            if let Some(entity) = self.mod_api.entities.get(&document.entity_type) {
-                for (func_name, func) in entity.on_functions.iter() {
-                    if !document
-                        .on_functions
-                        .iter()
-                        .any(|func| func.name == *func_name)
-                    {
-                        let markup = MarkupContent {
-                            kind: MarkupKind::Markdown,
-                            value: func.description.clone(),
-                        };
-                        items.push(CompletionItem {
-                            label: func_name.clone(),
-                            detail: Some(func_name.clone()),
-                            documentation: Some(Documentation::MarkupContent(markup)),
-                            kind: Some(CompletionItemKind::FUNCTION),
-
-                            ..Default::default()
-                        })
-                    }
+            for (func_name, func) in entity.on_functions.iter() {
+                if !document
+                    .on_functions
+                    .iter()
+                    .any(|func| func.name == *func_name)
+                {
+                    let markup = MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: func.description.clone(),
+                    };
+                    items.push(CompletionItem {
+                        label: func_name.clone(),
+                        detail: Some(func_name.clone()),
+                        documentation: Some(Documentation::MarkupContent(markup)),
+                        kind: Some(CompletionItemKind::FUNCTION),
+                        sort_text: sort_text(HELPER_TIER, func_name),
+
+                        ..Default::default()
+                    })
                 }
             }
         }
 
+        // Sorting keeps the first of each duplicate, like the innermost of the variables sharing a name
+        items.sort_by(|a, b| a.sort_text.cmp(&b.sort_text));
+        items.dedup_by(|a, b| a.label == b.label && a.kind == b.kind);
+
         items
     }
 
-    pub fn handle_completion(
+    // What the expression being typed has to evaluate to, from the text since it rarely parses yet
+    fn expected_type(
         &self,
-        params: CompletionParams,
-        connection: &mut Connection,
-        id: RequestId,
+        document: &Document,
+        node: &tree_sitter::Node<'_>,
+        before: &[u8],
+    ) -> Option<Type> {
+        let before = before.trim_ascii_end();
+
+        match before.last()? {
+            b'(' | b',' => {
+                let (name, idx) = open_calls(before).pop()?;
+                let name = str::from_utf8(name).ok()?;
+
+                if let Some(game_function) = self.mod_api.game_functions.get(name) {
+                    return game_function
+                        .arguments
+                        .get(idx as usize)
+                        .map(|argument| argument.get_type());
+                }
+
+                let helper = document.get_helper(name.as_bytes())?;
+                helper
+                    .params
+                    .get(idx as usize)
+                    .map(|param| param.r#type.clone())
+            }
+            b'=' => {
+                let target = &before[..before.len() - 1];
+                if matches!(target.last(), Some(b'=' | b'!' | b'<' | b'>')) {
+                    return None;
+                }
+
+                let line_start = target
+                    .iter()
+                    .rposition(|chr| *chr == b'\n')
+                    .map_or(0, |idx| idx + 1);
+                let target = str::from_utf8(&target[line_start..]).ok()?.trim();
+
+                // A declaration says its type, an assignment has to match the variable's
+                if let Some((_, r#type)) = target.split_once(':') {
+                    return Some(Type::from_str(r#type.trim()));
+                }
+                get_spot_info(document, node)
+                    .variables
+                    .into_iter()
+                    .find(|var| var.name == target)
+                    .map(|var| var.r#type)
+            }
+            _ => None,
+        }
+    }
+
+    // Marks the one item enter should insert: the in-scope variable being typed,
+    // or else the only game function that returns what's expected
+    fn preselect(
+        &self,
+        document: &Document,
+        node: &tree_sitter::Node<'_>,
+        prefix: &str,
+        expected: Option<Type>,
+        items: &mut [CompletionItem],
     ) {
+        let variables = get_spot_info(document, node).variables;
+        let fits = |r#type: &Type| expected.as_ref().is_none_or(|expected| expected == r#type);
+
+        let mut best: Vec<usize> = items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| {
+                !prefix.is_empty()
+                    && item.kind == Some(CompletionItemKind::VARIABLE)
+                    && item.label.starts_with(prefix)
+                    && variables
+                        .iter()
+                        .any(|var| var.name == item.label && fits(&var.r#type))
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        // Shadowed variables show up once per scope
+        best.dedup_by_key(|idx| items[*idx].label.clone());
+
+        if best.is_empty() && expected.is_some() {
+            best = items
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| {
+                    item.kind == Some(CompletionItemKind::FUNCTION)
+                        && self
+                            .mod_api
+                            .game_functions
+                            .get(&item.label)
+                            .and_then(|game_function| game_function.return_type.as_ref())
+                            .is_some_and(|return_type| fits(&return_type.as_type()))
+                })
+                .map(|(idx, _)| idx)
+                .collect();
+        }
+
+        if let [idx] = best[..] {
+            items[idx].preselect = Some(true);
+        }
+    }
+
+    pub fn handle_completion(&self, params: CompletionParams) -> RequestResult {
         let uri = params.text_document_position.text_document.uri.as_str();
-        let path = &uri["file.//".len()..];
-        let document = self.document_map.get(path).unwrap();
+        let Some(path) = uri_to_path(uri) else {
+            return Err(unsupported_uri(uri));
+        };
+        let Some(document) = self.document_map.get(path) else {
+            return ok(serde_json::Value::Null);
+        };
 
         let text = if let Ok(src) = str::from_utf8(&document.content) {
             src
         } else {
             log::error!("Invalid message: {:?}", document.content);
-            return;
+            return ok(serde_json::Value::Null);
         };
 
-        let line = if let Some(line) = text
+        // An empty file, or the cursor on a trailing newline, has no line to look at
+        let line = text
             .lines()
             .nth(params.text_document_position.position.line as usize)
-        {
-            line
-        } else {
-            return;
-        };
+            .unwrap_or_default();
 
-        let line = &line[0..params.text_document_position.position.character as usize];
+        // A column inside a character, from a client counting in something else, can't split it
+        let character = params.text_document_position.position.character as usize;
+        let column = line.floor_char_boundary(character);
+        let rest = &line[column..];
+        let suffix = &rest[..rest
+            .find(|chr: char| !(chr.is_ascii_alphanumeric() || chr == '_'))
+            .unwrap_or(rest.len())];
+        let line = &line[0..column];
         let mut is_type = false;
         let mut can_skip = false;
         for chr in line.chars().rev() {
@@ -174,9 +496,21 @@ impl Server {
         }
         let node = get_nearest_node(document, params.text_document_position.position);
 
-        let is_string = node.kind() == "string" || node.kind() == "comment";
+        // The nearest node can be a comment or string that ended before the cursor
+        let point = tree_sitter::Point {
+            row: params.text_document_position.position.line as usize,
+            column: character,
+        };
+        let is_string = match node.kind() {
+            "string" => node.start_position() <= point && point <= node.end_position(),
+            // Comments can include their trailing newline
+            "comment" => node.start_position() <= point && point.row == node.start_position().row,
+            _ => false,
+        };
 
-        let completion = if is_string {
+        let completion = if is_string && node.kind() == "string" {
+            self.get_entity_completion(path, document, &node)
+        } else if is_string {
             Vec::new()
         } else if is_type {
             let mut completion: Vec<CompletionItem> = Vec::new();
@@ -206,9 +540,443 @@ impl Server {
             self.get_completion(document, &node)
         };
 
-        info!("Sending this completion: {:?}", completion);
-        let response = Response::new_ok(id, completion);
+        // Strings are matched by the client against what's between the quotes
+        let completion = if is_string {
+            completion
+        } else {
+            let prefix = typed_prefix(line);
+            let mut completion: Vec<CompletionItem> = completion
+                .into_iter()
+                .filter(|item| subword_match(prefix, &item.label))
+                .map(|item| CompletionItem {
+                    filter_text: item.filter_text.clone().or(filter_text(&item.label)),
+                    ..item
+                })
+                .collect();
+
+            if !is_type {
+                let cursor =
+                    position_to_byte(&document.content, params.text_document_position.position);
+                let before = &document.content[..cursor - prefix.len()];
+                let expected = self.expected_type(document, &node, before);
+                self.preselect(document, &node, prefix, expected, &mut completion);
+            }
+
+            completion
+        };
+
+        let mut completion: Vec<CompletionItem> = completion
+            .into_iter()
+            .map(|item| self.adapt_completion_item(item))
+            .collect();
+
+        // Accepting a completion in the middle of a name shouldn't leave the rest of it behind
+        if !is_string && !suffix.is_empty() {
+            let position = params.text_document_position.position;
+            let start = Position::new(
+                position.line,
+                position.character - typed_prefix(line).len() as u32,
+            );
+            let end = Position::new(position.line, position.character + suffix.len() as u32);
+
+            completion = completion
+                .into_iter()
+                .map(|item| {
+                    self.adapt_completion_edit(
+                        item,
+                        Range::new(start, position),
+                        Range::new(start, end),
+                    )
+                })
+                .collect();
+        }
+
+        debug!("Sending {} completion items", completion.len());
+        trace!("Completion items: {:?}", completion);
+        ok(completion)
+    }
+}
+
+#[test]
+fn entity_template_test() {
+    use std::collections::HashMap;
+
+    use crate::server::mod_api::GrugOnFunction;
+
+    let on_function = |description: &str| GrugOnFunction {
+        description: description.to_string(),
+        arguments: Vec::new(),
+        range: tree_sitter::Range {
+            start_byte: 0,
+            end_byte: 0,
+            start_point: tree_sitter::Point { row: 0, column: 0 },
+            end_point: tree_sitter::Point { row: 0, column: 0 },
+        },
+    };
+    let entity = GrugEntity {
+        description: "A box".to_string(),
+        on_functions: HashMap::from([
+            ("on_tick".to_string(), on_function("Called every tick")),
+            ("on_spawn".to_string(), on_function("Costs $5")),
+        ]),
+        range: on_function("").range,
+    };
+
+    assert_eq!(
+        entity_template(&entity, false, true),
+        "on_spawn() {\n\t$1\n}\n\non_tick() {\n\t$2\n}\n"
+    );
+    assert_eq!(
+        entity_template(&entity, true, true),
+        "on_spawn() {\n\t# TODO: Costs \\$5\n}\n\non_tick() {\n\t# TODO: Called every tick\n}\n"
+    );
+    assert_eq!(
+        entity_template(&entity, false, false),
+        "on_spawn() {\n\t\n}\n\non_tick() {\n\t\n}\n"
+    );
+}
+
+#[test]
+fn user_snippets_test() {
+    use std::collections::HashMap;
+
+    use lsp_types::{CompletionParams, CompletionResponse, request::Completion};
+
+    use crate::{
+        server::settings::{Settings, UserSnippet},
+        test_support::TestClient,
+    };
+
+    let snippet = |body: &str, description: &str| UserSnippet {
+        body: body.to_string(),
+        description: description.to_string(),
+    };
+    let settings = Settings {
+        diagnostics_delay: 0,
+        snippets: HashMap::from([
+            ("if".to_string(), snippet("if ${1:cond} {\n\t$0\n}", "Mine")),
+            (
+                "cooldown".to_string(),
+                snippet("cooldown = ${1:1.0}", "Resets the cooldown"),
+            ),
+        ]),
+        ..Default::default()
+    };
+    let mut client = TestClient::with_settings(settings);
+    client.open("tired-box.grug", "on_spawn() {\n    $0\n}\n");
+
+    let response = client.expect::<Completion>(CompletionParams {
+        text_document_position: client.cursor("tired-box.grug"),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: None,
+    });
+    let Some(CompletionResponse::Array(items)) = response else {
+        panic!("{:?}", response);
+    };
+    // The client has no snippet support, so the documentation is what tells them apart
+    let documentation = |label: &str| -> Vec<Documentation> {
+        items
+            .iter()
+            .filter(|item| item.label == label && item.kind == Some(CompletionItemKind::SNIPPET))
+            .filter_map(|item| item.documentation.clone())
+            .collect()
+    };
+
+    assert_eq!(documentation("if"), [Documentation::String("Mine".into())]);
+    assert_eq!(
+        documentation("cooldown"),
+        [Documentation::String("Resets the cooldown".into())]
+    );
+    assert_eq!(documentation("while").len(), 1);
+}
+
+#[test]
+fn subword_completion_test() {
+    use lsp_types::{CompletionParams, CompletionResponse, request::Completion};
+
+    use crate::test_support::TestClient;
+
+    let mut client = TestClient::new();
+    client.open(
+        "tired-box.grug",
+        "on_spawn() {\n    has$0\n}\n\nhelper_add_score() {\n}\n",
+    );
+
+    let response = client.expect::<Completion>(CompletionParams {
+        text_document_position: client.cursor("tired-box.grug"),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: None,
+    });
+    let Some(CompletionResponse::Array(items)) = response else {
+        panic!("{:?}", response);
+    };
+
+    let items: Vec<(String, Option<String>)> = items
+        .into_iter()
+        .map(|item| (item.label, item.filter_text))
+        .collect();
+    assert_eq!(
+        items,
+        [(
+            "helper_add_score".to_string(),
+            Some("helper_add_score has".to_string())
+        )]
+    );
+}
+
+#[test]
+fn preselect_test() {
+    use lsp_types::{CompletionParams, CompletionResponse, request::Completion};
+
+    use crate::test_support::TestClient;
+
+    let mut client = TestClient::new();
+    client.open(
+        "mod_api.json",
+        r#"{"entities": {}, "game_functions": {
+            "get_speed": {"return_type": "f32"},
+            "get_count": {"return_type": "i32"},
+            "set_speed": {"arguments": [{"name": "speed", "type": "f32"}]}
+        }}"#,
+    );
+
+    let mut preselected = |fixture: &str| {
+        client.open("tired-box.grug", fixture);
+        let response = client.expect::<Completion>(CompletionParams {
+            text_document_position: client.cursor("tired-box.grug"),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        });
+        let Some(CompletionResponse::Array(items)) = response else {
+            panic!("{:?}", response);
+        };
+
+        items
+            .into_iter()
+            .filter(|item| item.preselect == Some(true))
+            .map(|item| item.label)
+            .collect::<Vec<String>>()
+    };
+
+    assert_eq!(
+        preselected("velocity: f32 = 1.0\n\non_spawn() {\n    set_speed(vel$0)\n}\n"),
+        ["velocity"]
+    );
+    assert_eq!(
+        preselected("on_spawn() {\n    set_speed($0)\n}\n"),
+        ["get_speed"]
+    );
+    assert_eq!(
+        preselected("on_spawn() {\n    count: i32 = $0\n}\n"),
+        ["get_count"]
+    );
+    assert!(preselected("on_spawn() {\n    $0\n}\n").is_empty());
+}
+
+#[test]
+fn completion_order_test() {
+    use lsp_types::{CompletionParams, CompletionResponse, request::Completion};
+
+    use crate::test_support::TestClient;
+
+    let mut client = TestClient::new();
+    client.open(
+        "mod_api.json",
+        r#"{"entities": {}, "game_functions": {"sum_b": {}, "sum_a": {}}}"#,
+    );
+    client.open(
+        "tired-box.grug",
+        "sum_global: i32 = 0\n\non_spawn() {\n}\n\nhelper_sum(sum_param: i32) {\n    sum_local: i32 = 1\n    sum$0\n}\n",
+    );
+
+    let response = client.expect::<Completion>(CompletionParams {
+        text_document_position: client.cursor("tired-box.grug"),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: None,
+    });
+    let Some(CompletionResponse::Array(items)) = response else {
+        panic!("{:?}", response);
+    };
+
+    let labels: Vec<String> = items.into_iter().map(|item| item.label).collect();
+    assert_eq!(
+        labels,
+        [
+            "sum_local",
+            "sum_param",
+            "sum_global",
+            "helper_sum",
+            "sum_a",
+            "sum_b"
+        ]
+    );
+}
+
+#[test]
+fn helper_snippet_test() {
+    use lsp_types::{
+        ClientCapabilities, CompletionClientCapabilities, CompletionItemCapability,
+        CompletionParams, CompletionResponse, TextDocumentClientCapabilities, request::Completion,
+    };
+
+    use crate::{server::settings::Settings, test_support::TestClient};
+
+    let capabilities = ClientCapabilities {
+        text_document: Some(TextDocumentClientCapabilities {
+            completion: Some(CompletionClientCapabilities {
+                completion_item: Some(CompletionItemCapability {
+                    snippet_support: Some(true),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let mut client = TestClient::with_capabilities(Settings::default(), capabilities);
+    client.open(
+        "tired-box.grug",
+        "on_spawn() {\n    helper$0\n}\n\nhelper_move(x: f32, y: f32) {\n}\n\nhelper_stop() {\n}\n\nhelper_jump(height: f32, speed) {\n}\n",
+    );
+
+    let response = client.expect::<Completion>(CompletionParams {
+        text_document_position: client.cursor("tired-box.grug"),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: None,
+    });
+    let Some(CompletionResponse::Array(items)) = response else {
+        panic!("{:?}", response);
+    };
 
-        connection.sender.send(Message::Response(response)).unwrap();
+    let mut items: Vec<(String, Option<String>)> = items
+        .into_iter()
+        .map(|item| (item.label, item.insert_text))
+        .collect();
+    items.sort();
+    assert_eq!(
+        items,
+        [
+            ("helper_jump".to_string(), None),
+            (
+                "helper_move".to_string(),
+                Some("helper_move(${1:x}, ${2:y})".to_string())
+            ),
+            ("helper_stop".to_string(), Some("helper_stop()".to_string())),
+        ]
+    );
+}
+
+#[test]
+fn insert_replace_test() {
+    use lsp_types::{
+        ClientCapabilities, CompletionClientCapabilities, CompletionItemCapability,
+        CompletionParams, CompletionResponse, InsertReplaceEdit, TextDocumentClientCapabilities,
+        request::Completion,
+    };
+
+    use crate::{server::settings::Settings, test_support::TestClient};
+
+    let complete = |insert_replace_support: bool| {
+        let capabilities = ClientCapabilities {
+            text_document: Some(TextDocumentClientCapabilities {
+                completion: Some(CompletionClientCapabilities {
+                    completion_item: Some(CompletionItemCapability {
+                        snippet_support: Some(true),
+                        insert_replace_support: Some(insert_replace_support),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut client = TestClient::with_capabilities(Settings::default(), capabilities);
+        client.open(
+            "mod_api.json",
+            r#"{"entities": {}, "game_functions": {"set_gun": {"arguments": [{"name": "gun", "type": "string"}]}}}"#,
+        );
+        client.open(
+            "tired-box.grug",
+            "on_spawn() {\n    set_gu$0n_oldname()\n}\n",
+        );
+
+        let response = client.expect::<Completion>(CompletionParams {
+            text_document_position: client.cursor("tired-box.grug"),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        });
+        let Some(CompletionResponse::Array(items)) = response else {
+            panic!("{:?}", response);
+        };
+
+        items
+            .into_iter()
+            .find(|item| item.label == "set_gun")
+            .and_then(|item| item.text_edit)
+            .unwrap()
+    };
+
+    let insert = Range::new(Position::new(1, 4), Position::new(1, 10));
+    let replace = Range::new(Position::new(1, 4), Position::new(1, 19));
+    assert_eq!(
+        complete(true),
+        CompletionTextEdit::InsertAndReplace(InsertReplaceEdit {
+            new_text: "set_gun(${1:gun})".to_string(),
+            insert,
+            replace,
+        })
+    );
+    assert_eq!(
+        complete(false),
+        CompletionTextEdit::Edit(TextEdit::new(replace, "set_gun(${1:gun})".to_string()))
+    );
+}
+
+#[test]
+fn call_snippet_test() {
+    let placeholders = ["\"$5 {a\\b}\"", "speed"].map(String::from).into_iter();
+
+    // A default's text can't end the placeholder or start a tab stop early
+    assert_eq!(
+        call_snippet("buy", placeholders),
+        "buy(${1:\"\\$5 {a\\\\b\\}\"}, ${2:speed})"
+    );
+}
+
+#[test]
+fn non_ascii_completion_test() {
+    use lsp_types::{
+        CompletionParams, Position, TextDocumentIdentifier, TextDocumentPositionParams,
+        request::Completion,
+    };
+
+    use crate::test_support::{TestClient, uri};
+
+    let mut client = TestClient::new();
+    client.open(
+        "tired-box.grug",
+        "on_spawn() {\n    print_string(\"é unicode\") # 🐸 frog\n}\n",
+    );
+
+    // Every column of the line, including the ones inside é and the emoji
+    for character in 0..40 {
+        let params = CompletionParams {
+            text_document_position: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(uri("tired-box.grug")),
+                Position::new(1, character),
+            ),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        };
+        client.expect::<Completion>(params);
     }
 }
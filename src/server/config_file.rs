@@ -0,0 +1,666 @@
+// `.grug-ls.toml` holds the same settings as the initializationOptions, so studios can
+// check them into the workspace. Only the parts of TOML settings need are understood:
+// tables, arrays of tables, dotted keys, strings, numbers, booleans, arrays and inline tables
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+
+use log::error;
+use log::info;
+
+use crate::server::{Server, settings::Settings};
+
+pub const CONFIG_FILE_NAME: &str = ".grug-ls.toml";
+
+// Objects are merged key by key, anything else in overlay replaces what is in base
+pub fn merge_json(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+pub fn read_config_file(dir: &Path) -> Option<Value> {
+    let path = dir.join(CONFIG_FILE_NAME);
+    let text = std::fs::read_to_string(&path).ok()?;
+
+    match parse_toml(&text) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            error!("Couldn't parse {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+// What the editor sends wins over the workspace's file, it's the user's own choice
+pub fn with_workspace_config(root_path: Option<&Path>, options: Option<Value>) -> Option<Value> {
+    let Some(mut config) = root_path.and_then(read_config_file) else {
+        return options;
+    };
+    if let Some(options) = options {
+        merge_json(&mut config, options);
+    }
+
+    Some(config)
+}
+
+// The directories between the workspace root and the file that could have a .grug-ls.toml,
+// outermost first. The one in the root itself is already in the server's settings
+fn nested_config_dirs(root_path: &Path, path: &Path) -> Vec<PathBuf> {
+    let Some(relative) = path
+        .parent()
+        .and_then(|dir| dir.strip_prefix(root_path).ok())
+    else {
+        return Vec::new();
+    };
+
+    let mut dir = root_path.to_path_buf();
+    relative
+        .components()
+        .map(|component| {
+            dir.push(component);
+            dir.clone()
+        })
+        .collect()
+}
+
+// What `grug-ls check` and `grug-ls format` run with, the command line can only turn ignoring off
+pub fn cli_settings(dir: &Path, respect_ignore_files: bool) -> Settings {
+    let mut settings =
+        Settings::from_initialization_options(with_workspace_config(Some(dir), None));
+    settings.respect_ignore_files &= respect_ignore_files;
+
+    settings
+}
+
+impl Server {
+    // Settings are looked up on every keystroke, so each file is only read once
+    fn config_file(&self, dir: &Path) -> Option<Value> {
+        let mut config_files = self.config_files.lock().unwrap();
+        config_files
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| read_config_file(dir))
+            .clone()
+    }
+
+    // For the watcher, when a .grug-ls.toml is written or removed
+    pub fn forget_config_file(&self, path: &Path) {
+        if let Some(dir) = path.parent() {
+            self.config_files.lock().unwrap().remove(dir);
+        }
+    }
+
+    // The root's file is part of the server's own settings, so they are worked out again
+    // the way they were at startup, with the editor's settings on top
+    pub fn reload_settings(&mut self) {
+        let options = with_workspace_config(
+            self.root_path.as_deref(),
+            self.initialization_options.clone(),
+        );
+        self.settings = Settings::from_initialization_options(options);
+        info!("Settings: {:?}", self.settings);
+    }
+
+    // Mods can override settings like the formatter width or lint levels for their own files.
+    // The editor's settings still win, like they do over the root's file
+    pub fn settings_for(&self, path: &str) -> Cow<'_, Settings> {
+        let Some(root_path) = self.root_path.as_deref() else {
+            return Cow::Borrowed(&self.settings);
+        };
+        let nested: Vec<Value> = nested_config_dirs(root_path, Path::new(path))
+            .iter()
+            .filter_map(|dir| self.config_file(dir))
+            .collect();
+        if nested.is_empty() {
+            return Cow::Borrowed(&self.settings);
+        }
+
+        let mut config = self
+            .config_file(root_path)
+            .unwrap_or_else(|| Value::Object(Map::new()));
+        for overlay in nested {
+            merge_json(&mut config, overlay);
+        }
+        if let Some(options) = self.initialization_options.clone() {
+            merge_json(&mut config, options);
+        }
+
+        Cow::Owned(Settings::from_initialization_options(Some(config)))
+    }
+}
+
+pub fn parse_toml(text: &str) -> Result<Value, String> {
+    let mut parser = TomlParser {
+        chars: text.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+
+    parser
+        .parse()
+        .map_err(|err| format!("line {}: {}", parser.line, err))
+}
+
+struct TomlParser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl TomlParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(idx, chr)| self.chars.get(self.pos + idx) == Some(&chr))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let chr = self.peek()?;
+        self.pos += 1;
+        if chr == '\n' {
+            self.line += 1;
+        }
+
+        Some(chr)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.bump() {
+            Some(chr) if chr == expected => Ok(()),
+            Some(chr) => Err(format!("Expected '{}', found '{}'", expected, chr)),
+            None => Err(format!(
+                "Expected '{}', found the end of the file",
+                expected
+            )),
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    // Inside of arrays, values can be spread over lines with comments in between
+    fn skip_whitespace(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n' | '\r') => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        self.skip_comment();
+        match self.peek() {
+            None => Ok(()),
+            Some('\r' | '\n') => {
+                self.bump();
+                Ok(())
+            }
+            Some(chr) => Err(format!("Unexpected '{}' after a value", chr)),
+        }
+    }
+
+    fn parse(&mut self) -> Result<Value, String> {
+        let mut root = Value::Object(Map::new());
+        // Where key/value pairs go, changed by table headers
+        let mut current: Vec<String> = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+            let Some(chr) = self.peek() else {
+                break;
+            };
+
+            if chr == '[' {
+                self.bump();
+                let is_array = self.peek() == Some('[');
+                if is_array {
+                    self.bump();
+                }
+
+                self.skip_spaces();
+                let path = self.parse_key()?;
+                self.skip_spaces();
+                self.expect(']')?;
+                if is_array {
+                    self.expect(']')?;
+                }
+                self.end_of_line()?;
+
+                if is_array {
+                    let (last, parents) = path.split_last().unwrap();
+                    let parent = table_at(&mut root, parents)?;
+                    let array = parent
+                        .entry(last.clone())
+                        .or_insert_with(|| Value::Array(Vec::new()));
+                    let Value::Array(array) = array else {
+                        return Err(format!("{} isn't an array of tables", path.join(".")));
+                    };
+                    array.push(Value::Object(Map::new()));
+                } else {
+                    table_at(&mut root, &path)?;
+                }
+                current = path;
+                continue;
+            }
+
+            let key = self.parse_key()?;
+            self.skip_spaces();
+            self.expect('=')?;
+            self.skip_spaces();
+            let value = self.parse_value()?;
+
+            let (last, parents) = key.split_last().unwrap();
+            let mut path = current.clone();
+            path.extend_from_slice(parents);
+            let table = table_at(&mut root, &path)?;
+            if table.contains_key(last) {
+                return Err(format!("{} is defined twice", key.join(".")));
+            }
+            table.insert(last.clone(), value);
+            self.end_of_line()?;
+        }
+
+        Ok(root)
+    }
+
+    // Dotted keys, where each part is bare or quoted
+    fn parse_key(&mut self) -> Result<Vec<String>, String> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip_spaces();
+            let part = match self.peek() {
+                Some('"') => self.parse_basic_string()?,
+                Some('\'') => self.parse_literal_string()?,
+                _ => {
+                    let mut part = String::new();
+                    while let Some(chr) = self.peek() {
+                        if !(chr.is_ascii_alphanumeric() || chr == '_' || chr == '-') {
+                            break;
+                        }
+                        part.push(chr);
+                        self.bump();
+                    }
+                    if part.is_empty() {
+                        return Err("Expected a key".to_string());
+                    }
+                    part
+                }
+            };
+            parts.push(part);
+
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(parts);
+            }
+            self.bump();
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => self.parse_basic_string().map(Value::String),
+            Some('\'') => self.parse_literal_string().map(Value::String),
+            Some('[') => {
+                self.bump();
+                let mut values = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some(']') {
+                        self.bump();
+                        return Ok(Value::Array(values));
+                    }
+
+                    values.push(self.parse_value()?);
+                    self.skip_whitespace();
+                    match self.bump() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::Array(values)),
+                        _ => return Err("Expected ',' or ']' in an array".to_string()),
+                    }
+                }
+            }
+            Some('{') => {
+                self.bump();
+                let mut table = Value::Object(Map::new());
+                loop {
+                    self.skip_spaces();
+                    if self.peek() == Some('}') {
+                        self.bump();
+                        return Ok(table);
+                    }
+
+                    let key = self.parse_key()?;
+                    self.skip_spaces();
+                    self.expect('=')?;
+                    self.skip_spaces();
+                    let value = self.parse_value()?;
+
+                    let (last, parents) = key.split_last().unwrap();
+                    table_at(&mut table, parents)?.insert(last.clone(), value);
+
+                    self.skip_spaces();
+                    match self.bump() {
+                        Some(',') => {}
+                        Some('}') => return Ok(table),
+                        _ => return Err("Expected ',' or '}' in an inline table".to_string()),
+                    }
+                }
+            }
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(chr) = self.peek() {
+                    if !(chr.is_ascii_alphanumeric() || matches!(chr, '_' | '-' | '+' | '.')) {
+                        break;
+                    }
+                    word.push(chr);
+                    self.bump();
+                }
+
+                parse_scalar(&word).ok_or_else(|| format!("Invalid value: {}", word))
+            }
+            None => Err("Expected a value".to_string()),
+        }
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String, String> {
+        let multiline = self.starts_with("\"\"\"");
+        if multiline {
+            self.pos += 3;
+            // A newline right after the opening quotes isn't part of the string
+            if self.peek() == Some('\r') {
+                self.bump();
+            }
+            if self.peek() == Some('\n') {
+                self.bump();
+            }
+        } else {
+            self.bump();
+        }
+
+        let mut text = String::new();
+        loop {
+            if multiline && self.starts_with("\"\"\"") {
+                self.pos += 3;
+                return Ok(text);
+            }
+
+            match self.bump() {
+                Some('"') if !multiline => return Ok(text),
+                Some('\n') if !multiline => return Err("Unterminated string".to_string()),
+                Some('\\') => match self.bump() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some('r') => text.push('\r'),
+                    Some('"') => text.push('"'),
+                    Some('\\') => text.push('\\'),
+                    Some('u') => {
+                        let code: String = (0..4).filter_map(|_| self.bump()).collect();
+                        let chr = u32::from_str_radix(&code, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("Invalid escape: \\u{}", code))?;
+                        text.push(chr);
+                    }
+                    // A backslash at the end of a line joins it with the next one
+                    Some('\n') if multiline => self.skip_whitespace(),
+                    Some(chr) => return Err(format!("Invalid escape: \\{}", chr)),
+                    None => return Err("Unterminated string".to_string()),
+                },
+                Some(chr) => text.push(chr),
+                None => return Err("Unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String, String> {
+        let multiline = self.starts_with("'''");
+        if multiline {
+            self.pos += 3;
+            if self.peek() == Some('\n') {
+                self.bump();
+            }
+        } else {
+            self.bump();
+        }
+
+        let mut text = String::new();
+        loop {
+            if multiline && self.starts_with("'''") {
+                self.pos += 3;
+                return Ok(text);
+            }
+
+            match self.bump() {
+                Some('\'') if !multiline => return Ok(text),
+                Some('\n') if !multiline => return Err("Unterminated string".to_string()),
+                Some(chr) => text.push(chr),
+                None => return Err("Unterminated string".to_string()),
+            }
+        }
+    }
+}
+
+fn parse_scalar(word: &str) -> Option<Value> {
+    match word {
+        "true" => return Some(Value::Bool(true)),
+        "false" => return Some(Value::Bool(false)),
+        _ => {}
+    }
+
+    let number = word.replace('_', "");
+    if let Ok(int) = number.parse::<i64>() {
+        return Some(Value::from(int));
+    }
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|float| float.is_finite() && number.chars().any(|chr| chr.is_ascii_digit()))
+        .map(Value::from)
+}
+
+// The table at path, created along the way. Arrays of tables resolve to their last element
+fn table_at<'a>(
+    root: &'a mut Value,
+    path: &[String],
+) -> Result<&'a mut Map<String, Value>, String> {
+    let mut table = root;
+    for key in path {
+        let Value::Object(map) = table else {
+            return Err(format!("{} isn't a table", key));
+        };
+        table = map
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Array(array) = table {
+            table = array
+                .last_mut()
+                .ok_or_else(|| format!("{} is an empty array", key))?;
+        }
+    }
+
+    match table {
+        Value::Object(map) => Ok(map),
+        _ => Err(format!("{} isn't a table", path.join("."))),
+    }
+}
+
+#[test]
+fn parse_toml_test() {
+    use serde_json::json;
+
+    let text = r#"
+# Shared settings for the whole studio
+formatterWidth = 2
+disabledFeatures = [
+    "hover", # too noisy
+    'rename',
+]
+lints = { "syntax-error" = "warning", unused-variable = "off" }
+
+[snippets.cooldown]
+description = "Skips the rest of the function while cooling down"
+body = """
+if cooldown > 0.0 {
+\tcooldown = cooldown - 1.0
+\treturn
+}"""
+
+[[overrides]]
+path = 'mods/legacy'
+ratio = 1_000.5
+"#;
+
+    assert_eq!(
+        parse_toml(text).unwrap(),
+        json!({
+            "formatterWidth": 2,
+            "disabledFeatures": ["hover", "rename"],
+            "lints": { "syntax-error": "warning", "unused-variable": "off" },
+            "snippets": {
+                "cooldown": {
+                    "description": "Skips the rest of the function while cooling down",
+                    "body": "if cooldown > 0.0 {\n\tcooldown = cooldown - 1.0\n\treturn\n}"
+                }
+            },
+            "overrides": [{ "path": "mods/legacy", "ratio": 1000.5 }]
+        })
+    );
+
+    assert_eq!(
+        parse_toml("a = 1\na = 2\n").unwrap_err(),
+        "line 2: a is defined twice"
+    );
+    assert!(parse_toml("a = \"unterminated\n").is_err());
+
+    let mut base = json!({ "lints": { "a": "off" }, "formatterWidth": 2 });
+    merge_json(
+        &mut base,
+        json!({ "lints": { "b": "error" }, "formatterWidth": 4 }),
+    );
+    assert_eq!(
+        base,
+        json!({ "lints": { "a": "off", "b": "error" }, "formatterWidth": 4 })
+    );
+}
+
+#[test]
+fn nested_config_test() {
+    use crate::server::{lint::LintLevel, mod_api::ModApi};
+
+    let root = std::env::temp_dir().join(format!("grug-ls-nested-config-{}", std::process::id()));
+    let dir = root.join("mods").join("legacy");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        root.join(CONFIG_FILE_NAME),
+        "formatterWidth = 8\n[lints]\nunused-variable = \"off\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join(CONFIG_FILE_NAME),
+        "formatterWidth = 2\n[lints]\nmagic-number = \"warning\"\nunused-parameter = \"off\"\n",
+    )
+    .unwrap();
+
+    let options = serde_json::json!({ "lints": { "unused-parameter": "error" } });
+    let settings = Settings::from_initialization_options(with_workspace_config(
+        Some(&root),
+        Some(options.clone()),
+    ));
+    let (watcher, updates) = std::sync::mpsc::channel();
+    let mut server = Server::new(
+        Some(root.clone()),
+        settings,
+        Default::default(),
+        ModApi::default(),
+        updates,
+        crossbeam_channel::unbounded().0,
+    );
+    server.initialization_options = Some(options);
+
+    let legacy_path = dir.join("crate-box.grug");
+    let legacy = server.settings_for(legacy_path.to_str().unwrap());
+    assert_eq!(legacy.formatter_width, 2);
+    assert_eq!(legacy.lints["unused-variable"], LintLevel::Off);
+    assert_eq!(legacy.lints["magic-number"], LintLevel::Warning);
+    assert_eq!(legacy.lints["unused-parameter"], LintLevel::Error);
+
+    let other = root.join("mods").join("foo").join("crate-box.grug");
+    let other = server.settings_for(other.to_str().unwrap());
+    assert!(matches!(other, Cow::Borrowed(_)));
+    assert_eq!(other.formatter_width, 8);
+
+    // The files are only read again once the watcher says they changed
+    std::fs::write(dir.join(CONFIG_FILE_NAME), "formatterWidth = 3\n").unwrap();
+    let width = |server: &Server| {
+        server
+            .settings_for(legacy_path.to_str().unwrap())
+            .formatter_width
+    };
+    assert_eq!(width(&server), 2);
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    watcher
+        .send(crate::server::helper::ServerUpdate::ConfigFileChange(
+            dir.join(CONFIG_FILE_NAME),
+        ))
+        .unwrap();
+    server.handle_worker_messages(&mut parser);
+    assert_eq!(width(&server), 3);
+
+    // Files without a config of their own follow the root's
+    std::fs::write(
+        root.join(CONFIG_FILE_NAME),
+        "formatterWidth = 6
+diagnosticsDelay = 40
+",
+    )
+    .unwrap();
+    watcher
+        .send(crate::server::helper::ServerUpdate::ConfigFileChange(
+            root.join(CONFIG_FILE_NAME),
+        ))
+        .unwrap();
+    server.handle_worker_messages(&mut parser);
+    assert_eq!(server.settings.formatter_width, 6);
+    assert_eq!(server.settings.diagnostics_delay, 40);
+    assert_eq!(server.settings.lints["unused-parameter"], LintLevel::Error);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
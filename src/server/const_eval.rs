@@ -0,0 +1,300 @@
+use std::fmt::Display;
+
+use lsp_types::{ExecuteCommandParams, MessageType, Range, Uri};
+use serde_json::{Value, json};
+use tree_sitter::{Node, Parser};
+
+use crate::server::{
+    Server,
+    document::Document,
+    utils::{get_inner_expression, show_message},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstValue {
+    I32(i32),
+    F32(f32),
+    Bool(bool),
+    String(String),
+}
+
+impl Display for ConstValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConstValue::I32(value) => write!(f, "{}", value),
+            // Debug keeps the decimal point, which grug needs to see an f32
+            ConstValue::F32(value) => write!(f, "{:?}", value),
+            ConstValue::Bool(value) => write!(f, "{}", value),
+            ConstValue::String(value) => write!(f, "\"{}\"", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstErrorKind {
+    DivisionByZero,
+    Overflow,
+    LiteralOutOfRange,
+}
+
+impl ConstErrorKind {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ConstErrorKind::DivisionByZero => "Division by zero",
+            ConstErrorKind::Overflow => "This arithmetic overflows an i32",
+            ConstErrorKind::LiteralOutOfRange => "This number doesn't fit in its type",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    // The expression depends on something only known at runtime, like a variable or a call
+    NotConstant,
+    Error {
+        kind: ConstErrorKind,
+        range: tree_sitter::Range,
+    },
+}
+
+fn error<T>(kind: ConstErrorKind, node: &Node) -> Result<T, EvalError> {
+    Err(EvalError::Error {
+        kind,
+        range: node.range(),
+    })
+}
+
+fn parse_number(content: &[u8], node: &Node, negated: bool) -> Result<ConstValue, EvalError> {
+    let text = str::from_utf8(&content[node.byte_range()]).map_err(|_| EvalError::NotConstant)?;
+    let sign = if negated { "-" } else { "" };
+
+    if text.contains('.') {
+        let value: f32 = format!("{}{}", sign, text)
+            .parse()
+            .map_err(|_| EvalError::NotConstant)?;
+        if value.is_infinite() {
+            return error(ConstErrorKind::LiteralOutOfRange, node);
+        }
+
+        Ok(ConstValue::F32(value))
+    } else {
+        // Parsing with the sign lets -2147483648 through
+        match format!("{}{}", sign, text).parse::<i32>() {
+            Ok(value) => Ok(ConstValue::I32(value)),
+            Err(_) => error(ConstErrorKind::LiteralOutOfRange, node),
+        }
+    }
+}
+
+fn evaluate_binary(
+    node: &Node,
+    operator: &str,
+    left: ConstValue,
+    right: ConstValue,
+) -> Result<ConstValue, EvalError> {
+    use ConstValue::*;
+
+    let value = match (operator, left, right) {
+        ("+", I32(l), I32(r)) => I32(l
+            .checked_add(r)
+            .map_or_else(|| error(ConstErrorKind::Overflow, node), Ok)?),
+        ("-", I32(l), I32(r)) => I32(l
+            .checked_sub(r)
+            .map_or_else(|| error(ConstErrorKind::Overflow, node), Ok)?),
+        ("*", I32(l), I32(r)) => I32(l
+            .checked_mul(r)
+            .map_or_else(|| error(ConstErrorKind::Overflow, node), Ok)?),
+        ("/", I32(l), I32(r)) => I32(l
+            .checked_div(r)
+            .map_or_else(|| error(ConstErrorKind::Overflow, node), Ok)?),
+        ("%", I32(l), I32(r)) => I32(l
+            .checked_rem(r)
+            .map_or_else(|| error(ConstErrorKind::Overflow, node), Ok)?),
+        ("+", F32(l), F32(r)) => F32(l + r),
+        ("-", F32(l), F32(r)) => F32(l - r),
+        ("*", F32(l), F32(r)) => F32(l * r),
+        ("/", F32(l), F32(r)) => F32(l / r),
+
+        ("<", I32(l), I32(r)) => Bool(l < r),
+        (">", I32(l), I32(r)) => Bool(l > r),
+        ("<=", I32(l), I32(r)) => Bool(l <= r),
+        (">=", I32(l), I32(r)) => Bool(l >= r),
+        ("<", F32(l), F32(r)) => Bool(l < r),
+        (">", F32(l), F32(r)) => Bool(l > r),
+        ("<=", F32(l), F32(r)) => Bool(l <= r),
+        (">=", F32(l), F32(r)) => Bool(l >= r),
+
+        ("and", Bool(l), Bool(r)) => Bool(l && r),
+        ("or", Bool(l), Bool(r)) => Bool(l || r),
+
+        ("==", l, r) if std::mem::discriminant(&l) == std::mem::discriminant(&r) => Bool(l == r),
+        ("!=", l, r) if std::mem::discriminant(&l) == std::mem::discriminant(&r) => Bool(l != r),
+
+        // Mismatched types are for the type checker to complain about
+        _ => return Err(EvalError::NotConstant),
+    };
+
+    Ok(value)
+}
+
+pub fn evaluate(content: &[u8], node: &Node) -> Result<ConstValue, EvalError> {
+    match node.kind() {
+        "number" => parse_number(content, node, false),
+        "string" => {
+            let text = &content[node.byte_range()];
+            let text = String::from_utf8(text[1..text.len() - 1].to_vec())
+                .map_err(|_| EvalError::NotConstant)?;
+
+            Ok(ConstValue::String(text))
+        }
+        "identifier" => match &content[node.byte_range()] {
+            b"true" => Ok(ConstValue::Bool(true)),
+            b"false" => Ok(ConstValue::Bool(false)),
+            _ => Err(EvalError::NotConstant),
+        },
+        "contained_expression" | "argument" => {
+            let inner = get_inner_expression(node).ok_or(EvalError::NotConstant)?;
+            evaluate(content, &inner)
+        }
+        "unary_expression" => {
+            let operator = node
+                .child_by_field_name("operator")
+                .ok_or(EvalError::NotConstant)?;
+            let operand = node
+                .child_by_field_name("operand")
+                .ok_or(EvalError::NotConstant)?;
+
+            match (operator.kind(), operand.kind()) {
+                ("-", "number") => parse_number(content, &operand, true),
+                ("-", _) => match evaluate(content, &operand)? {
+                    ConstValue::I32(value) => value
+                        .checked_neg()
+                        .map(ConstValue::I32)
+                        .map_or_else(|| error(ConstErrorKind::Overflow, node), Ok),
+                    ConstValue::F32(value) => Ok(ConstValue::F32(-value)),
+                    _ => Err(EvalError::NotConstant),
+                },
+                ("not", _) => match evaluate(content, &operand)? {
+                    ConstValue::Bool(value) => Ok(ConstValue::Bool(!value)),
+                    _ => Err(EvalError::NotConstant),
+                },
+                _ => Err(EvalError::NotConstant),
+            }
+        }
+        "binary_expression" => {
+            let operator = node
+                .child_by_field_name("operator")
+                .ok_or(EvalError::NotConstant)?;
+            let left = node
+                .child_by_field_name("left")
+                .ok_or(EvalError::NotConstant)?;
+            let right = node
+                .child_by_field_name("right")
+                .ok_or(EvalError::NotConstant)?;
+
+            let left = evaluate(content, &left);
+            let right = evaluate(content, &right);
+
+            // Errors inside either side win over the other side not being constant
+            if let Err(err @ EvalError::Error { .. }) = left {
+                return Err(err);
+            }
+            if let Err(err @ EvalError::Error { .. }) = right {
+                return Err(err);
+            }
+
+            if matches!(operator.kind(), "/" | "%")
+                && matches!(right, Ok(ConstValue::I32(0)) | Ok(ConstValue::F32(0.0)))
+            {
+                return error(ConstErrorKind::DivisionByZero, node);
+            }
+
+            evaluate_binary(node, operator.kind(), left?, right?)
+        }
+        _ => Err(EvalError::NotConstant),
+    }
+}
+
+// Uses the smallest node that covers the whole selection
+pub fn evaluate_range(document: &Document, range: Range) -> Result<ConstValue, String> {
+    let start = tree_sitter::Point {
+        row: range.start.line as usize,
+        column: range.start.character as usize,
+    };
+    let end = tree_sitter::Point {
+        row: range.end.line as usize,
+        column: range.end.character as usize,
+    };
+    let node = document
+        .tree
+        .root_node()
+        .named_descendant_for_point_range(start, end)
+        .ok_or("Nothing is selected")?;
+
+    match evaluate(&document.content, &node) {
+        Ok(value) => Ok(value),
+        Err(EvalError::NotConstant) => Err("The selection isn't a constant expression".to_string()),
+        Err(EvalError::Error { kind, .. }) => Err(kind.message().to_string()),
+    }
+}
+
+impl Server {
+    // Expects the document uri and the selected range as arguments
+    pub fn evaluate_selection(
+        &mut self,
+        _parser: &mut Parser,
+        params: ExecuteCommandParams,
+    ) -> Result<Value, String> {
+        let [uri, range] = params.arguments.as_slice() else {
+            return Err("Expected a document uri and a range".to_string());
+        };
+        let uri: Uri = serde_json::from_value(uri.clone()).map_err(|err| err.to_string())?;
+        let range: Range = serde_json::from_value(range.clone()).map_err(|err| err.to_string())?;
+
+        let document = self
+            .get_document_by_uri(&uri)
+            .ok_or_else(|| format!("{} isn't open", uri.as_str()))?;
+
+        let value = evaluate_range(document, range)?;
+        show_message(&self.client, MessageType::INFO, format!("= {}", value));
+
+        Ok(json!({ "value": value.to_string() }))
+    }
+}
+
+#[test]
+fn evaluate_range_test() {
+    use std::str::FromStr;
+
+    let source = "on_tick() {\n    damage: i32 = (3 + 4) * 2 - speed\n}\n";
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let range = |start: u32, end: u32| {
+        Range::new(
+            lsp_types::Position::new(1, start),
+            lsp_types::Position::new(1, end),
+        )
+    };
+
+    assert_eq!(
+        evaluate_range(&document, range(18, 29)),
+        Ok(ConstValue::I32(14))
+    );
+    assert_eq!(
+        evaluate_range(&document, range(19, 24)),
+        Ok(ConstValue::I32(7))
+    );
+    assert!(evaluate_range(&document, range(18, 37)).is_err());
+}
@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use lsp_types::{Diagnostic, PublishDiagnosticsParams, notification::PublishDiagnostics};
+
+use crate::server::{
+    Server,
+    document::Document,
+    lint::{LintContext, lint},
+    utils::send_notification,
+};
+
+impl Server {
+    pub fn lint_document(&self, path: &str, document: &Document) -> Vec<Diagnostic> {
+        let settings = self.settings_for(path);
+        let context = LintContext {
+            mod_api: &self.mod_api,
+            mod_name: self.get_mod_name(Path::new(path)),
+            entities: self.get_workspace_entities(),
+            magic_number_threshold: settings.magic_number_threshold,
+        };
+
+        lint(document, &context, &settings.lints)
+    }
+
+    pub fn publish_diagnostics(&self, path: &str) {
+        let Some(document) = self.document_map.get(path) else {
+            return;
+        };
+        if self.is_excluded(Path::new(path)) {
+            return;
+        }
+
+        let diagnostics = self.lint_document(path, document);
+        let params = PublishDiagnosticsParams::new(document.uri.clone(), diagnostics, None);
+        send_notification::<PublishDiagnostics>(&self.client, params);
+    }
+
+    // Diagnostics can depend on the mod API, so every open document needs a recheck when it changes
+    pub fn publish_all_diagnostics(&self) {
+        for path in self.open_documents.iter() {
+            self.publish_diagnostics(path);
+        }
+    }
+}
@@ -3,6 +3,8 @@ use std::{borrow::Borrow, collections::HashMap};
 use lazy_static::lazy_static;
 use lsp_types::Uri;
 
+use crate::server::workspace::get_entity_type;
+
 pub struct SnippetCompletion {
     pub label: &'static str,
     pub snippet: &'static str,
@@ -25,32 +27,51 @@ lazy_static! {
         ("bool", "Represents a value that is either true or false"),
         ("string", "Represents text"),
     ]);
-
     pub static ref KEYWORDS: HashMap<&'static str, &'static str> = HashMap::from([
-        ("true", " A value of type `bool` representing logical **true**."),
-        ("false", " A value of type `bool` representing logical **false**."),
+        (
+            "true",
+            " A value of type `bool` representing logical **true**."
+        ),
+        (
+            "false",
+            " A value of type `bool` representing logical **false**."
+        ),
         ("me", "The current entity id"),
-        ("or", "Logical or. Only true if at least 1 of the values are true."),
+        (
+            "or",
+            "Logical or. Only true if at least 1 of the values are true."
+        ),
         ("and", "Logical and. Only true when both values are true."),
-        ("not", "Unary operator; inverts the input (i.e. `true` goes to `false`, and `false goes to `true`)")
+        (
+            "not",
+            "Unary operator; inverts the input (i.e. `true` goes to `false`, and `false goes to `true`)"
+        )
     ]);
-
     pub static ref STATEMENT_SNIPPETS: HashMap<&'static str, SnippetCompletion> = HashMap::from([
-        ("if", SnippetCompletion {
-            label: "if",
-            snippet: "if ${1:condition} {\n\t$0\n}",
-            doc: "Executes code if the condition is true",
-        }),
-        ("while", SnippetCompletion {
-            label: "while",
-            snippet: "while ${1:condition} {\n\t$0\n}",
-            doc: "Continues repeating code while the condition is true",
-        }),
-        ("return", SnippetCompletion {
-            label: "return",
-            snippet: "return ${1:value}",
-            doc: "Stops executing the current function, and returns a specific value",
-        }),
+        (
+            "if",
+            SnippetCompletion {
+                label: "if",
+                snippet: "if ${1:condition} {\n\t$0\n}",
+                doc: "Executes code if the condition is true",
+            }
+        ),
+        (
+            "while",
+            SnippetCompletion {
+                label: "while",
+                snippet: "while ${1:condition} {\n\t$0\n}",
+                doc: "Continues repeating code while the condition is true",
+            }
+        ),
+        (
+            "return",
+            SnippetCompletion {
+                label: "return",
+                snippet: "return ${1:value}",
+                doc: "Stops executing the current function, and returns a specific value",
+            }
+        ),
     ]);
 }
 
@@ -66,7 +87,7 @@ pub enum Type {
 }
 
 impl Type {
-    fn from_str<S: Borrow<str>>(s: S) -> Type {
+    pub fn from_str<S: Borrow<str>>(s: S) -> Type {
         let s = s.borrow();
 
         match s {
@@ -80,7 +101,7 @@ impl Type {
         }
     }
 
-    pub fn as_str<'a>(&'a self) -> &'a str {
+    pub fn as_str(&self) -> &str {
         match self {
             Type::ID => "id",
             Type::Bool => "bool",
@@ -113,6 +134,8 @@ type Parameter = Variable;
 pub struct Function {
     pub name: String,
     pub params: Vec<Parameter>,
+    // False while the parameter list has syntax errors, params can be missing some then
+    pub params_parsed: bool,
     pub ret_type: Option<Type>,
     pub range: tree_sitter::Range,
 }
@@ -124,7 +147,7 @@ impl Function {
         for (idx, param) in self.params.iter().enumerate() {
             out.push_str(&param.name);
             out.push_str(": ");
-            out.push_str(&param.r#type.as_str());
+            out.push_str(param.r#type.as_str());
 
             if idx < self.params.len() - 1 {
                 out.push_str(", ");
@@ -183,36 +206,49 @@ pub mod parser_utils {
         let name = node.child_by_field_name("name").unwrap();
         let kind = node.child_by_field_name("type").unwrap();
 
-        let name = parser_utils::node_get_content(&content, &name);
-        let kind = parser_utils::node_get_content(&content, &kind);
+        let name = parser_utils::node_get_content(content, &name);
+        let kind = parser_utils::node_get_content(content, &kind);
 
         let name = String::from_utf8(name.to_vec())
-            .map_err(|err| ParseVariableDeclarationErr::StringParseError(err))?;
+            .map_err(ParseVariableDeclarationErr::StringParseError)?;
         let kind = String::from_utf8(kind.to_vec())
-            .map_err(|err| ParseVariableDeclarationErr::StringParseError(err))?;
+            .map_err(ParseVariableDeclarationErr::StringParseError)?;
 
         let kind = Type::from_str(kind);
 
-        return Ok(Variable {
+        Ok(Variable {
             name,
             r#type: kind,
             range: node.range(),
-        });
+        })
     }
 }
 
 impl Document {
-    pub fn new(parser: &mut tree_sitter::Parser, content: Vec<u8>, name: String, uri: Uri) -> Document {
-        let tree = parser.parse(&content, None).unwrap();
+    // Helpers are collected from the whole file up front, and grug lets them be
+    // called before their definition, so this doesn't care where `name` is used
+    pub fn get_helper(&self, name: &[u8]) -> Option<&Function> {
+        self.helpers
+            .iter()
+            .find(|helper| helper.name.as_bytes() == name)
+    }
+
+    // Only grug scripts have an entity type in their name, anything else is None
+    pub fn new(
+        parser: &mut tree_sitter::Parser,
+        content: Vec<u8>,
+        name: String,
+        uri: Uri,
+    ) -> Option<Document> {
+        let entity_type = get_entity_type(&name)?.to_string();
+        let tree = parser.parse(&content, None)?;
 
         let mut cursor = tree.root_node().walk();
         let global_vars: Vec<Variable> = tree
             .root_node()
             .children(&mut cursor)
             .filter(|child| child.kind() == "variable_declaration")
-            .map(|child| parser_utils::parse_variable_declaration(&content, &child))
-            .filter(|var| var.is_ok())
-            .map(|var| var.unwrap())
+            .flat_map(|child| parser_utils::parse_variable_declaration(&content, &child))
             .collect();
 
         cursor.reset(tree.root_node());
@@ -233,35 +269,28 @@ impl Document {
                     })
                     .filter_map(|decl| {
                         let name = decl.child_by_field_name("name").unwrap();
-                        let ret_type = decl
-                            .child_by_field_name("ret_type")
-                            .map(|ret_type| {
-                                let ret_type = parser_utils::node_get_content(&content, &ret_type);
-                                let ret_type = String::from_utf8(ret_type.to_vec()).ok()?;
-
-                                let ret_type = match ret_type.as_str() {
-                                    "f32" => Type::F32,
-                                    "i32" => Type::F32,
-                                    "id" => Type::ID,
-                                    "string" => Type::String,
-                                    _ => {
-                                        return None;
-                                    }
-                                };
-
-                                Some(ret_type)
-                            })
-                            .flatten();
+                        let ret_type = decl.child_by_field_name("ret_type").and_then(|ret_type| {
+                            let ret_type = parser_utils::node_get_content(&content, &ret_type);
+                            let ret_type = String::from_utf8(ret_type.to_vec()).ok()?;
+
+                            Some(Type::from_str(ret_type))
+                        });
 
                         let name = parser_utils::node_get_content(&content, &name);
                         let name = String::from_utf8(name.to_vec()).ok()?;
 
                         let mut cursor = decl.walk();
+                        let params_parsed = !decl
+                            .children(&mut cursor)
+                            .filter(|child| child.kind() != "body")
+                            .any(|child| child.has_error());
                         let params: Vec<Parameter> = decl
                             .children_by_field_name("param", &mut cursor)
-                            .find_map(|param| {
-                                let name = param.child_by_field_name("name").unwrap();
-                                let kind = param.child_by_field_name("type").unwrap();
+                            // The commas between parameters are part of the field too
+                            .filter(|param| param.kind() == "function_parameter")
+                            .filter_map(|param| {
+                                let name = param.child_by_field_name("name")?;
+                                let kind = param.child_by_field_name("type")?;
 
                                 let name = parser_utils::node_get_content(&content, &name);
                                 let kind = parser_utils::node_get_content(&content, &kind);
@@ -276,12 +305,12 @@ impl Document {
                                     range: param.range(),
                                 })
                             })
-                            .into_iter()
                             .collect();
 
                         Some(Function {
                             name,
                             params,
+                            params_parsed,
                             ret_type,
                             range: decl.range(),
                         })
@@ -290,25 +319,50 @@ impl Document {
             };
         }
 
-        let entity_type = name
-            .split('-')
-            .last()
-            .unwrap()
-            .strip_suffix(".grug")
-            .unwrap();
-
         let helpers: Vec<Function> = parse_functions!(tree, "helper_identifier");
         let on_functions: Vec<Function> = parse_functions!(tree, "on_identifier");
 
         drop(cursor);
-        Document {
+        Some(Document {
             content,
             tree,
             global_vars,
             helpers,
             on_functions,
-            entity_type: entity_type.to_string(),
+            entity_type,
             uri,
-        }
+        })
     }
 }
+
+#[test]
+fn forward_helper_test() {
+    use std::str::FromStr;
+
+    let source = r#"on_spawn() {
+    helper_later(1)
+}
+
+helper_later(x: i32, scale: f32) i32 {
+    return x
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let helper = document.get_helper(b"helper_later").unwrap();
+    assert_eq!(helper.range.start_point.row, 4);
+    assert_eq!(helper.format(), "helper_later(x: i32, scale: f32) i32");
+    assert!(document.get_helper(b"helper_missing").is_none());
+}
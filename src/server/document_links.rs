@@ -0,0 +1,217 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use lsp_types::{DocumentLink, DocumentLinkParams, Uri};
+use serde::{Deserialize, Serialize};
+use tree_sitter::Node;
+
+use crate::server::{
+    Server,
+    document::Document,
+    mod_api::{GrugArgument, ModApi},
+    utils::{
+        RequestResult, get_call_argument, get_descendants_of_kind, ok, parse_params,
+        treesitter_range_to_lsp, unsupported_uri, uri_to_path,
+    },
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum LinkKind {
+    Entity,
+    Resource,
+}
+
+// Everything documentLink/resolve needs to find the target, so the links themselves stay cheap
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LinkData {
+    // The grug file the string is in
+    path: String,
+    kind: LinkKind,
+    text: String,
+}
+
+// The string literals passed to game functions, along with the argument they are passed as
+pub fn argument_strings<'a, 'b>(
+    document: &'a Document,
+    mod_api: &'b ModApi,
+) -> Vec<(Node<'a>, &'b GrugArgument)> {
+    get_descendants_of_kind(&document.tree.root_node(), "string")
+        .into_iter()
+        .filter_map(|string| {
+            let (call, idx) = get_call_argument(&string)?;
+            let name = call.child_by_field_name("name")?;
+            let name = str::from_utf8(&document.content[name.byte_range()]).ok()?;
+            let argument = mod_api.game_functions.get(name)?.arguments.get(idx)?;
+
+            Some((string, argument))
+        })
+        .collect()
+}
+
+impl Server {
+    // Resources are relative to the directory of the mod the script is in
+    fn resolve_resource(&self, path: &Path, text: &str) -> Option<PathBuf> {
+        let resource = self.get_mod_dir(path)?.join(text);
+        self.file_system
+            .exists(resource.to_str()?)
+            .then_some(resource)
+    }
+
+    // Entity and resource strings passed to game functions, without looking up where they lead yet
+    pub fn handle_document_link(&self, params: DocumentLinkParams) -> RequestResult {
+        let uri = params.text_document.uri;
+        let Some(path) = uri_to_path(uri.as_str()) else {
+            return Err(unsupported_uri(uri.as_str()));
+        };
+        let Some(document) = self.get_document_by_uri(&uri) else {
+            return ok(None::<Vec<DocumentLink>>);
+        };
+
+        let mut links: Vec<DocumentLink> = Vec::new();
+        for (string, argument) in argument_strings(document, &self.mod_api) {
+            let kind = match argument {
+                GrugArgument::Entity { .. } => LinkKind::Entity,
+                GrugArgument::Resource { .. } => LinkKind::Resource,
+                _ => continue,
+            };
+
+            // The link covers what's between the quotes
+            let mut range = string.range();
+            let text = &document.content[string.byte_range()];
+            if text.len() < 3 || range.start_point.row != range.end_point.row {
+                continue;
+            }
+            range.start_byte += 1;
+            range.start_point.column += 1;
+            range.end_byte -= 1;
+            range.end_point.column -= 1;
+
+            links.push(DocumentLink {
+                range: treesitter_range_to_lsp(&range),
+                target: None,
+                tooltip: None,
+                data: Some(
+                    serde_json::to_value(LinkData {
+                        path: path.to_string(),
+                        kind,
+                        text: String::from_utf8_lossy(&text[1..text.len() - 1]).to_string(),
+                    })
+                    .unwrap(),
+                ),
+            });
+        }
+
+        ok(links)
+    }
+
+    // Links that can't be resolved are sent back as they came
+    pub fn handle_document_link_resolve(&self, mut link: DocumentLink) -> RequestResult {
+        let Some(data) = link.data.as_ref() else {
+            return ok(link);
+        };
+        let data: LinkData = parse_params("documentLink/resolve", data)?;
+        let path = Path::new(&data.path);
+
+        let target = match data.kind {
+            LinkKind::Entity => self
+                .resolve_entity_string(path, &data.text)
+                .map(|entity| PathBuf::from(entity.path)),
+            LinkKind::Resource => self.resolve_resource(path, &data.text),
+        };
+
+        link.target =
+            target.and_then(|target| Uri::from_str(&format!("file://{}", target.to_str()?)).ok());
+        if link.target.is_some() {
+            link.data = None;
+        }
+
+        ok(link)
+    }
+}
+
+#[test]
+fn document_link_test() {
+    use lsp_types::{
+        TextDocumentIdentifier,
+        request::{DocumentLinkRequest, DocumentLinkResolve},
+    };
+
+    use crate::test_support::{TestClient, uri};
+
+    let mut client = TestClient::new();
+    client.open(
+        "mod_api.json",
+        r#"{"entities": {"box": {}}, "game_functions": {
+            "spawn": {"arguments": [{"name": "what", "type": "entity", "entity_type": "box"}]},
+            "play_sound": {"arguments": [{"name": "sound", "type": "resource", "resource_extension": ".wav"}]},
+            "print_string": {"arguments": [{"name": "text", "type": "string"}]}
+        }}"#,
+    );
+    client.open("mods/foo/crate-box.grug", "");
+    client.open(
+        "mods/foo/tired-box.grug",
+        "on_spawn() {\n    spawn(\"crate\")\n    spawn(\"gone\")\n    play_sound(\"sounds/thud.wav\")\n    print_string(\"crate\")\n}\n",
+    );
+
+    let links = client
+        .expect::<DocumentLinkRequest>(DocumentLinkParams {
+            text_document: TextDocumentIdentifier::new(uri("mods/foo/tired-box.grug")),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        })
+        .unwrap();
+    let ranges: Vec<(u32, u32, u32)> = links
+        .iter()
+        .map(|link| {
+            (
+                link.range.start.line,
+                link.range.start.character,
+                link.range.end.character,
+            )
+        })
+        .collect();
+    assert_eq!(ranges, [(1, 11, 16), (2, 11, 15), (3, 16, 31)]);
+    assert!(links.iter().all(|link| link.target.is_none()));
+
+    let targets: Vec<Option<Uri>> = links
+        .into_iter()
+        .map(|link| client.expect::<DocumentLinkResolve>(link).target)
+        .collect();
+    assert_eq!(targets, [Some(uri("mods/foo/crate-box.grug")), None, None]);
+}
+
+#[test]
+fn resource_link_test() {
+    use crate::server::{mod_api::ModApi, settings::Settings};
+
+    let root = std::env::temp_dir().join(format!("grug-ls-resource-link-{}", std::process::id()));
+    let sounds = root.join("mods").join("foo").join("sounds");
+    std::fs::create_dir_all(&sounds).unwrap();
+    std::fs::write(sounds.join("thud.wav"), "").unwrap();
+
+    let server = Server::new(
+        Some(root.clone()),
+        Settings::default(),
+        Default::default(),
+        ModApi::default(),
+        std::sync::mpsc::channel().1,
+        crossbeam_channel::unbounded().0,
+    );
+    let script = root
+        .join("mods")
+        .join("foo")
+        .join("ai")
+        .join("tired-box.grug");
+
+    assert_eq!(
+        server.resolve_resource(&script, "sounds/thud.wav"),
+        Some(sounds.join("thud.wav"))
+    );
+    assert_eq!(server.resolve_resource(&script, "sounds/boom.wav"), None);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,373 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use lsp_server::{ErrorCode, ResponseError};
+use lsp_types::{ExecuteCommandParams, RenameFile, RenameParams, TextEdit, Uri, WorkspaceEdit};
+use serde_json::Value;
+use tree_sitter::{Node, Parser, Point};
+
+use crate::server::{
+    Server,
+    mod_api::JSON_PARSER,
+    utils::{RequestResult, get_descendants_of_kind, ok, request_error, treesitter_range_to_lsp},
+    workspace::{get_entity_name, get_entity_type},
+};
+
+use log::info;
+
+// The text between the quotes of a JSON string
+pub fn string_content(json: &[u8], string: &Node) -> Option<tree_sitter::Range> {
+    if string.kind() != "string" || json.get(string.byte_range())?.len() < 2 {
+        return None;
+    }
+
+    let mut range = string.range();
+    range.start_byte += 1;
+    range.start_point.column += 1;
+    range.end_byte -= 1;
+    range.end_point.column -= 1;
+    Some(range)
+}
+
+// The "entities" object at the top of mod_api.json
+pub fn entities_object<'a>(json: &[u8], root: Node<'a>) -> Option<Node<'a>> {
+    let root = root.child(0).filter(|root| root.kind() == "object")?;
+
+    let mut cursor = root.walk();
+    let entities = root
+        .children(&mut cursor)
+        .filter(|entry| entry.kind() == "pair")
+        .find(|entry| {
+            entry
+                .child_by_field_name("key")
+                .and_then(|key| string_content(json, &key))
+                .is_some_and(|key| &json[key.start_byte..key.end_byte] == b"entities")
+        })?;
+
+    entities
+        .child_by_field_name("value")
+        .filter(|value| value.kind() == "object")
+}
+
+// Everywhere mod_api.json names the entity type: its key under "entities",
+// and the types of the arguments and return values that take one
+fn entity_type_strings(json: &[u8], root: Node, entity_type: &str) -> Vec<tree_sitter::Range> {
+    let mut ranges: Vec<tree_sitter::Range> = Vec::new();
+
+    if let Some(entities) = entities_object(json, root) {
+        let mut cursor = entities.walk();
+        ranges.extend(
+            entities
+                .children(&mut cursor)
+                .filter_map(|entry| string_content(json, &entry.child_by_field_name("key")?))
+                .filter(|key| &json[key.start_byte..key.end_byte] == entity_type.as_bytes()),
+        );
+    }
+
+    for pair in get_descendants_of_kind(&root, "pair") {
+        let Some(key) = pair
+            .child_by_field_name("key")
+            .and_then(|key| string_content(json, &key))
+        else {
+            continue;
+        };
+        if !matches!(
+            &json[key.start_byte..key.end_byte],
+            b"type" | b"entity_type" | b"return_type"
+        ) {
+            continue;
+        }
+
+        if let Some(value) = pair
+            .child_by_field_name("value")
+            .and_then(|value| string_content(json, &value))
+            .filter(|value| &json[value.start_byte..value.end_byte] == entity_type.as_bytes())
+        {
+            ranges.push(value);
+        }
+    }
+
+    ranges
+}
+
+impl Server {
+    pub fn mod_api_text(&self) -> Option<Vec<u8>> {
+        if let Some(buffer) = &self.mod_api_buffer {
+            return Some(buffer.as_bytes().to_vec());
+        }
+
+        self.file_system.read(self.mod_api_path()?.to_str()?)
+    }
+
+    // Renaming an entity key in mod_api.json renames the entity type everywhere,
+    // including the `<name>-<entity type>.grug` scripts for it
+    pub fn rename_mod_api_entity(&self, params: RenameParams) -> RequestResult {
+        let Some(json) = self.mod_api_text() else {
+            return ok(serde_json::Value::Null);
+        };
+        let Some(tree) = JSON_PARSER.lock().unwrap().parse(&json, None) else {
+            return ok(serde_json::Value::Null);
+        };
+        let root = tree.root_node();
+
+        let position = params.text_document_position.position;
+        let point = Point {
+            row: position.line as usize,
+            column: position.character as usize,
+        };
+        let old_name = entities_object(&json, root).and_then(|entities| {
+            let mut cursor = entities.walk();
+            entities
+                .children(&mut cursor)
+                .filter_map(|entry| entry.child_by_field_name("key"))
+                .filter(|key| key.start_position() <= point && point <= key.end_position())
+                .find_map(|key| string_content(&json, &key))
+                .map(|key| String::from_utf8_lossy(&json[key.start_byte..key.end_byte]).to_string())
+        });
+        let Some(old_name) = old_name else {
+            return ok(serde_json::Value::Null);
+        };
+
+        ok(self.rename_entity_type_edit(&old_name, params.new_name)?)
+    }
+
+    fn rename_entity_type_edit(
+        &self,
+        old_name: &str,
+        new_name: String,
+    ) -> Result<WorkspaceEdit, ResponseError> {
+        let Some(json) = self.mod_api_text() else {
+            return Err(request_error(
+                ErrorCode::RequestFailed,
+                "mod_api.json couldn't be read".to_string(),
+            ));
+        };
+        let Some(tree) = JSON_PARSER.lock().unwrap().parse(&json, None) else {
+            return Err(request_error(
+                ErrorCode::RequestFailed,
+                "mod_api.json couldn't be parsed".to_string(),
+            ));
+        };
+        let root = tree.root_node();
+
+        if !self.mod_api.entities.contains_key(old_name) {
+            return Err(request_error(
+                ErrorCode::InvalidParams,
+                format!("There is no entity called {}", old_name),
+            ));
+        }
+        if new_name.is_empty() || new_name.contains(['-', '"', '/', '\\']) {
+            return Err(request_error(
+                ErrorCode::InvalidParams,
+                format!("\"{}\" can't be the name of an entity type", new_name),
+            ));
+        }
+        if self.mod_api.entities.contains_key(&new_name) {
+            return Err(request_error(
+                ErrorCode::InvalidParams,
+                format!("There already is an entity called {}", new_name),
+            ));
+        }
+
+        info!("Renaming the entity type {} to {}", old_name, new_name);
+        let mut changes: HashMap<Uri, Vec<TextEdit>> = HashMap::new();
+
+        let edits: Vec<TextEdit> = entity_type_strings(&json, root, old_name)
+            .iter()
+            .map(|range| TextEdit::new(treesitter_range_to_lsp(range), new_name.clone()))
+            .collect();
+        if let Some(uri) = self.mod_api_uri() {
+            changes.insert(uri, edits);
+        }
+
+        let mut renames: Vec<RenameFile> = Vec::new();
+        for path in self.document_paths() {
+            if self.past_deadline() {
+                return Err(self.timed_out("Renaming the entity type"));
+            }
+
+            // Variables and parameters can be declared with the entity type
+            self.with_document(&path, |document| {
+                let edits: Vec<TextEdit> =
+                    get_descendants_of_kind(&document.tree.root_node(), "type")
+                        .iter()
+                        .filter(|node| &document.content[node.byte_range()] == old_name.as_bytes())
+                        .map(|node| {
+                            TextEdit::new(treesitter_range_to_lsp(&node.range()), new_name.clone())
+                        })
+                        .collect();
+
+                if !edits.is_empty() {
+                    changes.insert(document.uri.clone(), edits);
+                }
+            });
+
+            let old_path = Path::new(&path);
+            let Some(file_name) = old_path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if get_entity_type(file_name) != Some(old_name) {
+                continue;
+            }
+            let Some(entity_name) = get_entity_name(file_name) else {
+                continue;
+            };
+
+            let new_path: PathBuf =
+                old_path.with_file_name(format!("{}-{}.grug", entity_name, new_name));
+            let (Ok(old_uri), Some(new_uri)) = (
+                Uri::from_str(&format!("file://{}", path)),
+                new_path
+                    .to_str()
+                    .and_then(|new_path| Uri::from_str(&format!("file://{}", new_path)).ok()),
+            ) else {
+                continue;
+            };
+
+            // Entity strings follow the files, like they do when a file is renamed by hand
+            for (uri, mut edits) in self.get_entity_rename_edits(old_path, &new_path)? {
+                changes.entry(uri).or_default().append(&mut edits);
+            }
+
+            renames.push(RenameFile {
+                old_uri,
+                new_uri,
+                options: None,
+                annotation_id: None,
+            });
+        }
+
+        Ok(self.make_workspace_edit_with_renames(changes, renames))
+    }
+
+    // grug.renameEntityType, with the old and new name as arguments, for editors that want to
+    // rename an entity type without going through mod_api.json
+    pub fn rename_entity_type(
+        &mut self,
+        _parser: &mut Parser,
+        params: ExecuteCommandParams,
+    ) -> Result<Value, String> {
+        let [Value::String(old_name), Value::String(new_name)] = params.arguments.as_slice() else {
+            return Err("Expected the old and new name of the entity type".to_string());
+        };
+
+        let edit = self
+            .rename_entity_type_edit(old_name, new_name.clone())
+            .map_err(|err| err.message)?;
+        self.apply_edit(&format!("Rename {} to {}", old_name, new_name), edit)?;
+
+        Ok(Value::Null)
+    }
+}
+
+#[test]
+fn rename_mod_api_entity_test() {
+    use lsp_types::{
+        ClientCapabilities, DocumentChangeOperation, DocumentChanges, ResourceOp,
+        ResourceOperationKind, TextDocumentPositionParams, WorkspaceClientCapabilities,
+        WorkspaceEdit, WorkspaceEditClientCapabilities, request::Rename,
+    };
+
+    use crate::{
+        server::settings::Settings,
+        test_support::{TestClient, uri},
+    };
+
+    let capabilities = ClientCapabilities {
+        workspace: Some(WorkspaceClientCapabilities {
+            workspace_edit: Some(WorkspaceEditClientCapabilities {
+                document_changes: Some(true),
+                resource_operations: Some(vec![ResourceOperationKind::Rename]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let mut client = TestClient::with_capabilities(Settings::default(), capabilities);
+
+    client.open(
+        "mod_api.json",
+        r#"{
+    "entities": {"b$0ox": {"description": "A box"}},
+    "game_functions": {
+        "spawn": {"arguments": [{"name": "what", "type": "entity", "entity_type": "box"}]},
+        "get_box": {"return_type": "box"}
+    }
+}"#,
+    );
+    client.open(
+        "mods/foo/crate-box.grug",
+        "other: box = get_box()\n\non_spawn() {\n    spawn(\"crate\")\n}\n",
+    );
+
+    let rename = |client: &mut TestClient, new_name: &str| {
+        client.request::<Rename>(RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: client.cursor("mod_api.json").text_document,
+                position: client.cursor("mod_api.json").position,
+            },
+            new_name: new_name.to_string(),
+            work_done_progress_params: Default::default(),
+        })
+    };
+
+    assert!(rename(&mut client, "big-box").is_err());
+
+    let Ok(Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(operations)),
+        ..
+    })) = rename(&mut client, "crate_box")
+    else {
+        panic!();
+    };
+
+    let mut edits: Vec<(String, u32, u32)> = Vec::new();
+    let mut renames: Vec<(Uri, Uri)> = Vec::new();
+    for operation in operations {
+        match operation {
+            DocumentChangeOperation::Edit(edit) => {
+                assert!(renames.is_empty(), "files are renamed after the edits");
+                let file = edit.text_document.uri.path().to_string();
+                for edit in edit.edits {
+                    let lsp_types::OneOf::Left(edit) = edit else {
+                        panic!();
+                    };
+                    assert_eq!(edit.new_text, "crate_box");
+                    edits.push((
+                        file.clone(),
+                        edit.range.start.line,
+                        edit.range.start.character,
+                    ));
+                }
+            }
+            DocumentChangeOperation::Op(ResourceOp::Rename(rename)) => {
+                renames.push((rename.old_uri, rename.new_uri));
+            }
+            operation => panic!("{:?}", operation),
+        }
+    }
+    edits.sort();
+
+    let json = uri("mod_api.json").path().to_string();
+    let script = uri("mods/foo/crate-box.grug").path().to_string();
+    assert_eq!(
+        edits,
+        [
+            (json.clone(), 1, 18),
+            (json.clone(), 3, 83),
+            (json, 4, 36),
+            (script, 0, 7),
+        ]
+    );
+    assert_eq!(
+        renames,
+        [(
+            uri("mods/foo/crate-box.grug"),
+            uri("mods/foo/crate-crate_box.grug")
+        )]
+    );
+}
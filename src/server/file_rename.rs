@@ -0,0 +1,223 @@
+use std::{collections::HashMap, path::Path};
+
+use lsp_server::ResponseError;
+use lsp_types::{MessageType, RenameFilesParams, TextEdit, Uri};
+use tree_sitter::{Node, Parser};
+
+use crate::server::{
+    Server,
+    document::Document,
+    utils::{RequestResult, ok, show_message, treesitter_range_to_lsp, uri_to_path},
+};
+
+use log::info;
+
+fn collect_entity_strings(
+    document: &Document,
+    node: &Node,
+    reference: &str,
+    new_reference: &str,
+    out: &mut Vec<TextEdit>,
+) {
+    if node.kind() == "string" {
+        let text = &document.content[node.byte_range()];
+        if text.len() >= 2 && &text[1..text.len() - 1] == reference.as_bytes() {
+            let mut range = node.range();
+            range.start_point.column += 1;
+            range.end_point.column -= 1;
+
+            out.push(TextEdit::new(
+                treesitter_range_to_lsp(&range),
+                new_reference.to_string(),
+            ));
+        }
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_entity_strings(document, &child, reference, new_reference, out);
+    }
+}
+
+impl Server {
+    pub fn get_entity_rename_edits(
+        &self,
+        old_path: &Path,
+        new_path: &Path,
+    ) -> Result<HashMap<Uri, Vec<TextEdit>>, ResponseError> {
+        let mut changes: HashMap<Uri, Vec<TextEdit>> = HashMap::new();
+
+        let (Some(old_reference), Some(new_reference)) = (
+            self.get_entity_reference(old_path),
+            self.get_entity_reference(new_path),
+        ) else {
+            return Ok(changes);
+        };
+        if old_reference == new_reference {
+            return Ok(changes);
+        }
+
+        info!("Renaming entity {} to {}", old_reference, new_reference);
+        for path in self.document_paths() {
+            if self.past_deadline() {
+                return Err(self.timed_out("Updating the references to the entity"));
+            }
+
+            self.with_document(&path, |document| {
+                let mut edits: Vec<TextEdit> = Vec::new();
+                collect_entity_strings(
+                    document,
+                    &document.tree.root_node(),
+                    &old_reference,
+                    &new_reference,
+                    &mut edits,
+                );
+
+                if !edits.is_empty() {
+                    changes.insert(document.uri.clone(), edits);
+                }
+            });
+        }
+
+        Ok(changes)
+    }
+
+    pub fn handle_will_rename_files(&self, params: RenameFilesParams) -> RequestResult {
+        let mut changes: HashMap<Uri, Vec<TextEdit>> = HashMap::new();
+
+        for file in params.files.iter() {
+            let (Some(old_path), Some(new_path)) = (
+                uri_to_path(&file.old_uri).map(Path::new),
+                uri_to_path(&file.new_uri).map(Path::new),
+            ) else {
+                continue;
+            };
+            let Some(new_name) = new_path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let Some(stem) = new_name.strip_suffix(".grug") else {
+                continue;
+            };
+
+            let entity_type = stem.split('-').next_back().unwrap();
+            if !self.mod_api.entities.contains_key(entity_type) {
+                show_message(
+                    &self.client,
+                    MessageType::WARNING,
+                    format!(
+                        "{} doesn't end in the name of an entity from mod_api.json, \"{}\" is unknown",
+                        new_name, entity_type
+                    ),
+                );
+            }
+
+            for (uri, mut edits) in self.get_entity_rename_edits(old_path, new_path)? {
+                changes.entry(uri).or_default().append(&mut edits);
+            }
+        }
+
+        let edit = if changes.is_empty() {
+            None
+        } else {
+            Some(self.make_workspace_edit(changes))
+        };
+
+        ok(edit)
+    }
+
+    // Moves renamed documents to their new path, which also recomputes their entity type
+    pub fn handle_did_rename_files(&mut self, params: RenameFilesParams, parser: &mut Parser) {
+        for file in params.files {
+            let (Some(old_path), Some(new_path)) =
+                (uri_to_path(&file.old_uri), uri_to_path(&file.new_uri))
+            else {
+                continue;
+            };
+            if !new_path.ends_with(".grug") {
+                continue;
+            }
+
+            self.rename_document(old_path, new_path, parser);
+        }
+    }
+}
+
+#[test]
+fn rename_files_test() {
+    use lsp_types::{
+        FileRename, WorkspaceEdit,
+        notification::{DidRenameFiles, Notification, PublishDiagnostics},
+        request::WillRenameFiles,
+    };
+
+    use crate::test_support::{TestClient, uri};
+
+    let mut client = TestClient::new();
+    client.open(
+        "mod_api.json",
+        r#"{
+    "entities": {"box": {"description": "A box"}},
+    "game_functions": {
+        "spawn": {"arguments": [{"name": "what", "type": "entity", "entity_type": "box"}]}
+    }
+}"#,
+    );
+    client.open("mods/foo/crate-box.grug", "on_spawn() {\n}\n");
+    client.open(
+        "mods/foo/tired-box.grug",
+        "on_spawn() {\n    spawn(\"foo:crate\")\n}\n",
+    );
+
+    let params = RenameFilesParams {
+        files: vec![FileRename {
+            old_uri: uri("mods/foo/crate-box.grug").to_string(),
+            new_uri: uri("mods/foo/big-box.grug").to_string(),
+        }],
+    };
+    let Some(WorkspaceEdit {
+        changes: Some(changes),
+        ..
+    }) = client.expect::<WillRenameFiles>(params.clone())
+    else {
+        panic!();
+    };
+    assert_eq!(changes.len(), 1);
+    let edits = &changes[&uri("mods/foo/tired-box.grug")];
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].new_text, "foo:big");
+    assert_eq!(edits[0].range.start, lsp_types::Position::new(1, 11));
+
+    // Edited but not checked yet when the editor renames it
+    client.change(
+        "mods/foo/crate-box.grug",
+        "on_spawn() {\n    spawn(\"foo:tired\")\n}\n",
+    );
+    client.notify::<DidRenameFiles>(params);
+
+    let server = client.server();
+    let (old_path, new_path) = (
+        "/grug-ls-fixture/mods/foo/crate-box.grug",
+        "/grug-ls-fixture/mods/foo/big-box.grug",
+    );
+    for path in [old_path, new_path] {
+        let moved = path == new_path;
+        assert_eq!(server.open_documents.contains(path), moved);
+        assert_eq!(server.document_map.contains_key(path), moved);
+        assert_eq!(server.generations.contains_key(path), moved);
+        assert_eq!(server.load_order.iter().any(|loaded| loaded == path), moved);
+    }
+    assert_eq!(server.document_versions.get(old_path), None);
+    assert_eq!(server.document_versions.get(new_path), Some(&2));
+    let document = &server.document_map[new_path];
+    assert_eq!(document.uri, uri("mods/foo/big-box.grug"));
+    assert!(String::from_utf8_lossy(&document.content).contains("foo:tired"));
+
+    // The old URI is cleared and the document is checked again under the new one
+    assert!(client.diagnostics("mods/foo/big-box.grug").is_empty());
+    assert!(client.notifications().iter().any(|notification| {
+        notification.method == PublishDiagnostics::METHOD
+            && notification.params["uri"] == uri("mods/foo/crate-box.grug").as_str()
+            && notification.params["diagnostics"] == serde_json::json!([])
+    }));
+}
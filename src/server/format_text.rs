@@ -0,0 +1,154 @@
+use lsp_server::ErrorCode;
+use lsp_types::{Uri, request::Request};
+use serde::{Deserialize, Serialize};
+use tree_sitter::Parser;
+
+use crate::server::{
+    Server,
+    formatting::FormatOptions,
+    utils::{RequestResult, line_ending, ok, request_error, uri_to_path},
+};
+
+// grug-ls/formatText, for editor extensions that format code before inserting it
+pub enum FormatTextRequest {}
+
+impl Request for FormatTextRequest {
+    type Params = FormatTextParams;
+    type Result = FormatTextResult;
+    const METHOD: &'static str = "grug-ls/formatText";
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FormatTextMode {
+    // A whole file, with globals and functions
+    #[default]
+    File,
+    // Statements from a function body
+    Statement,
+    Expression,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatTextParams {
+    pub text: String,
+    #[serde(default)]
+    pub mode: FormatTextMode,
+    // The file the text is going into, for its formatter settings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<Uri>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatTextResult {
+    pub text: String,
+}
+
+// Statements and expressions are formatted inside a file made up around them
+fn wrap(text: &str, mode: FormatTextMode, newline: &str) -> String {
+    match mode {
+        FormatTextMode::File => text.to_string(),
+        FormatTextMode::Statement => {
+            format!("on_a() {{{}{}{}}}{}", newline, text, newline, newline)
+        }
+        FormatTextMode::Expression => format!("a: i32 = {}{}", text, newline),
+    }
+}
+
+fn unwrap(formatted: &str, mode: FormatTextMode, options: &FormatOptions, newline: &str) -> String {
+    let lines: Vec<&str> = formatted.trim_end().split(newline).collect();
+
+    match mode {
+        FormatTextMode::File => formatted.to_string(),
+        FormatTextMode::Statement => lines
+            .get(1..lines.len().saturating_sub(1))
+            .unwrap_or_default()
+            .iter()
+            .map(|line| line.strip_prefix(options.indent.as_str()).unwrap_or(line))
+            .collect::<Vec<&str>>()
+            .join(newline),
+        FormatTextMode::Expression => lines
+            .join(newline)
+            .strip_prefix("a: i32 = ")
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+pub fn format_text(
+    parser: &mut Parser,
+    options: &FormatOptions,
+    text: &str,
+    mode: FormatTextMode,
+) -> Option<String> {
+    let newline = line_ending(text.as_bytes());
+    let wrapped = wrap(text, mode, newline);
+
+    let tree = parser.parse(&wrapped, None)?;
+    let formatted = Server::format_source(options, wrapped.as_bytes(), &tree.root_node())?;
+    let mut formatted = unwrap(&formatted, mode, options, newline);
+
+    // Snippets keep whether they end in a newline, so they can be inserted as they were
+    if mode != FormatTextMode::File && text.ends_with('\n') && !formatted.is_empty() {
+        formatted.push_str(newline);
+    }
+    Some(formatted)
+}
+
+impl Server {
+    pub fn handle_format_text(
+        &self,
+        params: FormatTextParams,
+        parser: &mut Parser,
+    ) -> RequestResult {
+        let settings = match &params.uri {
+            Some(uri) => self.settings_for(uri_to_path(uri.as_str()).unwrap_or_default()),
+            None => std::borrow::Cow::Borrowed(&self.settings),
+        };
+        let options = FormatOptions::from_settings(&settings);
+
+        match format_text(parser, &options, &params.text, params.mode) {
+            Some(text) => ok(FormatTextResult { text }),
+            None => Err(request_error(
+                ErrorCode::RequestFailed,
+                "The text has syntax errors, so it can't be formatted".to_string(),
+            )),
+        }
+    }
+}
+
+#[test]
+fn format_text_test() {
+    use crate::test_support::TestClient;
+
+    let mut client = TestClient::new();
+    let mut format = |text: &str, mode: FormatTextMode| {
+        client
+            .request::<FormatTextRequest>(FormatTextParams {
+                text: text.to_string(),
+                mode,
+                uri: None,
+            })
+            .map(|result| result.text)
+    };
+
+    assert_eq!(
+        format(
+            "on_spawn(){\nprint_string( \"hi\" )\n}",
+            FormatTextMode::File
+        )
+        .unwrap(),
+        "on_spawn() {\n    print_string(\"hi\")\n}\n\n"
+    );
+    assert_eq!(
+        format("if x>1{\ny=2\n}\nz( x )\n", FormatTextMode::Statement).unwrap(),
+        "if x > 1 {\n    y = 2\n}\nz(x)\n"
+    );
+    assert_eq!(
+        format("(a+b)  *c", FormatTextMode::Expression).unwrap(),
+        "(a + b) * c"
+    );
+    assert!(format("if {", FormatTextMode::Statement).is_err());
+}
@@ -1,20 +1,81 @@
-use lsp_server::{Connection, Message, RequestId, Response};
-use lsp_types::{DocumentFormattingParams, TextEdit};
-use tree_sitter::Node;
+use std::{collections::HashMap, path::Path};
+
+use lsp_server::ErrorCode;
+use lsp_types::{DocumentFormattingParams, ExecuteCommandParams, TextEdit, Uri};
+use serde_json::{Value, json};
+use tree_sitter::{Node, Parser};
+
+use crate::server::{
+    Server,
+    config_file::cli_settings,
+    settings::{Parentheses, Settings},
+    utils::{
+        RequestResult, line_ending, needs_parentheses, ok, precedence, request_error,
+        strip_parentheses, treesitter_range_to_lsp, unsupported_uri, uri_to_path,
+    },
+    workspace::collect_grug_files_except,
+};
+
+pub struct FormatOptions {
+    pub indent: String,
+    pub keep_banners: bool,
+    pub parentheses: Parentheses,
+}
+
+impl FormatOptions {
+    pub fn from_settings(settings: &Settings) -> FormatOptions {
+        FormatOptions {
+            indent: " ".repeat(settings.formatter_width),
+            keep_banners: settings.formatter_keep_banners,
+            parentheses: settings.formatter_parentheses,
+        }
+    }
+}
+
+// Banners are drawn with punctuation, like `#=====` or `# /\_/\`
+fn is_banner(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_punctuation())
+        || (!text.trim().is_empty() && !text.chars().any(|c| c.is_alphanumeric()))
+}
+
+fn format_comment(options: &FormatOptions, comment: &str) -> String {
+    let comment = comment.trim_end();
+    let text = comment.strip_prefix('#').unwrap_or(comment);
+
+    if options.keep_banners && is_banner(text) {
+        return comment.to_string();
+    }
+
+    match text.trim_start() {
+        "" => "#".to_string(),
+        text => format!("# {}", text),
+    }
+}
+
+// How far a comment that shares its line with the code before it goes after that code
+fn trailing_comment_gap(content: &[u8], previous: Option<&Node>, node: &Node) -> Option<usize> {
+    let previous = previous?;
+    if node.kind() != "comment"
+        || previous.kind() == "comment"
+        || previous.end_position().row != node.start_position().row
+    {
+        return None;
+    }
 
-use crate::server::{Server, utils::treesitter_range_to_lsp};
+    Some(content[previous.end_byte()..node.start_byte()].len().max(1))
+}
 
 impl Server {
-    fn format_node(content: &[u8], node: &Node) -> Vec<String> {
+    fn format_node(options: &FormatOptions, content: &[u8], node: &Node) -> Vec<String> {
         let lines: Vec<String> = match node.kind() {
             "variable_declaration" => {
                 let name_node = node.child_by_field_name("name").unwrap();
-                let name = Self::format_node(content, &name_node);
+                let name = Self::format_node(options, content, &name_node);
                 assert_eq!(name.len(), 1);
                 let name = &name[0];
 
                 let type_node = node.child_by_field_name("type").unwrap();
-                let type_name = Self::format_node(content, &type_node);
+                let type_name = Self::format_node(options, content, &type_node);
                 assert_eq!(type_name.len(), 1);
                 let type_name = &type_name[0];
 
@@ -22,7 +83,7 @@ impl Server {
 
                 if let Some(value_text) = node
                     .child_by_field_name("value")
-                    .map(|node| Self::format_node(content, &node))
+                    .map(|node| Self::format_node(options, content, &node))
                 {
                     assert_eq!(value_text.len(), 1);
                     text.push_str(&format!(" = {}", value_text[0]));
@@ -32,7 +93,7 @@ impl Server {
             }
             "function_call" => {
                 let function_name =
-                    Self::format_node(content, &node.child_by_field_name("name").unwrap());
+                    Self::format_node(options, content, &node.child_by_field_name("name").unwrap());
                 assert_eq!(function_name.len(), 1);
 
                 let mut text = format!("{}(", function_name[0]);
@@ -44,7 +105,7 @@ impl Server {
                 let mut cursor = node.walk();
                 let params = node.children_by_field_name("argument", &mut cursor);
                 for (idx, param) in params.enumerate() {
-                    let param = Self::format_node(content, &param);
+                    let param = Self::format_node(options, content, &param);
                     assert_eq!(param.len(), 1);
                     let param = &param[0];
 
@@ -58,62 +119,95 @@ impl Server {
                 text.push(')');
                 vec![text]
             }
-            "identifier" | "number" | "type" | "on_identifier" | "helper_identifier"
-            | "comment" | "me" | "+" | "-" | "*" | "/" | "string" | "not" | "empty_return"
-            | "<" | ">" | "<=" | ">=" | "==" | "!=" | "and" | "or" | "boolean" => {
+            "comment" => {
+                let text = String::from_utf8_lossy(&content[node.byte_range()]);
+                vec![format_comment(options, &text)]
+            }
+            "identifier" | "number" | "type" | "on_identifier" | "helper_identifier" | "me"
+            | "+" | "-" | "*" | "/" | "string" | "not" | "empty_return" | "<" | ">" | "<="
+            | ">=" | "==" | "!=" | "and" | "or" | "boolean" => {
                 let text_bytes = &content[node.byte_range()];
 
                 let text: String = text_bytes
-                    .into_iter()
+                    .iter()
                     .filter_map(|c| match c {
-                        b'\n' => None,
+                        b'\n' | b'\r' => None,
                         c => Some(*c as char),
                     })
                     .collect();
 
                 vec![text]
             }
-            "argument" => Self::format_node(content, &node.child(0).unwrap()),
+            "argument" => Self::format_node(options, content, &node.child(0).unwrap()),
             "binary_expression" => {
-                let left =
-                    &Self::format_node(content, &node.child_by_field_name("left").unwrap())[0];
-                let right =
-                    &Self::format_node(content, &node.child_by_field_name("right").unwrap())[0];
-                let operator =
-                    &Self::format_node(content, &node.child_by_field_name("operator").unwrap())[0];
+                let left = &Self::format_node(
+                    options,
+                    content,
+                    &node.child_by_field_name("left").unwrap(),
+                )[0];
+                let right = &Self::format_node(
+                    options,
+                    content,
+                    &node.child_by_field_name("right").unwrap(),
+                )[0];
+                let operator = &Self::format_node(
+                    options,
+                    content,
+                    &node.child_by_field_name("operator").unwrap(),
+                )[0];
 
                 let text = format!("{} {} {}", left, operator, right);
 
                 vec![text]
             }
             "unary_expression" => {
-                let operand =
-                    &Self::format_node(content, &node.child_by_field_name("operand").unwrap())[0];
-                let operator =
-                    &Self::format_node(content, &node.child_by_field_name("operator").unwrap())[0];
+                let operand = &Self::format_node(
+                    options,
+                    content,
+                    &node.child_by_field_name("operand").unwrap(),
+                )[0];
+                let operator = &Self::format_node(
+                    options,
+                    content,
+                    &node.child_by_field_name("operator").unwrap(),
+                )[0];
 
                 let operator = if operator == "not" { "not " } else { operator };
 
                 vec![format!("{}{}", operator, operand)]
             }
             "contained_expression" => {
-                let expr =
-                    &Self::format_node(content, &node.child(1).unwrap())[0];
+                let expr = &Self::format_node(options, content, &node.child(1).unwrap())[0];
 
-                vec![format!("({})", expr)]
+                let redundant = options.parentheses == Parentheses::Minimal
+                    && !needs_parentheses(node, precedence(&strip_parentheses(*node)));
+                if redundant {
+                    vec![expr.clone()]
+                } else {
+                    vec![format!("({})", expr)]
+                }
             }
             "assignment" => {
-                let name =
-                    &Self::format_node(content, &node.child_by_field_name("name").unwrap())[0];
-
-                let value =
-                    &Self::format_node(content, &node.child_by_field_name("value").unwrap())[0];
+                let name = &Self::format_node(
+                    options,
+                    content,
+                    &node.child_by_field_name("name").unwrap(),
+                )[0];
+
+                let value = &Self::format_node(
+                    options,
+                    content,
+                    &node.child_by_field_name("value").unwrap(),
+                )[0];
 
                 vec![format!("{} = {}", name, value)]
             }
             "return_statement" => {
-                let value =
-                    &Self::format_node(content, &node.child_by_field_name("value").unwrap())[0];
+                let value = &Self::format_node(
+                    options,
+                    content,
+                    &node.child_by_field_name("value").unwrap(),
+                )[0];
 
                 vec![format!("return {}", value)]
             }
@@ -123,8 +217,11 @@ impl Server {
                 } else {
                     "while"
                 };
-                let condition =
-                    Self::format_node(content, &node.child_by_field_name("condition").unwrap());
+                let condition = Self::format_node(
+                    options,
+                    content,
+                    &node.child_by_field_name("condition").unwrap(),
+                );
 
                 assert_eq!(condition.len(), 1);
                 let condition = &condition[0];
@@ -133,12 +230,12 @@ impl Server {
 
                 let mut lines: Vec<String> = Vec::new();
                 let mut body =
-                    Self::format_node(content, &node.child_by_field_name("body").unwrap());
+                    Self::format_node(options, content, &node.child_by_field_name("body").unwrap());
                 body[0] = format!("{} {}", text, body[0]);
                 lines.append(&mut body);
 
                 if let Some(else_node) = node.child_by_field_name("else") {
-                    let mut else_text = Self::format_node(content, &else_node);
+                    let mut else_text = Self::format_node(options, content, &else_node);
 
                     let last_line = lines.last_mut().unwrap();
                     *last_line = format!("{} else {}", *last_line, else_text.remove(0));
@@ -164,17 +261,34 @@ impl Server {
                 let content_str = String::from_utf8(content.to_vec()).unwrap();
                 let mut current_child: usize = 0;
 
-                let content_lines: Vec<&str> = content_str.lines().into_iter().collect();
+                let content_lines: Vec<&str> = content_str.lines().collect();
                 let line_amt = content_lines.len();
                 let mut line_idx: usize = 0;
                 let mut can_push_line = false;
                 while line_idx < line_amt && current_child < children.len() {
                     let mut child = &children[current_child];
-                    if line_idx >= child.start_position().row {
-                        let new_line = Self::format_node(content, child);
+                    let previous = match current_child {
+                        0 => node.child(0),
+                        idx => Some(children[idx - 1]),
+                    };
+                    if let Some(gap) = trailing_comment_gap(content, previous.as_ref(), child) {
+                        let comment = &Self::format_node(options, content, child)[0];
+                        let line = stmt_lines.last_mut().or(lines.last_mut()).unwrap();
+                        line.push_str(&" ".repeat(gap));
+                        line.push_str(comment);
+
+                        current_child += 1;
+
+                        if let Some(next_child) = children.get(current_child) {
+                            child = next_child;
+                        } else {
+                            break;
+                        }
+                    } else if line_idx >= child.start_position().row {
+                        let new_line = Self::format_node(options, content, child);
                         let mut new_line: Vec<String> = new_line
                             .into_iter()
-                            .map(|line| format!("    {}", line))
+                            .map(|line| format!("{}{}", options.indent, line))
                             .collect();
                         stmt_lines.append(&mut new_line);
 
@@ -202,7 +316,7 @@ impl Server {
                     }
                 }
 
-                if children.len() == 0 {
+                if children.is_empty() {
                     stmt_lines.push("".to_string());
                 }
 
@@ -226,14 +340,28 @@ impl Server {
                 let content_str = String::from_utf8(content.to_vec()).unwrap();
                 let mut current_child: usize = 0;
 
-                let content_lines: Vec<&str> = content_str.lines().into_iter().collect();
+                let content_lines: Vec<&str> = content_str.lines().collect();
                 let line_amt = content_lines.len();
                 let mut line_idx: usize = 0;
                 let mut can_push_line = false;
                 while line_idx < line_amt && current_child < children.len() {
                     let mut child = &children[current_child];
-                    if line_idx >= child.start_position().row {
-                        let mut new_line = Self::format_node(content, child);
+                    let previous = current_child.checked_sub(1).map(|idx| children[idx]);
+                    if let Some(gap) = trailing_comment_gap(content, previous.as_ref(), child) {
+                        let comment = &Self::format_node(options, content, child)[0];
+                        let line = stmt_lines.last_mut().unwrap();
+                        line.push_str(&" ".repeat(gap));
+                        line.push_str(comment);
+
+                        current_child += 1;
+
+                        if let Some(next_child) = children.get(current_child) {
+                            child = next_child;
+                        } else {
+                            break;
+                        }
+                    } else if line_idx >= child.start_position().row {
+                        let mut new_line = Self::format_node(options, content, child);
                         stmt_lines.append(&mut new_line);
 
                         current_child += 1;
@@ -265,10 +393,14 @@ impl Server {
                 lines
             }
             "function_declaration" => {
-                let name =
-                    &Self::format_node(content, &node.child_by_field_name("name").unwrap())[0];
+                let name = &Self::format_node(
+                    options,
+                    content,
+                    &node.child_by_field_name("name").unwrap(),
+                )[0];
 
-                let body = Self::format_node(content, &node.child_by_field_name("body").unwrap());
+                let body =
+                    Self::format_node(options, content, &node.child_by_field_name("body").unwrap());
 
                 let mut decl_line = format!("{}(", name);
 
@@ -282,7 +414,7 @@ impl Server {
                     if param.kind() == "," {
                         continue;
                     }
-                    let param = &Self::format_node(content, &param)[0];
+                    let param = &Self::format_node(options, content, &param)[0];
                     decl_line.push_str(param);
 
                     if idx < param_amt - 1 {
@@ -294,7 +426,7 @@ impl Server {
                 decl_line.push(')');
 
                 if let Some(ret_node) = node.child_by_field_name("ret_type") {
-                    let ret_type = &Self::format_node(content, &ret_node)[0];
+                    let ret_type = &Self::format_node(options, content, &ret_node)[0];
                     decl_line.push_str(&format!(" {}", ret_type));
                 }
 
@@ -304,45 +436,549 @@ impl Server {
                 lines
             }
             "function_parameter" => {
-                let name =
-                    &Self::format_node(content, &node.child_by_field_name("name").unwrap())[0];
-                let param_type =
-                    &Self::format_node(content, &node.child_by_field_name("type").unwrap())[0];
+                let name = &Self::format_node(
+                    options,
+                    content,
+                    &node.child_by_field_name("name").unwrap(),
+                )[0];
+                let param_type = &Self::format_node(
+                    options,
+                    content,
+                    &node.child_by_field_name("type").unwrap(),
+                )[0];
 
                 vec![format!("{}: {}", name, param_type)]
             }
 
+            // Anything else is kept as it was written, rather than dropped
             _ => {
                 log::error!("Cannot format node: {:?}", node);
-                Vec::new()
+                String::from_utf8_lossy(&content[node.byte_range()])
+                    .lines()
+                    .map(|line| line.trim_end_matches('\r').to_string())
+                    .collect()
             }
         };
 
         lines
     }
 
-    pub fn formatting(
-        &self,
-        params: DocumentFormattingParams,
-        connection: &mut Connection,
-        id: RequestId,
-    ) {
+    fn format_root(options: &FormatOptions, content: &[u8], root: &Node) -> String {
+        let mut new_lines: Vec<String> = Self::format_node(options, content, root);
+
+        new_lines.push("".to_string());
+        new_lines.push("".to_string());
+
+        new_lines.join(line_ending(content))
+    }
+
+    // Files with syntax errors are left alone, since the formatter would drop the broken parts
+    pub fn format_source(options: &FormatOptions, content: &[u8], root: &Node) -> Option<String> {
+        if root.has_error() {
+            return None;
+        }
+
+        Some(Self::format_root(options, content, root))
+    }
+
+    pub fn format_workspace(
+        &mut self,
+        _parser: &mut Parser,
+        params: ExecuteCommandParams,
+    ) -> Result<Value, String> {
+        let paths = self.document_paths();
+
+        let progress = self.begin_progress(
+            params.work_done_progress_params.work_done_token,
+            "Formatting the workspace",
+        );
+
+        let mut changes: HashMap<Uri, Vec<TextEdit>> = HashMap::new();
+        let mut skipped: Vec<String> = Vec::new();
+        for (idx, path) in paths.iter().enumerate() {
+            progress.report(path.to_string(), (idx * 100 / paths.len()) as u32);
+
+            let options = FormatOptions::from_settings(&self.settings_for(path));
+            let formatted = self.with_document(path, |document| {
+                if self.is_large_file(&document.content) {
+                    return None;
+                }
+
+                let root = document.tree.root_node();
+                let text = Self::format_source(&options, &document.content, &root)?;
+
+                let edit = (text.as_bytes() != document.content).then(|| {
+                    let range = treesitter_range_to_lsp(&root.range());
+                    (document.uri.clone(), TextEdit::new(range, text))
+                });
+                Some(edit)
+            });
+
+            match formatted.flatten() {
+                Some(Some((uri, edit))) => {
+                    changes.insert(uri, vec![edit]);
+                }
+                Some(None) => {}
+                None => skipped.push(path.to_string()),
+            }
+        }
+
+        let formatted = changes.len();
+        let applied = if changes.is_empty() {
+            Ok(())
+        } else {
+            let edit = self.make_workspace_edit(changes);
+            self.apply_edit("Format workspace", edit)
+        };
+
+        progress.end(match &applied {
+            Ok(()) => format!("Formatted {} files", formatted),
+            Err(_) => "Couldn't format the workspace".to_string(),
+        });
+        applied?;
+
+        Ok(json!({ "formatted": formatted, "skipped": skipped }))
+    }
+
+    pub fn formatting(&self, params: DocumentFormattingParams) -> RequestResult {
         let uri = params.text_document.uri;
-        let document = self.get_document_by_uri(&uri).unwrap();
+        let Some(path) = uri_to_path(uri.as_str()) else {
+            return Err(unsupported_uri(uri.as_str()));
+        };
+        let options = FormatOptions::from_settings(&self.settings_for(path));
+        let Some(document) = self.get_document_by_uri(&uri) else {
+            return ok(None::<Vec<TextEdit>>);
+        };
+        if self.is_large_file(&document.content) {
+            return Err(request_error(
+                ErrorCode::RequestFailed,
+                "The file is too large to format, see largeFileSize and largeFileLines".to_string(),
+            ));
+        }
 
         let range = document.tree.root_node().range();
         let range = treesitter_range_to_lsp(&range);
 
-        let mut new_lines: Vec<String> =
-            Self::format_node(&document.content, &document.tree.root_node());
+        // Like on the command line, broken files are left for the user to fix first
+        let Some(string) =
+            Self::format_source(&options, &document.content, &document.tree.root_node())
+        else {
+            return ok(Vec::<TextEdit>::new());
+        };
+        let edit = TextEdit::new(range, string);
 
-        new_lines.push("".to_string());
-        new_lines.push("".to_string());
+        ok(vec![edit])
+    }
+}
 
-        let string = new_lines.join("\n");
-        let edit = TextEdit::new(range, string);
+// Formats every grug file under the directory in place, for `grug-ls format`.
+// Returns false if any file couldn't be formatted
+pub fn format_files(dir: &Path, respect_ignore_files: bool) -> bool {
+    // Only there for the workspace's settings and what they exclude
+    let server = Server::new(
+        Some(dir.to_path_buf()),
+        cli_settings(dir, respect_ignore_files),
+        Default::default(),
+        Default::default(),
+        std::sync::mpsc::channel().1,
+        crossbeam_channel::unbounded().0,
+    );
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let mut paths = Vec::new();
+    collect_grug_files_except(
+        dir,
+        &|path| server.is_excluded(path),
+        server.settings.respect_ignore_files,
+        &mut paths,
+    );
+    paths.sort();
+
+    let mut success = true;
+    for path in paths {
+        let content = match std::fs::read(&path) {
+            Ok(content) => content,
+            Err(err) => {
+                eprintln!("Couldn't read {}: {}", path.display(), err);
+                success = false;
+                continue;
+            }
+        };
+
+        let settings = server.settings_for(path.to_str().unwrap_or_default());
+        let options = FormatOptions::from_settings(&settings);
+        let tree = parser.parse(&content, None).unwrap();
+        let Some(text) = Server::format_source(&options, &content, &tree.root_node()) else {
+            eprintln!("Skipping {}, it has syntax errors", path.display());
+            success = false;
+            continue;
+        };
+
+        if text.as_bytes() == content {
+            continue;
+        }
 
-        let message = Message::Response(Response::new_ok(id, vec![edit]));
-        connection.sender.send(message).unwrap();
+        match std::fs::write(&path, text) {
+            Ok(()) => println!("Formatted {}", path.display()),
+            Err(err) => {
+                eprintln!("Couldn't write {}: {}", path.display(), err);
+                success = false;
+            }
+        }
+    }
+
+    success
+}
+
+#[test]
+fn crlf_format_test() {
+    let source = "speed: f32 = 1.0 # fast\r\n\r\non_spawn() {\r\n    speed   =  2.0\r\n}\r\n";
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    let tree = parser.parse(source, None).unwrap();
+
+    let options = FormatOptions::from_settings(&Settings::default());
+    let formatted = Server::format_source(&options, source.as_bytes(), &tree.root_node()).unwrap();
+
+    assert!(!formatted.replace("\r\n", "").contains(['\r', '\n']));
+    assert!(formatted.contains("on_spawn() {\r\n    speed = 2.0\r\n}\r\n"));
+}
+
+#[test]
+fn format_fuzz_test() {
+    // A tiny xorshift, so the programs are the same on every run
+    struct Random(u64);
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.below(items.len())]
+        }
     }
+
+    const NAMES: &[&str] = &["a", "speed", "box_count", "x2"];
+    const TYPES: &[&str] = &["i32", "f32", "string", "id", "resource", "box"];
+    const OPERATORS: &[&str] = &[
+        "or", "and", "==", "!=", ">=", ">", "<=", "<", "+", "-", "*", "/", "%",
+    ];
+
+    fn expression(random: &mut Random, depth: usize) -> String {
+        match random.below(if depth == 0 { 4 } else { 8 }) {
+            0 => random.pick(NAMES).to_string(),
+            1 => random.pick(&["1", "20", "3.5", "0."]).to_string(),
+            // Strings are left out, two on a line lex as a single string
+            2 => "me".to_string(),
+            3 => format!("{}()", random.pick(NAMES)),
+            4 => format!(
+                "{}  {} {}",
+                expression(random, depth - 1),
+                random.pick(OPERATORS),
+                expression(random, depth - 1)
+            ),
+            5 => format!(
+                "{} {}",
+                random.pick(&["not", "-"]),
+                expression(random, depth - 1)
+            ),
+            6 => format!("( {} )", expression(random, depth - 1)),
+            _ => {
+                let arguments: Vec<String> = (0..random.below(3) + 1)
+                    .map(|_| expression(random, depth - 1))
+                    .collect();
+                format!("helper_{}({})", random.pick(NAMES), arguments.join(" ,"))
+            }
+        }
+    }
+
+    fn body(random: &mut Random, depth: usize, indent: &str) -> String {
+        let mut text = "{\n".to_string();
+        let inner = format!("{}\t", indent);
+        for _ in 0..random.below(4) {
+            text.push_str(&inner);
+            text.push_str(&statement(random, depth, &inner));
+            text.push('\n');
+            if random.below(3) == 0 {
+                text.push('\n');
+            }
+        }
+        if random.below(4) == 0 {
+            text.push_str(&format!("{}return\n", inner));
+        }
+        text.push_str(indent);
+        text.push('}');
+        text
+    }
+
+    fn statement(random: &mut Random, depth: usize, indent: &str) -> String {
+        match random.below(if depth == 0 { 5 } else { 8 }) {
+            0 => format!(
+                "{}:{} = {}",
+                random.pick(NAMES),
+                random.pick(TYPES),
+                expression(random, 3)
+            ),
+            1 => format!("{}:  {}", random.pick(NAMES), random.pick(TYPES)),
+            2 => format!("{}={}", random.pick(NAMES), expression(random, 3)),
+            3 => format!("return {}", expression(random, 3)),
+            4 => format!("# {}", random.pick(NAMES)),
+            5 => format!(
+                "while {} {}",
+                expression(random, 2),
+                body(random, depth - 1, indent)
+            ),
+            _ => {
+                let mut text = format!(
+                    "if {} {}",
+                    expression(random, 2),
+                    body(random, depth - 1, indent)
+                );
+                match random.below(3) {
+                    0 => text.push_str(&format!(" else {}", body(random, depth - 1, indent))),
+                    1 => text.push_str(&format!(
+                        " else if {} {}",
+                        expression(random, 2),
+                        body(random, depth - 1, indent)
+                    )),
+                    _ => {}
+                }
+                text
+            }
+        }
+    }
+
+    // Every token of the file, comments included, in order
+    fn collect_tokens(content: &[u8], node: &Node, tokens: &mut Vec<String>) {
+        if node.child_count() == 0 {
+            let text = String::from_utf8_lossy(&content[node.byte_range()]);
+            tokens.push(text.trim().to_string());
+        }
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            collect_tokens(content, &child, tokens);
+        }
+    }
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    let options = FormatOptions::from_settings(&Settings::default());
+
+    let mut random = Random(0x9e3779b97f4a7c15);
+    for _ in 0..500 {
+        let mut source = String::new();
+        for _ in 0..random.below(3) {
+            source.push_str(&format!(
+                "{}: {} = {}\n",
+                random.pick(NAMES),
+                random.pick(TYPES),
+                expression(&mut random, 2)
+            ));
+        }
+        source.push('\n');
+        for _ in 0..random.below(3) + 1 {
+            let params: Vec<String> = (0..random.below(3))
+                .map(|_| format!("{}:{}", random.pick(NAMES), random.pick(TYPES)))
+                .collect();
+            source.push_str(&format!(
+                "{}{}({}) {}\n\n",
+                random.pick(&["on_", "helper_"]),
+                random.pick(NAMES),
+                params.join(" , "),
+                body(&mut random, 3, "")
+            ));
+        }
+
+        let tree = parser.parse(&source, None).unwrap();
+        assert!(
+            !tree.root_node().has_error(),
+            "{}\n{}",
+            source,
+            tree.root_node().to_sexp()
+        );
+
+        let formatted = Server::format_source(&options, source.as_bytes(), &tree.root_node())
+            .unwrap_or_else(|| panic!("{}", source));
+        let formatted_tree = parser.parse(&formatted, None).unwrap();
+        assert!(!formatted_tree.root_node().has_error(), "{}", formatted);
+
+        let (mut before, mut after) = (Vec::new(), Vec::new());
+        collect_tokens(source.as_bytes(), &tree.root_node(), &mut before);
+        collect_tokens(
+            formatted.as_bytes(),
+            &formatted_tree.root_node(),
+            &mut after,
+        );
+        assert_eq!(before, after, "{}\n=>\n{}", source, formatted);
+    }
+}
+
+#[test]
+fn comment_format_test() {
+    let source = r#"#Movement
+#-------- Globals --------
+speed: f32 = 1.0    #   per tick
+#
+
+on_spawn() { # starts here
+    #    /\_/\
+    speed = 2.0 # faster
+    if speed > 1.0 {
+        speed = 1.0
+    } #clamped
+}
+"#;
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    let tree = parser.parse(source, None).unwrap();
+
+    let mut options = FormatOptions::from_settings(&Settings::default());
+    let formatted = Server::format_source(&options, source.as_bytes(), &tree.root_node()).unwrap();
+    assert_eq!(
+        formatted,
+        r#"# Movement
+#-------- Globals --------
+speed: f32 = 1.0    # per tick
+#
+
+on_spawn() { # starts here
+    #    /\_/\
+    speed = 2.0 # faster
+    if speed > 1.0 {
+        speed = 1.0
+    } # clamped
+}
+
+"#
+    );
+
+    options.keep_banners = false;
+    let formatted = Server::format_source(&options, source.as_bytes(), &tree.root_node()).unwrap();
+    assert!(formatted.contains("\n# -------- Globals --------\n"));
+    assert!(formatted.contains("\n    # /\\_/\\\n"));
+}
+
+#[test]
+fn parentheses_format_test() {
+    let source = "on_spawn() {\n    x = ((a * b)) + (c)\n    y = a - (b - c) * (d)\n    z = not (a and b) or (not c)\n    w = -(a + b)\n    f((a + b), (c))\n}\n";
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    let tree = parser.parse(source, None).unwrap();
+
+    let mut options = FormatOptions::from_settings(&Settings::default());
+    let formatted = Server::format_source(&options, source.as_bytes(), &tree.root_node()).unwrap();
+    assert_eq!(formatted, format!("{}\n", source));
+
+    options.parentheses = Parentheses::Minimal;
+    let formatted = Server::format_source(&options, source.as_bytes(), &tree.root_node()).unwrap();
+    assert_eq!(
+        formatted,
+        "on_spawn() {\n    x = a * b + c\n    y = a - (b - c) * d\n    z = not (a and b) or not c\n    w = -(a + b)\n    f(a + b, c)\n}\n\n"
+    );
+}
+
+#[test]
+fn formatting_request_test() {
+    use lsp_types::{FormattingOptions, TextDocumentIdentifier, request::Formatting};
+
+    use crate::test_support::{TestClient, uri};
+
+    let mut client = TestClient::new();
+    let format = |client: &mut TestClient, path: &str| {
+        client.expect::<Formatting>(DocumentFormattingParams {
+            text_document: TextDocumentIdentifier::new(uri(path)),
+            options: FormattingOptions::default(),
+            work_done_progress_params: Default::default(),
+        })
+    };
+
+    client.open("mods/foo/a-box.grug", "on_spawn() {\nx: i32 = 1\n}\n");
+    let edits = format(&mut client, "mods/foo/a-box.grug").unwrap();
+    assert!(
+        edits[0]
+            .new_text
+            .starts_with("on_spawn() {\n    x: i32 = 1\n}\n")
+    );
+
+    // Neither do files with syntax errors, the formatter doesn't know what the broken parts were
+    client.change("mods/foo/a-box.grug", "on_spawn() {\nx: i32 = \n}\n");
+    assert_eq!(format(&mut client, "mods/foo/a-box.grug"), Some(Vec::new()));
+
+    // Files the editor never opened have nothing to format
+    assert_eq!(format(&mut client, "mods/foo/closed-box.grug"), None);
+}
+
+#[test]
+fn broken_file_format_test() {
+    use lsp_types::{FormattingOptions, TextDocumentIdentifier, request::Formatting};
+
+    use crate::test_support::{TestClient, uri};
+
+    // The stray `)` becomes an ERROR node, which would keep its text while the lines around it
+    // got indented again
+    let source = "on_spawn() {\nx: i32 = 1\n    )\n  print_i32(x)\n}\n";
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    assert!(tree.root_node().has_error());
+    let options = FormatOptions::from_settings(&Settings::default());
+    assert_eq!(
+        Server::format_source(&options, source.as_bytes(), &tree.root_node()),
+        None
+    );
+
+    let mut client = TestClient::new();
+    client.open("mods/foo/a-box.grug", source);
+    let edits = client.expect::<Formatting>(DocumentFormattingParams {
+        text_document: TextDocumentIdentifier::new(uri("mods/foo/a-box.grug")),
+        options: FormattingOptions::default(),
+        work_done_progress_params: Default::default(),
+    });
+    assert_eq!(edits, Some(Vec::new()));
+}
+
+#[test]
+fn format_files_test() {
+    let root = std::env::temp_dir().join(format!("grug-ls-format-files-{}", std::process::id()));
+    let foo = root.join("mods").join("foo");
+    let vendor = root.join("mods").join("vendor");
+    std::fs::create_dir_all(&foo).unwrap();
+    std::fs::create_dir_all(&vendor).unwrap();
+    std::fs::write(root.join(".grug-ls.toml"), "exclude = [\"mods/vendor\"]\n").unwrap();
+
+    let unformatted = "on_spawn() {\nx: i32 = 1\n}\n";
+    std::fs::write(foo.join("a-box.grug"), unformatted).unwrap();
+    std::fs::write(vendor.join("b-box.grug"), unformatted).unwrap();
+
+    assert!(format_files(&root, true));
+    assert_ne!(
+        std::fs::read_to_string(foo.join("a-box.grug")).unwrap(),
+        unformatted
+    );
+    assert_eq!(
+        std::fs::read_to_string(vendor.join("b-box.grug")).unwrap(),
+        unformatted
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
 }
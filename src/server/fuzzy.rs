@@ -0,0 +1,72 @@
+// Subword matching for snake_case names, so `sgn` and `gunname` both find `set_gun_name`.
+// Every character of the query has to either follow the previous match directly,
+// or start one of the words of the name. Underscores in the query are ignored
+pub fn subword_match(query: &str, name: &str) -> bool {
+    let query: Vec<u8> = query.bytes().filter(|chr| *chr != b'_').collect();
+
+    matches_from(&query, name.as_bytes(), None)
+}
+
+fn matches_from(query: &[u8], name: &[u8], last: Option<usize>) -> bool {
+    let Some((first, rest)) = query.split_first() else {
+        return true;
+    };
+
+    let start = last.map_or(0, |last| last + 1);
+    (start..name.len()).any(|idx| {
+        let starts_word = idx == 0 || name[idx - 1] == b'_';
+        let follows_match = last.is_some_and(|last| last + 1 == idx);
+
+        (starts_word || follows_match)
+            && name[idx].eq_ignore_ascii_case(first)
+            && matches_from(rest, name, Some(idx))
+    })
+}
+
+// The name followed by the first letter of each of its words, which lets clients that do their
+// own fuzzy matching rank `sgn` as a close match for `set_gun_name`
+pub fn filter_text(name: &str) -> Option<String> {
+    if !name.contains('_') {
+        return None;
+    }
+
+    let initials: String = name
+        .split('_')
+        .filter_map(|word| word.chars().next())
+        .collect();
+
+    Some(format!("{} {}", name, initials))
+}
+
+// The part of the name the user already typed before the cursor
+pub fn typed_prefix(line: &str) -> &str {
+    let start = line
+        .rfind(|chr: char| !(chr.is_ascii_alphanumeric() || chr == '_'))
+        .map_or(0, |idx| idx + 1);
+
+    &line[start..]
+}
+
+#[test]
+fn subword_match_test() {
+    assert!(subword_match("sgn", "set_gun_name"));
+    assert!(subword_match("gunname", "set_gun_name"));
+    assert!(subword_match("set_gun", "set_gun_name"));
+    assert!(subword_match("SGN", "set_gun_name"));
+    assert!(subword_match("", "set_gun_name"));
+    assert!(!subword_match("etg", "set_gun_name"));
+    assert!(!subword_match("sgnx", "set_gun_name"));
+
+    // Needs to backtrack past the `n` in `gun`
+    assert!(subword_match("gna", "set_gun_name"));
+
+    assert_eq!(
+        filter_text("set_gun_name").as_deref(),
+        Some("set_gun_name sgn")
+    );
+    assert_eq!(filter_text("if"), None);
+
+    assert_eq!(typed_prefix("    x = set_gu"), "set_gu");
+    assert_eq!(typed_prefix("    print("), "");
+    assert_eq!(typed_prefix("sgn"), "sgn");
+}
@@ -1,16 +1,17 @@
-use std::str::FromStr;
+use std::{path::Path, str::FromStr};
 
-use lsp_server::{Connection, ErrorCode, Message, RequestId, Response};
-use lsp_types::{GotoDefinitionParams, GotoDefinitionResponse, LocationLink, Uri};
-use vfs::FileSystem;
+use lsp_types::{GotoDefinitionParams, GotoDefinitionResponse, LocationLink, Range, Uri};
 
 use crate::server::{
     Server,
     document::Document,
-    utils::{get_spot_info, is_function_call, treesitter_range_to_lsp},
+    utils::{
+        RequestResult, get_spot_info, is_function_call, ok, treesitter_range_to_lsp,
+        unsupported_uri, uri_to_path,
+    },
 };
 
-use log::info;
+use log::debug;
 
 impl Server {
     fn get_definition(
@@ -22,34 +23,30 @@ impl Server {
         let uri = Uri::from_str(&uri).ok()?;
         let spot_info = get_spot_info(document, node);
         let text = String::from_utf8(document.content[node.byte_range()].to_vec()).ok()?;
-        info!("Trying to get definition for: {}", node.kind());
+        debug!("Trying to get definition for: {}", node.kind());
         if node.kind() == "identifier" {
-            if !is_function_call(&node) {
-                if let Some(var) = spot_info.variables.iter().find(|var| var.name == text) {
-                    let node = document
-                        .tree
-                        .root_node()
-                        .descendant_for_byte_range(var.range.start_byte, var.range.end_byte)
-                        .unwrap();
-                    let link = LocationLink {
-                        target_uri: uri,
-                        target_range: treesitter_range_to_lsp(&node.range()),
-                        target_selection_range: treesitter_range_to_lsp(
-                            &node.child_by_field_name("name").unwrap().range(),
-                        ),
-                        origin_selection_range: None,
-                    };
-                    return Some(GotoDefinitionResponse::Link(vec![link]));
-                }
+            if !is_function_call(node)
+                && let Some(var) = spot_info.variables.iter().find(|var| var.name == text)
+            {
+                let node = document
+                    .tree
+                    .root_node()
+                    .descendant_for_byte_range(var.range.start_byte, var.range.end_byte)
+                    .unwrap();
+                let link = LocationLink {
+                    target_uri: uri,
+                    target_range: treesitter_range_to_lsp(&node.range()),
+                    target_selection_range: treesitter_range_to_lsp(
+                        &node.child_by_field_name("name").unwrap().range(),
+                    ),
+                    origin_selection_range: None,
+                };
+                return Some(GotoDefinitionResponse::Link(vec![link]));
             }
 
             if let Some(entity) = self.mod_api.entities.get(&text) {
                 let link = LocationLink {
-                    target_uri: Uri::from_str(&format!(
-                        "file://{}",
-                        self.root_path.join("mod_api.json").to_str().unwrap()
-                    ))
-                    .unwrap(),
+                    target_uri: self.mod_api_uri()?,
                     target_range: treesitter_range_to_lsp(&entity.range),
                     // Store the name key for the entity
                     target_selection_range: treesitter_range_to_lsp(&entity.range),
@@ -60,11 +57,7 @@ impl Server {
 
             if let Some(func) = self.mod_api.game_functions.get(&text) {
                 let link = LocationLink {
-                    target_uri: Uri::from_str(&format!(
-                        "file://{}",
-                        self.root_path.join("mod_api.json").to_str().unwrap()
-                    ))
-                    .unwrap(),
+                    target_uri: self.mod_api_uri()?,
                     target_range: treesitter_range_to_lsp(&func.range),
                     target_selection_range: treesitter_range_to_lsp(&func.range),
                     origin_selection_range: None,
@@ -73,100 +66,163 @@ impl Server {
             }
         }
 
-        if node.kind() == "helper_identifier" {
-            if let Some(helper) = document.helpers.iter().find(|func| func.name == text) {
-                let node = document
-                    .tree
-                    .root_node()
-                    .descendant_for_byte_range(helper.range.start_byte, helper.range.end_byte)
-                    .unwrap();
-                let link = LocationLink {
-                    target_uri: uri,
-                    target_range: treesitter_range_to_lsp(&node.range()),
-                    target_selection_range: treesitter_range_to_lsp(
-                        &node.child_by_field_name("name").unwrap().range(),
-                    ),
-                    origin_selection_range: None,
-                };
-                return Some(GotoDefinitionResponse::Link(vec![link]));
-            }
+        // Entity strings lead to the file that defines the entity, which doesn't need to be open
+        if node.kind() == "string" {
+            let path = uri_to_path(uri.as_str())?;
+            let entity = self.resolve_entity_string(Path::new(path), text.trim_matches('"'))?;
+            let range = self.with_document(&entity.path, |document| {
+                treesitter_range_to_lsp(&document.tree.root_node().range())
+            })?;
+
+            let link = LocationLink {
+                target_uri: Uri::from_str(&format!("file://{}", entity.path)).ok()?,
+                target_range: range,
+                target_selection_range: Range::new(range.start, range.start),
+                origin_selection_range: Some(treesitter_range_to_lsp(&node.range())),
+            };
+            return Some(GotoDefinitionResponse::Link(vec![link]));
         }
-        if node.kind() == "on_identifier" {
-            if let Some(entity) = self.mod_api.entities.get(&document.entity_type) {
-                if let Some(on_func) = entity.on_functions.get(&text) {
-                    let link = LocationLink {
-                        target_uri: Uri::from_str(&format!(
-                            "file://{}",
-                            self.root_path.join("mod_api.json").to_str().unwrap()
-                        ))
-                        .unwrap(),
-                        target_range: treesitter_range_to_lsp(&on_func.range),
-                        target_selection_range: treesitter_range_to_lsp(&on_func.range),
-                        origin_selection_range: None,
-                    };
-                    return Some(GotoDefinitionResponse::Link(vec![link]));
-                }
-            }
+
+        if node.kind() == "helper_identifier"
+            && let Some(helper) = document.get_helper(text.as_bytes())
+        {
+            let node = document
+                .tree
+                .root_node()
+                .descendant_for_byte_range(helper.range.start_byte, helper.range.end_byte)
+                .unwrap();
+            let link = LocationLink {
+                target_uri: uri,
+                target_range: treesitter_range_to_lsp(&node.range()),
+                target_selection_range: treesitter_range_to_lsp(
+                    &node.child_by_field_name("name").unwrap().range(),
+                ),
+                origin_selection_range: None,
+            };
+            return Some(GotoDefinitionResponse::Link(vec![link]));
+        }
+        // The `bullet` in `b: bullet`
+        if node.kind() == "type"
+            && let Some(entity) = self.mod_api.entities.get(&text)
+        {
+            let link = LocationLink {
+                target_uri: self.mod_api_uri()?,
+                target_range: treesitter_range_to_lsp(&entity.range),
+                target_selection_range: treesitter_range_to_lsp(&entity.range),
+                origin_selection_range: Some(treesitter_range_to_lsp(&node.range())),
+            };
+            return Some(GotoDefinitionResponse::Link(vec![link]));
+        }
+        if node.kind() == "on_identifier"
+            && let Some(entity) = self.mod_api.entities.get(&document.entity_type)
+            && let Some(on_func) = entity.on_functions.get(&text)
+        {
+            let link = LocationLink {
+                target_uri: self.mod_api_uri()?,
+                target_range: treesitter_range_to_lsp(&on_func.range),
+                target_selection_range: treesitter_range_to_lsp(&on_func.range),
+                origin_selection_range: None,
+            };
+            return Some(GotoDefinitionResponse::Link(vec![link]));
         }
         None
     }
-    pub fn handle_goto_definition(
-        &self,
-        params: GotoDefinitionParams,
-        connection: &mut Connection,
-        id: RequestId,
-    ) {
+    pub fn handle_goto_definition(&self, params: GotoDefinitionParams) -> RequestResult {
         let uri = params
             .text_document_position_params
             .text_document
             .uri
             .as_str();
+        let Some(path) = uri_to_path(uri) else {
+            return Err(unsupported_uri(uri));
+        };
 
-        // We probably wont need to use this server on TCP
-        assert!(uri.starts_with("file://"));
-
-        let path = &uri["file.//".len()..];
-
-        if !self.file_system.exists(path).unwrap_or(false) {
-            connection
-                .sender
-                .send(Message::Response(Response::new_err(
-                    id,
-                    ErrorCode::InvalidRequest as i32,
-                    format!("File doesnt exist: {}", path),
-                )))
-                .unwrap();
-            return;
+        // mod_api.json would be parsed as grug otherwise
+        if self.is_mod_api_path(path) {
+            return ok(None::<GotoDefinitionResponse>);
         }
 
-        let document = &self.document_map.get(path).unwrap();
-
         let point = tree_sitter::Point {
             column: params.text_document_position_params.position.character as usize,
             row: params.text_document_position_params.position.line as usize,
         };
 
-        let node = document
-            .tree
-            .root_node()
-            .named_descendant_for_point_range(point, point)
-            .unwrap();
+        // The file might not be in memory, when the editor asks about a file it didn't open
+        let definition = self
+            .with_document(path, |document| {
+                let node = document
+                    .tree
+                    .root_node()
+                    .named_descendant_for_point_range(point, point)?;
 
-        let definition = self.get_definition(uri.to_string(), document, &node);
+                self.get_definition(uri.to_string(), document, &node)
+            })
+            .flatten();
 
-        if let Some(definition) = definition {
-            connection
-                .sender
-                .send(Message::Response(Response::new_ok(id, definition)))
-                .unwrap();
-        } else {
-            connection
-                .sender
-                .send(Message::Response(Response::new_ok(
-                    id,
-                    serde_json::Value::Null,
-                )))
-                .unwrap();
-        }
+        ok(definition.map(|definition| self.adapt_definition(definition)))
     }
 }
+
+#[test]
+fn type_definition_test() {
+    use lsp_types::request::GotoDefinition;
+
+    use crate::test_support::{TestClient, uri};
+
+    let mut client = TestClient::new();
+    client.open(
+        "mod_api.json",
+        r#"{ "entities": { "bullet": {} }, "game_functions": {} }"#,
+    );
+    client.open(
+        "ak47-gun.grug",
+        "on_spawn() {\n}\n\nhelper_aim(target: bul$0let) {\n}\n",
+    );
+
+    let definition = client.expect::<GotoDefinition>(GotoDefinitionParams {
+        text_document_position_params: client.cursor("ak47-gun.grug"),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    });
+    // The client doesn't support links, so it gets plain locations
+    let Some(GotoDefinitionResponse::Array(locations)) = definition else {
+        panic!("{:?}", definition);
+    };
+    assert_eq!(locations[0].uri, uri("mod_api.json"));
+    assert_eq!(locations[0].range.start, lsp_types::Position::new(0, 26));
+
+    // Clients that support links still get them
+    let capabilities = lsp_types::ClientCapabilities {
+        text_document: Some(lsp_types::TextDocumentClientCapabilities {
+            definition: Some(lsp_types::GotoCapability {
+                link_support: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let mut client = TestClient::with_capabilities(Default::default(), capabilities);
+    client.open(
+        "mod_api.json",
+        r#"{ "entities": { "bullet": {} }, "game_functions": {} }"#,
+    );
+    client.open(
+        "ak47-gun.grug",
+        "on_spawn() {\n}\n\nhelper_aim(target: bul$0let) {\n}\n",
+    );
+
+    let definition = client.expect::<GotoDefinition>(GotoDefinitionParams {
+        text_document_position_params: client.cursor("ak47-gun.grug"),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    });
+    let Some(GotoDefinitionResponse::Link(links)) = definition else {
+        panic!("{:?}", definition);
+    };
+    assert_eq!(links[0].target_uri, uri("mod_api.json"));
+    assert_eq!(
+        links[0].target_selection_range.start,
+        lsp_types::Position::new(0, 26)
+    );
+}
@@ -1,25 +1,57 @@
 use std::{
+    collections::HashMap,
     path::PathBuf,
-    sync::mpsc::{Receiver, Sender, channel},
+    sync::mpsc::{Receiver, TryRecvError, channel},
 };
 
-use crate::server::{Server, mod_api::ModApi};
+#[cfg(feature = "native")]
+use std::sync::mpsc::Sender;
 
+#[cfg(feature = "native")]
+use crate::server::config_file::CONFIG_FILE_NAME;
+
+use lsp_types::{
+    Diagnostic, ExecuteCommandParams, MessageType, PublishDiagnosticsParams, Uri,
+    notification::PublishDiagnostics,
+};
+use serde_json::Value;
+use tree_sitter::Parser;
+
+use crate::server::{
+    Server,
+    document::Document,
+    lint::{LintContext, LintLevel, lint},
+    mod_api::{ModApi, ModApiError},
+    utils::{send_notification, show_message},
+    workspace::WorkspaceEntity,
+};
+
+use log::debug;
 use log::error;
 use log::info;
+#[cfg(feature = "native")]
 use log::warn;
+#[cfg(feature = "native")]
 use notify::Watcher;
 
+// Only the file watcher sends these
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
 pub enum ServerUpdate {
     ModApiChange(ModApi),
+    ModApiInvalid(ModApiError),
+    GrugFileChange(PathBuf),
+    GrugFileRemove(PathBuf),
+    ConfigFileChange(PathBuf),
 }
 
+#[cfg(feature = "native")]
 struct ServerWorker {
     mod_api_path: PathBuf,
     sender: Sender<ServerUpdate>,
     watcher_recv: Receiver<notify::Result<notify::Event>>,
 }
 
+#[cfg(feature = "native")]
 impl ServerWorker {
     pub fn new(
         mod_api_path: PathBuf,
@@ -33,66 +65,360 @@ impl ServerWorker {
         }
     }
 
-    pub fn update(&mut self) {
-        let recv = self.watcher_recv.try_recv();
-        if let Ok(notify::Result::Err(err)) = &recv {
-            warn!("What error: {:?}", err);
-        }
+    fn update_mod_api(&self) {
+        if let Ok(json) = std::fs::read_to_string(&self.mod_api_path) {
+            // Editors truncate the file before writing it, which shows up as its own event
+            if json.trim().is_empty() {
+                return;
+            }
 
-        if let Ok(Ok(event)) = recv {
-            if let notify::EventKind::Access(_) = event.kind {
-            } else {
-                if let Ok(json) = std::fs::read_to_string(&self.mod_api_path) {
-                    let mod_api: Option<ModApi> = ModApi::from_json(&json);
-
-                    match mod_api {
-                        Some(mod_api) => {
-                            info!("Sending new mod_api: {:?}", mod_api);
-                            self.sender
-                                .send(ServerUpdate::ModApiChange(mod_api))
-                                .unwrap();
-                        }
-                        None => {
-                            error!("Error deserializing mod_api");
-                        }
-                    }
+            match ModApi::from_json_strict(&json) {
+                Ok(mod_api) => {
+                    debug!("Sending new mod_api: {:?}", mod_api);
+                    self.sender
+                        .send(ServerUpdate::ModApiChange(mod_api))
+                        .unwrap();
+                }
+                Err(err) => {
+                    error!("Error deserializing mod_api: {:?}", err);
+                    self.sender.send(ServerUpdate::ModApiInvalid(err)).unwrap();
                 }
             }
         }
     }
+
+    // Blocks until the watcher has something, returns false once the watcher is gone
+    pub fn update(&mut self) -> bool {
+        let event = match self.watcher_recv.recv() {
+            Ok(Ok(event)) => event,
+            Ok(Err(err)) => {
+                warn!("What error: {:?}", err);
+                return true;
+            }
+            Err(_) => return false,
+        };
+
+        if let notify::EventKind::Access(_) = event.kind {
+            return true;
+        }
+
+        for path in event.paths {
+            if path == self.mod_api_path {
+                self.update_mod_api();
+            } else if path
+                .file_name()
+                .is_some_and(|name| name == CONFIG_FILE_NAME)
+            {
+                self.sender
+                    .send(ServerUpdate::ConfigFileChange(path))
+                    .unwrap();
+            } else if path.extension().is_some_and(|ext| ext == "grug") {
+                // Renames show up as modifications of both paths, so look at
+                // the disk instead of trusting the event kind
+                let update = if path.exists() {
+                    ServerUpdate::GrugFileChange(path)
+                } else {
+                    ServerUpdate::GrugFileRemove(path)
+                };
+                self.sender.send(update).unwrap();
+            }
+        }
+
+        true
+    }
+}
+
+// Everything a document needs to be parsed and linted away from the main thread
+pub struct AnalysisJob {
+    pub path: String,
+    pub uri: Uri,
+    pub text: Vec<u8>,
+    // Bumped on every edit, so results for older text can be thrown away
+    pub generation: u64,
+    pub mod_api: ModApi,
+    pub mod_name: Option<String>,
+    pub entities: Vec<WorkspaceEntity>,
+    pub lints: HashMap<String, LintLevel>,
+    pub magic_number_threshold: f64,
+}
+
+pub struct AnalysisResult {
+    pub path: String,
+    pub generation: u64,
+    pub document: Document,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+// Parses and lints jobs in the order they come in
+pub struct AnalysisWorker {
+    parser: Parser,
+    jobs: crossbeam_channel::Receiver<AnalysisJob>,
+    results: crossbeam_channel::Sender<AnalysisResult>,
+}
+
+impl std::fmt::Debug for AnalysisWorker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnalysisWorker")
+            .field("jobs", &self.jobs.len())
+            .finish()
+    }
+}
+
+impl AnalysisWorker {
+    fn analyze(&mut self, job: AnalysisJob) -> Option<AnalysisResult> {
+        let file_name = job.path.split("/").last().unwrap().to_string();
+        let document = Document::new(&mut self.parser, job.text, file_name, job.uri)?;
+
+        let context = LintContext {
+            mod_api: &job.mod_api,
+            mod_name: job.mod_name,
+            entities: job.entities,
+            magic_number_threshold: job.magic_number_threshold,
+        };
+        let diagnostics = lint(&document, &context, &job.lints);
+
+        Some(AnalysisResult {
+            path: job.path,
+            generation: job.generation,
+            document,
+            diagnostics,
+        })
+    }
+
+    // Returns false once nobody is listening for results anymore
+    #[cfg(not(feature = "native"))]
+    pub fn run_pending(&mut self) -> bool {
+        while let Ok(job) = self.jobs.try_recv() {
+            let Some(result) = self.analyze(job) else {
+                continue;
+            };
+            if self.results.send(result).is_err() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub fn analysis_channels() -> (
+    crossbeam_channel::Sender<AnalysisJob>,
+    crossbeam_channel::Receiver<AnalysisResult>,
+    AnalysisWorker,
+) {
+    let (job_send, job_recv) = crossbeam_channel::unbounded::<AnalysisJob>();
+    let (result_send, result_recv) = crossbeam_channel::unbounded::<AnalysisResult>();
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let worker = AnalysisWorker {
+        parser,
+        jobs: job_recv,
+        results: result_send,
+    };
+    (job_send, result_recv, worker)
+}
+
+#[cfg(feature = "native")]
+pub fn spawn_analysis_worker() -> (
+    crossbeam_channel::Sender<AnalysisJob>,
+    crossbeam_channel::Receiver<AnalysisResult>,
+) {
+    let (job_send, result_recv, mut worker) = analysis_channels();
+
+    std::thread::spawn(move || {
+        // Blocks until there is a job, and stops once the server is gone
+        while let Ok(job) = worker.jobs.recv() {
+            let Some(result) = worker.analyze(job) else {
+                continue;
+            };
+            if worker.results.send(result).is_err() {
+                break;
+            }
+        }
+    });
+
+    (job_send, result_recv)
 }
 
-pub fn spawn_worker(root_path: PathBuf) -> Option<Receiver<ServerUpdate>> {
+// Fails when the workspace can't be watched, like when the OS is out of watches
+#[cfg(feature = "native")]
+pub fn spawn_worker(
+    root_path: PathBuf,
+    mod_api_path: PathBuf,
+) -> Result<Receiver<ServerUpdate>, String> {
     let (send, recv) = channel::<ServerUpdate>();
 
     let (watch_send, watch_recv) = channel::<notify::Result<notify::Event>>();
-    let mut watcher = notify::recommended_watcher(watch_send).ok()?;
+    let mut watcher = notify::recommended_watcher(watch_send).map_err(|err| err.to_string())?;
+    watcher
+        .watch(&root_path, notify::RecursiveMode::Recursive)
+        .map_err(|err| format!("Couldn't watch {}: {}", root_path.display(), err))?;
+    if !mod_api_path.starts_with(&root_path)
+        && let Err(err) = watcher.watch(&mod_api_path, notify::RecursiveMode::NonRecursive)
+    {
+        error!("Couldn't watch {:?}: {:?}", mod_api_path, err);
+    }
 
     std::thread::spawn(move || {
-        let mut worker = ServerWorker::new(root_path.join("mod_api.json"), send, watch_recv);
+        // Events stop coming once the watcher is dropped
+        let _watcher = watcher;
+        let mut worker = ServerWorker::new(mod_api_path, send, watch_recv);
 
         info!("Initializing worker main loop");
-        loop {
-            while let notify::Result::Err(_) = watcher.watch(
-                &root_path.join("mod_api.json"),
-                notify::RecursiveMode::NonRecursive,
-            ) {}
-            worker.update();
-        }
+        while worker.update() {}
     });
 
-    Some(recv)
+    Ok(recv)
+}
+
+// Nothing watches the disk without notify, the host reopens files that change instead
+#[cfg(not(feature = "native"))]
+pub fn spawn_worker(
+    _root_path: PathBuf,
+    _mod_api_path: PathBuf,
+) -> Result<Receiver<ServerUpdate>, String> {
+    Ok(channel::<ServerUpdate>().1)
 }
 
 impl Server {
-    pub fn handle_worker_messages(&mut self) {
-        if let Ok(message) = self.messages_chan.try_recv() {
+    pub fn handle_worker_messages(&mut self, parser: &mut Parser) {
+        loop {
+            let message = match self.messages_chan.try_recv() {
+                Ok(message) => message,
+                Err(TryRecvError::Empty) => break,
+                // The watcher thread is gone, so changes on disk go unnoticed from now on
+                Err(TryRecvError::Disconnected) => {
+                    self.watcher_stopped = true;
+                    break;
+                }
+            };
+
             match message {
+                ServerUpdate::ModApiChange(_) | ServerUpdate::ModApiInvalid(_)
+                    if self.mod_api_buffer.is_some() =>
+                {
+                    info!("Ignoring mod_api change on disk, the editor buffer is open");
+                }
                 ServerUpdate::ModApiChange(mod_api) => {
-                    info!("New mod_api: {:?}", mod_api);
+                    info!(
+                        "New mod_api with {} entities and {} game functions",
+                        mod_api.entities.len(),
+                        mod_api.game_functions.len()
+                    );
+                    debug!("New mod_api: {:?}", mod_api);
                     self.mod_api = mod_api;
+                    self.publish_all_diagnostics();
+                    self.send_mod_api_status(Ok(()));
+                }
+                ServerUpdate::ModApiInvalid(err) => {
+                    // Keep the last good mod API, so completions don't suddenly disappear
+                    self.send_mod_api_status(Err(&err));
+                }
+                ServerUpdate::GrugFileChange(path)
+                    if self.is_excluded(&path) || self.is_ignored(&path) => {}
+                ServerUpdate::GrugFileChange(path) => {
+                    self.index_file(&path, parser);
+                }
+                ServerUpdate::GrugFileRemove(path) => {
+                    let Some(document) = self.remove_from_index(&path) else {
+                        continue;
+                    };
+
+                    self.clear_diagnostics(document.uri);
                 }
+                ServerUpdate::ConfigFileChange(path) => {
+                    info!("{} changed", path.display());
+                    self.forget_config_file(&path);
+                    if path.parent() == self.root_path.as_deref() {
+                        self.reload_settings();
+                    }
+                    self.publish_all_diagnostics();
+                }
+            }
+        }
+    }
+
+    // For when the watcher misses changes, like on network drives or inside containers.
+    // While the editor has mod_api.json open, its buffer is reread instead of the disk
+    pub fn reload_mod_api(
+        &mut self,
+        _parser: &mut Parser,
+        _params: ExecuteCommandParams,
+    ) -> Result<Value, String> {
+        let json = match &self.mod_api_buffer {
+            Some(buffer) => Ok(buffer.clone()),
+            None => self
+                .mod_api_path()
+                .ok_or("There is no workspace to load mod_api.json from".to_string())
+                .and_then(|path| {
+                    std::fs::read_to_string(&path)
+                        .map_err(|err| format!("Couldn't read {}: {}", path.display(), err))
+                }),
+        };
+        let json = match json {
+            Ok(json) => json,
+            Err(err) => {
+                error!("{}", err);
+                show_message(&self.client, MessageType::ERROR, err.clone());
+
+                return Err(err);
+            }
+        };
+
+        match ModApi::from_json_strict(&json) {
+            Ok(mod_api) => {
+                self.mod_api = mod_api;
+                self.publish_all_diagnostics();
+                self.send_mod_api_status(Ok(()));
+                show_message(
+                    &self.client,
+                    MessageType::INFO,
+                    "Reloaded the mod API".to_string(),
+                );
+
+                Ok(Value::Null)
+            }
+            Err(err) => {
+                error!("{:?}", err);
+                self.send_mod_api_status(Err(&err));
+
+                Err(err.message)
             }
         }
     }
+
+    pub fn clear_diagnostics(&self, uri: Uri) {
+        let params = PublishDiagnosticsParams::new(uri, Vec::new(), None);
+        send_notification::<PublishDiagnostics>(&self.client, params);
+    }
+}
+
+#[test]
+fn reload_mod_api_test() {
+    use lsp_types::request::ExecuteCommand;
+
+    use crate::test_support::TestClient;
+
+    let reload = |client: &mut TestClient| {
+        client.request::<ExecuteCommand>(ExecuteCommandParams {
+            command: "grug.reloadModApi".to_string(),
+            arguments: Vec::new(),
+            work_done_progress_params: Default::default(),
+        })
+    };
+
+    // There is no mod_api.json on disk for the fixture
+    let mut client = TestClient::new();
+    assert!(reload(&mut client).is_err());
+
+    // The unsaved buffer is kept instead of whatever is on disk
+    client.open(
+        "mod_api.json",
+        r#"{ "entities": { "box": {} }, "game_functions": {} }"#,
+    );
+    assert!(reload(&mut client).is_ok());
+    assert!(client.server().mod_api.entities.contains_key("box"));
 }
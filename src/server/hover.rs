@@ -1,9 +1,10 @@
-use lsp_server::{Connection, ErrorCode, Message, RequestId, Response};
 use lsp_types::{Hover, HoverContents, HoverParams, MarkupContent, MarkupKind, Position, Range};
-use vfs::FileSystem;
 
 use crate::server::{
-    document::{Document, KEYWORDS, PRIMITIVE_TYPES, STATEMENT_SNIPPETS}, mod_api::ModApi, utils::{get_spot_info, is_function_call}, Server
+    Server, const_eval,
+    document::{Document, KEYWORDS, PRIMITIVE_TYPES, STATEMENT_SNIPPETS},
+    mod_api::ModApi,
+    utils::{RequestResult, get_spot_info, is_function_call, ok, unsupported_uri, uri_to_path},
 };
 
 struct HoverContent {
@@ -21,6 +22,31 @@ impl HoverContent {
 }
 
 impl Server {
+    // Globals are often tuning constants, so their hover shows the value they start with.
+    // Folded when it's constant, otherwise as it's written
+    fn global_initializer(document: &Document, range: &tree_sitter::Range) -> Option<String> {
+        if !document
+            .global_vars
+            .iter()
+            .any(|global| global.range == *range)
+        {
+            return None;
+        }
+
+        let value = document
+            .tree
+            .root_node()
+            .descendant_for_byte_range(range.start_byte, range.end_byte)?
+            .child_by_field_name("value")?;
+
+        match const_eval::evaluate(&document.content, &value) {
+            Ok(constant) => Some(constant.to_string()),
+            Err(_) => {
+                Some(String::from_utf8_lossy(&document.content[value.byte_range()]).to_string())
+            }
+        }
+    }
+
     fn get_hover(
         mod_api: &ModApi,
         document: &Document,
@@ -31,10 +57,13 @@ impl Server {
             let name = &document.content[range];
             let spot_info = get_spot_info(document, node);
 
-            if !is_function_call(&node) {
+            if !is_function_call(node) {
                 for var in spot_info.variables.into_iter() {
                     if var.name.as_bytes() == name {
-                        let code = format!("{}: {}", var.name, var.r#type.as_str());
+                        let mut code = format!("{}: {}", var.name, var.r#type.as_str());
+                        if let Some(value) = Self::global_initializer(document, &var.range) {
+                            code.push_str(&format!(" = {}", value));
+                        }
                         return Some(HoverContent::new_code_only(code));
                     }
                 }
@@ -57,30 +86,36 @@ impl Server {
                     text: entity.description.to_string(),
                 });
             }
+        } else if node.kind() == "me" {
+            // The entity type comes from the file name, which is easy to get wrong in a copied file
+            let text = match mod_api.entities.get(&document.entity_type) {
+                Some(entity) => entity.description.to_string(),
+                None => format!("There is no {} entity in the mod API", document.entity_type),
+            };
+            return Some(HoverContent {
+                code: format!("me: {}", document.entity_type),
+                text,
+            });
         } else if node.kind() == "helper_identifier" {
             let name = &document.content[range];
 
-            if let Some(helper) = document
-                .helpers
-                .iter()
-                .find(|helper| helper.name.as_bytes() == name)
-            {
+            if let Some(helper) = document.get_helper(name) {
                 return Some(HoverContent::new_code_only(helper.format()));
             }
         } else if node.kind() == "on_identifier" {
             let name = String::from_utf8(document.content[range].to_vec()).ok()?;
-            if let Some(entity) = mod_api.entities.get(&document.entity_type) {
-                if let Some(on_func) = entity.on_functions.get(&name) {
-                    return Some(HoverContent {
-                        code: name,
-                        text: on_func.description.to_string(),
-                    });
-                }
+            if let Some(entity) = mod_api.entities.get(&document.entity_type)
+                && let Some(on_func) = entity.on_functions.get(&name)
+            {
+                return Some(HoverContent {
+                    code: name,
+                    text: on_func.description.to_string(),
+                });
             }
         }
 
         let name = String::from_utf8(document.content[node.byte_range()].to_vec()).ok()?;
-        log::info!("{}", name);
+        log::debug!("Hovering over {}", name);
         if let Some(desc) = KEYWORDS.get(name.as_str()) {
             return Some(HoverContent {
                 code: name.to_string(),
@@ -98,36 +133,25 @@ impl Server {
             return Some(HoverContent {
                 code: snippet.label.to_string(),
                 text: snippet.doc.to_string(),
-            })
+            });
         }
 
         None
     }
-    pub fn handle_hover(&self, params: HoverParams, connection: &mut Connection, id: RequestId) {
+    pub fn handle_hover(&self, params: HoverParams) -> RequestResult {
         let uri = params
             .text_document_position_params
             .text_document
             .uri
             .as_str();
+        let Some(path) = uri_to_path(uri) else {
+            return Err(unsupported_uri(uri));
+        };
 
-        // We probably wont need to use this server on TCP
-        assert!(uri.starts_with("file://"));
-
-        let path = &uri["file.//".len()..];
-
-        if !self.file_system.exists(path).unwrap_or(false) {
-            connection
-                .sender
-                .send(Message::Response(Response::new_err(
-                    id,
-                    ErrorCode::InvalidRequest as i32,
-                    format!("File doesnt exist: {}", path),
-                )))
-                .unwrap();
-            return;
-        }
-
-        let document = &self.document_map.get(path).unwrap();
+        // mod_api.json is never parsed as grug, and neither are files the server doesn't know
+        let Some(document) = self.document_map.get(path) else {
+            return ok(None::<Hover>);
+        };
 
         let ast = &document.tree;
 
@@ -136,35 +160,36 @@ impl Server {
             row: params.text_document_position_params.position.line as usize,
         };
 
+        // `me` is an anonymous node, so the named lookup would skip past it
         let node = ast
             .root_node()
-            .named_descendant_for_point_range(point, point)
-            .unwrap();
+            .descendant_for_point_range(point, point)
+            .filter(|node| node.kind() == "me")
+            .unwrap_or_else(|| {
+                ast.root_node()
+                    .named_descendant_for_point_range(point, point)
+                    .unwrap()
+            });
 
         let node = match node.kind() {
-            "if_statement" | "while_statement" | "return_statement" | "empty_return" | "unary_expression" => node.child(0).unwrap(),
+            "if_statement" | "while_statement" | "return_statement" | "empty_return"
+            | "unary_expression" => node.child(0).unwrap(),
             _ => node,
         };
 
         let range = node.range();
 
-        let content = Self::get_hover(&self.mod_api, document, &node);
-        if content.is_none() {
-            connection
-                .sender
-                .send(Message::Response(Response::new_ok(
-                    id,
-                    serde_json::Value::Null,
-                )))
-                .unwrap();
-            return;
-        }
-
-        let content = content.unwrap();
+        let Some(content) = Self::get_hover(&self.mod_api, document, &node) else {
+            return ok(serde_json::Value::Null);
+        };
+        let markup_kind = self.hover_markup_kind();
         let mut hover_text = String::new();
 
         if !content.code.is_empty() {
-            hover_text = format!("```rust\n{}\n```", content.code);
+            hover_text = match markup_kind {
+                MarkupKind::Markdown => format!("```rust\n{}\n```", content.code),
+                MarkupKind::PlainText => format!("{}\n", content.code),
+            };
         }
         if !content.text.is_empty() {
             if !content.code.is_empty() {
@@ -173,26 +198,139 @@ impl Server {
             hover_text.push_str(&content.text);
         }
 
-        let res = Response::new_ok(
-            id,
-            Hover {
-                contents: HoverContents::Markup(MarkupContent {
-                    kind: MarkupKind::Markdown,
-                    value: String::from_utf8(hover_text.as_bytes().to_vec()).unwrap(),
-                }),
-                range: Some(Range {
-                    start: Position {
-                        line: range.start_point.row as u32,
-                        character: range.start_point.column as u32,
-                    },
-                    end: Position {
-                        line: range.end_point.row as u32,
-                        character: range.end_point.column as u32,
-                    },
-                }),
-            },
-        );
-
-        connection.sender.send(Message::Response(res)).unwrap();
+        ok(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: markup_kind,
+                value: String::from_utf8(hover_text.as_bytes().to_vec()).unwrap(),
+            }),
+            range: Some(Range {
+                start: Position {
+                    line: range.start_point.row as u32,
+                    character: range.start_point.column as u32,
+                },
+                end: Position {
+                    line: range.end_point.row as u32,
+                    character: range.end_point.column as u32,
+                },
+            }),
+        })
+    }
+}
+
+#[test]
+fn me_hover_test() {
+    use lsp_types::request::HoverRequest;
+
+    use crate::test_support::TestClient;
+
+    let mut client = TestClient::new();
+    client.open("tired-box.grug", "on_spawn() {\n    despawn(m$0e)\n}\n");
+
+    let hover = client
+        .expect::<HoverRequest>(HoverParams {
+            text_document_position_params: client.cursor("tired-box.grug"),
+            work_done_progress_params: Default::default(),
+        })
+        .unwrap();
+    let HoverContents::Markup(markup) = hover.contents else {
+        panic!("{:?}", hover.contents);
+    };
+    assert!(markup.value.starts_with("me: box\n"), "{}", markup.value);
+    assert!(
+        markup
+            .value
+            .contains("There is no box entity in the mod API")
+    );
+}
+
+#[test]
+fn global_hover_test() {
+    use lsp_types::request::HoverRequest;
+
+    use crate::test_support::TestClient;
+
+    let mut client = TestClient::new();
+    let mut hover = |fixture: &str| -> String {
+        client.open("tired-box.grug", fixture);
+        let hover = client
+            .expect::<HoverRequest>(HoverParams {
+                text_document_position_params: client.cursor("tired-box.grug"),
+                work_done_progress_params: Default::default(),
+            })
+            .unwrap();
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("{:?}", hover.contents);
+        };
+        markup.value
+    };
+
+    assert_eq!(
+        hover("speed: f32 = 2.0 * 3.0\n\non_tick() {\n    x: f32 = spe$0ed\n}\n"),
+        "speed: f32 = 6.0\n"
+    );
+    assert_eq!(
+        hover("cap: i32 = 5\nlimit: i32 = cap + 1\n\non_tick() {\n    x: i32 = lim$0it\n}\n"),
+        "limit: i32 = cap + 1\n"
+    );
+    // Locals don't get a value, it changes too much to be useful
+    assert_eq!(
+        hover("on_tick() {\n    x: i32 = 1\n    y: i32 = $0x\n}\n"),
+        "x: i32\n"
+    );
+}
+
+#[test]
+fn mod_api_hover_test() {
+    use std::str::FromStr;
+
+    use serde_json::{Value, json};
+    use tree_sitter::Parser;
+
+    use crate::server::{document::Document, mod_api::ModApi, settings::Settings};
+
+    let root = std::env::temp_dir().join(format!("grug-ls-mod-api-hover-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let json = r#"{ "entities": { "box": {} }, "game_functions": {} }"#;
+    std::fs::write(root.join("mod_api.json"), json).unwrap();
+
+    let mut server = Server::new(
+        Some(root.clone()),
+        Settings::default(),
+        Default::default(),
+        ModApi::default(),
+        std::sync::mpsc::channel().1,
+        crossbeam_channel::unbounded().0,
+    );
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let uri = format!("file://{}", root.join("mod_api.json").display());
+    assert!(
+        Document::new(
+            &mut parser,
+            json.as_bytes().to_vec(),
+            "mod_api.json".to_string(),
+            lsp_types::Uri::from_str(&uri).unwrap(),
+        )
+        .is_none()
+    );
+
+    // The file is on disk, but its buffer is only ever read as the mod API
+    server.handle_notification(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": { "uri": uri, "languageId": "json", "version": 1, "text": json },
+        }),
+        &mut parser,
+    );
+    let position =
+        json!({ "textDocument": { "uri": uri }, "position": { "line": 0, "character": 4 } });
+    for method in ["textDocument/hover", "textDocument/definition"] {
+        let result = server.handle_request(method, position.clone(), &mut parser);
+        assert_eq!(result.unwrap(), Value::Null, "{}", method);
     }
+
+    std::fs::remove_dir_all(&root).unwrap();
 }
@@ -0,0 +1,260 @@
+use std::path::{Path, PathBuf};
+
+use crate::server::Server;
+
+// Read in every directory, with the rules of .ignore winning over .gitignore
+const IGNORE_FILE_NAMES: &[&str] = &[".gitignore", ".ignore"];
+
+// `*` and `?` within a single part of a path
+fn matches_part(pattern: &[u8], text: &[u8]) -> bool {
+    match (pattern.first(), text.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            matches_part(&pattern[1..], text)
+                || (!text.is_empty() && matches_part(pattern, &text[1..]))
+        }
+        (Some(b'?'), Some(_)) => matches_part(&pattern[1..], &text[1..]),
+        (Some(a), Some(b)) if a == b => matches_part(&pattern[1..], &text[1..]),
+        _ => false,
+    }
+}
+
+// `**` stands for any number of directories
+fn matches_parts(pattern: &[&str], parts: &[&str]) -> bool {
+    match (pattern.first(), parts.first()) {
+        (None, None) => true,
+        (Some(&"**"), _) => {
+            matches_parts(&pattern[1..], parts)
+                || (!parts.is_empty() && matches_parts(pattern, &parts[1..]))
+        }
+        (Some(pattern_part), Some(part)) => {
+            matches_part(pattern_part.as_bytes(), part.as_bytes())
+                && matches_parts(&pattern[1..], &parts[1..])
+        }
+        _ => false,
+    }
+}
+
+// Globs are relative to the workspace root, like `mods/vendor/**` or `build`.
+// One without a `/` matches a file or directory with that name anywhere,
+// and a directory that matches takes everything inside of it along
+pub fn glob_matches(glob: &str, relative: &Path) -> bool {
+    let glob = glob.trim_start_matches("./").trim_end_matches('/');
+    let parts: Vec<&str> = relative
+        .components()
+        .filter_map(|component| component.as_os_str().to_str())
+        .collect();
+
+    if !glob.contains('/') {
+        return parts
+            .iter()
+            .any(|part| matches_part(glob.as_bytes(), part.as_bytes()));
+    }
+
+    let pattern: Vec<&str> = glob.split('/').collect();
+    (1..=parts.len()).any(|len| matches_parts(&pattern, &parts[..len]))
+}
+
+// A line of a .gitignore or .ignore file
+struct IgnoreRule {
+    pattern: Vec<String>,
+    // Patterns with a `/` in them are relative to the directory of their file,
+    // the others match a name at any depth
+    anchored: bool,
+    dir_only: bool,
+    // `!` lets back in what an earlier rule ignored
+    negated: bool,
+}
+
+pub struct IgnoreFile {
+    dir: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreFile {
+    pub fn parse(dir: &Path, text: &str) -> IgnoreFile {
+        let rules = text
+            .lines()
+            .map(|line| line.trim_end())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(line) => (true, line),
+                    None => (false, line.strip_prefix('\\').unwrap_or(line)),
+                };
+                let (dir_only, line) = match line.strip_suffix('/') {
+                    Some(line) => (true, line),
+                    None => (false, line),
+                };
+
+                IgnoreRule {
+                    pattern: line
+                        .trim_start_matches('/')
+                        .split('/')
+                        .map(str::to_string)
+                        .collect(),
+                    anchored: line.contains('/'),
+                    dir_only,
+                    negated,
+                }
+            })
+            .collect();
+
+        IgnoreFile {
+            dir: dir.to_path_buf(),
+            rules,
+        }
+    }
+
+    // Whether the last rule that matches the path ignores it, None when no rule does
+    fn matches(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        let parts: Vec<&str> = relative
+            .components()
+            .filter_map(|component| component.as_os_str().to_str())
+            .collect();
+        let name = parts.last()?;
+
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| is_dir || !rule.dir_only)
+            .find(|rule| {
+                if rule.anchored {
+                    let pattern: Vec<&str> = rule.pattern.iter().map(String::as_str).collect();
+                    matches_parts(&pattern, &parts)
+                } else {
+                    matches_part(rule.pattern[0].as_bytes(), name.as_bytes())
+                }
+            })
+            .map(|rule| !rule.negated)
+    }
+}
+
+pub fn read_ignore_files(dir: &Path) -> Vec<IgnoreFile> {
+    IGNORE_FILE_NAMES
+        .iter()
+        .filter_map(|name| std::fs::read_to_string(dir.join(name)).ok())
+        .map(|text| IgnoreFile::parse(dir, &text))
+        .collect()
+}
+
+// Files deeper in the tree have the last word
+pub fn is_ignored(ignore_files: &[IgnoreFile], path: &Path, is_dir: bool) -> bool {
+    ignore_files
+        .iter()
+        .filter_map(|ignore_file| ignore_file.matches(path, is_dir))
+        .next_back()
+        .unwrap_or(false)
+}
+
+// Reads the ignore files from the root down to the path. A file in an ignored directory
+// stays ignored, like in git
+pub fn is_ignored_below(root_path: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(root_path) else {
+        return false;
+    };
+    let components: Vec<_> = relative.components().collect();
+
+    let mut ignore_files = read_ignore_files(root_path);
+    let mut current = root_path.to_path_buf();
+    for (idx, component) in components.iter().enumerate() {
+        current.push(component);
+        let is_dir = idx + 1 < components.len();
+        if is_ignored(&ignore_files, &current, is_dir) {
+            return true;
+        }
+        if is_dir {
+            ignore_files.extend(read_ignore_files(&current));
+        }
+    }
+
+    false
+}
+
+impl Server {
+    // For files the watcher sees, the workspace scan keeps track of the ignore files itself
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        self.settings.respect_ignore_files
+            && self
+                .root_path
+                .as_deref()
+                .is_some_and(|root_path| is_ignored_below(root_path, path))
+    }
+
+    // Files the workspace scan, the watcher and workspace-wide requests leave out
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let Some(relative) = self
+            .root_path
+            .as_deref()
+            .and_then(|root_path| path.strip_prefix(root_path).ok())
+        else {
+            return false;
+        };
+
+        self.settings
+            .exclude
+            .iter()
+            .any(|glob| glob_matches(glob, relative))
+    }
+}
+
+#[test]
+fn glob_matches_test() {
+    let path = Path::new("mods/vendor/guns/ak47-gun.grug");
+
+    assert!(glob_matches("mods/vendor", path));
+    assert!(glob_matches("mods/vendor/", path));
+    assert!(glob_matches("mods/*/guns/**", path));
+    assert!(glob_matches("**/*-gun.grug", path));
+    assert!(glob_matches("vendor", path));
+    assert!(glob_matches("ak47-???.grug", path));
+    assert!(!glob_matches("mods/guns", path));
+    // Globs with a `/` start from the root
+    assert!(!glob_matches("vendor/guns", path));
+    assert!(!glob_matches("guns", Path::new("mods/shotguns/a-gun.grug")));
+    assert!(!glob_matches("*.json", path));
+}
+
+#[test]
+fn ignore_files_test() {
+    use crate::server::workspace::collect_grug_files_except;
+
+    let root = std::env::temp_dir().join(format!("grug-ls-ignore-{}", std::process::id()));
+    let foo = root.join("mods").join("foo");
+    for dir in ["build", "generated", "vendor"] {
+        std::fs::create_dir_all(foo.join(dir)).unwrap();
+    }
+    std::fs::write(
+        root.join(".gitignore"),
+        "# Build output\nbuild/\n*-tmp.grug\n!keep-tmp.grug\n/mods/foo/generated\n",
+    )
+    .unwrap();
+    std::fs::write(foo.join(".ignore"), "vendor\n").unwrap();
+    for file in [
+        "a-box.grug",
+        "b-tmp.grug",
+        "keep-tmp.grug",
+        "build/c-box.grug",
+        "generated/d-box.grug",
+        "vendor/e-box.grug",
+    ] {
+        std::fs::write(foo.join(file), "on_spawn() {\n}\n").unwrap();
+    }
+
+    let mut paths = Vec::new();
+    collect_grug_files_except(&root, &|_| false, true, &mut paths);
+    paths.sort();
+    assert_eq!(paths, [foo.join("a-box.grug"), foo.join("keep-tmp.grug")]);
+
+    assert!(is_ignored_below(&root, &foo.join("build/c-box.grug")));
+    assert!(is_ignored_below(&root, &foo.join("vendor/e-box.grug")));
+    assert!(is_ignored_below(&root, &foo.join("b-tmp.grug")));
+    assert!(!is_ignored_below(&root, &foo.join("keep-tmp.grug")));
+
+    let mut paths = Vec::new();
+    collect_grug_files_except(&root, &|_| false, false, &mut paths);
+    assert_eq!(paths.len(), 6);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,212 @@
+use std::str::FromStr;
+
+use lsp_types::{
+    Location, Range, Uri,
+    request::{GotoImplementationParams, GotoImplementationResponse},
+};
+use tree_sitter::Point;
+
+use crate::server::{
+    Server,
+    entity_rename::{entities_object, string_content},
+    mod_api::JSON_PARSER,
+    utils::{RequestResult, ok, treesitter_range_to_lsp, uri_to_path},
+};
+
+// What the cursor is on, for the scripts that implement it
+enum Target {
+    Entity(String),
+    OnFunction(String, String),
+}
+
+impl Server {
+    // The entity key in mod_api.json the point is in, or one of its on_functions
+    fn mod_api_target(&self, point: Point) -> Option<Target> {
+        let json = self.mod_api_text()?;
+        let tree = JSON_PARSER.lock().unwrap().parse(&json, None)?;
+        let entities = entities_object(&json, tree.root_node())?;
+
+        let mut cursor = entities.walk();
+        let entry = entities
+            .children(&mut cursor)
+            .filter(|entry| entry.kind() == "pair")
+            .find(|entry| entry.start_position() <= point && point <= entry.end_position())?;
+        let key = string_content(&json, &entry.child_by_field_name("key")?)?;
+        let entity_type = String::from_utf8_lossy(&json[key.start_byte..key.end_byte]).to_string();
+
+        let on_function = self
+            .mod_api
+            .entities
+            .get(&entity_type)?
+            .on_functions
+            .iter()
+            .find(|(_, on_function)| {
+                on_function.range.start_point <= point && point <= on_function.range.end_point
+            });
+
+        Some(match on_function {
+            Some((name, _)) => Target::OnFunction(entity_type, name.clone()),
+            None => Target::Entity(entity_type),
+        })
+    }
+
+    // An entity type in grug code, from a type or `me`
+    fn script_target(&self, path: &str, point: Point) -> Option<Target> {
+        self.with_document(path, |document| {
+            let node = document
+                .tree
+                .root_node()
+                .descendant_for_point_range(point, point)?;
+
+            let entity_type = match node.kind() {
+                "me" => document.entity_type.clone(),
+                "type" | "identifier" => {
+                    String::from_utf8_lossy(&document.content[node.byte_range()]).to_string()
+                }
+                _ => return None,
+            };
+
+            self.mod_api
+                .entities
+                .contains_key(&entity_type)
+                .then_some(Target::Entity(entity_type))
+        })
+        .flatten()
+    }
+
+    // The scripts of an entity type, or where they declare one of its on_functions
+    fn implementations(&self, target: &Target) -> Vec<Location> {
+        let entity_type = match target {
+            Target::Entity(entity_type) | Target::OnFunction(entity_type, _) => entity_type,
+        };
+
+        self.get_workspace_entities()
+            .into_iter()
+            .filter(|entity| &entity.entity_type == entity_type)
+            .filter_map(|entity| {
+                let uri = Uri::from_str(&format!("file://{}", entity.path)).ok()?;
+
+                let range = match target {
+                    Target::Entity(_) => Range::default(),
+                    Target::OnFunction(_, name) => self
+                        .with_document(&entity.path, |document| {
+                            let on_function = document
+                                .on_functions
+                                .iter()
+                                .find(|on_function| &on_function.name == name)?;
+                            let declaration = document.tree.root_node().descendant_for_byte_range(
+                                on_function.range.start_byte,
+                                on_function.range.end_byte,
+                            )?;
+
+                            Some(treesitter_range_to_lsp(
+                                &declaration.child_by_field_name("name")?.range(),
+                            ))
+                        })
+                        .flatten()?,
+                };
+
+                Some(Location::new(uri, range))
+            })
+            .collect()
+    }
+
+    pub fn handle_implementation(&self, params: GotoImplementationParams) -> RequestResult {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .as_str();
+        // Unsaved buffers aren't scripts anything could be implemented in
+        let Some(path) = uri_to_path(uri) else {
+            return ok(None::<GotoImplementationResponse>);
+        };
+        let position = params.text_document_position_params.position;
+        let point = Point {
+            row: position.line as usize,
+            column: position.character as usize,
+        };
+
+        let target = if self.is_mod_api_path(path) {
+            self.mod_api_target(point)
+        } else {
+            self.script_target(path, point)
+        };
+
+        let locations = target.map(|target| self.implementations(&target));
+        ok(locations.map(GotoImplementationResponse::Array))
+    }
+}
+
+#[test]
+fn implementation_test() {
+    use lsp_types::{Position, request::GotoImplementation};
+
+    use crate::test_support::{TestClient, uri};
+
+    let mut client = TestClient::new();
+    client.open(
+        "mod_api.json",
+        r#"{
+    "entities": {
+        "b$0ox": {
+            "on_functions": {"on_spawn": {}, "on_tick": {}}
+        }
+    },
+    "game_functions": {}
+}"#,
+    );
+    client.open(
+        "mods/foo/crate-box.grug",
+        "on_tick() {\n}\n\non_spawn() {\n    other: b$0ox = me\n}\n",
+    );
+    client.open("mods/foo/barrel-box.grug", "on_spawn() {\n}\n");
+    client.open("mods/foo/ak47-gun.grug", "on_spawn() {\n}\n");
+
+    let implementations = |client: &mut TestClient, path: &str, position: Position| {
+        let response = client.expect::<GotoImplementation>(GotoImplementationParams {
+            text_document_position_params: lsp_types::TextDocumentPositionParams {
+                text_document: client.cursor(path).text_document,
+                position,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let Some(GotoImplementationResponse::Array(locations)) = response else {
+            panic!("{:?}", response);
+        };
+
+        locations
+            .into_iter()
+            .map(|location| (location.uri, location.range.start))
+            .collect::<Vec<_>>()
+    };
+
+    let scripts = [
+        (uri("mods/foo/barrel-box.grug"), Position::new(0, 0)),
+        (uri("mods/foo/crate-box.grug"), Position::new(0, 0)),
+    ];
+    let entity_key = client.cursor("mod_api.json").position;
+    assert_eq!(
+        implementations(&mut client, "mod_api.json", entity_key),
+        scripts
+    );
+    let entity_type = client.cursor("mods/foo/crate-box.grug").position;
+    assert_eq!(
+        implementations(&mut client, "mods/foo/crate-box.grug", entity_type),
+        scripts
+    );
+
+    // Only crate-box.grug has an on_tick, and it's the first function there
+    assert_eq!(
+        implementations(&mut client, "mod_api.json", Position::new(3, 45)),
+        [(uri("mods/foo/crate-box.grug"), Position::new(0, 0))]
+    );
+    assert_eq!(
+        implementations(&mut client, "mod_api.json", Position::new(3, 32)),
+        [
+            (uri("mods/foo/barrel-box.grug"), Position::new(0, 0)),
+            (uri("mods/foo/crate-box.grug"), Position::new(3, 0)),
+        ]
+    );
+}
@@ -1,16 +1,42 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    path::PathBuf,
+    str::FromStr,
+    sync::mpsc::{Receiver, channel},
+    time::Instant,
+};
 
-use lsp_types::InitializeParams;
+use lsp_types::{
+    ClientCapabilities, CodeActionKind, CodeActionOptions, CodeActionProviderCapability,
+    CodeLensOptions, CompletionOptions, DocumentLinkOptions, FileOperationFilter,
+    FileOperationPattern, FileOperationPatternKind, FileOperationRegistrationOptions,
+    HoverProviderCapability, ImplementationProviderCapability, InitializeParams, InlayHintOptions,
+    InlayHintServerCapabilities, OneOf, PositionEncodingKind, SemanticTokensFullOptions,
+    SemanticTokensOptions, SemanticTokensServerCapabilities, ServerCapabilities,
+    SignatureHelpOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextDocumentSyncSaveOptions,
+    WorkspaceFileOperationsServerCapabilities, WorkspaceServerCapabilities,
+};
 use serde::Serialize;
-use vfs::MemoryFS;
 
-use crate::server::{Server, helper::spawn_worker, mod_api::ModApi};
+use crate::server::{
+    Server,
+    commands::execute_command_options,
+    config_file::with_workspace_config,
+    helper::{ServerUpdate, spawn_worker},
+    mod_api::{ModApi, ModApiError},
+    overlay_fs::OverlayFS,
+    semantic_tokens,
+    settings::{Feature, Settings},
+    signature_help,
+    utils::{Client, uri_to_path},
+};
 
 use log::error;
+use log::info;
+use log::warn;
 
 #[derive(Debug, Serialize)]
 pub enum ServerInitError {
-    NoRootPath,
     RootPathParseError,
     NoModApi,
     InitParseError(String),
@@ -19,29 +45,137 @@ pub enum ServerInitError {
     ModApiIOError(String),
     ModApiParseError(String),
 }
+
+// Failing to load the mod API or to watch the workspace doesn't stop the server,
+// the errors are shown once it's up
+type LoadedWorkspace = (
+    PathBuf,
+    Result<ModApi, ModApiError>,
+    Result<Receiver<ServerUpdate>, String>,
+);
+
+fn grug_file_operation_options() -> FileOperationRegistrationOptions {
+    FileOperationRegistrationOptions {
+        filters: vec![FileOperationFilter {
+            scheme: Some("file".to_string()),
+            pattern: FileOperationPattern {
+                glob: "**/*.grug".to_string(),
+                matches: Some(FileOperationPatternKind::File),
+                options: None,
+            },
+        }],
+    }
+}
+
 impl Server {
-    pub fn from_request(params: InitializeParams) -> Result<Server, ServerInitError> {
-        let mut root_path: Option<String> = None;
+    pub fn get_capabilities(&self) -> ServerCapabilities {
+        let enabled = |feature: Feature| self.settings.is_enabled(feature);
 
-        #[allow(deprecated)]
-        if let Some(ref folders) = params.workspace_folders {
-            root_path = Some(folders[0].name.to_string());
-        } else if let Some(ref uri) = params.root_uri {
-            let uri = uri.as_str();
-            assert!(uri.starts_with("file://"));
-            root_path = Some(uri["file://".len()..].to_string());
-        }
+        ServerCapabilities {
+            // Without it clients count in UTF-16, so lines with anything but ASCII come out shifted
+            position_encoding: self
+                .supports_utf8_positions()
+                .then_some(PositionEncodingKind::UTF8),
+            text_document_sync: Some(TextDocumentSyncCapability::Options(
+                TextDocumentSyncOptions {
+                    open_close: Some(true),
+                    // Ranges in edits are only read right when they count in bytes, so
+                    // everyone else sends the whole text
+                    change: Some(if self.supports_utf8_positions() {
+                        TextDocumentSyncKind::INCREMENTAL
+                    } else {
+                        TextDocumentSyncKind::FULL
+                    }),
+                    save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                    ..Default::default()
+                },
+            )),
+            hover_provider: enabled(Feature::Hover)
+                .then_some(HoverProviderCapability::Simple(true)),
+            completion_provider: enabled(Feature::Completion)
+                .then_some(CompletionOptions::default()),
+            definition_provider: enabled(Feature::Definition).then_some(OneOf::Left(true)),
+            implementation_provider: enabled(Feature::Implementation)
+                .then_some(ImplementationProviderCapability::Simple(true)),
+            rename_provider: enabled(Feature::Rename).then_some(OneOf::Right(
+                lsp_types::RenameOptions {
+                    prepare_provider: Some(false),
+                    work_done_progress_options: lsp_types::WorkDoneProgressOptions {
+                        work_done_progress: None,
+                    },
+                },
+            )),
+            document_formatting_provider: enabled(Feature::Formatting).then_some(OneOf::Left(true)),
+            references_provider: enabled(Feature::References).then_some(OneOf::Left(true)),
+            document_highlight_provider: enabled(Feature::DocumentHighlight)
+                .then_some(OneOf::Left(true)),
+            semantic_tokens_provider: enabled(Feature::SemanticTokens).then(|| {
+                SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                    legend: semantic_tokens::legend(),
+                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                    ..Default::default()
+                })
+            }),
+            code_action_provider: enabled(Feature::CodeActions).then(|| {
+                CodeActionProviderCapability::Options(CodeActionOptions {
+                    code_action_kinds: Some(vec![
+                        CodeActionKind::QUICKFIX,
+                        CodeActionKind::REFACTOR_REWRITE,
+                        CodeActionKind::SOURCE_FIX_ALL,
+                    ]),
+                    ..Default::default()
+                })
+            }),
+            signature_help_provider: enabled(Feature::SignatureHelp).then(|| {
+                SignatureHelpOptions {
+                    trigger_characters: Some(
+                        signature_help::TRIGGER_CHARACTERS
+                            .iter()
+                            .map(|chr| chr.to_string())
+                            .collect(),
+                    ),
+                    retrigger_characters: Some(
+                        signature_help::RETRIGGER_CHARACTERS
+                            .iter()
+                            .map(|chr| chr.to_string())
+                            .collect(),
+                    ),
+                    ..Default::default()
+                }
+            }),
+            workspace_symbol_provider: enabled(Feature::WorkspaceSymbols)
+                .then_some(OneOf::Left(true)),
+            inlay_hint_provider: enabled(Feature::InlayHints).then_some(OneOf::Right(
+                InlayHintServerCapabilities::Options(InlayHintOptions {
+                    resolve_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                }),
+            )),
+            code_lens_provider: enabled(Feature::CodeLens).then_some(CodeLensOptions {
+                resolve_provider: Some(true),
+            }),
+            document_link_provider: enabled(Feature::DocumentLinks).then_some(
+                DocumentLinkOptions {
+                    resolve_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                },
+            ),
+            execute_command_provider: Some(execute_command_options()),
+            workspace: Some(WorkspaceServerCapabilities {
+                workspace_folders: None,
+                file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                    will_rename: Some(grug_file_operation_options()),
+                    did_rename: Some(grug_file_operation_options()),
+                    ..Default::default()
+                }),
+            }),
 
-        let root_path = match root_path {
-            Some(root_path) => root_path,
-            None => {
-                error!("Couldn't get a root path");
-                error!("Init Request: {:?}", params);
-                return Err(ServerInitError::NoRootPath);
-            }
-        };
+            ..Default::default()
+        }
+    }
 
-        let mut root_path = match PathBuf::from_str(&root_path) {
+    fn resolve_root_path(root_path: &str) -> Result<PathBuf, ServerInitError> {
+        let mut root_path = match PathBuf::from_str(root_path) {
             Ok(root_path) => root_path,
             Err(_) => {
                 return Err(ServerInitError::RootPathParseError);
@@ -52,39 +186,206 @@ impl Server {
             // Vscode
             if let Some(cwd) = std::env::current_dir()
                 .ok()
-                .map(|cwd| cwd.parent().map(|p| p.to_path_buf()))
-                .flatten()
+                .and_then(|cwd| cwd.parent().map(|p| p.to_path_buf()))
             {
                 root_path = cwd.join(root_path);
             }
         }
 
-        let mod_api_json = match std::fs::read_to_string(&root_path.join("mod_api.json")) {
-            Ok(json) => json,
+        Ok(root_path)
+    }
+
+    fn load_workspace(
+        root_path: PathBuf,
+        settings: &Settings,
+    ) -> Result<LoadedWorkspace, ServerInitError> {
+        // A missing or broken mod_api.json is reported to the user once the server is up,
+        // the watcher picks the file up again when it gets fixed
+        let mod_api_path = root_path.join(&settings.mod_api_path);
+        let mod_api = match std::fs::read_to_string(&mod_api_path) {
+            Ok(json) => ModApi::from_json_strict(&json),
             Err(err) => {
-                return Err(ServerInitError::ModApiIOError(format!(
-                    "At {}: {}",
-                    root_path.to_string_lossy().into_owned(),
-                    err.to_string()
-                )));
+                error!("Couldn't read {}: {}", mod_api_path.display(), err);
+                Err(ModApiError {
+                    message: format!("Not found at {}", mod_api_path.display()),
+                    line: None,
+                    column: None,
+                })
             }
         };
-        let mod_api: ModApi = ModApi::from_json(&mod_api_json).unwrap_or(ModApi::default());
 
-        log::info!("{:?}", mod_api);
+        match &mod_api {
+            Ok(mod_api) => {
+                log::info!(
+                    "Loaded the mod_api with {} entities and {} game functions",
+                    mod_api.entities.len(),
+                    mod_api.game_functions.len()
+                );
+                log::debug!("{:?}", mod_api);
+            }
+            Err(err) => log::info!("{:?}", err),
+        }
+
+        let chan = spawn_worker(root_path.clone(), mod_api_path);
+        if let Err(err) = &chan {
+            error!("Couldn't start the file watcher: {}", err);
+        }
 
-        let chan = spawn_worker(root_path.clone()).unwrap();
+        Ok((root_path, mod_api, chan))
+    }
 
-        let client_capabilities = params.capabilities;
+    // Everything the server sends to the editor on its own goes to client
+    pub fn from_request(
+        params: InitializeParams,
+        client: Client,
+    ) -> Result<Server, ServerInitError> {
+        let mut root_path: Option<String> = None;
 
-        Ok(Server {
-            file_system: MemoryFS::new(),
+        #[allow(deprecated)]
+        if let Some(folder) = params
+            .workspace_folders
+            .as_ref()
+            .and_then(|folders| folders.first())
+        {
+            root_path = Some(folder.name.to_string());
+        } else if let Some(ref uri) = params.root_uri {
+            match uri_to_path(uri.as_str()) {
+                Some(path) => root_path = Some(path.to_string()),
+                None => warn!("Ignoring the root {}, it isn't a file:// URI", uri.as_str()),
+            }
+        }
+
+        let root_path = root_path
+            .map(|root_path| Self::resolve_root_path(&root_path))
+            .transpose()?;
+
+        // .grug-ls.toml in the workspace root fills in what the editor didn't send
+        let options =
+            with_workspace_config(root_path.as_deref(), params.initialization_options.clone());
+        let settings = Settings::from_initialization_options(options);
+        log::set_max_level(settings.get_log_level());
+        #[cfg(feature = "native")]
+        crate::logging::set_limits(settings.log_max_size, settings.log_max_files);
+        info!("Settings: {:?}", settings);
+
+        let (root_path, mod_api, chan) = match root_path {
+            Some(root_path) => {
+                let (root_path, mod_api, chan) = Self::load_workspace(root_path, &settings)?;
+                (Some(root_path), mod_api, chan)
+            }
+            None => {
+                // Without a workspace there is no mod_api.json to find, so only
+                // the features that work on a lone file are useful
+                info!("No root path, running in single-file mode");
+                (None, Ok(ModApi::default()), Ok(channel().1))
+            }
+        };
+
+        let mod_api_error = mod_api.as_ref().err().cloned();
+        let watcher_error = chan.as_ref().err().cloned();
+        let mut server = Server::new(
+            root_path,
+            settings,
+            params.capabilities,
+            mod_api.unwrap_or_default(),
+            chan.unwrap_or_else(|_| channel().1),
+            client,
+        );
+        server.initialization_options = params.initialization_options;
+        server.mod_api_loaded = server.root_path.is_some() && mod_api_error.is_none();
+        server.mod_api_error = mod_api_error;
+        server.watcher_stopped = watcher_error.is_some();
+        server.watcher_error = watcher_error;
+
+        Ok(server)
+    }
+
+    pub(crate) fn new(
+        root_path: Option<PathBuf>,
+        settings: Settings,
+        client_capabilities: ClientCapabilities,
+        mod_api: ModApi,
+        messages_chan: Receiver<ServerUpdate>,
+        client: Client,
+    ) -> Server {
+        #[cfg(feature = "native")]
+        let (analysis_jobs, analysis_results) = crate::server::helper::spawn_analysis_worker();
+        #[cfg(not(feature = "native"))]
+        let (analysis_jobs, analysis_results, analysis_worker) =
+            crate::server::helper::analysis_channels();
+
+        Server {
+            file_system: OverlayFS::new(root_path.clone()),
             root_path,
+            settings,
+            initialization_options: None,
+            config_files: Default::default(),
             client_capabilities,
             document_map: std::collections::HashMap::new(),
-            messages_chan: chan,
+            evicted_documents: std::collections::HashSet::new(),
+            load_order: std::collections::VecDeque::new(),
+            open_documents: std::collections::HashSet::new(),
+            messages_chan,
             mod_api,
+            mod_api_buffer: None,
             should_exit: false,
-        })
+            shutdown_requested: false,
+            outgoing_requests: 0,
+            pending_edits: std::collections::HashMap::new(),
+            document_versions: std::collections::HashMap::new(),
+            unparsed_changes: std::collections::HashMap::new(),
+            generations: std::collections::HashMap::new(),
+            analysis_jobs,
+            analysis_results,
+            #[cfg(not(feature = "native"))]
+            analysis_worker,
+            diagnostics_due: std::collections::HashMap::new(),
+            mod_api_error: None,
+            watcher_error: None,
+            message_stats: Default::default(),
+            request_deadline: None,
+            client,
+            started: Instant::now(),
+            mod_api_loaded: false,
+            watcher_stopped: false,
+        }
     }
 }
+
+#[cfg(feature = "native")]
+#[test]
+fn unwatched_root_test() {
+    use lsp_server::Message;
+    use lsp_types::{
+        MessageType, ShowMessageParams, Uri,
+        notification::{Notification, ShowMessage},
+    };
+
+    use crate::server::ping::WatcherStatus;
+
+    let root = std::env::temp_dir().join(format!("grug-ls-missing-root-{}", std::process::id()));
+    #[allow(deprecated)]
+    let params = InitializeParams {
+        root_uri: Some(Uri::from_str(&format!("file://{}", root.display())).unwrap()),
+        ..Default::default()
+    };
+    let (client, messages) = crossbeam_channel::unbounded();
+
+    // The server keeps going without watching the disk, and says so
+    let mut server = Server::from_request(params, client).unwrap();
+    assert_eq!(server.ping().watcher, WatcherStatus::Stopped);
+
+    server.report_startup_problems();
+    let warnings: Vec<ShowMessageParams> = messages
+        .try_iter()
+        .filter_map(|message| match message {
+            Message::Notification(notification) if notification.method == ShowMessage::METHOD => {
+                serde_json::from_value::<ShowMessageParams>(notification.params).ok()
+            }
+            _ => None,
+        })
+        .filter(|params| params.typ == MessageType::WARNING)
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].message.contains("go unnoticed until a restart"));
+}
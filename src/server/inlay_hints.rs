@@ -0,0 +1,541 @@
+use lsp_types::{
+    InlayHint, InlayHintKind, InlayHintLabel, InlayHintLabelPart, InlayHintLabelPartTooltip,
+    InlayHintParams, Location, MarkupContent, MarkupKind, Uri,
+};
+use serde::{Deserialize, Serialize};
+use tree_sitter::Node;
+
+use crate::server::{
+    Server,
+    document::Document,
+    mod_api::ModApi,
+    utils::{
+        RequestResult, doc_comment, get_descendants_of_kind, get_inner_expression, ok,
+        strip_parentheses, treesitter_range_to_lsp, unsupported_uri, uri_to_path,
+    },
+};
+
+// What inlayHint/resolve needs to fill in a hint's tooltip and link
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum HintData {
+    Me {
+        uri: Uri,
+    },
+    Parameter {
+        uri: Uri,
+        function: String,
+        index: usize,
+    },
+}
+
+impl HintData {
+    fn uri(&self) -> &Uri {
+        match self {
+            HintData::Me { uri } | HintData::Parameter { uri, .. } => uri,
+        }
+    }
+}
+
+// The entity type after every `me`, since it comes from the file name and copied files often keep the wrong one
+fn me_hints(document: &Document, range: lsp_types::Range) -> Vec<InlayHint> {
+    get_descendants_of_kind(&document.tree.root_node(), "me")
+        .into_iter()
+        .map(|me| treesitter_range_to_lsp(&me.range()))
+        .filter(|me| range.start <= me.start && me.end <= range.end)
+        .map(|me| InlayHint {
+            position: me.end,
+            label: InlayHintLabel::LabelParts(vec![
+                InlayHintLabelPart {
+                    value: ": ".to_string(),
+                    ..Default::default()
+                },
+                InlayHintLabelPart {
+                    value: document.entity_type.clone(),
+                    ..Default::default()
+                },
+            ]),
+            kind: Some(InlayHintKind::TYPE),
+            text_edits: None,
+            tooltip: None,
+            padding_left: None,
+            padding_right: None,
+            data: Some(
+                serde_json::to_value(HintData::Me {
+                    uri: document.uri.clone(),
+                })
+                .unwrap(),
+            ),
+        })
+        .collect()
+}
+
+// The type links to the entity in mod_api.json when the mod API has it
+fn resolve_me_hint(
+    part: &mut InlayHintLabelPart,
+    document: &Document,
+    mod_api: &ModApi,
+    mod_api_uri: Option<&Uri>,
+) {
+    let entity = mod_api.entities.get(&document.entity_type);
+
+    part.tooltip = Some(InlayHintLabelPartTooltip::String(match entity {
+        Some(entity) => entity.description.clone(),
+        None => format!("There is no {} entity in the mod API", document.entity_type),
+    }));
+    part.location = entity.zip(mod_api_uri).map(|(entity, uri)| Location {
+        uri: uri.clone(),
+        range: treesitter_range_to_lsp(&entity.range),
+    });
+}
+
+// The parameter's type and the function's description, linking to where the parameter is declared.
+// Game functions only have their entry in mod_api.json to link to
+fn resolve_parameter_hint(
+    part: &mut InlayHintLabelPart,
+    document: &Document,
+    mod_api: &ModApi,
+    mod_api_uri: Option<&Uri>,
+    markup_kind: MarkupKind,
+    function: &str,
+    index: usize,
+) {
+    let (parameter, r#type, description, location) =
+        if let Some(game_function) = mod_api.game_functions.get(function) {
+            let Some(argument) = game_function.arguments.get(index) else {
+                return;
+            };
+            let location = mod_api_uri.map(|uri| Location {
+                uri: uri.clone(),
+                range: treesitter_range_to_lsp(&game_function.range),
+            });
+
+            (
+                argument.get_name().to_string(),
+                argument.get_type(),
+                Some(game_function.description.clone()),
+                location,
+            )
+        } else {
+            let Some(helper) = document.get_helper(function.as_bytes()) else {
+                return;
+            };
+            let Some(param) = helper.params.get(index) else {
+                return;
+            };
+            let description = document
+                .tree
+                .root_node()
+                .descendant_for_byte_range(helper.range.start_byte, helper.range.end_byte)
+                .and_then(|declaration| doc_comment(&document.content, &declaration));
+            let location = Location {
+                uri: document.uri.clone(),
+                range: treesitter_range_to_lsp(&param.range),
+            };
+
+            (
+                param.name.clone(),
+                param.r#type.clone(),
+                description,
+                Some(location),
+            )
+        };
+
+    let declaration = format!("{}: {}", parameter, r#type.as_str());
+    let mut value = match markup_kind {
+        MarkupKind::Markdown => format!("```grug\n{}\n```", declaration),
+        MarkupKind::PlainText => declaration,
+    };
+    if let Some(description) = description.filter(|description| !description.is_empty()) {
+        value.push_str("\n\n");
+        value.push_str(&description);
+    }
+
+    part.tooltip = Some(InlayHintLabelPartTooltip::MarkupContent(MarkupContent {
+        kind: markup_kind,
+        value,
+    }));
+    part.location = location;
+}
+
+fn resolve_hint(
+    hint: &mut InlayHint,
+    document: &Document,
+    mod_api: &ModApi,
+    mod_api_uri: Option<&Uri>,
+    markup_kind: MarkupKind,
+) {
+    let Some(data) = hint
+        .data
+        .take()
+        .and_then(|data| serde_json::from_value::<HintData>(data).ok())
+    else {
+        return;
+    };
+    let InlayHintLabel::LabelParts(parts) = &mut hint.label else {
+        return;
+    };
+
+    match (data, parts.last_mut()) {
+        (HintData::Me { .. }, Some(part)) => resolve_me_hint(part, document, mod_api, mod_api_uri),
+        (
+            HintData::Parameter {
+                function, index, ..
+            },
+            Some(part),
+        ) => resolve_parameter_hint(
+            part,
+            document,
+            mod_api,
+            mod_api_uri,
+            markup_kind,
+            &function,
+            index,
+        ),
+        (_, None) => {}
+    }
+}
+
+// The names of the parameters of a game function or a helper in the same file
+fn parameter_names(document: &Document, mod_api: &ModApi, name: &str) -> Option<Vec<String>> {
+    if let Some(game_function) = mod_api.game_functions.get(name) {
+        return Some(
+            game_function
+                .arguments
+                .iter()
+                .map(|argument| argument.get_name().to_string())
+                .collect(),
+        );
+    }
+
+    let helper = document.get_helper(name.as_bytes())?;
+    Some(
+        helper
+            .params
+            .iter()
+            .map(|param| param.name.clone())
+            .collect(),
+    )
+}
+
+// Whether the argument already tells what it is, like `speed` or `get_speed()` for `speed`,
+// or `box_speed` and `speed_x` which only add to it
+fn matches_parameter(document: &Document, argument: &Node, parameter: &str) -> bool {
+    let Some(expression) = get_inner_expression(argument).map(strip_parentheses) else {
+        return false;
+    };
+
+    let name = match expression.kind() {
+        "identifier" => expression,
+        "function_call" => match expression.child_by_field_name("name") {
+            Some(name) => name,
+            None => return false,
+        },
+        _ => return false,
+    };
+    let name = String::from_utf8_lossy(&document.content[name.byte_range()]).to_lowercase();
+    let name = name.strip_prefix("get_").unwrap_or(&name).trim_matches('_');
+    let parameter = parameter.to_lowercase();
+    let parameter = parameter.trim_matches('_');
+
+    !parameter.is_empty()
+        && (name == parameter
+            || name.starts_with(&format!("{}_", parameter))
+            || name.ends_with(&format!("_{}", parameter)))
+}
+
+// The parameter name before every argument
+fn parameter_hints(
+    document: &Document,
+    mod_api: &ModApi,
+    hide_matching: bool,
+    range: lsp_types::Range,
+) -> Vec<InlayHint> {
+    let mut hints: Vec<InlayHint> = Vec::new();
+
+    for call in get_descendants_of_kind(&document.tree.root_node(), "function_call") {
+        let Some(name) = call
+            .child_by_field_name("name")
+            .and_then(|name| str::from_utf8(&document.content[name.byte_range()]).ok())
+        else {
+            continue;
+        };
+        let Some(parameters) = parameter_names(document, mod_api, name) else {
+            continue;
+        };
+
+        let mut cursor = call.walk();
+        for (index, (argument, parameter)) in call
+            .children_by_field_name("argument", &mut cursor)
+            .zip(parameters)
+            .enumerate()
+        {
+            let position = treesitter_range_to_lsp(&argument.range()).start;
+            if position < range.start || range.end < position {
+                continue;
+            }
+            if hide_matching && matches_parameter(document, &argument, &parameter) {
+                continue;
+            }
+
+            hints.push(InlayHint {
+                position,
+                label: InlayHintLabel::LabelParts(vec![InlayHintLabelPart {
+                    value: format!("{}:", parameter),
+                    ..Default::default()
+                }]),
+                kind: Some(InlayHintKind::PARAMETER),
+                text_edits: None,
+                tooltip: None,
+                padding_left: None,
+                padding_right: Some(true),
+                data: Some(
+                    serde_json::to_value(HintData::Parameter {
+                        uri: document.uri.clone(),
+                        function: name.to_string(),
+                        index,
+                    })
+                    .unwrap(),
+                ),
+            });
+        }
+    }
+
+    hints
+}
+
+impl Server {
+    // Clients that can't resolve the tooltips and links get them right away
+    fn supports_inlay_hint_resolve(&self) -> bool {
+        self.client_capabilities
+            .text_document
+            .as_ref()
+            .and_then(|text_document| text_document.inlay_hint.as_ref())
+            .and_then(|inlay_hint| inlay_hint.resolve_support.as_ref())
+            .is_some_and(|resolve_support| {
+                ["label.tooltip", "label.location"].iter().all(|property| {
+                    resolve_support
+                        .properties
+                        .iter()
+                        .any(|resolved| resolved == property)
+                })
+            })
+    }
+
+    // Hints are recomputed on every edit, so the tooltips and links wait for inlayHint/resolve
+    pub fn handle_inlay_hint(&self, params: InlayHintParams) -> RequestResult {
+        let uri = params.text_document.uri.as_str();
+        let Some(path) = uri_to_path(uri) else {
+            return Err(unsupported_uri(uri));
+        };
+        let hints = self
+            .get_document_by_uri(&params.text_document.uri)
+            .map(|document| {
+                let mut hints = me_hints(document, params.range);
+                hints.append(&mut parameter_hints(
+                    document,
+                    &self.mod_api,
+                    self.settings_for(path).hide_matching_parameter_hints,
+                    params.range,
+                ));
+
+                if !self.supports_inlay_hint_resolve() {
+                    for hint in hints.iter_mut() {
+                        self.resolve_inlay_hint(hint, document);
+                    }
+                }
+                hints
+            });
+
+        ok(hints)
+    }
+
+    fn resolve_inlay_hint(&self, hint: &mut InlayHint, document: &Document) {
+        resolve_hint(
+            hint,
+            document,
+            &self.mod_api,
+            self.mod_api_uri().as_ref(),
+            self.hover_markup_kind(),
+        );
+    }
+
+    pub fn handle_inlay_hint_resolve(&self, mut hint: InlayHint) -> RequestResult {
+        let document = hint
+            .data
+            .as_ref()
+            .and_then(|data| serde_json::from_value::<HintData>(data.clone()).ok())
+            .and_then(|data| self.get_document_by_uri(data.uri()));
+
+        if let Some(document) = document {
+            self.resolve_inlay_hint(&mut hint, document);
+        }
+
+        ok(hint)
+    }
+}
+
+#[test]
+fn me_hints_test() {
+    use std::str::FromStr;
+
+    use lsp_types::Position;
+
+    let source = "on_spawn() {\n    set_name(me)\n}\n\non_tick() {\n    despawn(me)\n}\n";
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let mod_api = ModApi::from_json(
+        r#"{"entities": {"box": {"description": "A box"}}, "game_functions": {}}"#,
+    )
+    .unwrap();
+    let mod_api_uri = Uri::from_str("file:///mod_api.json").unwrap();
+
+    let whole_file = lsp_types::Range::new(Position::new(0, 0), Position::new(7, 0));
+    let resolved = |mod_api: &ModApi, mut hint: InlayHint| {
+        resolve_hint(
+            &mut hint,
+            &document,
+            mod_api,
+            Some(&mod_api_uri),
+            MarkupKind::Markdown,
+        );
+        hint
+    };
+
+    let mut hints = me_hints(&document, whole_file);
+    assert_eq!(hints.len(), 2);
+    assert_eq!(hints[0].position, Position::new(1, 15));
+
+    // The link is left for inlayHint/resolve
+    let InlayHintLabel::LabelParts(parts) = &hints[0].label else {
+        panic!("{:?}", hints[0].label);
+    };
+    assert_eq!(parts[1].value, "box");
+    assert_eq!(parts[1].location, None);
+    let hint = resolved(&mod_api, hints.remove(0));
+    assert_eq!(hint.data, None);
+
+    let InlayHintLabel::LabelParts(parts) = &hint.label else {
+        panic!("{:?}", hint.label);
+    };
+    assert_eq!(
+        parts[1].location.as_ref().map(|location| &location.uri),
+        Some(&mod_api_uri)
+    );
+
+    // Only the ones in the requested range
+    let first_function = lsp_types::Range::new(Position::new(0, 0), Position::new(2, 1));
+    assert_eq!(me_hints(&document, first_function).len(), 1);
+
+    // A file named after an entity the mod API doesn't have still gets the hint, without the link
+    let hint = resolved(
+        &ModApi::default(),
+        me_hints(&document, whole_file).remove(0),
+    );
+    let InlayHintLabel::LabelParts(parts) = &hint.label else {
+        panic!("{:?}", hint.label);
+    };
+    assert_eq!(parts[1].location, None);
+    assert_eq!(
+        parts[1].tooltip,
+        Some(InlayHintLabelPartTooltip::String(
+            "There is no box entity in the mod API".to_string()
+        ))
+    );
+}
+
+#[test]
+fn parameter_hints_test() {
+    use std::str::FromStr;
+
+    use lsp_types::Position;
+
+    let source = "on_spawn() {\n    speed: f32 = 1.0\n    box_speed: f32 = 2.0\n    launch(speed, 3.0, box_speed)\n    launch(get_speed(), (speedy), helper_angle(2.0))\n}\n\nhelper_angle(_angle: f32) f32 {\n    return _angle\n}\n";
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let mod_api = ModApi::from_json(
+        r#"{"entities": {}, "game_functions": {"launch": {"arguments": [{"name": "speed", "type": "f32"}, {"name": "height", "type": "f32"}, {"name": "speed", "type": "f32"}]}}}"#,
+    )
+    .unwrap();
+
+    let labels = |hide_matching: bool| -> Vec<(Position, String)> {
+        let whole_file = lsp_types::Range::new(Position::new(0, 0), Position::new(10, 0));
+        parameter_hints(&document, &mod_api, hide_matching, whole_file)
+            .into_iter()
+            .map(|hint| {
+                let InlayHintLabel::LabelParts(parts) = hint.label else {
+                    panic!("{:?}", hint.label);
+                };
+                (hint.position, parts[0].value.clone())
+            })
+            .collect()
+    };
+
+    assert_eq!(
+        labels(true),
+        [
+            (Position::new(3, 18), "height:".to_string()),
+            (Position::new(4, 24), "height:".to_string()),
+            (Position::new(4, 34), "speed:".to_string()),
+            (Position::new(4, 47), "_angle:".to_string()),
+        ]
+    );
+    assert_eq!(labels(false).len(), 7);
+
+    // Helpers link to the parameter and are described by the comments above them
+    let source = source.replace(
+        "helper_angle(_angle",
+        "# Which way to go\nhelper_angle(_angle",
+    );
+    let document = Document::new(
+        &mut parser,
+        source.into_bytes(),
+        "tired-box.grug".to_string(),
+        Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+    let whole_file = lsp_types::Range::new(Position::new(0, 0), Position::new(11, 0));
+    let mut hint = parameter_hints(&document, &mod_api, true, whole_file)
+        .pop()
+        .unwrap();
+    resolve_hint(&mut hint, &document, &mod_api, None, MarkupKind::Markdown);
+    let InlayHintLabel::LabelParts(parts) = &hint.label else {
+        panic!("{:?}", hint.label);
+    };
+    assert_eq!(
+        parts[0].tooltip,
+        Some(InlayHintLabelPartTooltip::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "```grug\n_angle: f32\n```\n\nWhich way to go".to_string(),
+        }))
+    );
+    assert_eq!(
+        parts[0]
+            .location
+            .as_ref()
+            .map(|location| location.range.start),
+        Some(Position::new(8, 13))
+    );
+}
@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use lsp_types::MessageType;
+
+use crate::server::{Server, utils::show_message};
+
+impl Server {
+    // Past either limit, the features that go over the whole file skip it
+    pub fn is_large_file(&self, content: &[u8]) -> bool {
+        content.len() > self.settings.large_file_size
+            || content.iter().filter(|byte| **byte == b'\n').count()
+                >= self.settings.large_file_lines
+    }
+
+    // Told once when the file is opened, so the missing highlighting doesn't look like a bug
+    pub fn warn_about_large_file(&self, path: &str, content: &[u8]) {
+        if !self.is_large_file(content) {
+            return;
+        }
+
+        let file_name = Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(path);
+        show_message(
+            &self.client,
+            MessageType::WARNING,
+            format!(
+                "{} is past largeFileSize or largeFileLines, so it won't get semantic tokens, formatting or workspace symbols",
+                file_name
+            ),
+        );
+    }
+}
+
+#[test]
+fn large_file_test() {
+    use lsp_types::{
+        DocumentFormattingParams, FormattingOptions, SemanticTokensParams, TextDocumentIdentifier,
+        request::{Formatting, SemanticTokensFullRequest},
+    };
+
+    use crate::{
+        server::settings::Settings,
+        test_support::{TestClient, uri},
+    };
+
+    let mut client = TestClient::with_settings(Settings {
+        large_file_lines: 3,
+        ..Default::default()
+    });
+    client.open("small-box.grug", "on_spawn() {\n}\n");
+    client.open("big-box.grug", "on_spawn() {\n}\n\non_despawn() {\n}\n");
+
+    let semantic_tokens = |client: &mut TestClient, path: &str| {
+        client
+            .request::<SemanticTokensFullRequest>(SemanticTokensParams {
+                text_document: TextDocumentIdentifier::new(uri(path)),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .unwrap()
+    };
+    assert!(semantic_tokens(&mut client, "small-box.grug").is_some());
+    assert!(semantic_tokens(&mut client, "big-box.grug").is_none());
+
+    let formatting = client.request::<Formatting>(DocumentFormattingParams {
+        text_document: TextDocumentIdentifier::new(uri("big-box.grug")),
+        options: FormattingOptions::default(),
+        work_done_progress_params: Default::default(),
+    });
+    assert!(formatting.is_err());
+}
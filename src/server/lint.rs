@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, TextEdit};
+use serde::{Deserialize, Serialize};
+
+use crate::server::{
+    document::Document, mod_api::ModApi, settings::Settings, utils::position_to_byte,
+    workspace::WorkspaceEntity,
+};
+
+mod argument_type;
+mod constant_error;
+mod float_equality;
+mod global_initializer;
+mod helper_recursion;
+mod invalid_me;
+mod magic_number;
+mod missing_return;
+mod on_function_call;
+mod return_value;
+mod syntax_error;
+mod unknown_entity;
+mod unreachable;
+mod unused;
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    Off,
+    Hint,
+    Info,
+    Warning,
+    Error,
+}
+
+impl LintLevel {
+    pub fn as_severity(&self) -> Option<DiagnosticSeverity> {
+        match self {
+            LintLevel::Off => None,
+            LintLevel::Hint => Some(DiagnosticSeverity::HINT),
+            LintLevel::Info => Some(DiagnosticSeverity::INFORMATION),
+            LintLevel::Warning => Some(DiagnosticSeverity::WARNING),
+            LintLevel::Error => Some(DiagnosticSeverity::ERROR),
+        }
+    }
+}
+
+pub struct RuleMetadata {
+    // Shows up as the diagnostic code, and is the key used to configure the rule
+    pub code: &'static str,
+    pub description: &'static str,
+    pub default_level: LintLevel,
+}
+
+// Everything outside of the document a rule might want to look at
+pub struct LintContext<'a> {
+    pub mod_api: &'a ModApi,
+    // The mod the document is in, None outside of a workspace
+    pub mod_name: Option<String>,
+    pub entities: Vec<WorkspaceEntity>,
+    // Numbers further from zero than this are magic-number candidates
+    pub magic_number_threshold: f64,
+}
+
+impl<'a> LintContext<'a> {
+    pub fn new(mod_api: &'a ModApi) -> LintContext<'a> {
+        LintContext {
+            mod_api,
+            mod_name: None,
+            entities: Vec::new(),
+            magic_number_threshold: Settings::default().magic_number_threshold,
+        }
+    }
+}
+
+// An edit that is always safe to make, carried in the diagnostic's data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fix {
+    pub title: String,
+    pub edits: Vec<TextEdit>,
+}
+
+impl Fix {
+    pub fn attach(self, diagnostic: &mut Diagnostic) {
+        diagnostic.data = Some(serde_json::json!({ "fix": self }));
+    }
+
+    pub fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Fix> {
+        let fix = diagnostic.data.as_ref()?.get("fix")?;
+        serde_json::from_value(fix.clone()).ok()
+    }
+}
+
+// Fixes that overlap an earlier one are dropped whole, they get another chance on the next run
+fn non_overlapping_edits<'a>(
+    content: &[u8],
+    fixes: &'a [Fix],
+) -> Vec<(usize, usize, &'a TextEdit)> {
+    let mut edits: Vec<(usize, usize, &TextEdit)> = Vec::new();
+    for fix in fixes {
+        let fix_edits: Vec<(usize, usize, &TextEdit)> = fix
+            .edits
+            .iter()
+            .map(|edit| {
+                let start = position_to_byte(content, edit.range.start);
+                let end = position_to_byte(content, edit.range.end);
+                (start, end, edit)
+            })
+            .collect();
+
+        let overlaps = fix_edits.iter().any(|(start, end, _)| {
+            edits
+                .iter()
+                .any(|(other_start, other_end, _)| start < other_end && other_start < end)
+        });
+        if !overlaps {
+            edits.extend(fix_edits);
+        }
+    }
+
+    edits
+}
+
+// All the fixes as one list of edits, for source.fixAll
+pub fn combine_fixes(content: &[u8], fixes: &[Fix]) -> Vec<TextEdit> {
+    non_overlapping_edits(content, fixes)
+        .into_iter()
+        .map(|(_, _, edit)| edit.clone())
+        .collect()
+}
+
+pub fn apply_fixes(content: &[u8], fixes: &[Fix]) -> Vec<u8> {
+    let mut edits = non_overlapping_edits(content, fixes);
+    edits.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+
+    let mut content = content.to_vec();
+    for (start, end, edit) in edits {
+        content.splice(start..end, edit.new_text.bytes());
+    }
+
+    content
+}
+
+pub trait Rule: Send + Sync {
+    fn metadata(&self) -> &'static RuleMetadata;
+
+    // The severity of the returned diagnostics gets overwritten with the configured level
+    fn check(&self, document: &Document, context: &LintContext) -> Vec<Diagnostic>;
+}
+
+lazy_static! {
+    pub static ref RULES: Vec<Box<dyn Rule>> = vec![
+        Box::new(syntax_error::SyntaxError),
+        Box::new(helper_recursion::HelperRecursion),
+        Box::new(constant_error::ConstantError),
+        Box::new(invalid_me::InvalidMe),
+        Box::new(global_initializer::NonConstantGlobal),
+        Box::new(on_function_call::OnFunctionCall),
+        Box::new(return_value::ReturnValue),
+        Box::new(missing_return::MissingReturn),
+        Box::new(unreachable::UnreachableCode),
+        Box::new(unreachable::ConstantCondition),
+        Box::new(float_equality::FloatEquality),
+        Box::new(magic_number::MagicNumber),
+        Box::new(argument_type::ArgumentType),
+        Box::new(argument_type::ArgumentCount),
+        Box::new(unknown_entity::UnknownEntity),
+        Box::new(unused::UnusedParameter),
+        Box::new(unused::UnusedVariable),
+    ];
+}
+
+pub fn lint(
+    document: &Document,
+    context: &LintContext,
+    levels: &HashMap<String, LintLevel>,
+) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+    for rule in RULES.iter() {
+        let metadata = rule.metadata();
+        let level = levels
+            .get(metadata.code)
+            .copied()
+            .unwrap_or(metadata.default_level);
+
+        let Some(severity) = level.as_severity() else {
+            continue;
+        };
+
+        for mut diagnostic in rule.check(document, context) {
+            diagnostic.severity = Some(severity);
+            diagnostic.code = Some(NumberOrString::String(metadata.code.to_string()));
+            diagnostic.source = Some("grug-ls".to_string());
+
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    diagnostics
+}
+
+#[test]
+fn lint_level_test() {
+    use std::str::FromStr;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        b"on_spawn( {\n}\n".to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+    let mod_api = ModApi::default();
+
+    let diagnostics = lint(&document, &LintContext::new(&mod_api), &HashMap::new());
+    assert!(!diagnostics.is_empty());
+    assert!(diagnostics.iter().all(|diagnostic| {
+        diagnostic.severity == Some(DiagnosticSeverity::ERROR)
+            && diagnostic.code == Some(NumberOrString::String("syntax-error".to_string()))
+    }));
+
+    let levels = HashMap::from([("syntax-error".to_string(), LintLevel::Off)]);
+    assert!(lint(&document, &LintContext::new(&mod_api), &levels).is_empty());
+}
+
+#[test]
+fn apply_fixes_test() {
+    use lsp_types::{Position, Range};
+
+    let edit = |line: u32, start: u32, end: u32, new_text: &str| Fix {
+        title: String::new(),
+        edits: vec![TextEdit::new(
+            Range::new(Position::new(line, start), Position::new(line, end)),
+            new_text.to_string(),
+        )],
+    };
+
+    let content = b"a: f32 = 1\nb: f32 = 2\n";
+    let fixes = [
+        edit(0, 9, 10, "1.0"),
+        edit(1, 9, 10, "2.0"),
+        // Overlaps the first fix, so it waits for the next run
+        edit(0, 0, 10, "c: i32 = 3"),
+    ];
+
+    assert_eq!(
+        apply_fixes(content, &fixes),
+        b"a: f32 = 1.0\nb: f32 = 2.0\n"
+    );
+}
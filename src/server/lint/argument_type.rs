@@ -0,0 +1,336 @@
+use lsp_types::{Diagnostic, DiagnosticRelatedInformation, Location, TextEdit};
+use tree_sitter::Node;
+
+use crate::server::{
+    document::{Document, Type},
+    lint::{Fix, LintContext, LintLevel, Rule, RuleMetadata},
+    type_check::{infer_type, is_assignable},
+    utils::{get_descendants_of_kind, get_inner_expression, treesitter_range_to_lsp},
+};
+
+pub struct ArgumentType;
+pub struct ArgumentCount;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "argument-type",
+    description: "Arguments passed to game functions and helpers must match the declared parameter types",
+    default_level: LintLevel::Error,
+};
+
+static COUNT_METADATA: RuleMetadata = RuleMetadata {
+    code: "argument-count",
+    description: "Game functions and helpers must be called with as many arguments as they take",
+    default_level: LintLevel::Error,
+};
+
+// What a call has to match, from the mod API or a helper in the same file
+pub(super) struct Callee<'a> {
+    pub name: &'a str,
+    pub params: Vec<(String, Type)>,
+    // Where a helper is declared, shown alongside the diagnostic
+    declaration: Option<Location>,
+}
+
+pub(super) fn get_callee<'a>(
+    document: &'a Document,
+    context: &LintContext,
+    call: &Node,
+) -> Option<Callee<'a>> {
+    let name = call.child_by_field_name("name")?;
+    let name = str::from_utf8(&document.content[name.byte_range()]).ok()?;
+
+    if let Some(game_function) = context.mod_api.game_functions.get(name) {
+        return Some(Callee {
+            name,
+            params: game_function
+                .arguments
+                .iter()
+                .map(|argument| (argument.get_name().to_string(), argument.get_type()))
+                .collect(),
+            declaration: None,
+        });
+    }
+
+    let helper = document.get_helper(name.as_bytes())?;
+    let declaration = document
+        .tree
+        .root_node()
+        .descendant_for_byte_range(helper.range.start_byte, helper.range.end_byte)
+        .and_then(|declaration| declaration.child_by_field_name("name"))
+        .map(|name| Location {
+            uri: document.uri.clone(),
+            range: treesitter_range_to_lsp(&name.range()),
+        });
+
+    Some(Callee {
+        name,
+        params: helper
+            .params
+            .iter()
+            .map(|param| (param.name.clone(), param.r#type.clone()))
+            .collect(),
+        declaration,
+    })
+}
+
+impl Callee<'_> {
+    fn related_information(&self) -> Option<Vec<DiagnosticRelatedInformation>> {
+        let location = self.declaration.clone()?;
+
+        Some(vec![DiagnosticRelatedInformation {
+            location,
+            message: format!("{} is declared here", self.name),
+        }])
+    }
+}
+
+// Number literals can be rewritten to the expected type when no precision is lost
+fn convert_literal(document: &Document, argument: &Node, expected: &Type) -> Option<Fix> {
+    let mut number = get_inner_expression(argument)?;
+    if number.kind() == "unary_expression" {
+        number = number.child_by_field_name("operand")?;
+    }
+    if number.kind() != "number" {
+        return None;
+    }
+
+    let text = str::from_utf8(&document.content[number.byte_range()]).ok()?;
+    let new_text = match expected {
+        Type::F32 if !text.contains('.') => format!("{}.0", text),
+        Type::I32 => {
+            let (whole, fraction) = text.split_once('.')?;
+            if !fraction.bytes().all(|digit| digit == b'0') {
+                return None;
+            }
+            whole.to_string()
+        }
+        _ => return None,
+    };
+
+    Some(Fix {
+        title: format!("Change {} to {}", text, new_text),
+        edits: vec![TextEdit::new(
+            treesitter_range_to_lsp(&number.range()),
+            new_text,
+        )],
+    })
+}
+
+impl Rule for ArgumentType {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, context: &LintContext) -> Vec<Diagnostic> {
+        let mod_api = context.mod_api;
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+        for call in get_descendants_of_kind(&document.tree.root_node(), "function_call") {
+            let Some(callee) = get_callee(document, context, &call) else {
+                continue;
+            };
+
+            let mut cursor = call.walk();
+            for (argument, (param_name, expected)) in call
+                .children_by_field_name("argument", &mut cursor)
+                .zip(callee.params.iter())
+            {
+                let Some(actual) = infer_type(document, mod_api, &argument) else {
+                    continue;
+                };
+
+                if !is_assignable(expected, &actual) {
+                    let mut diagnostic = Diagnostic {
+                        range: treesitter_range_to_lsp(&argument.range()),
+                        message: format!(
+                            "{} expects {} to be {}, but got {}",
+                            callee.name,
+                            param_name,
+                            expected.as_str(),
+                            actual.as_str()
+                        ),
+                        related_information: callee.related_information(),
+                        ..Default::default()
+                    };
+                    if let Some(fix) = convert_literal(document, &argument, expected) {
+                        fix.attach(&mut diagnostic);
+                    }
+
+                    diagnostics.push(diagnostic);
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+impl Rule for ArgumentCount {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &COUNT_METADATA
+    }
+
+    fn check(&self, document: &Document, context: &LintContext) -> Vec<Diagnostic> {
+        get_descendants_of_kind(&document.tree.root_node(), "function_call")
+            .into_iter()
+            .filter_map(|call| {
+                let callee = get_callee(document, context, &call)?;
+
+                let mut cursor = call.walk();
+                let count = call.children_by_field_name("argument", &mut cursor).count();
+                if count == callee.params.len() {
+                    return None;
+                }
+
+                let plural = |count: usize| if count == 1 { "" } else { "s" };
+                Some(Diagnostic {
+                    range: treesitter_range_to_lsp(&call.range()),
+                    message: format!(
+                        "{} expects {} argument{}, but got {}",
+                        callee.name,
+                        callee.params.len(),
+                        plural(callee.params.len()),
+                        count
+                    ),
+                    related_information: callee.related_information(),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn argument_type_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let mod_api = ModApi::from_json(
+        r#"{
+    "entities": {},
+    "game_functions": {
+        "despawn_entity": {
+            "description": "Despawns an entity",
+            "arguments": [{ "name": "entity_id", "type": "id" }]
+        },
+        "print_i32": {
+            "description": "Prints an i32",
+            "arguments": [{ "name": "value", "type": "i32" }]
+        }
+    }
+}"#,
+    )
+    .unwrap();
+
+    let source = r#"on_spawn() {
+    despawn_entity(me)
+    print_i32(me)
+    print_i32(1 + 2)
+    print_i32(2.5)
+    print_i32(-4.0)
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let diagnostics = ArgumentType.check(&document, &LintContext::new(&mod_api));
+    let messages: Vec<&str> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.message.as_str())
+        .collect();
+
+    assert_eq!(
+        messages,
+        [
+            "print_i32 expects value to be i32, but got id",
+            "print_i32 expects value to be i32, but got f32",
+            "print_i32 expects value to be i32, but got f32",
+        ]
+    );
+
+    // 2.5 can't become an i32 without losing precision
+    let fixes: Vec<Option<Fix>> = diagnostics.iter().map(Fix::from_diagnostic).collect();
+    assert_eq!(fixes[0], None);
+    assert_eq!(fixes[1], None);
+    assert_eq!(fixes[2].as_ref().unwrap().edits[0].new_text, "4");
+}
+
+#[test]
+fn helper_arguments_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let source = r#"on_spawn() {
+    helper_move(1.0, 2)
+    helper_move(1.0)
+    helper_move(1.0, 2.0, 3.0)
+    print_i32(1, 2)
+}
+
+helper_move(x: f32, y: f32) {
+}
+"#;
+
+    let mod_api = ModApi::from_json(
+        r#"{"entities": {}, "game_functions": {"print_i32": {"arguments": [{ "name": "value", "type": "i32" }]}}}"#,
+    )
+    .unwrap();
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+    let context = LintContext::new(&mod_api);
+
+    let types = ArgumentType.check(&document, &context);
+    assert_eq!(types.len(), 1);
+    assert_eq!(
+        types[0].message,
+        "helper_move expects y to be f32, but got i32"
+    );
+    // Points at the helper_move in the declaration
+    let related = types[0].related_information.as_ref().unwrap();
+    assert_eq!(
+        related[0].location.range.start,
+        lsp_types::Position::new(7, 0)
+    );
+    assert_eq!(
+        Fix::from_diagnostic(&types[0]).unwrap().edits[0].new_text,
+        "2.0"
+    );
+
+    let counts: Vec<String> = ArgumentCount
+        .check(&document, &context)
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect();
+    assert_eq!(
+        counts,
+        [
+            "helper_move expects 2 arguments, but got 1",
+            "helper_move expects 2 arguments, but got 3",
+            "print_i32 expects 1 argument, but got 2",
+        ]
+    );
+}
@@ -0,0 +1,115 @@
+use lsp_types::Diagnostic;
+use tree_sitter::Node;
+
+use crate::server::{
+    const_eval::{EvalError, evaluate},
+    document::Document,
+    lint::{LintContext, LintLevel, Rule, RuleMetadata},
+    utils::treesitter_range_to_lsp,
+};
+
+pub struct ConstantError;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "constant-error",
+    description: "Constant expressions that would crash at runtime, like dividing by zero",
+    default_level: LintLevel::Error,
+};
+
+fn is_nested_expression(node: &Node) -> bool {
+    matches!(
+        node.kind(),
+        "binary_expression" | "unary_expression" | "contained_expression"
+    )
+}
+
+// Only the outermost expression gets evaluated, since it already folds
+// everything inside of it and would otherwise report the same error twice
+fn collect_errors(content: &[u8], node: &Node, out: &mut Vec<Diagnostic>) {
+    let is_root = !node
+        .parent()
+        .is_some_and(|parent| is_nested_expression(&parent));
+
+    if is_root
+        && (is_nested_expression(node) || node.kind() == "number")
+        && let Err(EvalError::Error { kind, range }) = evaluate(content, node)
+    {
+        out.push(Diagnostic {
+            range: treesitter_range_to_lsp(&range),
+            message: kind.message().to_string(),
+            ..Default::default()
+        });
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_errors(content, &child, out);
+    }
+}
+
+impl Rule for ConstantError {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, _context: &LintContext) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        collect_errors(
+            &document.content,
+            &document.tree.root_node(),
+            &mut diagnostics,
+        );
+
+        diagnostics
+    }
+}
+
+#[test]
+fn constant_error_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let source = r#"on_spawn() {
+    a: i32 = 1 / 0
+    b: i32 = 2147483647 + 1
+    c: i32 = 2147483648
+    d: i32 = -2147483648
+    e: f32 = 1.0 / (2.0 - 2.0)
+    f: i32 = a % 0
+    g: i32 = a / 2
+    print_i32(10 * (3 - 3) / 0)
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let diagnostics = ConstantError.check(&document, &LintContext::new(&ModApi::default()));
+    let found: Vec<(u32, &str)> = diagnostics
+        .iter()
+        .map(|diagnostic| (diagnostic.range.start.line, diagnostic.message.as_str()))
+        .collect();
+
+    assert_eq!(
+        found,
+        [
+            (1, "Division by zero"),
+            (2, "This arithmetic overflows an i32"),
+            (3, "This number doesn't fit in its type"),
+            (5, "Division by zero"),
+            (6, "Division by zero"),
+            (8, "Division by zero"),
+        ]
+    );
+}
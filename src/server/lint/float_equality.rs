@@ -0,0 +1,109 @@
+use lsp_types::Diagnostic;
+
+use crate::server::{
+    document::{Document, Type},
+    lint::{LintContext, LintLevel, Rule, RuleMetadata},
+    type_check::infer_type,
+    utils::{get_descendants_of_kind, treesitter_range_to_lsp},
+};
+
+pub struct FloatEquality;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "float-equality",
+    description: "f32 values compared with == or !=, which rounding errors can keep from ever being equal",
+    default_level: LintLevel::Off,
+};
+
+impl Rule for FloatEquality {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, context: &LintContext) -> Vec<Diagnostic> {
+        get_descendants_of_kind(&document.tree.root_node(), "binary_expression")
+            .into_iter()
+            .filter_map(|comparison| {
+                let operator = comparison.child_by_field_name("operator")?.kind();
+                if !matches!(operator, "==" | "!=") {
+                    return None;
+                }
+
+                let left = comparison.child_by_field_name("left")?;
+                let right = comparison.child_by_field_name("right")?;
+                let is_f32 = |node| infer_type(document, context.mod_api, node) == Some(Type::F32);
+                if !is_f32(&left) && !is_f32(&right) {
+                    return None;
+                }
+
+                let text = |node: &tree_sitter::Node| {
+                    String::from_utf8_lossy(&document.content[node.byte_range()]).to_string()
+                };
+                let (left, right) = (text(&left), text(&right));
+                let suggestion = if operator == "==" {
+                    format!("{} - {} < 0.001 and {} - {} < 0.001", left, right, right, left)
+                } else {
+                    format!("{} - {} > 0.001 or {} - {} > 0.001", left, right, right, left)
+                };
+
+                Some(Diagnostic {
+                    range: treesitter_range_to_lsp(&comparison.range()),
+                    message: format!(
+                        "f32 values are rarely exactly equal, compare how far apart they are instead: {}",
+                        suggestion
+                    ),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn float_equality_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let source = r#"speed: f32 = 1.0
+
+on_tick() {
+    if speed == 0.0 {
+        return
+    }
+    if 2 != 3 {
+        return
+    }
+    if speed != 1.5 {
+        return
+    }
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let messages: Vec<String> = FloatEquality
+        .check(&document, &LintContext::new(&ModApi::default()))
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect();
+
+    assert_eq!(
+        messages,
+        [
+            "f32 values are rarely exactly equal, compare how far apart they are instead: speed - 0.0 < 0.001 and 0.0 - speed < 0.001",
+            "f32 values are rarely exactly equal, compare how far apart they are instead: speed - 1.5 > 0.001 or 1.5 - speed > 0.001",
+        ]
+    );
+}
@@ -0,0 +1,118 @@
+use lsp_types::Diagnostic;
+use tree_sitter::Node;
+
+use crate::server::{
+    document::Document,
+    lint::{LintContext, LintLevel, Rule, RuleMetadata},
+    utils::treesitter_range_to_lsp,
+};
+
+pub struct NonConstantGlobal;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "non-constant-global",
+    description: "Global variables have to start out with a value known before the game runs",
+    default_level: LintLevel::Error,
+};
+
+// The parts of the expression that are only known at runtime. `me` is left to invalid-me
+fn runtime_values<'a>(
+    document: &Document,
+    node: Node<'a>,
+    out: &mut Vec<(Node<'a>, &'static str)>,
+) {
+    match node.kind() {
+        "function_call" => out.push((node, "calls")),
+        "identifier" => {
+            if !matches!(&document.content[node.byte_range()], b"true" | b"false") {
+                out.push((node, "variables"));
+            }
+        }
+        _ => {
+            let mut cursor = node.walk();
+            for child in node.named_children(&mut cursor) {
+                runtime_values(document, child, out);
+            }
+        }
+    }
+}
+
+impl Rule for NonConstantGlobal {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, _context: &LintContext) -> Vec<Diagnostic> {
+        let root = document.tree.root_node();
+        let mut cursor = root.walk();
+
+        let mut found = Vec::new();
+        for declaration in root.named_children(&mut cursor) {
+            if declaration.kind() != "variable_declaration" {
+                continue;
+            }
+            if let Some(value) = declaration.child_by_field_name("value") {
+                runtime_values(document, value, &mut found);
+            }
+        }
+
+        found
+            .into_iter()
+            .map(|(node, what)| Diagnostic {
+                range: treesitter_range_to_lsp(&node.range()),
+                message: format!("Global variables can't be initialized with {}", what),
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn non_constant_global_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let source = r#"speed: f32 = 2.0 * 3.0
+alive: bool = not false
+name: string = "box"
+limit: i32 = speed_limit + 1
+spawned: id = spawn_box(get_x(), 2.0)
+
+on_spawn() {
+    x: f32 = get_x()
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let diagnostics: Vec<(u32, String)> = NonConstantGlobal
+        .check(&document, &LintContext::new(&ModApi::default()))
+        .into_iter()
+        .map(|diagnostic| (diagnostic.range.start.line, diagnostic.message))
+        .collect();
+    assert_eq!(
+        diagnostics,
+        [
+            (
+                3,
+                "Global variables can't be initialized with variables".to_string()
+            ),
+            (
+                4,
+                "Global variables can't be initialized with calls".to_string()
+            ),
+        ]
+    );
+}
@@ -0,0 +1,182 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use lsp_types::Diagnostic;
+
+use crate::server::{
+    document::Document,
+    lint::{LintContext, LintLevel, Rule, RuleMetadata},
+    utils::{get_descendants_of_kind, treesitter_range_to_lsp},
+};
+
+pub struct HelperRecursion;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "helper-recursion",
+    description: "Helpers can't call themselves, directly or through other helpers",
+    default_level: LintLevel::Error,
+};
+
+struct CallSite {
+    caller: String,
+    callee: String,
+    range: tree_sitter::Range,
+}
+
+// Finds the shortest chain of calls that gets from `from` back to `to`
+fn find_path(calls: &HashMap<&str, Vec<&str>>, from: &str, to: &str) -> Option<Vec<String>> {
+    let mut previous: HashMap<&str, &str> = HashMap::new();
+    let mut visited: HashSet<&str> = HashSet::from([from]);
+    let mut queue: VecDeque<&str> = VecDeque::from([from]);
+
+    while let Some(current) = queue.pop_front() {
+        if current == to {
+            let mut path = vec![current.to_string()];
+            let mut current = current;
+            while let Some(prev) = previous.get(current) {
+                path.push(prev.to_string());
+                current = prev;
+            }
+            path.reverse();
+
+            return Some(path);
+        }
+
+        for callee in calls.get(current).into_iter().flatten() {
+            if visited.insert(callee) {
+                previous.insert(callee, current);
+                queue.push_back(callee);
+            }
+        }
+    }
+
+    None
+}
+
+impl Rule for HelperRecursion {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, _context: &LintContext) -> Vec<Diagnostic> {
+        let mut call_sites: Vec<CallSite> = Vec::new();
+
+        for decl in get_descendants_of_kind(&document.tree.root_node(), "function_declaration") {
+            let Some(name) = decl.child_by_field_name("name") else {
+                continue;
+            };
+            if name.kind() != "helper_identifier" {
+                continue;
+            }
+            let Ok(caller) = str::from_utf8(&document.content[name.byte_range()]) else {
+                continue;
+            };
+
+            for call in get_descendants_of_kind(&decl, "function_call") {
+                let Some(callee) = call.child_by_field_name("name") else {
+                    continue;
+                };
+                if callee.kind() != "helper_identifier" {
+                    continue;
+                }
+                let Ok(callee_name) = str::from_utf8(&document.content[callee.byte_range()]) else {
+                    continue;
+                };
+
+                call_sites.push(CallSite {
+                    caller: caller.to_string(),
+                    callee: callee_name.to_string(),
+                    range: callee.range(),
+                });
+            }
+        }
+
+        let mut calls: HashMap<&str, Vec<&str>> = HashMap::new();
+        for call_site in call_sites.iter() {
+            calls
+                .entry(call_site.caller.as_str())
+                .or_default()
+                .push(call_site.callee.as_str());
+        }
+
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        for call_site in call_sites.iter() {
+            let Some(path) = find_path(&calls, &call_site.callee, &call_site.caller) else {
+                continue;
+            };
+
+            let message = if path.len() == 1 {
+                format!(
+                    "{} calls itself, grug doesn't allow recursion",
+                    call_site.caller
+                )
+            } else {
+                let mut cycle = vec![call_site.caller.clone()];
+                cycle.extend(path);
+                format!(
+                    "Recursive call, grug doesn't allow recursion: {}",
+                    cycle.join(" -> ")
+                )
+            };
+
+            diagnostics.push(Diagnostic {
+                range: treesitter_range_to_lsp(&call_site.range),
+                message,
+                ..Default::default()
+            });
+        }
+
+        diagnostics
+    }
+}
+
+#[test]
+fn helper_recursion_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let source = r#"helper_a() {
+    helper_b()
+}
+
+helper_b() {
+    helper_a()
+}
+
+helper_c() {
+    helper_c()
+}
+
+helper_d() {
+    helper_a()
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let diagnostics = HelperRecursion.check(&document, &LintContext::new(&ModApi::default()));
+    let messages: Vec<&str> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.message.as_str())
+        .collect();
+
+    assert_eq!(
+        messages,
+        [
+            "Recursive call, grug doesn't allow recursion: helper_a -> helper_b -> helper_a",
+            "Recursive call, grug doesn't allow recursion: helper_b -> helper_a -> helper_b",
+            "helper_c calls itself, grug doesn't allow recursion",
+        ]
+    );
+}
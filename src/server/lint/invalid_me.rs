@@ -0,0 +1,75 @@
+use lsp_types::Diagnostic;
+
+use crate::server::{
+    document::Document,
+    lint::{LintContext, LintLevel, Rule, RuleMetadata},
+    utils::{get_descendants_of_kind, treesitter_range_to_lsp},
+};
+
+pub struct InvalidMe;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "invalid-me",
+    description: "`me` is only available inside of on_ functions and helpers",
+    default_level: LintLevel::Error,
+};
+
+impl Rule for InvalidMe {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, _context: &LintContext) -> Vec<Diagnostic> {
+        // Global initializers run before there is an entity for `me` to refer to
+        get_descendants_of_kind(&document.tree.root_node(), "me")
+            .into_iter()
+            .filter(|node| {
+                let mut parent = node.parent();
+                while let Some(node) = parent {
+                    if node.kind() == "function_declaration" {
+                        return false;
+                    }
+                    parent = node.parent();
+                }
+
+                true
+            })
+            .map(|node| Diagnostic {
+                range: treesitter_range_to_lsp(&node.range()),
+                message: "`me` can't be used outside of on_ functions and helpers".to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn invalid_me_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let source = r#"owner: id = me
+
+on_spawn() {
+    target: id = me
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let diagnostics = InvalidMe.check(&document, &LintContext::new(&ModApi::default()));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].range.start.line, 0);
+}
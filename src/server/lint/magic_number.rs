@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+
+use lsp_types::{Diagnostic, Position, TextEdit};
+use tree_sitter::Node;
+
+use crate::server::{
+    document::Document,
+    lint::{Fix, LintContext, LintLevel, Rule, RuleMetadata, argument_type::get_callee},
+    utils::{get_call_argument, get_descendants_of_kind, treesitter_range_to_lsp},
+};
+
+pub struct MagicNumber;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "magic-number",
+    description: "Numbers in function bodies that would be easier to tune as named globals",
+    default_level: LintLevel::Off,
+};
+
+// The literal with its minus sign, if it has one
+fn signed_literal<'a>(number: Node<'a>) -> Node<'a> {
+    number
+        .parent()
+        .filter(|parent| {
+            parent.kind() == "unary_expression"
+                && parent
+                    .child_by_field_name("operator")
+                    .is_some_and(|operator| operator.kind() == "-")
+        })
+        .unwrap_or(number)
+}
+
+// Declarations already give their value a name
+fn is_named(literal: &Node) -> bool {
+    let mut node = *literal;
+    while let Some(parent) = node.parent() {
+        match parent.kind() {
+            "variable_declaration" => return true,
+            "contained_expression" | "binary_expression" | "unary_expression" => node = parent,
+            _ => return false,
+        }
+    }
+    false
+}
+
+// Named after the parameter the number is passed to, when there is one
+fn global_name(document: &Document, context: &LintContext, literal: &Node) -> String {
+    let base = get_call_argument(literal)
+        .and_then(|(call, idx)| {
+            let callee = get_callee(document, context, &call)?;
+            callee.params.get(idx).map(|(name, _)| name.clone())
+        })
+        .unwrap_or_else(|| "constant".to_string());
+
+    let taken: HashSet<&[u8]> = get_descendants_of_kind(&document.tree.root_node(), "identifier")
+        .iter()
+        .map(|identifier| &document.content[identifier.byte_range()])
+        .collect();
+
+    let mut name = base.clone();
+    let mut suffix = 2;
+    while taken.contains(name.as_bytes()) {
+        name = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+    name
+}
+
+fn extract_global(document: &Document, context: &LintContext, literal: &Node, text: &str) -> Fix {
+    let name = global_name(document, context, literal);
+    let r#type = if text.contains('.') { "f32" } else { "i32" };
+
+    // New globals go below the existing ones, or at the top of the file
+    let declaration = match document.global_vars.last() {
+        Some(global) => TextEdit {
+            range: lsp_types::Range {
+                start: Position::new(global.range.end_point.row as u32 + 1, 0),
+                end: Position::new(global.range.end_point.row as u32 + 1, 0),
+            },
+            new_text: format!("{}: {} = {}\n", name, r#type, text),
+        },
+        None => TextEdit {
+            range: lsp_types::Range::default(),
+            new_text: format!("{}: {} = {}\n\n", name, r#type, text),
+        },
+    };
+
+    Fix {
+        title: format!("Extract {} into the global {}", text, name),
+        edits: vec![
+            declaration,
+            TextEdit {
+                range: treesitter_range_to_lsp(&literal.range()),
+                new_text: name,
+            },
+        ],
+    }
+}
+
+impl Rule for MagicNumber {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, context: &LintContext) -> Vec<Diagnostic> {
+        get_descendants_of_kind(&document.tree.root_node(), "number")
+            .into_iter()
+            .map(signed_literal)
+            .filter(|literal| {
+                literal
+                    .parent()
+                    .is_some_and(|parent| parent.kind() != "source_file")
+                    && !is_named(literal)
+            })
+            .filter_map(|literal| {
+                let text = String::from_utf8_lossy(&document.content[literal.byte_range()])
+                    .replace(' ', "");
+                let value: f64 = text.parse().ok()?;
+                if value.abs() <= context.magic_number_threshold {
+                    return None;
+                }
+
+                let mut diagnostic = Diagnostic {
+                    range: treesitter_range_to_lsp(&literal.range()),
+                    message: format!(
+                        "{} is a magic number, a named global would make it easier to tune",
+                        text
+                    ),
+                    ..Default::default()
+                };
+                extract_global(document, context, &literal, &text).attach(&mut diagnostic);
+
+                Some(diagnostic)
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn magic_number_test() {
+    use std::str::FromStr;
+
+    use crate::server::{lint::apply_fixes, mod_api::ModApi};
+
+    let source = r#"speed: f32 = 250.0
+
+on_tick() {
+    jump_height: f32 = 40.0
+    set_velocity(speed * 1.0, -9.81)
+    if speed > 300.0 {
+        speed = 2.0 * speed
+    }
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+    let mod_api = ModApi::from_json(
+        r#"{"entities": {}, "game_functions": {"set_velocity": {"arguments": [{"name": "x", "type": "f32"}, {"name": "gravity", "type": "f32"}]}}}"#,
+    )
+    .unwrap();
+    let mut context = LintContext::new(&mod_api);
+
+    let diagnostics = MagicNumber.check(&document, &context);
+    let messages: Vec<&str> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.message.as_str())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "-9.81 is a magic number, a named global would make it easier to tune",
+            "300.0 is a magic number, a named global would make it easier to tune",
+            "2.0 is a magic number, a named global would make it easier to tune",
+        ]
+    );
+
+    let fix = Fix::from_diagnostic(&diagnostics[0]).unwrap();
+    let fixed = String::from_utf8(apply_fixes(source.as_bytes(), &[fix])).unwrap();
+    assert!(fixed.starts_with("speed: f32 = 250.0\ngravity: f32 = -9.81\n"));
+    assert!(fixed.contains("set_velocity(speed * 1.0, gravity)"));
+
+    context.magic_number_threshold = 100.0;
+    assert_eq!(MagicNumber.check(&document, &context).len(), 1);
+}
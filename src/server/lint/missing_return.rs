@@ -0,0 +1,150 @@
+use lsp_types::Diagnostic;
+use tree_sitter::Node;
+
+use crate::server::{
+    const_eval::{ConstValue, evaluate},
+    document::Document,
+    lint::{LintContext, LintLevel, Rule, RuleMetadata},
+    utils::{get_descendants_of_kind, treesitter_range_to_lsp},
+};
+
+pub struct MissingReturn;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "missing-return",
+    description: "Helpers with a return type have to return on every path through them",
+    default_level: LintLevel::Error,
+};
+
+// Whether running the statement never gets past it
+pub fn always_returns(document: &Document, statement: &Node) -> bool {
+    match statement.kind() {
+        "return_statement" | "empty_return" => true,
+        "body" => {
+            let mut cursor = statement.walk();
+            statement
+                .named_children(&mut cursor)
+                .any(|statement| always_returns(document, &statement))
+        }
+        // Without an else, the condition being false skips the body
+        "if_statement" => {
+            let body = statement.child_by_field_name("body");
+            let else_branch = statement.child_by_field_name("else");
+
+            match (body, else_branch) {
+                (Some(body), Some(else_branch)) => {
+                    always_returns(document, &body) && always_returns(document, &else_branch)
+                }
+                _ => false,
+            }
+        }
+        // grug has no break, so only a condition that stays true keeps the loop from ending
+        "while_statement" => statement
+            .child_by_field_name("condition")
+            .is_some_and(|condition| {
+                evaluate(&document.content, &condition) == Ok(ConstValue::Bool(true))
+            }),
+        _ => false,
+    }
+}
+
+impl Rule for MissingReturn {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, _context: &LintContext) -> Vec<Diagnostic> {
+        get_descendants_of_kind(&document.tree.root_node(), "function_declaration")
+            .into_iter()
+            .filter(|function| function.child_by_field_name("ret_type").is_some())
+            .filter_map(|function| {
+                let body = function.child_by_field_name("body")?;
+                if always_returns(document, &body) {
+                    return None;
+                }
+
+                let name = function.child_by_field_name("name")?;
+                Some(Diagnostic {
+                    range: treesitter_range_to_lsp(&name.range()),
+                    message: format!(
+                        "{} can reach its end without returning a value",
+                        String::from_utf8_lossy(&document.content[name.byte_range()])
+                    ),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn missing_return_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let source = r#"helper_sign(x: i32) i32 {
+    if x < 0 {
+        return -1
+    } else if x > 0 {
+        return 1
+    } else {
+        return 0
+    }
+}
+
+helper_abs(x: i32) i32 {
+    if x < 0 {
+        return -x
+    }
+}
+
+helper_forever() i32 {
+    while true {
+        print_i32(1)
+    }
+}
+
+helper_maybe(x: i32) i32 {
+    while x > 0 {
+        return x
+    }
+}
+
+helper_nothing() {
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let diagnostics: Vec<(u32, String)> = MissingReturn
+        .check(&document, &LintContext::new(&ModApi::default()))
+        .into_iter()
+        .map(|diagnostic| (diagnostic.range.start.line, diagnostic.message))
+        .collect();
+
+    assert_eq!(
+        diagnostics,
+        [
+            (
+                10,
+                "helper_abs can reach its end without returning a value".to_string()
+            ),
+            (
+                22,
+                "helper_maybe can reach its end without returning a value".to_string()
+            ),
+        ]
+    );
+}
@@ -0,0 +1,110 @@
+use lsp_types::Diagnostic;
+use tree_sitter::Node;
+
+use crate::server::{
+    document::Document,
+    lint::{LintContext, LintLevel, Rule, RuleMetadata},
+    utils::{get_descendants_of_kind, treesitter_range_to_lsp},
+};
+
+pub struct OnFunctionCall;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "on-function-call",
+    description: "on_ functions are called by the game, scripts can't call them",
+    default_level: LintLevel::Error,
+};
+
+// The grammar doesn't allow these calls, so they only show up as an
+// on_identifier inside of an ERROR node in a function body, followed by an opening parenthesis
+pub fn is_on_function_call(content: &[u8], node: &Node) -> bool {
+    if node.kind() != "on_identifier"
+        || node
+            .parent()
+            .is_some_and(|parent| parent.kind() == "function_declaration")
+    {
+        return false;
+    }
+
+    // A broken declaration at the top level looks the same
+    let mut parent = node.parent();
+    while let Some(node) = parent
+        && node.kind() != "body"
+    {
+        parent = node.parent();
+    }
+    if parent.is_none() {
+        return false;
+    }
+
+    content[node.end_byte()..]
+        .iter()
+        .find(|chr| !chr.is_ascii_whitespace())
+        .is_some_and(|chr| *chr == b'(')
+}
+
+impl Rule for OnFunctionCall {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, _context: &LintContext) -> Vec<Diagnostic> {
+        get_descendants_of_kind(&document.tree.root_node(), "on_identifier")
+            .into_iter()
+            .filter(|node| is_on_function_call(&document.content, node))
+            .map(|node| {
+                let name = String::from_utf8_lossy(&document.content[node.byte_range()]);
+
+                Diagnostic {
+                    range: treesitter_range_to_lsp(&node.range()),
+                    message: format!(
+                        "{} can't be called directly, on_ functions are called by the game",
+                        name
+                    ),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn on_function_call_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let source = r#"on_spawn() {
+    on_tick()
+    a = on_tick (1)
+}
+
+on_tick() {
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let diagnostics = OnFunctionCall.check(&document, &LintContext::new(&ModApi::default()));
+    let lines: Vec<u32> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.range.start.line)
+        .collect();
+
+    assert_eq!(lines, [1, 2]);
+    assert_eq!(
+        diagnostics[0].message,
+        "on_tick can't be called directly, on_ functions are called by the game"
+    );
+}
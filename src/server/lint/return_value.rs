@@ -0,0 +1,125 @@
+use lsp_types::Diagnostic;
+use tree_sitter::Node;
+
+use crate::server::{
+    document::Document,
+    lint::{LintContext, LintLevel, Rule, RuleMetadata},
+    utils::{get_descendants_of_kind, treesitter_range_to_lsp},
+};
+
+pub struct ReturnValue;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "return-value",
+    description: "Functions without a return type can't return a value, and helpers with one always have to",
+    default_level: LintLevel::Error,
+};
+
+fn enclosing_function<'a>(node: &Node<'a>) -> Option<Node<'a>> {
+    let mut parent = node.parent();
+    while let Some(node) = parent {
+        if node.kind() == "function_declaration" {
+            return Some(node);
+        }
+        parent = node.parent();
+    }
+
+    None
+}
+
+impl Rule for ReturnValue {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, _context: &LintContext) -> Vec<Diagnostic> {
+        let root = document.tree.root_node();
+        let returns = get_descendants_of_kind(&root, "return_statement")
+            .into_iter()
+            .chain(get_descendants_of_kind(&root, "empty_return"));
+
+        returns
+            .filter_map(|statement| {
+                let function = enclosing_function(&statement)?;
+                let name = function.child_by_field_name("name")?;
+                let name = String::from_utf8_lossy(&document.content[name.byte_range()]);
+
+                // on_ functions can't declare a return type, so they're always void
+                let message = match (statement.kind(), function.child_by_field_name("ret_type")) {
+                    ("return_statement", None) => format!("{} doesn't return a value", name),
+                    ("empty_return", Some(ret_type)) => format!(
+                        "{} has to return a value of type {}",
+                        name,
+                        String::from_utf8_lossy(&document.content[ret_type.byte_range()])
+                    ),
+                    _ => return None,
+                };
+
+                Some(Diagnostic {
+                    range: treesitter_range_to_lsp(&statement.range()),
+                    message,
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn return_value_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let source = r#"on_spawn() {
+    return 1
+}
+
+on_tick() {
+    return
+}
+
+helper_speed() f32 {
+    if fast {
+        return
+    }
+    return 2.0
+}
+
+helper_reset() {
+    return 0
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let mut diagnostics: Vec<(u32, String)> = ReturnValue
+        .check(&document, &LintContext::new(&ModApi::default()))
+        .into_iter()
+        .map(|diagnostic| (diagnostic.range.start.line, diagnostic.message))
+        .collect();
+    diagnostics.sort();
+
+    assert_eq!(
+        diagnostics,
+        [
+            (1, "on_spawn doesn't return a value".to_string()),
+            (
+                10,
+                "helper_speed has to return a value of type f32".to_string()
+            ),
+            (16, "helper_reset doesn't return a value".to_string()),
+        ]
+    );
+}
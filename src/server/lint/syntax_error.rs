@@ -0,0 +1,201 @@
+use lsp_types::Diagnostic;
+use tree_sitter::Node;
+
+use crate::server::{
+    document::Document,
+    lint::{LintContext, LintLevel, Rule, RuleMetadata, on_function_call::is_on_function_call},
+    utils::treesitter_range_to_lsp,
+};
+
+pub struct SyntaxError;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "syntax-error",
+    description: "The file doesn't match grug's grammar",
+    default_level: LintLevel::Error,
+};
+
+fn describe_missing(node: &Node) -> String {
+    let parent = node
+        .parent()
+        .map(|parent| parent.kind())
+        .unwrap_or_default();
+
+    match (node.kind(), parent) {
+        (")", "function_call") => "Expected ')' to close the call".to_string(),
+        (")", "contained_expression") => "Expected ')' to close the parentheses".to_string(),
+        (")", "function_declaration") => "Expected ')' to close the parameter list".to_string(),
+        ("{", "if_statement" | "while_statement") => "Expected '{' after the condition".to_string(),
+        ("{", "function_declaration") => "Expected '{' to start the function body".to_string(),
+        ("}", _) => "Expected '}' to close the block".to_string(),
+        (kind, _) if node.is_named() => format!("Expected {}", kind.replace('_', " ")),
+        (kind, _) => format!("Expected '{}'", kind),
+    }
+}
+
+fn describe_error(content: &[u8], node: &Node) -> String {
+    let text = String::from_utf8_lossy(&content[node.byte_range()]);
+    let text = text.trim();
+    let first_word = text.split_whitespace().next().unwrap_or_default();
+    let parent = node
+        .parent()
+        .map(|parent| parent.kind())
+        .unwrap_or_default();
+
+    if matches!(first_word, "if" | "while") && !text.contains('{') {
+        return "Expected '{' after the condition".to_string();
+    }
+
+    if text.starts_with("on_") || text.starts_with("helper_") {
+        if text.contains('(') && !text.contains(')') {
+            return "Expected ')' to close the parameter list".to_string();
+        }
+        return "Expected a function declaration like on_name() { }".to_string();
+    }
+
+    if let Some(prev) = node.prev_sibling()
+        && matches!(prev.kind(), "variable_declaration" | "assignment")
+        && prev.child_by_field_name("value").is_none()
+    {
+        return "Expected a value after '='".to_string();
+    }
+
+    let mut cursor = node.walk();
+    let is_expression = node.named_children(&mut cursor).next().is_some()
+        && node.named_children(&mut cursor).all(|child| {
+            matches!(
+                child.kind(),
+                "number" | "string" | "binary_expression" | "unary_expression"
+            )
+        });
+    if parent == "body" && is_expression {
+        return "Statements must start with a declaration, assignment, call, if, while or return"
+            .to_string();
+    }
+
+    if text.ends_with(['+', '-', '*', '/', '%', '=', '<', '>']) || text.ends_with("and") {
+        return "Expected an expression after the operator".to_string();
+    }
+
+    match text.lines().next() {
+        Some(line) if !line.is_empty() => format!("Unexpected '{}'", line),
+        _ => "Syntax error".to_string(),
+    }
+}
+
+fn contains_on_function_call(content: &[u8], node: &Node) -> bool {
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .any(|child| is_on_function_call(content, &child))
+}
+
+fn collect_errors(content: &[u8], node: &Node, out: &mut Vec<Diagnostic>) {
+    let message = if node.is_missing() {
+        Some(describe_missing(node))
+    } else if node.is_error() && contains_on_function_call(content, node) {
+        // The on-function-call rule explains these better
+        None
+    } else if node.is_error() {
+        Some(describe_error(content, node))
+    } else if node
+        .parent()
+        .is_some_and(|parent| parent.kind() == "source_file")
+        && !matches!(
+            node.kind(),
+            "variable_declaration" | "function_declaration" | "comment"
+        )
+    {
+        // The grammar accepts these, but grug only allows them inside of a function
+        Some("Statements must be inside of an on_ function or helper".to_string())
+    } else {
+        None
+    };
+
+    if let Some(message) = message {
+        out.push(Diagnostic {
+            range: treesitter_range_to_lsp(&node.range()),
+            message,
+            ..Default::default()
+        });
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_errors(content, &child, out);
+    }
+}
+
+impl Rule for SyntaxError {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, _context: &LintContext) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        collect_errors(
+            &document.content,
+            &document.tree.root_node(),
+            &mut diagnostics,
+        );
+
+        diagnostics
+    }
+}
+
+#[test]
+fn syntax_error_messages_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let cases = [
+        (
+            "on_spawn() {\n    print(1\n}\n",
+            "Expected ')' to close the call",
+        ),
+        (
+            "on_spawn() {\n    a = (1 + 2\n}\n",
+            "Expected ')' to close the parentheses",
+        ),
+        (
+            "on_spawn() {\n    if a \n        b = 1\n    }\n}\n",
+            "Expected '{' after the condition",
+        ),
+        (
+            "on_spawn() {\n    a = 1\n",
+            "Expected '}' to close the block",
+        ),
+        (
+            "on_spawn( {\n}\n",
+            "Expected ')' to close the parameter list",
+        ),
+        (
+            "print(1)\n",
+            "Statements must be inside of an on_ function or helper",
+        ),
+        (
+            "on_spawn() {\n  1 + 2\n}\n",
+            "Statements must start with a declaration, assignment, call, if, while or return",
+        ),
+    ];
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    for (source, message) in cases {
+        let document = Document::new(
+            &mut parser,
+            source.as_bytes().to_vec(),
+            "tired-box.grug".to_string(),
+            lsp_types::Uri::from_str("some_uri").unwrap(),
+        )
+        .unwrap();
+
+        let diagnostics = SyntaxError.check(&document, &LintContext::new(&ModApi::default()));
+        assert_eq!(diagnostics.len(), 1, "{:?}", source);
+        assert_eq!(diagnostics[0].message, message, "{:?}", source);
+    }
+}
@@ -0,0 +1,151 @@
+use lsp_types::Diagnostic;
+
+use crate::server::{
+    document::Document,
+    lint::{LintContext, LintLevel, Rule, RuleMetadata},
+    mod_api::GrugArgument,
+    utils::{get_call_argument, get_descendants_of_kind, treesitter_range_to_lsp},
+};
+
+pub struct UnknownEntity;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "unknown-entity",
+    description: "Entity strings passed to game functions should name an entity in the workspace",
+    default_level: LintLevel::Warning,
+};
+
+impl Rule for UnknownEntity {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, context: &LintContext) -> Vec<Diagnostic> {
+        // Without a mod there is no way to tell what an entity string refers to
+        let Some(mod_name) = &context.mod_name else {
+            return Vec::new();
+        };
+
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        for string in get_descendants_of_kind(&document.tree.root_node(), "string") {
+            let Some((call, idx)) = get_call_argument(&string) else {
+                continue;
+            };
+            let name = call.child_by_field_name("name").unwrap();
+            let Ok(name) = str::from_utf8(&document.content[name.byte_range()]) else {
+                continue;
+            };
+            let Some(GrugArgument::Entity { entity_type, .. }) = context
+                .mod_api
+                .game_functions
+                .get(name)
+                .and_then(|game_function| game_function.arguments.get(idx))
+            else {
+                continue;
+            };
+
+            let text = &document.content[string.byte_range()];
+            let Ok(text) = str::from_utf8(&text[1..text.len() - 1]) else {
+                continue;
+            };
+            // Entities in the same mod can leave out the mod name
+            let reference = if text.contains(':') {
+                text.to_string()
+            } else {
+                format!("{}:{}", mod_name, text)
+            };
+
+            let message = match context
+                .entities
+                .iter()
+                .find(|entity| entity.reference() == reference)
+            {
+                None => format!("There is no entity called {} in the workspace", reference),
+                Some(entity) if entity.entity_type != *entity_type => format!(
+                    "{} is a {}, but {} expects a {}",
+                    reference, entity.entity_type, name, entity_type
+                ),
+                Some(_) => continue,
+            };
+
+            diagnostics.push(Diagnostic {
+                range: treesitter_range_to_lsp(&string.range()),
+                message,
+                ..Default::default()
+            });
+        }
+
+        diagnostics
+    }
+}
+
+#[test]
+fn unknown_entity_test() {
+    use std::str::FromStr;
+
+    use crate::server::{mod_api::ModApi, workspace::WorkspaceEntity};
+
+    let mod_api = ModApi::from_json(
+        r#"{
+    "game_functions": {
+        "spawn_box": {
+            "arguments": [{ "name": "box", "type": "entity", "entity_type": "box" }]
+        }
+    }
+}"#,
+    )
+    .unwrap();
+
+    let source = r#"on_spawn() {
+    spawn_box("foo:crate")
+    spawn_box("crate")
+    spawn_box("bar:crate")
+    spawn_box("foo:pistol")
+    spawn_box("foo:missing")
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let entity = |mod_name: &str, name: &str, entity_type: &str| WorkspaceEntity {
+        path: format!("/mods/{}/{}-{}.grug", mod_name, name, entity_type),
+        mod_name: mod_name.to_string(),
+        name: name.to_string(),
+        entity_type: entity_type.to_string(),
+    };
+    let context = LintContext {
+        mod_api: &mod_api,
+        mod_name: Some("foo".to_string()),
+        entities: vec![
+            entity("foo", "crate", "box"),
+            entity("bar", "crate", "box"),
+            entity("foo", "pistol", "gun"),
+        ],
+        ..LintContext::new(&mod_api)
+    };
+
+    let diagnostics = UnknownEntity.check(&document, &context);
+    let messages: Vec<&str> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.message.as_str())
+        .collect();
+
+    assert_eq!(
+        messages,
+        [
+            "foo:pistol is a gun, but spawn_box expects a box",
+            "There is no entity called foo:missing in the workspace",
+        ]
+    );
+}
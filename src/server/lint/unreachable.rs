@@ -0,0 +1,229 @@
+use lsp_types::{Diagnostic, DiagnosticTag};
+use tree_sitter::Node;
+
+use crate::server::{
+    const_eval::{ConstValue, evaluate},
+    document::Document,
+    lint::{LintContext, LintLevel, Rule, RuleMetadata, missing_return::always_returns},
+    utils::{get_descendants_of_kind, treesitter_range_to_lsp},
+};
+
+pub struct UnreachableCode;
+pub struct ConstantCondition;
+
+static METADATA: RuleMetadata = RuleMetadata {
+    code: "unreachable-code",
+    description: "Statements after a return that always happens never run",
+    default_level: LintLevel::Warning,
+};
+
+static CONDITION_METADATA: RuleMetadata = RuleMetadata {
+    code: "constant-condition",
+    description: "Conditions that are always true or always false, like a leftover `if false`",
+    default_level: LintLevel::Warning,
+};
+
+fn unnecessary(node: &Node, message: &str) -> Diagnostic {
+    Diagnostic {
+        range: treesitter_range_to_lsp(&node.range()),
+        message: message.to_string(),
+        tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+        ..Default::default()
+    }
+}
+
+impl Rule for UnreachableCode {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &METADATA
+    }
+
+    fn check(&self, document: &Document, _context: &LintContext) -> Vec<Diagnostic> {
+        get_descendants_of_kind(&document.tree.root_node(), "body")
+            .into_iter()
+            .filter_map(|body| {
+                let mut cursor = body.walk();
+                let statements: Vec<Node> = body
+                    .named_children(&mut cursor)
+                    .filter(|statement| statement.kind() != "comment")
+                    .collect();
+
+                let returns_at = statements
+                    .iter()
+                    .position(|statement| always_returns(document, statement))?;
+                let first = statements.get(returns_at + 1)?;
+                let last = statements.last()?;
+
+                // One diagnostic for the whole rest of the block, rather than one per statement
+                let mut diagnostic = unnecessary(first, "Unreachable code");
+                diagnostic.range.end = treesitter_range_to_lsp(&last.range()).end;
+
+                Some(diagnostic)
+            })
+            .collect()
+    }
+}
+
+impl Rule for ConstantCondition {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &CONDITION_METADATA
+    }
+
+    fn check(&self, document: &Document, _context: &LintContext) -> Vec<Diagnostic> {
+        let root = document.tree.root_node();
+        let statements = get_descendants_of_kind(&root, "if_statement")
+            .into_iter()
+            .chain(get_descendants_of_kind(&root, "while_statement"));
+
+        let mut diagnostics = Vec::new();
+        for statement in statements {
+            let Some(condition) = statement.child_by_field_name("condition") else {
+                continue;
+            };
+            let Ok(ConstValue::Bool(value)) = evaluate(&document.content, &condition) else {
+                continue;
+            };
+
+            let is_while = statement.kind() == "while_statement";
+            // Without a break, `while true` is how a loop that only stops by returning is written
+            if is_while && value {
+                continue;
+            }
+
+            diagnostics.push(Diagnostic {
+                range: treesitter_range_to_lsp(&condition.range()),
+                message: format!("This condition is always {}", value),
+                ..Default::default()
+            });
+
+            let dead_branch = if value {
+                statement.child_by_field_name("else")
+            } else {
+                statement.child_by_field_name("body")
+            };
+            if let Some(dead_branch) = dead_branch {
+                let message = if is_while {
+                    "This loop never runs"
+                } else {
+                    "This branch never runs"
+                };
+                diagnostics.push(unnecessary(&dead_branch, message));
+            }
+        }
+
+        diagnostics
+    }
+}
+
+#[test]
+fn unreachable_code_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let source = r#"on_tick() {
+    if done {
+        return
+        # Kept around for debugging
+        x = 1
+        print_i32(x)
+    }
+    if ready {
+        return
+    } else {
+        return
+    }
+    print_i32(3)
+}
+
+helper_total() i32 {
+    return 1
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let mut ranges: Vec<(u32, u32)> = UnreachableCode
+        .check(&document, &LintContext::new(&ModApi::default()))
+        .into_iter()
+        .map(|diagnostic| (diagnostic.range.start.line, diagnostic.range.end.line))
+        .collect();
+    ranges.sort();
+
+    assert_eq!(ranges, [(4, 5), (12, 12)]);
+}
+
+#[test]
+fn constant_condition_test() {
+    use std::str::FromStr;
+
+    use crate::server::mod_api::ModApi;
+
+    let source = r#"on_tick() {
+    if false {
+        print_i32(1)
+    }
+    if 1 < 2 {
+        print_i32(2)
+    } else {
+        print_i32(3)
+    }
+    while not true {
+        print_i32(4)
+    }
+    while true {
+        return
+    }
+    if ready {
+        print_i32(5)
+    }
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let diagnostics: Vec<(u32, String, bool)> = ConstantCondition
+        .check(&document, &LintContext::new(&ModApi::default()))
+        .into_iter()
+        .map(|diagnostic| {
+            (
+                diagnostic.range.start.line,
+                diagnostic.message,
+                diagnostic.tags.is_some(),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        diagnostics,
+        [
+            (1, "This condition is always false".to_string(), false),
+            (1, "This branch never runs".to_string(), true),
+            (4, "This condition is always true".to_string(), false),
+            (6, "This branch never runs".to_string(), true),
+            (9, "This condition is always false".to_string(), false),
+            (9, "This loop never runs".to_string(), true),
+        ]
+    );
+}
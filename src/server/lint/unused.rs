@@ -0,0 +1,171 @@
+use lsp_types::{Diagnostic, DiagnosticTag};
+use tree_sitter::Node;
+
+use crate::server::{
+    Server,
+    document::Document,
+    lint::{Fix, LintContext, LintLevel, Rule, RuleMetadata},
+    references::find_variable_occurrences,
+    utils::{get_descendants_of_kind, treesitter_range_to_lsp},
+};
+
+pub struct UnusedParameter;
+pub struct UnusedVariable;
+
+static PARAMETER_METADATA: RuleMetadata = RuleMetadata {
+    code: "unused-parameter",
+    description: "Parameters that are never read, prefix them with `_` if that's on purpose",
+    default_level: LintLevel::Warning,
+};
+
+static VARIABLE_METADATA: RuleMetadata = RuleMetadata {
+    code: "unused-variable",
+    description: "Local variables that are never read, prefix them with `_` if that's on purpose",
+    default_level: LintLevel::Warning,
+};
+
+// The name nodes of the declarations nothing reads from. Names starting with `_` are unused on purpose
+fn unused_declarations<'a>(document: &'a Document, kind: &str) -> Vec<(Node<'a>, Node<'a>)> {
+    get_descendants_of_kind(&document.tree.root_node(), kind)
+        .into_iter()
+        // Globals can be read by other entities through the game
+        .filter(|declaration| {
+            declaration
+                .parent()
+                .is_some_and(|parent| parent.kind() != "source_file")
+        })
+        .filter_map(|declaration| {
+            let name = declaration.child_by_field_name("name")?;
+            if document.content[name.byte_range()].starts_with(b"_") {
+                return None;
+            }
+
+            let is_read = find_variable_occurrences(document, &declaration)
+                .iter()
+                .any(|occurrence| !occurrence.is_write);
+
+            (!is_read).then_some((declaration, name))
+        })
+        .collect()
+}
+
+fn unused_diagnostic(document: &Document, name: &Node, what: &str) -> Diagnostic {
+    Diagnostic {
+        range: treesitter_range_to_lsp(&name.range()),
+        message: format!(
+            "The {} {} is never used",
+            what,
+            String::from_utf8_lossy(&document.content[name.byte_range()])
+        ),
+        tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+        ..Default::default()
+    }
+}
+
+fn underscore_fix(document: &Document, declaration: &Node, name: &Node) -> Fix {
+    let old_name = String::from_utf8_lossy(&document.content[name.byte_range()]);
+    let new_name = format!("_{}", old_name);
+
+    Fix {
+        title: format!("Rename {} to {}", old_name, new_name),
+        edits: Server::rename_var(document, declaration, &old_name, &new_name),
+    }
+}
+
+impl Rule for UnusedParameter {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &PARAMETER_METADATA
+    }
+
+    fn check(&self, document: &Document, _context: &LintContext) -> Vec<Diagnostic> {
+        // on_ functions have to take what the game passes them, so renaming is the way out
+        unused_declarations(document, "function_parameter")
+            .into_iter()
+            .map(|(declaration, name)| {
+                let mut diagnostic = unused_diagnostic(document, &name, "parameter");
+                underscore_fix(document, &declaration, &name).attach(&mut diagnostic);
+                diagnostic
+            })
+            .collect()
+    }
+}
+
+impl Rule for UnusedVariable {
+    fn metadata(&self) -> &'static RuleMetadata {
+        &VARIABLE_METADATA
+    }
+
+    fn check(&self, document: &Document, _context: &LintContext) -> Vec<Diagnostic> {
+        // Removing the declaration could drop a call made for its side effects
+        unused_declarations(document, "variable_declaration")
+            .into_iter()
+            .map(|(declaration, name)| {
+                let mut diagnostic = unused_diagnostic(document, &name, "variable");
+                underscore_fix(document, &declaration, &name).attach(&mut diagnostic);
+                diagnostic
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn unused_test() {
+    use std::str::FromStr;
+
+    use crate::server::{lint::apply_fixes, mod_api::ModApi};
+
+    let source = r#"total: i32 = 0
+
+on_hit(damage: i32, _source: id, scale: f32) {
+    unused: i32 = 1
+    _ignored: i32 = 2
+    written: i32 = 3
+    written = 4
+    total = total + damage
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+    let mod_api = ModApi::default();
+    let context = LintContext::new(&mod_api);
+
+    let parameters = UnusedParameter.check(&document, &context);
+    assert_eq!(parameters.len(), 1);
+    assert_eq!(parameters[0].message, "The parameter scale is never used");
+
+    let fix = Fix::from_diagnostic(&parameters[0]).unwrap();
+    let fixed = apply_fixes(source.as_bytes(), &[fix]);
+    assert!(
+        String::from_utf8(fixed)
+            .unwrap()
+            .contains("_source: id, _scale: f32")
+    );
+
+    let variables = UnusedVariable.check(&document, &context);
+    let messages: Vec<&str> = variables
+        .iter()
+        .map(|diagnostic| diagnostic.message.as_str())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "The variable unused is never used",
+            "The variable written is never used"
+        ]
+    );
+
+    let fix = Fix::from_diagnostic(&variables[1]).unwrap();
+    let fixed = String::from_utf8(apply_fixes(source.as_bytes(), &[fix])).unwrap();
+    assert!(fixed.contains("    _written: i32 = 3\n    _written = 4\n"));
+}
@@ -6,13 +6,20 @@ use tree_sitter::Parser;
 
 use crate::server::document::Type;
 
+pub mod docs;
 pub mod parse;
+pub mod protocol;
+pub mod schema;
 
-#[derive(Serialize, Deserialize, Debug, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq)]
 pub struct GrugOnFunction {
     #[serde(default = "default_description")]
     pub description: String,
 
+    // What the game passes it, which the declaration has to take as parameters
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<GrugArgument>,
+
     #[serde(skip)]
     #[serde(default = "default_range")]
     pub range: tree_sitter::Range,
@@ -20,11 +27,11 @@ pub struct GrugOnFunction {
 
 impl PartialEq for GrugOnFunction {
     fn eq(&self, other: &Self) -> bool {
-        self.description == other.description
+        self.description == other.description && self.arguments == other.arguments
     }
 }
 
-#[derive(Serialize, Deserialize, Eq, Debug)]
+#[derive(Serialize, Deserialize, Eq, Debug, Clone)]
 pub struct GrugEntity {
     #[serde(default = "default_description")]
     pub description: String,
@@ -55,7 +62,7 @@ fn default_description() -> String {
     "<NO DESCRIPTION>".to_string()
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum GrugDetailedType {
     #[serde(rename = "string")]
     String,
@@ -90,46 +97,115 @@ impl GrugDetailedType {
 
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type")]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum GrugArgument {
     #[serde(rename = "string")]
-    String { name: String },
+    String {
+        name: String,
+        #[serde(default)]
+        default: Option<serde_json::Value>,
+    },
     #[serde(rename = "i32")]
-    I32 { name: String },
+    I32 {
+        name: String,
+        #[serde(default)]
+        default: Option<serde_json::Value>,
+    },
     #[serde(rename = "f32")]
-    F32 { name: String },
+    F32 {
+        name: String,
+        #[serde(default)]
+        default: Option<serde_json::Value>,
+    },
     #[serde(rename = "id")]
-    ID { name: String },
+    ID {
+        name: String,
+        #[serde(default)]
+        default: Option<serde_json::Value>,
+    },
 
     #[serde(rename = "bool")]
-    Bool { name: String },
+    Bool {
+        name: String,
+        #[serde(default)]
+        default: Option<serde_json::Value>,
+    },
 
     #[serde(rename = "resource")]
     Resource {
         name: String,
         resource_extension: String,
+        #[serde(default)]
+        default: Option<serde_json::Value>,
     },
     #[serde(rename = "entity")]
-    Entity { name: String, entity_type: String },
+    Entity {
+        name: String,
+        entity_type: String,
+        #[serde(default)]
+        default: Option<serde_json::Value>,
+    },
 
     #[serde(untagged)]
-    Unknown { name: String, r#type: String },
+    Unknown {
+        name: String,
+        r#type: String,
+        #[serde(default)]
+        default: Option<serde_json::Value>,
+    },
 }
 
 impl GrugArgument {
     pub fn get_name(&self) -> &str {
         match self {
-            GrugArgument::String { name }
-            | GrugArgument::I32 { name }
-            | GrugArgument::F32 { name }
-            | GrugArgument::ID { name }
-            | GrugArgument::Bool { name }
+            GrugArgument::String { name, .. }
+            | GrugArgument::I32 { name, .. }
+            | GrugArgument::F32 { name, .. }
+            | GrugArgument::ID { name, .. }
+            | GrugArgument::Bool { name, .. }
             | GrugArgument::Resource { name, .. }
             | GrugArgument::Entity { name, .. }
             | GrugArgument::Unknown { name, .. } => name,
         }
     }
 
+    pub fn get_default(&self) -> Option<&serde_json::Value> {
+        match self {
+            GrugArgument::String { default, .. }
+            | GrugArgument::I32 { default, .. }
+            | GrugArgument::F32 { default, .. }
+            | GrugArgument::ID { default, .. }
+            | GrugArgument::Bool { default, .. }
+            | GrugArgument::Resource { default, .. }
+            | GrugArgument::Entity { default, .. }
+            | GrugArgument::Unknown { default, .. } => default.as_ref(),
+        }
+    }
+
+    /// The text a completion snippet should use as this argument's placeholder.
+    /// Uses the mod_api `"default"` value as grug source when there is one,
+    /// falling back to the parameter name.
+    pub fn get_placeholder(&self) -> String {
+        match self.get_default() {
+            // grug strings have no escapes, so they can't hold a quote or span lines
+            Some(serde_json::Value::String(text)) if !text.contains(['"', '\n']) => {
+                format!("\"{}\"", text)
+            }
+            Some(serde_json::Value::Number(number)) => {
+                let number = number.to_string();
+
+                // f32 literals need a decimal point in grug
+                if matches!(self, GrugArgument::F32 { .. }) && !number.contains('.') {
+                    format!("{}.0", number)
+                } else {
+                    number
+                }
+            }
+            Some(serde_json::Value::Bool(value)) => value.to_string(),
+            _ => self.get_name().to_string(),
+        }
+    }
+
     pub fn get_type(&self) -> Type {
         match self {
             GrugArgument::String { .. } => Type::String,
@@ -144,7 +220,7 @@ impl GrugArgument {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq)]
 pub struct GrugGameFunction {
     #[serde(default = "default_description")]
     pub description: String,
@@ -154,6 +230,10 @@ pub struct GrugGameFunction {
 
     pub return_type: Option<GrugDetailedType>,
 
+    // Still callable, but the game wants mods to stop using it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deprecated: bool,
+
     #[serde(skip)]
     #[serde(default = "default_range")]
     pub range: tree_sitter::Range,
@@ -164,6 +244,7 @@ impl PartialEq for GrugGameFunction {
         self.description == other.description
             && self.arguments == other.arguments
             && self.return_type == other.return_type
+            && self.deprecated == other.deprecated
     }
 }
 
@@ -193,11 +274,31 @@ impl GrugGameFunction {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Default)]
+// A pattern the game suggests for using its API, offered as a statement completion
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GrugSnippet {
+    pub label: String,
+    pub body: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
 pub struct ModApi {
     pub entities: HashMap<String, GrugEntity>,
 
     pub game_functions: HashMap<String, GrugGameFunction>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub snippets: Vec<GrugSnippet>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModApiError {
+    pub message: String,
+    // 1-based, when the error is in the JSON syntax
+    pub line: Option<usize>,
+    pub column: Option<usize>,
 }
 
 lazy_static! {
@@ -525,14 +626,17 @@ fn mod_api_test() {
                 on_functions: HashMap::from([
                     ("on_spawn".to_string(), GrugOnFunction {
                         description: "Called when the entity is spawned.".to_string(),
+                        arguments: Vec::new(),
                         range: default_range(),
                     }),
                     ("on_despawn".to_string(), GrugOnFunction {
                         description: "Called when the entity is despawned.".to_string(),
+                        arguments: Vec::new(),
                         range: default_range(),
                     }),
                     ("on_fire".to_string(), GrugOnFunction {
                         description: "Called when the player's gun fires, which happens when the left mouse button is pressed or held.".to_string(),
+                        arguments: Vec::new(),
                         range: default_range(),
                     })
                 ]),
@@ -543,14 +647,17 @@ fn mod_api_test() {
                 on_functions: HashMap::from([
                     ("on_spawn".to_string(), GrugOnFunction {
                         description: "Called when the entity is spawned.".to_string(),
+                        arguments: Vec::new(),
                         range: default_range(),
                     }),
                     ("on_despawn".to_string(), GrugOnFunction {
                         description: "Called when the entity is despawned.".to_string(),
+                        arguments: Vec::new(),
                         range: default_range(),
                     }),
                     ("on_tick".to_string(), GrugOnFunction {
                         description: "Called every tick.".to_string(),
+                        arguments: Vec::new(),
                         range: default_range(),
                     })
                 ]),
@@ -561,10 +668,12 @@ fn mod_api_test() {
                 on_functions: HashMap::from([
                     ("on_spawn".to_string(), GrugOnFunction{
                         description: "Called when the entity is spawned.".to_string(),
+                        arguments: Vec::new(),
                         range: default_range(),
                     }),
                     ("on_despawn".to_string(), GrugOnFunction {
                         description: "Called when the entity is despawned.".to_string(),
+                        arguments: Vec::new(),
                         range: default_range(),
                     })
                 ]),
@@ -575,14 +684,17 @@ fn mod_api_test() {
                 on_functions: HashMap::from([
                     ("on_spawn".to_string(), GrugOnFunction{
                         description: "Called when the entity is spawned.".to_string(),
+                        arguments: Vec::new(),
                         range: default_range(),
                     }),
                     ("on_despawn".to_string(), GrugOnFunction {
                         description: "Called when the entity is despawned.".to_string(),
+                        arguments: Vec::new(),
                         range: default_range(),
                     }),
                     ("on_tick".to_string(), GrugOnFunction {
                         description: "Called every tick.".to_string(),
+                        arguments: Vec::new(),
                         range: default_range(),
                     })
                 ]),
@@ -594,190 +706,211 @@ fn mod_api_test() {
                 description: "Sets the name of the spawned gun.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::String { name: "name".to_string() }
+                    GrugArgument::String { name: "name".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("set_gun_sprite_path".to_string(), GrugGameFunction {
                 description: "Sets the sprite path of the spawned gun.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::Resource { name: "sprite_path".to_string(), resource_extension: ".png".to_string() }
+                    GrugArgument::Resource { name: "sprite_path".to_string(), resource_extension: ".png".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("set_gun_rounds_per_minute".to_string(), GrugGameFunction {
                 description: "Sets the rounds per minute of the spawned gun.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::I32 {name: "rounds_per_minute".to_string()},
+                    GrugArgument::I32 {name: "rounds_per_minute".to_string(), default: None },
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("set_gun_companion".to_string(), GrugGameFunction {
                 description: "Sets the companion of the spawned gun. This is a box that gets spawned alongside the gun, to show off being able to spawn other entitities during on_spawn().".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::Entity { name: "companion".to_string(), entity_type: "box".to_string() },
+                    GrugArgument::Entity { name: "companion".to_string(), entity_type: "box".to_string(), default: None },
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("set_bullet_name".to_string(), GrugGameFunction {
                 description: "Sets the name of the spawned bullet.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::String { name: "name".to_string() },
+                    GrugArgument::String { name: "name".to_string(), default: None },
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("set_bullet_sprite_path".to_string(), GrugGameFunction {
                 description: "Sets the sprite path of the spawned bullet.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::Resource { name: "sprite_path".to_string(), resource_extension: ".png".to_string() }
+                    GrugArgument::Resource { name: "sprite_path".to_string(), resource_extension: ".png".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("set_bullet_density".to_string(), GrugGameFunction {
                 description: "Sets the density of the spawned bullet.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::F32 {name: "density".to_string()}
+                    GrugArgument::F32 {name: "density".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("set_box_name".to_string(), GrugGameFunction {
                 description: "Sets the name of the spawned box.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::String{name: "name".to_string()}
+                    GrugArgument::String {name: "name".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("set_box_sprite_path".to_string(), GrugGameFunction {
                 description: "Sets the sprite path of the spawned box.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::Resource { name: "sprite_path".to_string(), resource_extension: ".png".to_string() }
+                    GrugArgument::Resource { name: "sprite_path".to_string(), resource_extension: ".png".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("set_counter_name".to_string(), GrugGameFunction {
                 description: "Sets the name of the spawned counter.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::String { name: "name".to_string() }
+                    GrugArgument::String { name: "name".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("spawn_bullet".to_string(), GrugGameFunction {
                 description: "Spawns a bullet.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::Entity { name: "name".to_string(), entity_type: "bullet".to_string() },
-                    GrugArgument::F32 {name: "x".to_string()},
-                    GrugArgument::F32 {name: "y".to_string()},
-                    GrugArgument::F32 {name: "angle_in_degrees".to_string()},
-                    GrugArgument::F32 {name: "velocity_in_meters_per_second".to_string()},
+                    GrugArgument::Entity { name: "name".to_string(), entity_type: "bullet".to_string(), default: None },
+                    GrugArgument::F32 {name: "x".to_string(), default: None },
+                    GrugArgument::F32 {name: "y".to_string(), default: None },
+                    GrugArgument::F32 {name: "angle_in_degrees".to_string(), default: None },
+                    GrugArgument::F32 {name: "velocity_in_meters_per_second".to_string(), default: None },
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("spawn_counter".to_string(), GrugGameFunction {
                 description: "Spawns a counter, and returns its ID.".to_string(),
                 return_type: Some(GrugDetailedType::ID),
                 arguments: vec![
-                    GrugArgument::Entity { name: "path".to_string(), entity_type: "counter".to_string() }
+                    GrugArgument::Entity { name: "path".to_string(), entity_type: "counter".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("despawn_entity".to_string(), GrugGameFunction {
                 description: "Despawns an entity, given its ID.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::ID {name: "entity_id".to_string()}
+                    GrugArgument::ID {name: "entity_id".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("rand".to_string(), GrugGameFunction {
                 description: "Gets a random f32 between min and max.".to_string(),
                 return_type: Some(GrugDetailedType::F32),
                 arguments: vec![
-                    GrugArgument::F32{name: "min".to_string()},
-                    GrugArgument::F32{name: "max".to_string()},
+                    GrugArgument::F32 {name: "min".to_string(), default: None },
+                    GrugArgument::F32 {name: "max".to_string(), default: None },
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("print_i32".to_string(), GrugGameFunction {
                 description: "Prints an i32.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::I32{name: "i".to_string()}
+                    GrugArgument::I32 {name: "i".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("print_f32".to_string(), GrugGameFunction {
                 description: "Prints an f32.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::F32 {name: "f".to_string()}
+                    GrugArgument::F32 {name: "f".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("print_string".to_string(), GrugGameFunction {
                 description: "Prints a string.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::String { name: "s".to_string() }
+                    GrugArgument::String { name: "s".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("print_bool".to_string(), GrugGameFunction {
                 description: "Prints a bool.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::Bool {name: "b".to_string()}
+                    GrugArgument::Bool {name: "b".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("play_sound".to_string(), GrugGameFunction {
                 description: "Plays a sound.".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::Resource { name: "path".to_string(), resource_extension: ".wav".to_string() }
+                    GrugArgument::Resource { name: "path".to_string(), resource_extension: ".wav".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("map_has_i32".to_string(), GrugGameFunction {
                 description: "Returns whether an entity's i32 map contains a key.".to_string(),
                 return_type: Some(GrugDetailedType::Bool),
                 arguments: vec![
-                    GrugArgument::ID {name: "entity_id".to_string()},
-                    GrugArgument::String {name: "key".to_string()}
+                    GrugArgument::ID {name: "entity_id".to_string(), default: None },
+                    GrugArgument::String {name: "key".to_string(), default: None }
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("map_get_i32".to_string(), GrugGameFunction {
                 description: "Returns the value of a key in an entity's i32 map. Note that if the map doesn't contain the key, the game will throw an error, so make sure to call map_has_i32() first!".to_string(),
                 return_type: Some(GrugDetailedType::I32),
                 arguments: vec![
-                    GrugArgument::ID {name: "entity_id".to_string()},
-                    GrugArgument::String {name: "key".to_string()},
+                    GrugArgument::ID {name: "entity_id".to_string(), default: None },
+                    GrugArgument::String {name: "key".to_string(), default: None },
                 ],
+                deprecated: false,
                 range: default_range(),
             }),
             ("map_set_i32".to_string(), GrugGameFunction {
                 description: "Sets the value of a key in an entity's i32 map. Note that if the map doesn't contain the key, the game will throw an error, so make sure to call map_has_i32() first!".to_string(),
                 return_type: None,
                 arguments: vec![
-                    GrugArgument::ID {name: "entity_id".to_string()},
-                    GrugArgument::String{name: "key".to_string()},
-                    GrugArgument::I32{name: "value".to_string()},
+                    GrugArgument::ID {name: "entity_id".to_string(), default: None },
+                    GrugArgument::String {name: "key".to_string(), default: None },
+                    GrugArgument::I32 {name: "value".to_string(), default: None },
                 ],
+                deprecated: false,
                 range: default_range(),
             })
         ]),
-
-        ..Default::default()
+        snippets: Vec::new(),
     };
 
     let result: ModApi = ModApi::from_json(source).unwrap();
@@ -790,3 +923,83 @@ fn mod_api_test() {
         assert_eq!(entity, *other);
     }
 }
+
+#[test]
+fn argument_default_test() {
+    let source = r#"{
+    "game_functions": {
+        "spawn_bullet": {
+            "arguments": [
+                { "name": "x", "type": "f32", "default": 0 },
+                { "name": "y", "type": "f32", "default": 1.5 },
+                { "name": "sprite_path", "type": "resource", "resource_extension": ".png", "default": "bullet.png" },
+                { "name": "speed", "type": "i32" },
+                { "name": "label", "type": "string", "default": "say \"hi\"" },
+                { "name": "price", "type": "string", "default": "$5 {a\\b}" }
+            ]
+        }
+    }
+}"#;
+
+    let mod_api = ModApi::from_json(source).unwrap();
+    let placeholders: Vec<String> = mod_api.game_functions["spawn_bullet"]
+        .arguments
+        .iter()
+        .map(|arg| arg.get_placeholder())
+        .collect();
+
+    assert_eq!(
+        placeholders,
+        [
+            "0.0",
+            "1.5",
+            "\"bullet.png\"",
+            "speed",
+            "label",
+            "\"$5 {a\\b}\""
+        ]
+    );
+}
+
+#[test]
+fn mod_api_strict_test() {
+    let err = ModApi::from_json_strict("{\n    \"entities\": {,\n}").unwrap_err();
+    assert_eq!((err.line, err.column), (Some(2), Some(18)));
+    assert_eq!(err.message, "key must be a string");
+
+    let err = ModApi::from_json_strict("[]").unwrap_err();
+    assert_eq!(err.line, None);
+
+    assert!(ModApi::from_json_strict("{}").is_ok());
+}
+
+#[test]
+fn snippets_test() {
+    let source = r#"{
+    "entities": {},
+    "game_functions": {},
+    "snippets": [
+        { "label": "spawn_box", "body": "spawn_box(${1:x}, ${2:y})", "description": "Spawns a box" },
+        { "label": "no_body" },
+        { "label": "despawn", "body": "despawn(me)" }
+    ]
+}"#;
+
+    let mod_api = ModApi::from_json(source).unwrap();
+
+    assert_eq!(
+        mod_api.snippets,
+        [
+            GrugSnippet {
+                label: "spawn_box".to_string(),
+                body: "spawn_box(${1:x}, ${2:y})".to_string(),
+                description: "Spawns a box".to_string(),
+            },
+            GrugSnippet {
+                label: "despawn".to_string(),
+                body: "despawn(me)".to_string(),
+                description: String::new(),
+            },
+        ]
+    );
+}
@@ -0,0 +1,179 @@
+use lsp_types::ExecuteCommandParams;
+use serde_json::{Value, json};
+use tree_sitter::Parser;
+
+use crate::server::{
+    Server,
+    mod_api::{GrugArgument, ModApi},
+};
+
+use log::info;
+
+fn describe_type(argument: &GrugArgument) -> String {
+    match argument {
+        GrugArgument::Resource {
+            resource_extension, ..
+        } => format!("resource (`{}`)", resource_extension),
+        GrugArgument::Entity { entity_type, .. } => format!("entity (`{}`)", entity_type),
+        GrugArgument::Unknown { r#type, .. } => r#type.to_string(),
+        argument => argument.get_type().as_str().to_string(),
+    }
+}
+
+impl ModApi {
+    // Everything is sorted by name so the output doesn't change between runs
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Mod API\n");
+
+        let mut entities: Vec<_> = self.entities.iter().collect();
+        entities.sort_by_key(|(name, _)| *name);
+
+        out.push_str("\n## Entities\n");
+        for (name, entity) in entities {
+            out.push_str(&format!("\n### {}\n\n{}\n", name, entity.description));
+
+            let mut on_functions: Vec<_> = entity.on_functions.iter().collect();
+            on_functions.sort_by_key(|(name, _)| *name);
+
+            for (name, on_function) in on_functions {
+                out.push_str(&format!("\n#### {}\n\n{}\n", name, on_function.description));
+            }
+        }
+
+        let mut game_functions: Vec<_> = self.game_functions.iter().collect();
+        game_functions.sort_by_key(|(name, _)| *name);
+
+        out.push_str("\n## Game functions\n");
+        for (name, game_function) in game_functions {
+            out.push_str(&format!(
+                "\n### {}\n\n```grug\n{}\n```\n\n{}\n",
+                name,
+                game_function.format(name),
+                game_function.description
+            ));
+
+            if game_function.arguments.is_empty() {
+                continue;
+            }
+
+            out.push_str("\n| Argument | Type | Default |\n| --- | --- | --- |\n");
+            for argument in game_function.arguments.iter() {
+                let default = match argument.get_default() {
+                    Some(_) => format!("`{}`", argument.get_placeholder()),
+                    None => String::new(),
+                };
+
+                out.push_str(&format!(
+                    "| {} | {} | {} |\n",
+                    argument.get_name(),
+                    describe_type(argument),
+                    default
+                ));
+            }
+        }
+
+        if !self.snippets.is_empty() {
+            out.push_str("\n## Snippets\n");
+        }
+        for snippet in self.snippets.iter() {
+            out.push_str(&format!(
+                "\n### {}\n\n```grug\n{}\n```\n\n{}\n",
+                snippet.label, snippet.body, snippet.description
+            ));
+        }
+
+        out
+    }
+}
+
+impl Server {
+    // Takes an optional path relative to the root, defaulting to mod_api.md
+    pub fn generate_api_docs(
+        &mut self,
+        _parser: &mut Parser,
+        params: ExecuteCommandParams,
+    ) -> Result<Value, String> {
+        let Some(root_path) = self.root_path.as_ref() else {
+            return Err("There is no workspace to put the docs in".to_string());
+        };
+
+        let file_name = match params.arguments.first() {
+            Some(Value::String(file_name)) => file_name.as_str(),
+            Some(_) => return Err("The docs path must be a string".to_string()),
+            None => "mod_api.md",
+        };
+        let path = root_path.join(file_name);
+
+        std::fs::write(&path, self.mod_api.to_markdown())
+            .map_err(|err| format!("Couldn't write {}: {}", path.display(), err))?;
+        info!("Wrote the mod API docs to {}", path.display());
+
+        Ok(json!({ "uri": format!("file://{}", path.display()) }))
+    }
+}
+
+#[test]
+fn mod_api_markdown_test() {
+    let source = r#"{
+    "entities": {
+        "gun": {
+            "description": "The player's gun.",
+            "on_functions": {
+                "on_spawn": { "description": "Called when the entity is spawned." }
+            }
+        }
+    },
+    "game_functions": {
+        "set_gun_sprite_path": {
+            "description": "Sets the sprite path of the spawned gun.",
+            "arguments": [
+                { "name": "sprite_path", "type": "resource", "resource_extension": ".png", "default": "gun.png" }
+            ]
+        },
+        "get_time": {
+            "description": "The current time.",
+            "return_type": "f32"
+        }
+    }
+}"#;
+
+    let mod_api = ModApi::from_json(source).unwrap();
+
+    assert_eq!(
+        mod_api.to_markdown(),
+        r#"# Mod API
+
+## Entities
+
+### gun
+
+The player's gun.
+
+#### on_spawn
+
+Called when the entity is spawned.
+
+## Game functions
+
+### get_time
+
+```grug
+get_time() f32
+```
+
+The current time.
+
+### set_gun_sprite_path
+
+```grug
+set_gun_sprite_path(sprite_path: string)
+```
+
+Sets the sprite path of the spawned gun.
+
+| Argument | Type | Default |
+| --- | --- | --- |
+| sprite_path | resource (`.png`) | `"gun.png"` |
+"#
+    );
+}
@@ -2,7 +2,9 @@ use std::collections::HashMap;
 
 use tree_sitter::Node;
 
-use crate::server::mod_api::{GrugEntity, GrugGameFunction, GrugOnFunction, JSON_PARSER, ModApi};
+use crate::server::mod_api::{
+    GrugEntity, GrugGameFunction, GrugOnFunction, GrugSnippet, JSON_PARSER, ModApi, ModApiError,
+};
 
 impl ModApi {
     fn parse_game_functions(
@@ -37,7 +39,7 @@ impl ModApi {
             };
 
             let buf = &json[obj.byte_range()];
-            let Ok(mut game_func) = serde_json::from_slice::<GrugGameFunction>(&buf) else {
+            let Ok(mut game_func) = serde_json::from_slice::<GrugGameFunction>(buf) else {
                 return;
             };
 
@@ -154,6 +156,17 @@ impl ModApi {
         }
     }
 
+    fn parse_snippets(out: &mut Vec<GrugSnippet>, entry: &Node, json: &[u8]) {
+        let mut cursor = entry.walk();
+        for snippet in entry.named_children(&mut cursor) {
+            // One broken snippet shouldn't take the others with it
+            if let Ok(snippet) = serde_json::from_slice::<GrugSnippet>(&json[snippet.byte_range()])
+            {
+                out.push(snippet);
+            }
+        }
+    }
+
     pub fn from_json(json: &str) -> Option<ModApi> {
         let json = json.as_bytes();
 
@@ -162,6 +175,7 @@ impl ModApi {
 
         let mut entities: HashMap<String, GrugEntity> = HashMap::new();
         let mut game_functions: HashMap<String, GrugGameFunction> = HashMap::new();
+        let mut snippets: Vec<GrugSnippet> = Vec::new();
 
         let root = tree.root_node();
         let root = root.child(0)?;
@@ -204,6 +218,16 @@ impl ModApi {
 
                     Self::parse_game_functions(&mut game_functions, &value, json);
                 }
+                b"\"snippets\"" => {
+                    let Some(value) = entry.child_by_field_name("value") else {
+                        continue;
+                    };
+                    if value.kind() != "array" {
+                        continue;
+                    }
+
+                    Self::parse_snippets(&mut snippets, &value, json);
+                }
                 _ => {
                     println!("Unkown key: {:?}", String::from_utf8(key.to_vec()));
                 }
@@ -215,6 +239,32 @@ impl ModApi {
         Some(ModApi {
             entities,
             game_functions,
+            snippets,
+        })
+    }
+
+    // Unlike from_json, which salvages what it can while the file is being edited,
+    // this rejects invalid JSON and says where the problem is
+    pub fn from_json_strict(json: &str) -> Result<ModApi, ModApiError> {
+        if let Err(err) = serde_json::from_str::<serde_json::Value>(json) {
+            // The location is kept separately, so it doesn't need to be in the message too
+            let location = format!(" at line {} column {}", err.line(), err.column());
+            let message = err.to_string();
+
+            return Err(ModApiError {
+                message: message
+                    .strip_suffix(&location)
+                    .unwrap_or(&message)
+                    .to_string(),
+                line: Some(err.line()),
+                column: Some(err.column()),
+            });
+        }
+
+        ModApi::from_json(json).ok_or(ModApiError {
+            message: "The mod API must be a JSON object".to_string(),
+            line: None,
+            column: None,
         })
     }
 }
@@ -0,0 +1,94 @@
+use lsp_types::{MessageType, notification::Notification};
+use serde::{Deserialize, Serialize};
+
+use crate::server::{
+    Server,
+    mod_api::{ModApiError, schema::mod_api_schema},
+    utils::{RequestResult, ok, send_notification, show_message},
+};
+
+// Sent whenever the mod API gets reloaded from disk, whether it worked or not
+pub enum ModApiStatus {}
+
+impl Notification for ModApiStatus {
+    type Params = ModApiStatusParams;
+    const METHOD: &'static str = "grug-ls/modApiStatus";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModApiStatusParams {
+    pub loaded: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+}
+
+impl Server {
+    // grug-ls/modApi, so editor extensions can list entities and game functions
+    // without parsing mod_api.json themselves
+    pub fn handle_mod_api_request(&self) -> RequestResult {
+        ok(&self.mod_api)
+    }
+
+    // grug-ls/modApiSchema, for validating mod_api.json in the editor
+    pub fn handle_mod_api_schema_request(&self) -> RequestResult {
+        ok(mod_api_schema())
+    }
+
+    pub fn report_startup_problems(&mut self) {
+        if let Some(err) = self.mod_api_error.take() {
+            self.send_mod_api_status(Err(&err));
+        }
+        if let Some(err) = self.watcher_error.take() {
+            show_message(
+                &self.client,
+                MessageType::WARNING,
+                format!(
+                    "Files changed outside of the editor go unnoticed until a restart. {}",
+                    err
+                ),
+            );
+        }
+    }
+
+    pub fn send_mod_api_status(&mut self, result: Result<(), &ModApiError>) {
+        self.mod_api_loaded = result.is_ok();
+        let params = match result {
+            Ok(()) => ModApiStatusParams {
+                loaded: true,
+                message: None,
+                line: None,
+                column: None,
+            },
+            Err(err) => {
+                let location = match (err.line, err.column) {
+                    (Some(line), Some(column)) => format!(":{}:{}", line, column),
+                    _ => String::new(),
+                };
+                show_message(
+                    &self.client,
+                    MessageType::ERROR,
+                    format!(
+                        "Couldn't load {}{}: {}",
+                        self.settings.mod_api_path.display(),
+                        location,
+                        err.message
+                    ),
+                );
+
+                ModApiStatusParams {
+                    loaded: false,
+                    message: Some(err.message.clone()),
+                    line: err.line,
+                    column: err.column,
+                }
+            }
+        };
+
+        send_notification::<ModApiStatus>(&self.client, params);
+    }
+}
@@ -0,0 +1,162 @@
+use serde_json::{Value, json};
+
+// The types arguments can have besides entity types, which can be any name
+const ARGUMENT_TYPES: &[&str] = &["string", "i32", "f32", "id", "bool", "resource", "entity"];
+
+// Describes the mod_api.json the server understands, for editors and CI to validate against.
+// Draft-07, since that's the newest one most editors support
+pub fn mod_api_schema() -> Value {
+    let description = json!({
+        "type": "string",
+        "description": "Shown in hovers and completions"
+    });
+
+    let argument = json!({
+        "type": "object",
+        "required": ["name", "type"],
+        "properties": {
+            "name": { "type": "string" },
+            "type": {
+                "type": "string",
+                "description": "One of the grug types, or the name of an entity type",
+                "examples": ARGUMENT_TYPES
+            },
+            "resource_extension": {
+                "type": "string",
+                "description": "The file extension of a resource, like \".png\""
+            },
+            "entity_type": {
+                "type": "string",
+                "description": "The type of entity the string has to name"
+            },
+            "default": {
+                "type": ["string", "number", "boolean"],
+                "description": "Used as the placeholder when completing a call"
+            }
+        },
+        "allOf": [
+            {
+                "if": { "properties": { "type": { "const": "resource" } } },
+                "then": { "required": ["resource_extension"] }
+            },
+            {
+                "if": { "properties": { "type": { "const": "entity" } } },
+                "then": { "required": ["entity_type"] }
+            }
+        ]
+    });
+
+    let return_type = json!({
+        "oneOf": [
+            {
+                "type": "string",
+                "description": "One of the grug types, or the name of an entity type"
+            },
+            {
+                "type": "object",
+                "required": ["resource"],
+                "properties": {
+                    "resource": {
+                        "type": "object",
+                        "required": ["resource_extension"],
+                        "properties": { "resource_extension": { "type": "string" } }
+                    }
+                },
+                "additionalProperties": false
+            }
+        ]
+    });
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "grug mod API",
+        "type": "object",
+        "required": ["entities", "game_functions"],
+        "properties": {
+            "entities": {
+                "type": "object",
+                "description": "Keyed by entity type, which scripts name after the last '-' of their file name",
+                "additionalProperties": {
+                    "type": "object",
+                    "properties": {
+                        "description": description,
+                        "on_functions": {
+                            "type": "object",
+                            "propertyNames": { "pattern": "^on_" },
+                            "additionalProperties": {
+                                "type": "object",
+                                "properties": {
+                                    "description": description,
+                                    "arguments": {
+                                        "type": "array",
+                                        "description": "What the game passes it, in order",
+                                        "items": argument
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            },
+            "game_functions": {
+                "type": "object",
+                "additionalProperties": {
+                    "type": "object",
+                    "properties": {
+                        "description": description,
+                        "arguments": { "type": "array", "items": argument },
+                        "return_type": return_type,
+                        "deprecated": {
+                            "type": "boolean",
+                            "description": "Marks calls to it as deprecated in the editor"
+                        }
+                    }
+                }
+            },
+            "snippets": {
+                "type": "array",
+                "description": "Offered as completions wherever a statement can go",
+                "items": {
+                    "type": "object",
+                    "required": ["label", "body"],
+                    "properties": {
+                        "label": { "type": "string" },
+                        "body": {
+                            "type": "string",
+                            "description": "In the LSP snippet syntax, like \"set_speed(${1:1.0})\""
+                        },
+                        "description": description
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[test]
+fn mod_api_schema_test() {
+    use crate::server::mod_api::GrugArgument;
+
+    let schema = mod_api_schema();
+    let argument = &schema["properties"]["game_functions"]["additionalProperties"]["properties"]["arguments"]
+        ["items"];
+    let conditions = argument["allOf"].as_array().unwrap();
+
+    // Every type the schema lists has to be one the server knows, not an entity type
+    for r#type in argument["properties"]["type"]["examples"]
+        .as_array()
+        .unwrap()
+    {
+        let mut arg = json!({ "name": "x", "type": r#type });
+        for condition in conditions {
+            if condition["if"]["properties"]["type"]["const"] == *r#type {
+                for field in condition["then"]["required"].as_array().unwrap() {
+                    arg[field.as_str().unwrap()] = json!("box");
+                }
+            }
+        }
+
+        let arg: GrugArgument = serde_json::from_value(arg).unwrap();
+        assert!(!matches!(arg, GrugArgument::Unknown { .. }), "{}", r#type);
+    }
+}
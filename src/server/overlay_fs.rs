@@ -0,0 +1,114 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use vfs::{FileSystem, MemoryFS};
+
+#[cfg(feature = "native")]
+type DiskFS = vfs::PhysicalFS;
+// There is no disk to read from on wasm, so this stays empty and
+// the host opens every file it wants analyzed
+#[cfg(not(feature = "native"))]
+type DiskFS = MemoryFS;
+
+// What the editor has open shadows the disk, every other read falls through to the workspace
+pub struct OverlayFS {
+    memory: MemoryFS,
+    // None outside of a workspace, where nothing can be read from disk
+    disk: Option<(PathBuf, DiskFS)>,
+}
+
+impl std::fmt::Debug for OverlayFS {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OverlayFS")
+            .field("memory", &self.memory)
+            .field("root", &self.disk.as_ref().map(|(root, _)| root))
+            .finish()
+    }
+}
+
+impl OverlayFS {
+    pub fn new(root_path: Option<PathBuf>) -> OverlayFS {
+        OverlayFS {
+            memory: MemoryFS::new(),
+            #[cfg(feature = "native")]
+            disk: root_path.map(|root| (root.clone(), DiskFS::new(root))),
+            #[cfg(not(feature = "native"))]
+            disk: root_path.map(|root| (root, DiskFS::new())),
+        }
+    }
+
+    // Paths on disk are only readable when they are inside of the workspace
+    fn disk_path<'a>(&self, path: &'a str) -> Option<(&DiskFS, &'a str)> {
+        let (root, disk) = self.disk.as_ref()?;
+        let relative = Path::new(path).strip_prefix(root).ok()?.to_str()?;
+
+        Some((disk, relative))
+    }
+
+    pub fn write(&self, path: &str, content: &[u8]) {
+        let dirs: Vec<&Path> = Path::new(path).ancestors().skip(1).collect();
+        for dir in dirs.iter().rev() {
+            let dir = dir.to_str().unwrap();
+            if !self.memory.exists(dir).unwrap() {
+                self.memory.create_dir(dir).unwrap();
+            }
+        }
+
+        let mut file = self.memory.create_file(path).unwrap();
+        file.write_all(content).unwrap();
+    }
+
+    // Only forgets the editor's copy, whatever is on disk shows through again
+    pub fn remove(&self, path: &str) {
+        if self.memory.exists(path).unwrap_or(false) {
+            self.memory.remove_file(path).unwrap();
+        }
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        if self.memory.exists(path).unwrap_or(false) {
+            return true;
+        }
+
+        self.disk_path(path)
+            .is_some_and(|(disk, relative)| disk.exists(relative).unwrap_or(false))
+    }
+
+    pub fn read(&self, path: &str) -> Option<Vec<u8>> {
+        let mut file = if self.memory.exists(path).unwrap_or(false) {
+            self.memory.open_file(path).ok()?
+        } else {
+            let (disk, relative) = self.disk_path(path)?;
+            disk.open_file(relative).ok()?
+        };
+
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).ok()?;
+
+        Some(content)
+    }
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn overlay_fs_test() {
+    let root = std::env::temp_dir().join(format!("grug-ls-overlay-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let on_disk = root.join("a-box.grug");
+    std::fs::write(&on_disk, "on disk").unwrap();
+    let on_disk = on_disk.to_str().unwrap();
+
+    let file_system = OverlayFS::new(Some(root.clone()));
+    assert_eq!(file_system.read(on_disk).unwrap(), b"on disk");
+    assert!(!file_system.exists("/somewhere/else.grug"));
+
+    file_system.write(on_disk, b"in the editor");
+    assert_eq!(file_system.read(on_disk).unwrap(), b"in the editor");
+
+    file_system.remove(on_disk);
+    assert_eq!(file_system.read(on_disk).unwrap(), b"on disk");
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
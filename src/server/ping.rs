@@ -0,0 +1,70 @@
+use lsp_types::request::Request;
+use serde::{Deserialize, Serialize};
+
+use crate::server::{
+    Server,
+    utils::{RequestResult, ok},
+};
+
+// grug-ls/ping, cheap enough for editor extensions to poll for a status bar item
+pub enum PingRequest {}
+
+impl Request for PingRequest {
+    type Params = ();
+    type Result = PingResult;
+    const METHOD: &'static str = "grug-ls/ping";
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PingResult {
+    pub uptime_secs: u64,
+    pub mod_api_loaded: bool,
+    pub watcher: WatcherStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum WatcherStatus {
+    Running,
+    // The watcher couldn't start or has died, files changed outside of the editor go unnoticed
+    Stopped,
+    // Outside of a workspace, or without the native feature
+    Disabled,
+}
+
+impl Server {
+    pub fn ping(&self) -> PingResult {
+        let watcher = if !cfg!(feature = "native") || self.root_path.is_none() {
+            WatcherStatus::Disabled
+        } else if self.watcher_stopped {
+            WatcherStatus::Stopped
+        } else {
+            WatcherStatus::Running
+        };
+
+        PingResult {
+            uptime_secs: self.started.elapsed().as_secs(),
+            mod_api_loaded: self.mod_api_loaded,
+            watcher,
+        }
+    }
+
+    pub fn handle_ping(&self) -> RequestResult {
+        ok(self.ping())
+    }
+}
+
+#[test]
+fn ping_test() {
+    use crate::test_support::TestClient;
+
+    let mut client = TestClient::new();
+    let ping = client.expect::<PingRequest>(());
+    assert!(!ping.mod_api_loaded);
+    // Nothing is watching the fixture root
+    assert_ne!(ping.watcher, WatcherStatus::Running);
+
+    client.open("mod_api.json", r#"{"entities": {}, "game_functions": {}}"#);
+    assert!(client.expect::<PingRequest>(()).mod_api_loaded);
+}
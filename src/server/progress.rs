@@ -0,0 +1,143 @@
+use lsp_server::{Message, Notification, Request, RequestId};
+use lsp_types::{
+    NumberOrString, ProgressParams, ProgressParamsValue, ProgressToken, WorkDoneProgress,
+    WorkDoneProgressBegin, WorkDoneProgressCreateParams, WorkDoneProgressEnd,
+    WorkDoneProgressReport, notification::Progress as ProgressNotification,
+    request::WorkDoneProgressCreate,
+};
+use serde::Serialize;
+
+use crate::server::{
+    Server,
+    utils::{Client, send_message, send_notification},
+};
+
+// A $/progress task shown by the client. Without a token every call is a no-op,
+// so callers don't have to care whether the client supports progress
+pub struct Progress {
+    token: Option<ProgressToken>,
+    client: Client,
+}
+
+// Results that go out in $/progress notifications as they are found, when the client sent a
+// partialResultToken. Without one they are collected for the response like usual
+pub struct PartialResults<T> {
+    token: Option<ProgressToken>,
+    client: Client,
+    results: Vec<T>,
+}
+
+impl Server {
+    pub fn partial_results<T>(&self, token: Option<ProgressToken>) -> PartialResults<T> {
+        PartialResults {
+            token,
+            client: self.client.clone(),
+            results: Vec::new(),
+        }
+    }
+
+    // Requests the server sends to the client. Their responses are ignored,
+    // unless the caller keeps the id to pick them out in handle_response
+    pub fn send_request<R: lsp_types::request::Request>(&mut self, params: R::Params) -> RequestId {
+        self.outgoing_requests += 1;
+        let id = RequestId::from(format!("grug-ls/{}", self.outgoing_requests));
+        let request = Request::new(id.clone(), R::METHOD.to_string(), params);
+
+        send_message(&self.client, Message::Request(request));
+        id
+    }
+
+    fn supports_work_done_progress(&self) -> bool {
+        self.client_capabilities
+            .window
+            .as_ref()
+            .and_then(|window| window.work_done_progress)
+            .unwrap_or(false)
+    }
+
+    // Uses the token the client sent along with the request if there is one
+    pub fn begin_progress(&mut self, token: Option<ProgressToken>, title: &str) -> Progress {
+        let token = match token {
+            Some(token) => Some(token),
+            None if self.supports_work_done_progress() => {
+                let token = NumberOrString::String(format!(
+                    "grug-ls/progress/{}",
+                    self.outgoing_requests + 1
+                ));
+                self.send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                    token: token.clone(),
+                });
+
+                Some(token)
+            }
+            None => None,
+        };
+
+        let progress = Progress {
+            token,
+            client: self.client.clone(),
+        };
+        progress.send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: title.to_string(),
+            percentage: Some(0),
+            ..Default::default()
+        }));
+
+        progress
+    }
+}
+
+impl Progress {
+    fn send(&self, value: WorkDoneProgress) {
+        let Some(token) = &self.token else {
+            return;
+        };
+
+        send_notification::<ProgressNotification>(
+            &self.client,
+            ProgressParams {
+                token: token.clone(),
+                value: ProgressParamsValue::WorkDone(value),
+            },
+        );
+    }
+
+    pub fn report(&self, message: String, percentage: u32) {
+        self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            message: Some(message),
+            percentage: Some(percentage),
+            ..Default::default()
+        }));
+    }
+
+    pub fn end(self, message: String) {
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd {
+            message: Some(message),
+        }));
+    }
+}
+
+impl<T: Serialize> PartialResults<T> {
+    pub fn extend(&mut self, chunk: Vec<T>) {
+        if chunk.is_empty() {
+            return;
+        }
+
+        let Some(token) = &self.token else {
+            self.results.extend(chunk);
+            return;
+        };
+
+        // lsp_types only has the work done kind of progress, so this one is built by hand
+        let params = serde_json::json!({ "token": token, "value": chunk });
+        send_message(
+            &self.client,
+            Message::Notification(Notification::new("$/progress".to_string(), params)),
+        );
+    }
+
+    // What's left for the response, which the spec wants empty once everything went out as progress
+    pub fn finish(self) -> Vec<T> {
+        self.results
+    }
+}
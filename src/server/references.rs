@@ -0,0 +1,205 @@
+use lsp_types::{
+    DocumentHighlight, DocumentHighlightKind, DocumentHighlightParams, Location, Position,
+    ReferenceParams,
+};
+use tree_sitter::Node;
+
+use crate::server::{
+    Server,
+    document::Document,
+    utils::{
+        RequestResult, get_descendants_of_kind, get_spot_info, is_function_call, ok,
+        treesitter_range_to_lsp,
+    },
+};
+
+pub struct Occurrence {
+    pub range: tree_sitter::Range,
+    pub is_declaration: bool,
+    // Declarations and assignments, as opposed to places that only read the value
+    pub is_write: bool,
+}
+
+fn is_write(node: &Node) -> bool {
+    node.parent().is_some_and(|parent| {
+        matches!(parent.kind(), "assignment" | "variable_declaration")
+            && parent
+                .child_by_field_name("name")
+                .is_some_and(|name| name.id() == node.id())
+    })
+}
+
+// Uses of a variable can only come after its declaration, in the same or a nested scope
+pub fn find_variable_occurrences(document: &Document, declaration: &Node) -> Vec<Occurrence> {
+    let Some(name_node) = declaration.child_by_field_name("name") else {
+        return Vec::new();
+    };
+    let name = &document.content[name_node.byte_range()];
+
+    let mut occurrences = vec![Occurrence {
+        range: name_node.range(),
+        is_declaration: true,
+        is_write: true,
+    }];
+
+    let mut node = *declaration;
+    while let Some(sibling) = node.next_sibling() {
+        for identifier in get_descendants_of_kind(&sibling, "identifier") {
+            if is_function_call(&identifier) || &document.content[identifier.byte_range()] != name {
+                continue;
+            }
+
+            occurrences.push(Occurrence {
+                range: identifier.range(),
+                is_declaration: false,
+                is_write: is_write(&identifier),
+            });
+        }
+
+        node = sibling;
+    }
+
+    occurrences
+}
+
+pub fn find_helper_occurrences(document: &Document, name: &[u8]) -> Vec<Occurrence> {
+    get_descendants_of_kind(&document.tree.root_node(), "helper_identifier")
+        .into_iter()
+        .filter(|node| &document.content[node.byte_range()] == name)
+        .map(|node| Occurrence {
+            range: node.range(),
+            is_declaration: node
+                .parent()
+                .is_some_and(|parent| parent.kind() == "function_declaration"),
+            is_write: false,
+        })
+        .collect()
+}
+
+pub fn find_occurrences(document: &Document, position: Position) -> Option<Vec<Occurrence>> {
+    let point = tree_sitter::Point {
+        column: position.character as usize,
+        row: position.line as usize,
+    };
+    let node = document
+        .tree
+        .root_node()
+        .descendant_for_point_range(point, point)?;
+    let name = &document.content[node.byte_range()];
+
+    match node.kind() {
+        "identifier" if !is_function_call(&node) => {
+            let var = get_spot_info(document, &node)
+                .variables
+                .into_iter()
+                .find(|var| var.name.as_bytes() == name)?;
+            let declaration = document
+                .tree
+                .root_node()
+                .descendant_for_byte_range(var.range.start_byte, var.range.end_byte)?;
+
+            Some(find_variable_occurrences(document, &declaration))
+        }
+        "helper_identifier" => Some(find_helper_occurrences(document, name)),
+        _ => None,
+    }
+}
+
+impl Server {
+    pub fn handle_document_highlight(&self, params: DocumentHighlightParams) -> RequestResult {
+        let position_params = params.text_document_position_params;
+        let highlights: Option<Vec<DocumentHighlight>> = self
+            .get_document_by_uri(&position_params.text_document.uri)
+            .and_then(|document| find_occurrences(document, position_params.position))
+            .map(|occurrences| {
+                occurrences
+                    .into_iter()
+                    .map(|occurrence| DocumentHighlight {
+                        range: treesitter_range_to_lsp(&occurrence.range),
+                        kind: Some(if occurrence.is_write {
+                            DocumentHighlightKind::WRITE
+                        } else {
+                            DocumentHighlightKind::READ
+                        }),
+                    })
+                    .collect()
+            });
+
+        ok(highlights)
+    }
+
+    pub fn handle_references(&self, params: ReferenceParams) -> RequestResult {
+        let uri = params.text_document_position.text_document.uri;
+        let include_declaration = params.context.include_declaration;
+        let mut partial = self.partial_results(params.partial_result_params.partial_result_token);
+
+        let locations: Option<Vec<Location>> = self
+            .get_document_by_uri(&uri)
+            .and_then(|document| find_occurrences(document, params.text_document_position.position))
+            .map(|occurrences| {
+                occurrences
+                    .into_iter()
+                    .filter(|occurrence| include_declaration || !occurrence.is_declaration)
+                    .map(|occurrence| {
+                        Location::new(uri.clone(), treesitter_range_to_lsp(&occurrence.range))
+                    })
+                    .collect()
+            });
+
+        ok(locations.map(|locations| {
+            partial.extend(locations);
+            partial.finish()
+        }))
+    }
+}
+
+#[test]
+fn occurrences_test() {
+    use std::str::FromStr;
+
+    let source = r#"count: i32 = 0
+
+on_tick() {
+    count = count + 1
+    if count > 10 {
+        print_i32(count)
+    }
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let occurrences = find_occurrences(&document, Position::new(4, 8)).unwrap();
+    let found: Vec<(usize, usize, bool)> = occurrences
+        .iter()
+        .map(|occurrence| {
+            (
+                occurrence.range.start_point.row,
+                occurrence.range.start_point.column,
+                occurrence.is_write,
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        found,
+        [
+            (0, 0, true),
+            (3, 4, true),
+            (3, 12, false),
+            (4, 7, false),
+            (5, 18, false),
+        ]
+    );
+}
@@ -1,17 +1,18 @@
 use std::collections::HashMap;
 
-use lsp_server::{Connection, ErrorCode, Message, RequestId, Response};
-use lsp_types::{RenameParams, TextEdit, WorkspaceEdit};
+use lsp_types::{Position, RenameParams, TextEdit, WorkspaceEdit};
 use tree_sitter::Node;
-use vfs::FileSystem;
 
 use crate::server::{
     Server,
     document::Document,
-    utils::{get_spot_info, treesitter_range_to_lsp},
+    utils::{
+        RequestResult, get_descendants_of_kind, get_spot_info, ok, treesitter_range_to_lsp,
+        unsupported_uri, uri_to_path,
+    },
 };
 
-use log::info;
+use log::{debug, info};
 
 #[derive(PartialEq, Eq)]
 enum RenameType {
@@ -71,6 +72,26 @@ impl Server {
                 }
             }
             "variable_declaration" => {
+                if let Some(value_node) = node.child_by_field_name("value") {
+                    edits.append(&mut Self::rename_in_node(
+                        document,
+                        &value_node,
+                        old_name,
+                        new_name,
+                        rename_type,
+                    ))
+                }
+            }
+            "assignment" => {
+                let name_node = node.child_by_field_name("name").unwrap();
+                edits.append(&mut Self::rename_in_node(
+                    document,
+                    &name_node,
+                    old_name,
+                    new_name,
+                    rename_type,
+                ));
+
                 let value_node = node.child_by_field_name("value").unwrap();
                 edits.append(&mut Self::rename_in_node(
                     document,
@@ -78,7 +99,7 @@ impl Server {
                     old_name,
                     new_name,
                     rename_type,
-                ))
+                ));
             }
             "function_call" => {
                 if *rename_type == RenameType::Function {
@@ -161,6 +182,15 @@ impl Server {
                     rename_type,
                 ));
             }
+            "contained_expression" => {
+                edits.append(&mut Self::rename_in_node(
+                    document,
+                    &node.child(1).unwrap(),
+                    old_name,
+                    new_name,
+                    rename_type,
+                ));
+            }
             "unary_expression" => {
                 let operand_node = node.child_by_field_name("operand").unwrap();
 
@@ -183,6 +213,8 @@ impl Server {
                     rename_type,
                 ));
             }
+            // Nothing in these can refer to a variable or a helper
+            "empty_return" | "comment" | "number" | "string" | "me" | "{" | "}" => {}
 
             _ => {
                 info!("Can't rename: {:?}", node);
@@ -192,7 +224,63 @@ impl Server {
         edits
     }
 
-    fn rename_var(
+    // The comments in the scope below the declaration, and the ones right above it
+    fn comments_in_scope<'a>(scope: &Node<'a>, declaration: &Node<'a>) -> Vec<Node<'a>> {
+        let mut comments: Vec<Node> = get_descendants_of_kind(scope, "comment")
+            .into_iter()
+            .filter(|comment| comment.start_byte() >= declaration.start_byte())
+            .collect();
+
+        let mut node = *declaration;
+        while let Some(previous) = node.prev_sibling().filter(|node| node.kind() == "comment") {
+            comments.push(previous);
+            node = previous;
+        }
+
+        comments
+    }
+
+    // Exact word occurrences of the old name in the comments, for renameInComments
+    fn rename_in_comments(
+        document: &Document,
+        comments: &[Node],
+        old_name: &str,
+        new_name: &str,
+    ) -> Vec<TextEdit> {
+        let is_word = |byte: u8| byte.is_ascii_alphanumeric() || byte == b'_';
+
+        let mut edits = Vec::new();
+        for comment in comments {
+            let text = &document.content[comment.byte_range()];
+            let mut start = 0;
+            while let Some(idx) = text[start..]
+                .windows(old_name.len())
+                .position(|window| window == old_name.as_bytes())
+            {
+                let idx = start + idx;
+                let end = idx + old_name.len();
+                start = end;
+
+                if (idx > 0 && is_word(text[idx - 1])) || text.get(end).is_some_and(|b| is_word(*b))
+                {
+                    continue;
+                }
+
+                let position = comment.start_position();
+                edits.push(TextEdit::new(
+                    lsp_types::Range::new(
+                        Position::new(position.row as u32, (position.column + idx) as u32),
+                        Position::new(position.row as u32, (position.column + end) as u32),
+                    ),
+                    new_name.to_string(),
+                ));
+            }
+        }
+
+        edits
+    }
+
+    pub fn rename_var(
         document: &Document,
         node: &Node,
         old_name: &str,
@@ -204,7 +292,7 @@ impl Server {
 
         let mut edits: Vec<TextEdit> = Vec::new();
 
-        let mut node = node.clone();
+        let mut node = *node;
 
         let range = treesitter_range_to_lsp(&node.child_by_field_name("name").unwrap().range());
         edits.push(TextEdit::new(range, new_name.to_string()));
@@ -237,7 +325,7 @@ impl Server {
 
         edits.append(&mut Self::rename_in_node(
             document,
-            &node,
+            node,
             old_name,
             new_name,
             &RenameType::Function,
@@ -246,33 +334,18 @@ impl Server {
         edits
     }
 
-    pub fn rename(&self, params: RenameParams, connection: &mut Connection, id: RequestId) {
+    pub fn rename(&self, params: RenameParams) -> RequestResult {
         let uri = params.text_document_position.text_document.uri.as_str();
-
-        // We probably wont need to use this server on TCP
-        assert!(uri.starts_with("file://"));
-
-        let path = &uri["file.//".len()..];
-
-        macro_rules! send_err {
-            ($($arg:tt)*) => {
-                connection
-                    .sender
-                    .send(Message::Response(Response::new_err(
-                        id,
-                        ErrorCode::InvalidRequest as i32,
-                        format!($($arg)*),
-                    )))
-                    .unwrap()
-            };
-        }
-
-        if !self.file_system.exists(path).unwrap_or(false) {
-            send_err!("File doesnt exist: {}", path);
-            return;
+        let Some(path) = uri_to_path(uri) else {
+            return Err(unsupported_uri(uri));
+        };
+        if self.is_mod_api_path(path) {
+            return self.rename_mod_api_entity(params);
         }
 
-        let document = self.document_map.get(path).unwrap();
+        let Some(document) = self.document_map.get(path) else {
+            return ok(None::<WorkspaceEdit>);
+        };
 
         let point = tree_sitter::Point {
             column: params.text_document_position.position.character as usize,
@@ -288,6 +361,7 @@ impl Server {
         let node_kind = node.kind();
 
         let spot_info = get_spot_info(document, &node);
+        let in_comments = self.settings_for(path).rename_in_comments;
 
         let edits: Option<WorkspaceEdit> = if node_kind != "identifier"
             && node_kind != "on_identifier"
@@ -306,34 +380,161 @@ impl Server {
                     .root_node()
                     .descendant_for_byte_range(var.range.start_byte, var.range.end_byte)
                     .unwrap();
-                let edits = Self::rename_var(document, &node, &var.name, &params.new_name);
-
-                Some(WorkspaceEdit::new(HashMap::from([(
-                    document.uri.clone(),
-                    edits,
-                )])))
-            } else if let Some(func) = document
-                .helpers
-                .iter()
-                .find(|func| func.name.as_bytes() == name)
-            {
+                let mut edits = Self::rename_var(document, &node, &var.name, &params.new_name);
+
+                if in_comments {
+                    // Parameters can be used in the whole function, other variables below them
+                    let scope = node.parent().unwrap_or(node);
+                    let declaration = if node.kind() == "function_parameter" {
+                        scope
+                    } else {
+                        node
+                    };
+                    // Where another variable shadows it, the comments are about that one. Inner ones come last
+                    let comments: Vec<Node> = Self::comments_in_scope(&scope, &declaration)
+                        .into_iter()
+                        .filter(|comment| {
+                            get_spot_info(document, comment)
+                                .variables
+                                .iter()
+                                .rfind(|other| other.name == var.name)
+                                .is_none_or(|other| other.range == var.range)
+                        })
+                        .collect();
+                    edits.append(&mut Self::rename_in_comments(
+                        document,
+                        &comments,
+                        &var.name,
+                        &params.new_name,
+                    ));
+                }
+
+                Some(self.make_workspace_edit(HashMap::from([(document.uri.clone(), edits)])))
+            } else if let Some(func) = document.get_helper(name) {
                 info!("Renaming helper {} to {}", func.name, params.new_name);
                 let node = document.tree.root_node();
-                let edits = Self::rename_helper(document, &node, &func.name, &params.new_name);
+                let mut edits = Self::rename_helper(document, &node, &func.name, &params.new_name);
+
+                if in_comments {
+                    let comments = get_descendants_of_kind(&node, "comment");
+                    edits.append(&mut Self::rename_in_comments(
+                        document,
+                        &comments,
+                        &func.name,
+                        &params.new_name,
+                    ));
+                }
 
-                Some(WorkspaceEdit::new(HashMap::from([(
-                    document.uri.clone(),
-                    edits,
-                )])))
+                Some(self.make_workspace_edit(HashMap::from([(document.uri.clone(), edits)])))
             } else {
                 None
             }
         };
-        info!("{:?}", edits);
+        debug!("{:?}", edits);
 
-        connection
-            .sender
-            .send(Message::Response(Response::new_ok(id, edits)))
-            .unwrap()
+        ok(edits)
     }
 }
+
+#[test]
+fn rename_statements_test() {
+    use std::str::FromStr;
+
+    let source = r#"on_spawn() {
+    speed: f32
+    speed = (speed + 1.0) * 2.0
+    if speed > 3.0 {
+        return
+    }
+    speed = -speed
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let declaration = document
+        .tree
+        .root_node()
+        .named_descendant_for_point_range(
+            tree_sitter::Point { row: 1, column: 4 },
+            tree_sitter::Point { row: 1, column: 14 },
+        )
+        .unwrap();
+    let edits = Server::rename_var(&document, &declaration, "speed", "velocity");
+
+    let mut positions: Vec<(u32, u32)> = edits
+        .iter()
+        .map(|edit| (edit.range.start.line, edit.range.start.character))
+        .collect();
+    positions.sort();
+    assert_eq!(
+        positions,
+        [(1, 4), (2, 4), (2, 13), (3, 7), (6, 4), (6, 13)]
+    );
+}
+
+#[test]
+fn rename_in_comments_test() {
+    use lsp_types::{TextDocumentPositionParams, request::Rename};
+
+    use crate::{server::settings::Settings, test_support::TestClient};
+
+    let source = r#"# How fast the box moves, speed_up doubles it
+speed: f32 = 1.0
+
+on_tick() {
+    # Slow down, the speed is too high
+    spe$0ed = speed / 2.0 # speed/speeds
+}
+
+# Adds to the speed
+helper_speed_up(speed: f32) {
+    # speed is the parameter here
+}
+"#;
+
+    let rename = |rename_in_comments: bool| {
+        let mut client = TestClient::with_settings(Settings {
+            rename_in_comments,
+            ..Default::default()
+        });
+        client.open("tired-box.grug", source);
+
+        let edit = client
+            .expect::<Rename>(RenameParams {
+                text_document_position: TextDocumentPositionParams {
+                    text_document: client.cursor("tired-box.grug").text_document,
+                    position: client.cursor("tired-box.grug").position,
+                },
+                new_name: "velocity".to_string(),
+                work_done_progress_params: Default::default(),
+            })
+            .unwrap();
+
+        let mut positions: Vec<(u32, u32)> = edit
+            .changes
+            .unwrap()
+            .into_values()
+            .flatten()
+            .map(|edit| (edit.range.start.line, edit.range.start.character))
+            .collect();
+        positions.sort();
+        positions
+    };
+
+    assert_eq!(rename(false), [(1, 0), (5, 4), (5, 12)]);
+    assert_eq!(
+        rename(true),
+        [(1, 0), (4, 21), (5, 4), (5, 12), (5, 26), (8, 14)]
+    );
+}
@@ -0,0 +1,272 @@
+use std::collections::HashSet;
+
+use lsp_types::{
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens, SemanticTokensLegend,
+    SemanticTokensParams,
+};
+use tree_sitter::Node;
+
+use crate::server::{
+    Server,
+    document::Document,
+    mod_api::ModApi,
+    utils::{RequestResult, get_descendants_of_kind, get_spot_info, is_function_call, ok},
+};
+
+// The indices of these are what the tokens refer to
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::TYPE,
+];
+const FUNCTION: u32 = 0;
+const VARIABLE: u32 = 1;
+const PARAMETER: u32 = 2;
+const TYPE: u32 = 3;
+
+// Bits of token_modifiers_bitset, in the same order
+const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::READONLY,
+    SemanticTokenModifier::DEPRECATED,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+];
+const DECLARATION: u32 = 1 << 0;
+const READONLY: u32 = 1 << 1;
+const DEPRECATED: u32 = 1 << 2;
+const DEFAULT_LIBRARY: u32 = 1 << 3;
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+fn is_declaration(node: &Node) -> bool {
+    node.parent().is_some_and(|parent| {
+        matches!(
+            parent.kind(),
+            "variable_declaration" | "function_parameter" | "function_declaration"
+        ) && parent
+            .child_by_field_name("name")
+            .is_some_and(|name| name.id() == node.id())
+    })
+}
+
+// Globals that no function ever assigns to again
+fn readonly_globals(document: &Document) -> HashSet<&[u8]> {
+    let assigned: HashSet<&[u8]> =
+        get_descendants_of_kind(&document.tree.root_node(), "assignment")
+            .iter()
+            .filter_map(|assignment| assignment.child_by_field_name("name"))
+            .map(|name| &document.content[name.byte_range()])
+            .collect();
+
+    document
+        .global_vars
+        .iter()
+        .map(|global| global.name.as_bytes())
+        .filter(|name| !assigned.contains(name))
+        .collect()
+}
+
+// The type and modifiers of an identifier, None for names that don't resolve to anything
+fn classify_identifier(
+    document: &Document,
+    mod_api: &ModApi,
+    readonly: &HashSet<&[u8]>,
+    node: &Node,
+) -> Option<(u32, u32)> {
+    let name = &document.content[node.byte_range()];
+
+    if is_function_call(node) {
+        let name = std::str::from_utf8(name).ok()?;
+        let game_function = mod_api.game_functions.get(name)?;
+        let deprecated = if game_function.deprecated {
+            DEPRECATED
+        } else {
+            0
+        };
+
+        return Some((FUNCTION, DEFAULT_LIBRARY | deprecated));
+    }
+
+    let declaration = if is_declaration(node) { DECLARATION } else { 0 };
+
+    // The globals come first, then the scopes from the innermost one out
+    let variables = get_spot_info(document, node).variables;
+    let (globals, locals) = variables.split_at(document.global_vars.len());
+    let variable = locals
+        .iter()
+        .chain(globals)
+        .find(|var| var.name.as_bytes() == name)?;
+    let is_global = document
+        .global_vars
+        .iter()
+        .any(|global| global.range == variable.range);
+    let declared_by = document
+        .tree
+        .root_node()
+        .descendant_for_byte_range(variable.range.start_byte, variable.range.end_byte)?;
+
+    if declared_by.kind() == "function_parameter" {
+        Some((PARAMETER, declaration))
+    } else if is_global && readonly.contains(name) {
+        Some((VARIABLE, declaration | READONLY))
+    } else {
+        Some((VARIABLE, declaration))
+    }
+}
+
+pub fn semantic_tokens(document: &Document, mod_api: &ModApi) -> Vec<SemanticToken> {
+    let readonly = readonly_globals(document);
+
+    let mut tokens = Vec::new();
+    let mut last = tree_sitter::Point { row: 0, column: 0 };
+
+    let mut cursor = document.tree.root_node().walk();
+    let mut stack = vec![document.tree.root_node()];
+    while let Some(node) = stack.pop() {
+        let token = match node.kind() {
+            "on_identifier" | "helper_identifier" => {
+                let declaration = if is_declaration(&node) {
+                    DECLARATION
+                } else {
+                    0
+                };
+                Some((FUNCTION, declaration))
+            }
+            "identifier" => classify_identifier(document, mod_api, &readonly, &node),
+            "type" => Some((TYPE, 0)),
+            _ => None,
+        };
+
+        match token {
+            // Tokens can't span lines, and none of these should
+            Some((token_type, modifiers))
+                if node.start_position().row == node.end_position().row =>
+            {
+                let start = node.start_position();
+                let delta_line = start.row - last.row;
+                let delta_start = if delta_line == 0 {
+                    start.column - last.column
+                } else {
+                    start.column
+                };
+
+                tokens.push(SemanticToken {
+                    delta_line: delta_line as u32,
+                    delta_start: delta_start as u32,
+                    length: (node.end_byte() - node.start_byte()) as u32,
+                    token_type,
+                    token_modifiers_bitset: modifiers,
+                });
+                last = start;
+            }
+            Some(_) => {}
+            // Children go on the stack backwards, so they come off in the order they are in the source
+            None => {
+                let children: Vec<Node> = node.children(&mut cursor).collect();
+                stack.extend(children.into_iter().rev());
+            }
+        }
+    }
+
+    tokens
+}
+
+impl Server {
+    pub fn handle_semantic_tokens(&self, params: SemanticTokensParams) -> RequestResult {
+        let tokens = self
+            .get_document_by_uri(&params.text_document.uri)
+            .filter(|document| !self.is_large_file(&document.content))
+            .map(|document| semantic_tokens(document, &self.mod_api));
+
+        ok(tokens.map(|data| SemanticTokens {
+            result_id: None,
+            data,
+        }))
+    }
+}
+
+#[test]
+fn semantic_tokens_test() {
+    use std::str::FromStr;
+
+    let source = r#"speed: f32 = 1.0
+count: i32 = 0
+
+on_spawn() {
+    count = helper_add(count, 2)
+    print_f32(speed)
+}
+
+helper_add(a: i32, b: i32) i32 {
+    return a + b
+}
+"#;
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    let document = Document::new(
+        &mut parser,
+        source.as_bytes().to_vec(),
+        "tired-box.grug".to_string(),
+        lsp_types::Uri::from_str("some_uri").unwrap(),
+    )
+    .unwrap();
+
+    let mod_api = ModApi::from_json(
+        r#"{"entities": {}, "game_functions": {"print_f32": {"deprecated": true, "arguments": [{"name": "f", "type": "f32"}]}}}"#,
+    )
+    .unwrap();
+
+    // Turns the deltas back into (line, column, length, type, modifiers)
+    let mut line = 0;
+    let mut column = 0;
+    let tokens: Vec<(u32, u32, u32, u32, u32)> = semantic_tokens(&document, &mod_api)
+        .into_iter()
+        .map(|token| {
+            if token.delta_line > 0 {
+                column = 0;
+            }
+            line += token.delta_line;
+            column += token.delta_start;
+            (
+                line,
+                column,
+                token.length,
+                token.token_type,
+                token.token_modifiers_bitset,
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        tokens,
+        vec![
+            (0, 0, 5, VARIABLE, DECLARATION | READONLY),
+            (0, 7, 3, TYPE, 0),
+            (1, 0, 5, VARIABLE, DECLARATION),
+            (1, 7, 3, TYPE, 0),
+            (3, 0, 8, FUNCTION, DECLARATION),
+            (4, 4, 5, VARIABLE, 0),
+            (4, 12, 10, FUNCTION, 0),
+            (4, 23, 5, VARIABLE, 0),
+            (5, 4, 9, FUNCTION, DEFAULT_LIBRARY | DEPRECATED),
+            (5, 14, 5, VARIABLE, READONLY),
+            (8, 0, 10, FUNCTION, DECLARATION),
+            (8, 11, 1, PARAMETER, DECLARATION),
+            (8, 14, 3, TYPE, 0),
+            (8, 19, 1, PARAMETER, DECLARATION),
+            (8, 22, 3, TYPE, 0),
+            (8, 27, 3, TYPE, 0),
+            (9, 11, 1, PARAMETER, 0),
+            (9, 15, 1, PARAMETER, 0),
+        ]
+    );
+}
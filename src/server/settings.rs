@@ -0,0 +1,159 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use log::LevelFilter;
+use serde::Deserialize;
+
+use crate::server::lint::LintLevel;
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum Feature {
+    Hover,
+    Completion,
+    Definition,
+    Implementation,
+    Rename,
+    Formatting,
+    References,
+    DocumentHighlight,
+    SemanticTokens,
+    CodeActions,
+    SignatureHelp,
+    InlayHints,
+    WorkspaceSymbols,
+    DocumentLinks,
+    CodeLens,
+}
+
+// What the formatter does with parentheses the precedence doesn't need
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum Parentheses {
+    Keep,
+    Minimal,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    // Relative paths are resolved from the workspace root
+    pub mod_api_path: PathBuf,
+    pub log_level: String,
+    // The log file is moved aside once it grows past this many bytes
+    pub log_max_size: u64,
+    // How many of the moved aside log files are kept around
+    pub log_max_files: usize,
+    // Spaces per indentation level in formatted code
+    pub formatter_width: usize,
+    // Leaves comments like `#----` or `# /\_/\` as they are, instead of spacing them like the others
+    pub formatter_keep_banners: bool,
+    pub formatter_parentheses: Parentheses,
+    pub disabled_features: HashSet<Feature>,
+    // Overrides the default level of lint rules, keyed by their code
+    pub lints: HashMap<String, LintLevel>,
+    // Bytes of source from closed documents that stay parsed in memory
+    pub document_cache_size: usize,
+    // Milliseconds to wait after the last edit before checking the file again
+    pub diagnostics_delay: u64,
+    // Sends grug-ls/syntaxTreeChanged after every edit
+    pub syntax_tree_notifications: bool,
+    // Extra snippets keyed by their label, replacing the built-in ones with the same label
+    pub snippets: HashMap<String, UserSnippet>,
+    // Numbers in function bodies further from zero than this are flagged by magic-number
+    pub magic_number_threshold: f64,
+    // Files past this many bytes or lines skip semantic tokens, formatting and workspace symbols
+    pub large_file_size: usize,
+    pub large_file_lines: usize,
+    // Leaves out the parameter name hint when the argument is already named like the parameter
+    pub hide_matching_parameter_hints: bool,
+    // Globs relative to the workspace root of files and directories that aren't indexed
+    pub exclude: Vec<String>,
+    // Leaves out what .gitignore and .ignore files in the workspace ignore
+    pub respect_ignore_files: bool,
+    // Milliseconds a request gets before it's cut short or fails
+    pub request_timeout: u64,
+    // Renaming a variable or helper also renames it where comments mention it by name
+    pub rename_in_comments: bool,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+pub struct UserSnippet {
+    // In the LSP snippet syntax, like `if ${1:condition} {\n\t$0\n}`
+    pub body: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            mod_api_path: PathBuf::from("mod_api.json"),
+            log_level: "INFO".to_string(),
+            log_max_size: 10 * 1024 * 1024,
+            log_max_files: 3,
+            formatter_width: 4,
+            formatter_keep_banners: true,
+            formatter_parentheses: Parentheses::Keep,
+            disabled_features: HashSet::new(),
+            lints: HashMap::new(),
+            document_cache_size: 32 * 1024 * 1024,
+            diagnostics_delay: 150,
+            syntax_tree_notifications: false,
+            snippets: HashMap::new(),
+            magic_number_threshold: 1.0,
+            large_file_size: 1024 * 1024,
+            large_file_lines: 20_000,
+            hide_matching_parameter_hints: true,
+            exclude: Vec::new(),
+            respect_ignore_files: true,
+            request_timeout: 2000,
+            rename_in_comments: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_initialization_options(options: Option<serde_json::Value>) -> Settings {
+        let Some(options) = options else {
+            return Settings::default();
+        };
+
+        match serde_json::from_value(options) {
+            Ok(settings) => settings,
+            Err(err) => {
+                log::error!("Invalid initializationOptions, using the defaults: {}", err);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        !self.disabled_features.contains(&feature)
+    }
+
+    pub fn get_log_level(&self) -> LevelFilter {
+        self.log_level.parse().unwrap_or(LevelFilter::Info)
+    }
+}
+
+#[test]
+fn settings_test() {
+    let options = serde_json::json!({
+        "modApiPath": "game/mod_api.json",
+        "logLevel": "debug",
+        "disabledFeatures": ["hover", "formatting"],
+        "lints": { "syntax-error": "warning" },
+    });
+
+    let settings = Settings::from_initialization_options(Some(options));
+
+    assert_eq!(settings.mod_api_path, PathBuf::from("game/mod_api.json"));
+    assert_eq!(settings.get_log_level(), LevelFilter::Debug);
+    assert_eq!(settings.formatter_width, 4);
+    assert!(!settings.is_enabled(Feature::Hover));
+    assert!(settings.is_enabled(Feature::Completion));
+    assert_eq!(settings.lints["syntax-error"], LintLevel::Warning);
+}
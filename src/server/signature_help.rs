@@ -0,0 +1,324 @@
+use lsp_types::{
+    Documentation, ParameterInformation, ParameterLabel, SignatureHelp, SignatureHelpParams,
+    SignatureInformation,
+};
+
+use crate::server::{
+    Server,
+    document::{Document, Type},
+    mod_api::{GrugArgument, ModApi},
+    utils::{RequestResult, doc_comment, ok, position_to_byte},
+};
+
+pub const TRIGGER_CHARACTERS: &[&str] = &["("];
+pub const RETRIGGER_CHARACTERS: &[&str] = &[","];
+
+// A `(` that hasn't been closed yet, with the name right before it if there is one
+struct OpenParen<'a> {
+    name: Option<&'a [u8]>,
+    commas: u32,
+}
+
+// The unclosed calls around the end of the source, innermost last.
+// Works on the text, since the call being typed usually doesn't parse yet
+pub fn open_calls(source: &[u8]) -> Vec<(&[u8], u32)> {
+    let mut stack: Vec<OpenParen> = Vec::new();
+    let mut idx = 0;
+
+    while idx < source.len() {
+        match source[idx] {
+            b'"' => {
+                idx += 1;
+                while idx < source.len() && !matches!(source[idx], b'"' | b'\n') {
+                    idx += 1;
+                }
+            }
+            b'#' => {
+                while idx < source.len() && source[idx] != b'\n' {
+                    idx += 1;
+                }
+            }
+            b'(' => {
+                let before = source[..idx].trim_ascii_end();
+                let start = before
+                    .iter()
+                    .rposition(|chr| !(chr.is_ascii_alphanumeric() || *chr == b'_'))
+                    .map_or(0, |start| start + 1);
+
+                stack.push(OpenParen {
+                    name: (start < before.len()).then(|| &before[start..]),
+                    commas: 0,
+                });
+            }
+            b')' => {
+                stack.pop();
+            }
+            b',' => {
+                if let Some(paren) = stack.last_mut() {
+                    paren.commas += 1;
+                }
+            }
+            _ => {}
+        }
+
+        idx += 1;
+    }
+
+    // Parentheses around an expression belong to the call they are in
+    stack
+        .into_iter()
+        .filter_map(|paren| Some((paren.name?, paren.commas)))
+        .collect()
+}
+
+// The same text as GrugGameFunction::format and Function::format, keeping track of where each argument ends up
+fn signature(
+    name: &str,
+    arguments: &[(&str, Type)],
+    return_type: Option<&Type>,
+    description: Option<String>,
+) -> SignatureInformation {
+    let mut label = format!("{}(", name);
+    let mut parameters = Vec::new();
+    for (idx, (argument, r#type)) in arguments.iter().enumerate() {
+        if idx > 0 {
+            label.push_str(", ");
+        }
+
+        let start = label.encode_utf16().count() as u32;
+        label.push_str(&format!("{}: {}", argument, r#type.as_str()));
+        let end = label.encode_utf16().count() as u32;
+
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, end]),
+            documentation: None,
+        });
+    }
+    label.push(')');
+
+    if let Some(return_type) = return_type {
+        label.push(' ');
+        label.push_str(return_type.as_str());
+    }
+
+    SignatureInformation {
+        label,
+        documentation: description.map(Documentation::String),
+        parameters: Some(parameters),
+        active_parameter: None,
+    }
+}
+
+// on_ functions can't be called, so an open one is the parameter list of its declaration
+fn on_function_signature(
+    mod_api: &ModApi,
+    entity_type: &str,
+    name: &str,
+) -> Option<SignatureInformation> {
+    let on_function = mod_api.entities.get(entity_type)?.on_functions.get(name)?;
+
+    Some(signature(
+        name,
+        &mod_api_arguments(&on_function.arguments),
+        None,
+        Some(on_function.description.clone()),
+    ))
+}
+
+fn mod_api_arguments(arguments: &[GrugArgument]) -> Vec<(&str, Type)> {
+    arguments
+        .iter()
+        .map(|argument| (argument.get_name(), argument.get_type()))
+        .collect()
+}
+
+// Helpers are documented by the comments right above them
+fn helper_signature(document: &Document, name: &str) -> Option<SignatureInformation> {
+    let helper = document.get_helper(name.as_bytes())?;
+    let declaration = document
+        .tree
+        .root_node()
+        .descendant_for_byte_range(helper.range.start_byte, helper.range.end_byte)?;
+
+    let arguments: Vec<(&str, Type)> = helper
+        .params
+        .iter()
+        .map(|param| (param.name.as_str(), param.r#type.clone()))
+        .collect();
+
+    Some(signature(
+        name,
+        &arguments,
+        helper.ret_type.as_ref(),
+        doc_comment(&document.content, &declaration),
+    ))
+}
+
+// The innermost call before the cursor that the mod API or the file knows about, and which argument the cursor is in
+fn signature_help(
+    mod_api: &ModApi,
+    entity_type: &str,
+    source: &[u8],
+    helper_signature: impl Fn(&str) -> Option<SignatureInformation>,
+) -> Option<SignatureHelp> {
+    open_calls(source)
+        .into_iter()
+        .rev()
+        .find_map(|(name, commas)| {
+            let name = std::str::from_utf8(name).ok()?;
+            let signature = if name.starts_with("on_") {
+                on_function_signature(mod_api, entity_type, name)?
+            } else if name.starts_with("helper_") {
+                helper_signature(name)?
+            } else {
+                let game_function = mod_api.game_functions.get(name)?;
+                signature(
+                    name,
+                    &mod_api_arguments(&game_function.arguments),
+                    game_function
+                        .return_type
+                        .as_ref()
+                        .map(|return_type| return_type.as_type())
+                        .as_ref(),
+                    Some(game_function.description.clone()),
+                )
+            };
+
+            Some(SignatureHelp {
+                signatures: vec![signature],
+                active_signature: Some(0),
+                active_parameter: Some(commas),
+            })
+        })
+}
+
+impl Server {
+    pub fn handle_signature_help(&self, params: SignatureHelpParams) -> RequestResult {
+        let position = params.text_document_position_params;
+        let Some(document) = self.get_document_by_uri(&position.text_document.uri) else {
+            return ok(None::<SignatureHelp>);
+        };
+
+        let cursor = position_to_byte(&document.content, position.position);
+        let mut help = signature_help(
+            &self.mod_api,
+            &document.entity_type,
+            &document.content[..cursor],
+            |name| helper_signature(document, name),
+        );
+
+        // While the popup is open, stay on the signature the user was looking at if it's still there
+        if let Some(context) = params.context
+            && context.is_retrigger
+            && let Some(previous) = context.active_signature_help
+            && let Some(help) = help.as_mut()
+        {
+            let active = previous
+                .active_signature
+                .and_then(|idx| previous.signatures.get(idx as usize));
+            if let Some(idx) = active.and_then(|active| {
+                help.signatures
+                    .iter()
+                    .position(|signature| signature.label == active.label)
+            }) {
+                help.active_signature = Some(idx as u32);
+            }
+        }
+
+        ok(help)
+    }
+}
+
+#[test]
+fn signature_help_test() {
+    let mod_api = ModApi::from_json(
+        r#"{"entities": {"gun": {"on_functions": {
+            "on_fire": {"description": "Called when the gun fires", "arguments": [{"name": "spread", "type": "f32"}, {"name": "target", "type": "id"}]}
+        }}}, "game_functions": {
+            "set_gun_name": {"description": "Names the gun", "arguments": [{"name": "name", "type": "string"}]},
+            "spawn_box": {"arguments": [{"name": "x", "type": "f32"}, {"name": "y", "type": "f32"}], "return_type": "id"}
+        }}"#,
+    )
+    .unwrap();
+
+    let help = |source: &str| {
+        signature_help(&mod_api, "gun", source.as_bytes(), |_| None)
+            .map(|help| (help.signatures[0].label.clone(), help.active_parameter))
+    };
+
+    assert_eq!(
+        help("on_spawn() {\n    spawn_box("),
+        Some(("spawn_box(x: f32, y: f32) id".to_string(), Some(0)))
+    );
+    assert_eq!(
+        help("on_spawn() {\n    spawn_box(1.0 + (2.0 * 3.0), "),
+        Some(("spawn_box(x: f32, y: f32) id".to_string(), Some(1)))
+    );
+    // Commas and parentheses in strings and comments don't count
+    assert_eq!(
+        help("on_spawn() {\n    # spawn_box(\n    set_gun_name(\"a, (b\""),
+        Some(("set_gun_name(name: string)".to_string(), Some(0)))
+    );
+    // Unknown calls fall through to the call they are an argument of
+    assert_eq!(
+        help("on_spawn() {\n    spawn_box(1.0, helper_y("),
+        Some(("spawn_box(x: f32, y: f32) id".to_string(), Some(1)))
+    );
+    assert_eq!(help("on_spawn() {\n    spawn_box(1.0, 2.0)\n"), None);
+    // The parameter list of an on_ function declaration
+    assert_eq!(
+        help("on_fire(spread: f32, "),
+        Some(("on_fire(spread: f32, target: id)".to_string(), Some(1)))
+    );
+    assert_eq!(help("on_tick("), None);
+
+    let signature = &signature_help(&mod_api, "gun", b"spawn_box(", |_| None)
+        .unwrap()
+        .signatures[0];
+    let offsets: Vec<ParameterLabel> = signature
+        .parameters
+        .iter()
+        .flatten()
+        .map(|parameter| parameter.label.clone())
+        .collect();
+    assert_eq!(
+        offsets,
+        [
+            ParameterLabel::LabelOffsets([10, 16]),
+            ParameterLabel::LabelOffsets([18, 24])
+        ]
+    );
+}
+
+#[test]
+fn helper_signature_help_test() {
+    use lsp_types::{TextDocumentPositionParams, request::SignatureHelpRequest};
+
+    use crate::test_support::TestClient;
+
+    let mut client = TestClient::new();
+    client.open(
+        "tired-box.grug",
+        "on_spawn() {\n    helper_move(1.0, $0)\n}\n\n# Moves the box\n# by the offset\nhelper_move(x: f32, y: f32) bool {\n    return true\n}\n",
+    );
+
+    let help = client
+        .expect::<SignatureHelpRequest>(SignatureHelpParams {
+            context: None,
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: client.cursor("tired-box.grug").text_document,
+                position: client.cursor("tired-box.grug").position,
+            },
+            work_done_progress_params: Default::default(),
+        })
+        .unwrap();
+
+    assert_eq!(help.active_parameter, Some(1));
+    assert_eq!(help.signatures[0].label, "helper_move(x: f32, y: f32) bool");
+    assert_eq!(
+        help.signatures[0].documentation,
+        Some(Documentation::String(
+            "Moves the box\nby the offset".to_string()
+        ))
+    );
+}
@@ -0,0 +1,98 @@
+use std::{cell::RefCell, time::Instant};
+
+use lsp_server::RequestId;
+use serde_json::Value;
+
+use log::debug;
+
+// The message the server is handling, every log line written meanwhile gets its fields
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub method: String,
+    pub request_id: Option<String>,
+    // The path of the document the message is about, if it names one
+    pub document: Option<String>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Span>> = const { RefCell::new(None) };
+}
+
+impl Span {
+    pub fn new(method: &str, request_id: Option<&RequestId>, params: &Value) -> Span {
+        let document = params
+            .pointer("/textDocument/uri")
+            .and_then(|uri| uri.as_str())
+            .map(|uri| uri.strip_prefix("file://").unwrap_or(uri).to_string());
+
+        Span {
+            method: method.to_string(),
+            request_id: request_id.map(|id| id.to_string()),
+            document,
+        }
+    }
+
+    // The span stays current until the guard is dropped, which logs how long it took
+    pub fn enter(self) -> SpanGuard {
+        let previous = CURRENT.with(|current| current.replace(Some(self)));
+
+        SpanGuard {
+            previous,
+            started: Instant::now(),
+        }
+    }
+}
+
+pub struct SpanGuard {
+    previous: Option<Span>,
+    started: Instant,
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        debug!("Handled in {:?}", self.started.elapsed());
+        CURRENT.with(|current| current.replace(self.previous.take()));
+    }
+}
+
+pub fn with_current_span<R>(f: impl FnOnce(Option<&Span>) -> R) -> R {
+    // A log line written while the span is being replaced goes without it
+    CURRENT.with(|current| match current.try_borrow() {
+        Ok(span) => f(span.as_ref()),
+        Err(_) => f(None),
+    })
+}
+
+#[test]
+fn span_test() {
+    use serde_json::json;
+
+    let params = json!({"textDocument": {"uri": "file:///mods/foo/box-crate.grug"}});
+    let outer = Span::new("textDocument/hover", Some(&RequestId::from(3)), &params).enter();
+    {
+        let _inner = Span::new("textDocument/didSave", None, &Value::Null).enter();
+        with_current_span(|span| {
+            assert_eq!(
+                span,
+                Some(&Span {
+                    method: "textDocument/didSave".to_string(),
+                    request_id: None,
+                    document: None,
+                })
+            );
+        });
+    }
+
+    with_current_span(|span| {
+        assert_eq!(
+            span,
+            Some(&Span {
+                method: "textDocument/hover".to_string(),
+                request_id: Some("3".to_string()),
+                document: Some("/mods/foo/box-crate.grug".to_string()),
+            })
+        );
+    });
+    drop(outer);
+    with_current_span(|span| assert_eq!(span, None));
+}
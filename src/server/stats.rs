@@ -0,0 +1,167 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    time::Duration,
+};
+
+use lsp_types::request::Request;
+use serde::{Deserialize, Serialize};
+
+use crate::server::{
+    Server,
+    utils::{RequestResult, ok},
+};
+
+// How many of the latest latencies are kept per method for the percentiles
+const LATENCY_SAMPLES: usize = 1024;
+
+// grug-ls/stats, for figuring out why the server is slow
+pub enum StatsRequest {}
+
+impl Request for StatsRequest {
+    type Params = ();
+    type Result = Stats;
+    const METHOD: &'static str = "grug-ls/stats";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+    pub open_documents: usize,
+    // Every grug file in the workspace, whether it's in memory or not
+    pub indexed_files: usize,
+    pub resident_documents: usize,
+    pub evicted_documents: usize,
+    pub mod_api: ModApiStats,
+    pub memory: MemoryStats,
+    // Keyed by method, sorted so the output is stable
+    pub messages: BTreeMap<String, MethodStats>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModApiStats {
+    pub entities: usize,
+    pub on_functions: usize,
+    pub game_functions: usize,
+}
+
+// Estimates, the parse trees are guessed from the size of the source
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStats {
+    pub document_bytes: usize,
+    pub unparsed_bytes: usize,
+    pub estimated_total_bytes: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodStats {
+    pub count: u64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct MessageStats {
+    counts: HashMap<String, u64>,
+    latencies: HashMap<String, VecDeque<Duration>>,
+}
+
+impl MessageStats {
+    pub fn record(&mut self, method: &str, elapsed: Duration) {
+        *self.counts.entry(method.to_string()).or_default() += 1;
+
+        let latencies = self.latencies.entry(method.to_string()).or_default();
+        if latencies.len() == LATENCY_SAMPLES {
+            latencies.pop_front();
+        }
+        latencies.push_back(elapsed);
+    }
+
+    pub fn summarize(&self) -> BTreeMap<String, MethodStats> {
+        self.counts
+            .iter()
+            .map(|(method, count)| {
+                let mut latencies: Vec<f64> = self.latencies[method]
+                    .iter()
+                    .map(|latency| latency.as_secs_f64() * 1000.0)
+                    .collect();
+                latencies.sort_by(f64::total_cmp);
+
+                let percentile = |p: f64| {
+                    let idx = ((latencies.len() - 1) as f64 * p).round() as usize;
+                    latencies[idx]
+                };
+
+                let stats = MethodStats {
+                    count: *count,
+                    p50_ms: percentile(0.5),
+                    p90_ms: percentile(0.9),
+                    p99_ms: percentile(0.99),
+                    max_ms: *latencies.last().unwrap(),
+                };
+                (method.clone(), stats)
+            })
+            .collect()
+    }
+}
+
+impl Server {
+    pub fn stats(&self) -> Stats {
+        let document_bytes: usize = self
+            .document_map
+            .values()
+            .map(|document| document.content.len())
+            .sum();
+        let unparsed_bytes: usize = self.unparsed_changes.values().map(|text| text.len()).sum();
+
+        Stats {
+            open_documents: self.open_documents.len(),
+            indexed_files: self.document_paths().len(),
+            resident_documents: self.document_map.len(),
+            evicted_documents: self.evicted_documents.len(),
+            mod_api: ModApiStats {
+                entities: self.mod_api.entities.len(),
+                on_functions: self
+                    .mod_api
+                    .entities
+                    .values()
+                    .map(|entity| entity.on_functions.len())
+                    .sum(),
+                game_functions: self.mod_api.game_functions.len(),
+            },
+            memory: MemoryStats {
+                document_bytes,
+                unparsed_bytes,
+                // A syntax tree takes up a few times the size of its source
+                estimated_total_bytes: document_bytes * 4 + unparsed_bytes,
+            },
+            messages: self.message_stats.summarize(),
+        }
+    }
+
+    pub fn handle_stats(&self) -> RequestResult {
+        ok(self.stats())
+    }
+}
+
+#[test]
+fn message_stats_test() {
+    let mut stats = MessageStats::default();
+    for ms in 1..=100 {
+        stats.record("textDocument/hover", Duration::from_millis(ms));
+    }
+    stats.record("textDocument/didOpen", Duration::from_millis(7));
+
+    let summary = stats.summarize();
+    let hover = &summary["textDocument/hover"];
+    assert_eq!(hover.count, 100);
+    assert_eq!(hover.max_ms, 100.0);
+    assert!((hover.p50_ms - 50.0).abs() <= 1.0);
+    assert!((hover.p90_ms - 90.0).abs() <= 1.0);
+
+    assert_eq!(summary["textDocument/didOpen"].p99_ms, 7.0);
+}
@@ -0,0 +1,221 @@
+use lsp_types::{
+    Location, OneOf, SymbolKind, WorkspaceSymbol, WorkspaceSymbolParams, WorkspaceSymbolResponse,
+};
+
+use crate::server::{
+    Server,
+    document::Document,
+    fuzzy::subword_match,
+    utils::{RequestResult, ok, treesitter_range_to_lsp},
+};
+
+fn symbol(
+    name: &str,
+    kind: SymbolKind,
+    container_name: Option<String>,
+    location: Location,
+) -> WorkspaceSymbol {
+    WorkspaceSymbol {
+        name: name.to_string(),
+        kind,
+        tags: None,
+        container_name,
+        location: OneOf::Left(location),
+        data: None,
+    }
+}
+
+fn document_symbols(document: &Document, query: &str) -> Vec<WorkspaceSymbol> {
+    let location = |range: &tree_sitter::Range| Location {
+        uri: document.uri.clone(),
+        range: treesitter_range_to_lsp(range),
+    };
+
+    let globals = document
+        .global_vars
+        .iter()
+        .map(|global| (&global.name, SymbolKind::VARIABLE, &global.range));
+    let functions = document
+        .on_functions
+        .iter()
+        .chain(document.helpers.iter())
+        .map(|function| (&function.name, SymbolKind::FUNCTION, &function.range));
+
+    globals
+        .chain(functions)
+        .filter(|(name, ..)| subword_match(query, name))
+        .map(|(name, kind, range)| symbol(name, kind, None, location(range)))
+        .collect()
+}
+
+impl Server {
+    // Globals, on_ functions and helpers of every grug file, along with everything in the mod API
+    pub fn handle_workspace_symbol(&self, params: WorkspaceSymbolParams) -> RequestResult {
+        let query = params.query.as_str();
+        let mut symbols = self.partial_results(params.partial_result_params.partial_result_token);
+
+        for path in self.document_paths() {
+            // What was found so far is still worth showing
+            if self.past_deadline() {
+                break;
+            }
+
+            let container_name = self.get_entity_reference(std::path::Path::new(&path));
+            let document_symbols = self
+                .with_document(&path, |document| {
+                    if self.is_large_file(&document.content) {
+                        return Vec::new();
+                    }
+                    document_symbols(document, query)
+                })
+                .unwrap_or_default();
+
+            symbols.extend(
+                document_symbols
+                    .into_iter()
+                    .map(|symbol| WorkspaceSymbol {
+                        container_name: container_name.clone(),
+                        ..symbol
+                    })
+                    .collect(),
+            );
+        }
+
+        if let Some(mod_api_uri) = self.mod_api_uri() {
+            let location = |range: &tree_sitter::Range| Location {
+                uri: mod_api_uri.clone(),
+                range: treesitter_range_to_lsp(range),
+            };
+            let container_name = Some("mod API".to_string());
+
+            let entities = self
+                .mod_api
+                .entities
+                .iter()
+                .map(|(name, entity)| (name, SymbolKind::CLASS, &entity.range));
+            let game_functions = self
+                .mod_api
+                .game_functions
+                .iter()
+                .map(|(name, function)| (name, SymbolKind::FUNCTION, &function.range));
+
+            let mut mod_api_symbols: Vec<WorkspaceSymbol> = entities
+                .chain(game_functions)
+                .filter(|(name, ..)| subword_match(query, name))
+                .map(|(name, kind, range)| {
+                    symbol(name, kind, container_name.clone(), location(range))
+                })
+                .collect();
+            // The maps don't keep an order of their own
+            mod_api_symbols.sort_by(|a, b| a.name.cmp(&b.name));
+
+            symbols.extend(mod_api_symbols);
+        }
+
+        ok(WorkspaceSymbolResponse::Nested(symbols.finish()))
+    }
+}
+
+#[test]
+fn workspace_symbol_test() {
+    use crate::test_support::TestClient;
+
+    let mut client = TestClient::new();
+    client.open(
+        "mod_api.json",
+        r#"{
+    "entities": { "bullet": {}, "gun": {} },
+    "game_functions": { "spawn_bullet": {}, "set_gun_name": {} }
+}"#,
+    );
+    client.open(
+        "mods/vanilla/ak47-gun.grug",
+        "ammo: i32 = 30\n\non_spawn() {\n}\n\nhelper_spawn_bullets() {\n}\n",
+    );
+
+    // Flat and nested symbols look the same on the wire, so this reads them as nested ones
+    let mut search = |query: &str| -> Vec<(String, SymbolKind, Option<String>)> {
+        let response = client
+            .request_json("workspace/symbol", serde_json::json!({ "query": query }))
+            .unwrap();
+        let symbols: Vec<WorkspaceSymbol> = serde_json::from_value(response).unwrap();
+
+        symbols
+            .into_iter()
+            .map(|symbol| (symbol.name, symbol.kind, symbol.container_name))
+            .collect()
+    };
+
+    assert_eq!(
+        search("spawnb"),
+        [
+            (
+                "helper_spawn_bullets".to_string(),
+                SymbolKind::FUNCTION,
+                Some("vanilla:ak47".to_string())
+            ),
+            (
+                "spawn_bullet".to_string(),
+                SymbolKind::FUNCTION,
+                Some("mod API".to_string())
+            ),
+        ]
+    );
+    assert_eq!(
+        search("gun"),
+        [
+            (
+                "gun".to_string(),
+                SymbolKind::CLASS,
+                Some("mod API".to_string())
+            ),
+            (
+                "set_gun_name".to_string(),
+                SymbolKind::FUNCTION,
+                Some("mod API".to_string())
+            ),
+        ]
+    );
+    assert_eq!(search("").len(), 7);
+}
+
+#[test]
+fn workspace_symbol_partial_results_test() {
+    use lsp_types::{PartialResultParams, request::Request};
+
+    use crate::test_support::TestClient;
+
+    let mut client = TestClient::new();
+    client.open("mods/vanilla/a-gun.grug", "ammo: i32 = 30\n");
+    client.open("mods/vanilla/b-gun.grug", "spread: f32 = 1.0\n");
+    client.notifications();
+
+    let params = WorkspaceSymbolParams {
+        query: String::new(),
+        work_done_progress_params: Default::default(),
+        partial_result_params: PartialResultParams {
+            partial_result_token: Some(lsp_types::NumberOrString::String("symbols".to_string())),
+        },
+    };
+    let response = client
+        .request_json(
+            lsp_types::request::WorkspaceSymbolRequest::METHOD,
+            serde_json::to_value(params).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(response, serde_json::json!([]));
+
+    // One chunk per file
+    let chunks: Vec<Vec<String>> = client
+        .notifications()
+        .into_iter()
+        .filter(|notification| notification.method == "$/progress")
+        .map(|notification| {
+            assert_eq!(notification.params["token"], "symbols");
+            let symbols: Vec<WorkspaceSymbol> =
+                serde_json::from_value(notification.params["value"].clone()).unwrap();
+            symbols.into_iter().map(|symbol| symbol.name).collect()
+        })
+        .collect();
+    assert_eq!(chunks, [["ammo"], ["spread"]]);
+}
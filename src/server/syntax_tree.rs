@@ -0,0 +1,163 @@
+use std::path::Path;
+
+use lsp_types::{Range, TextDocumentIdentifier, Uri, notification::Notification, request::Request};
+use serde::{Deserialize, Serialize};
+use tree_sitter::TreeCursor;
+
+use crate::server::{
+    Server,
+    utils::{RequestResult, ok, send_notification, treesitter_range_to_lsp},
+};
+
+// grug-ls/syntaxTree, for editor plugins that want to show the parse tree
+pub enum SyntaxTreeRequest {}
+
+impl Request for SyntaxTreeRequest {
+    type Params = SyntaxTreeParams;
+    type Result = Option<SyntaxNode>;
+    const METHOD: &'static str = "grug-ls/syntaxTree";
+}
+
+// Sent after every change to an open document when syntaxTreeNotifications is on
+pub enum SyntaxTreeChanged {}
+
+impl Notification for SyntaxTreeChanged {
+    type Params = SyntaxTreeChangedParams;
+    const METHOD: &'static str = "grug-ls/syntaxTreeChanged";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyntaxTreeParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyntaxTreeChangedParams {
+    pub uri: Uri,
+    pub tree: SyntaxNode,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyntaxNode {
+    pub kind: String,
+    pub named: bool,
+    pub missing: bool,
+    // The field of the parent this node is in, like "name" or "body"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub range: Range,
+    pub children: Vec<SyntaxNode>,
+}
+
+impl SyntaxNode {
+    fn from_cursor(cursor: &mut TreeCursor) -> SyntaxNode {
+        let node = cursor.node();
+        let field = cursor.field_name().map(|field| field.to_string());
+
+        let mut children = Vec::new();
+        if cursor.goto_first_child() {
+            loop {
+                children.push(SyntaxNode::from_cursor(cursor));
+                if !cursor.goto_next_sibling() {
+                    break;
+                }
+            }
+            cursor.goto_parent();
+        }
+
+        SyntaxNode {
+            kind: node.kind().to_string(),
+            named: node.is_named(),
+            missing: node.is_missing(),
+            field,
+            range: treesitter_range_to_lsp(&node.range()),
+            children,
+        }
+    }
+
+    pub fn from_tree(tree: &tree_sitter::Tree) -> SyntaxNode {
+        SyntaxNode::from_cursor(&mut tree.walk())
+    }
+}
+
+// grug-ls parse <file>, prints the same tree as grug-ls/syntaxTree
+pub fn print_syntax_tree(path: &Path) -> bool {
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(err) => {
+            eprintln!("Couldn't read {}: {}", path.display(), err);
+            return false;
+        }
+    };
+
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    let tree = parser.parse(&content, None).unwrap();
+
+    let json = serde_json::to_string_pretty(&SyntaxNode::from_tree(&tree)).unwrap();
+    println!("{}", json);
+    !tree.root_node().has_error()
+}
+
+impl Server {
+    pub fn handle_syntax_tree(&self, params: SyntaxTreeParams) -> RequestResult {
+        let tree = self
+            .get_document_by_uri(&params.text_document.uri)
+            .map(|document| SyntaxNode::from_tree(&document.tree));
+
+        ok(tree)
+    }
+
+    pub fn notify_syntax_tree(&self, path: &str) {
+        if !self.settings.syntax_tree_notifications {
+            return;
+        }
+        let Some(document) = self.document_map.get(path) else {
+            return;
+        };
+
+        send_notification::<SyntaxTreeChanged>(
+            &self.client,
+            SyntaxTreeChangedParams {
+                uri: document.uri.clone(),
+                tree: SyntaxNode::from_tree(&document.tree),
+            },
+        );
+    }
+}
+
+#[test]
+fn syntax_tree_test() {
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let tree = parser.parse("a: i32 = 1\n", None).unwrap();
+    let root = SyntaxNode::from_tree(&tree);
+
+    assert_eq!(root.kind, "source_file");
+    let declaration = &root.children[0];
+    assert_eq!(declaration.kind, "variable_declaration");
+
+    let fields: Vec<(&str, Option<&str>)> = declaration
+        .children
+        .iter()
+        .map(|child| (child.kind.as_str(), child.field.as_deref()))
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            ("identifier", Some("name")),
+            (":", None),
+            ("type", Some("type")),
+            ("=", None),
+            ("number", Some("value")),
+        ]
+    );
+}
@@ -1,77 +1,339 @@
 use std::{
     path::{Path, PathBuf},
     str::FromStr,
+    time::{Duration, Instant},
 };
 
-use lsp_types::{DidChangeTextDocumentParams, DidOpenTextDocumentParams};
+use lsp_types::{
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    PublishDiagnosticsParams, TextDocumentContentChangeEvent, Uri,
+    notification::PublishDiagnostics,
+};
 use tree_sitter::Parser;
-use vfs::FileSystem;
 
-use crate::server::{Server, document::Document};
+use crate::server::{
+    Server,
+    document::Document,
+    helper::{AnalysisJob, AnalysisResult},
+    mod_api::ModApi,
+    utils::{position_to_byte, send_notification, uri_to_path},
+};
 
+use log::debug;
 use log::error;
 use log::info;
 
-impl Server {
-    pub fn handle_did_open(&mut self, params: DidOpenTextDocumentParams, parser: &mut Parser) {
-        let uri = params.text_document.uri.as_str();
-        // We probably wont need to use this server on TCP
-        assert!(uri.starts_with("file://"));
-
-        let path = &uri["file.//".len()..];
-        let path = PathBuf::from_str(path).unwrap();
-        let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
-
-        info!("Opened the file: {:?}", path.to_str().unwrap());
-
-        let paths: Vec<&Path> = path.ancestors().collect();
-        let piece_amt = paths.len();
-
-        for (idx, path) in paths.into_iter().rev().enumerate() {
-            let is_file = idx == piece_amt - 1;
-            let path = path.to_str().unwrap();
-
-            if is_file {
-                if self.file_system.exists(path).unwrap() {
-                    error!("Trying to create file that already exists: {}", path);
-                    break;
-                }
-
-                let mut file = self.file_system.create_file(path).unwrap();
-                file.write(path.as_bytes()).unwrap();
-
-                let document = Document::new(
-                    parser,
-                    params.text_document.text.as_bytes().to_vec(),
-                    file_name,
-                    params.text_document.uri,
-                );
-                info!("New document: {:?}", document);
-                self.document_map.insert(path.to_string(), document);
-                break;
+// Changes without a range replace the whole text, which is all some clients send
+fn apply_changes(text: &mut String, changes: Vec<TextDocumentContentChangeEvent>) {
+    for change in changes {
+        match change.range {
+            Some(range) => {
+                let start = position_to_byte(text.as_bytes(), range.start);
+                let end = position_to_byte(text.as_bytes(), range.end).max(start);
+                text.replace_range(start..end, &change.text);
             }
+            None => *text = change.text,
+        }
+    }
+}
 
-            if !self.file_system.exists(path).unwrap() {
-                self.file_system.create_dir(path).unwrap();
+impl Server {
+    pub fn is_mod_api_path(&self, path: &str) -> bool {
+        self.mod_api_path()
+            .is_some_and(|mod_api_path| Path::new(path) == mod_api_path)
+    }
+
+    // While the mod API is open, its buffer is the source of truth
+    // instead of whatever the watcher reads from disk
+    fn update_mod_api_from_buffer(&mut self, text: &str) {
+        match ModApi::from_json(text) {
+            Some(mod_api) => {
+                info!("Updated mod_api from the editor buffer");
+                self.mod_api = mod_api;
+                self.mod_api_loaded = true;
+                self.publish_all_diagnostics();
+            }
+            None => {
+                error!("Error deserializing the mod_api buffer, keeping the last good one");
             }
         }
     }
 
-    pub fn handle_did_change(&mut self, params: DidChangeTextDocumentParams, parser: &mut Parser) {
-        let uri = params.text_document.uri.as_str();
-        assert!(uri.starts_with("file://"));
+    pub fn handle_did_open(&mut self, params: DidOpenTextDocumentParams) {
+        // Unsaved buffers have no path for the rest of the workspace to find them by
+        let Some(path) = uri_to_path(params.text_document.uri.as_str()) else {
+            return;
+        };
+        self.document_versions
+            .insert(path.to_string(), params.text_document.version);
+        if self.is_mod_api_path(path) {
+            self.update_mod_api_from_buffer(&params.text_document.text);
+            self.mod_api_buffer = Some(params.text_document.text);
+            return;
+        }
+
+        let path = path.to_string();
+        info!("Opened the file");
+        self.warn_about_large_file(&path, params.text_document.text.as_bytes());
+
+        self.open_documents.insert(path.clone());
+        *self.generations.entry(path.clone()).or_default() += 1;
+        self.unparsed_changes
+            .insert(path.clone(), params.text_document.text);
+        self.queue_analysis(&path);
+    }
+
+    pub fn handle_did_change(&mut self, params: DidChangeTextDocumentParams) {
+        let Some(path) = uri_to_path(params.text_document.uri.as_str()) else {
+            return;
+        };
+        self.document_versions
+            .insert(path.to_string(), params.text_document.version);
+        if self.is_mod_api_path(path) {
+            let mut text = self.mod_api_buffer.take().unwrap_or_default();
+            apply_changes(&mut text, params.content_changes);
+            self.update_mod_api_from_buffer(&text);
+            self.mod_api_buffer = Some(text);
+            return;
+        }
+
+        let path = path.to_string();
+        debug!("Updated the file");
+
+        // Edits arrive in bursts while typing, so they pile up until something needs them
+        let mut text = match self.unparsed_changes.remove(&path) {
+            Some(text) => text,
+            None => match self.document_map.get(&path) {
+                Some(document) => String::from_utf8_lossy(&document.content).into_owned(),
+                None => return,
+            },
+        };
+        apply_changes(&mut text, params.content_changes);
+        self.unparsed_changes.insert(path.clone(), text);
+        *self.generations.entry(path.clone()).or_default() += 1;
+
+        let delay = Duration::from_millis(self.settings.diagnostics_delay);
+        self.diagnostics_due.insert(path, Instant::now() + delay);
+    }
+
+    // Parses right away on the main thread, for requests that can't wait for the worker.
+    // Only the given path is parsed, when the request is about a single document
+    pub fn parse_pending_changes(&mut self, parser: &mut Parser, only: Option<&str>) {
+        let paths: Vec<String> = match only {
+            Some(path) if self.unparsed_changes.contains_key(path) => vec![path.to_string()],
+            Some(_) => Vec::new(),
+            None => self.unparsed_changes.keys().cloned().collect(),
+        };
 
-        let path = &uri["file.//".len()..];
-        let file_name = path.split("/").last().unwrap().to_string();
+        for path in paths {
+            let text = self.unparsed_changes.remove(&path).unwrap();
+            let Ok(uri) = Uri::from_str(&format!("file://{}", path)) else {
+                continue;
+            };
+            let file_name = path.split("/").last().unwrap().to_string();
 
-        info!("Updated file: {:?}", path);
+            let Some(document) = Document::new(parser, text.into_bytes(), file_name, uri) else {
+                continue;
+            };
+            self.insert_document(&path, document);
+        }
+    }
 
-        let document = self.document_map.get_mut(path).unwrap();
-        *document = Document::new(
-            parser,
-            params.content_changes[0].text.as_bytes().to_vec(),
-            file_name,
-            params.text_document.uri,
-        );
+    pub fn queue_analysis(&self, path: &str) {
+        let text = match self.unparsed_changes.get(path) {
+            Some(text) => text.as_bytes().to_vec(),
+            None => match self.document_map.get(path) {
+                Some(document) => document.content.clone(),
+                None => return,
+            },
+        };
+        let Ok(uri) = Uri::from_str(&format!("file://{}", path)) else {
+            return;
+        };
+
+        let settings = self.settings_for(path);
+        let job = AnalysisJob {
+            path: path.to_string(),
+            uri,
+            text,
+            generation: self.generations.get(path).copied().unwrap_or_default(),
+            mod_api: self.mod_api.clone(),
+            mod_name: self.get_mod_name(Path::new(path)),
+            entities: self.get_workspace_entities(),
+            lints: settings.lints.clone(),
+            magic_number_threshold: settings.magic_number_threshold,
+        };
+        self.analysis_jobs.send(job).unwrap();
+    }
+
+    pub fn analysis_results(&self) -> crossbeam_channel::Receiver<AnalysisResult> {
+        self.analysis_results.clone()
     }
+
+    // Without threads, the host calls this after handling messages to get results
+    // into analysis_results
+    #[cfg(not(feature = "native"))]
+    pub fn run_analysis_jobs(&mut self) {
+        self.analysis_worker.run_pending();
+    }
+
+    // Results for text that has been edited since are dropped, a newer job is on its way
+    pub fn handle_analysis_result(&mut self, result: AnalysisResult) {
+        if !self.open_documents.contains(&result.path)
+            || self.generations.get(&result.path) != Some(&result.generation)
+        {
+            return;
+        }
+
+        self.unparsed_changes.remove(&result.path);
+        let params =
+            PublishDiagnosticsParams::new(result.document.uri.clone(), result.diagnostics, None);
+        self.insert_document(&result.path, result.document);
+
+        send_notification::<PublishDiagnostics>(&self.client, params);
+        self.notify_syntax_tree(&result.path);
+    }
+
+    // The main loop waits for messages until then
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.diagnostics_due.values().min().copied()
+    }
+
+    pub fn analyze_due_documents(&mut self) {
+        let now = Instant::now();
+        let due: Vec<String> = self
+            .diagnostics_due
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for path in due {
+            self.diagnostics_due.remove(&path);
+            self.queue_analysis(&path);
+        }
+    }
+
+    pub fn handle_did_close(&mut self, params: DidCloseTextDocumentParams, parser: &mut Parser) {
+        let Some(path) = uri_to_path(params.text_document.uri.as_str()) else {
+            return;
+        };
+        self.document_versions.remove(path);
+        if self.is_mod_api_path(path) {
+            // Unsaved edits are gone, so go back to what is on disk
+            self.mod_api_buffer = None;
+            match std::fs::read_to_string(path) {
+                Ok(json) => self.update_mod_api_from_buffer(&json),
+                Err(err) => error!("Couldn't reread {}: {}", path, err),
+            }
+            return;
+        }
+
+        info!("Closed the file");
+        self.open_documents.remove(path);
+        self.diagnostics_due.remove(path);
+        self.unparsed_changes.remove(path);
+        self.generations.remove(path);
+
+        let path = PathBuf::from(path);
+        self.clear_diagnostics(params.text_document.uri);
+        if path.exists() {
+            self.index_file(&path, parser);
+        } else {
+            self.remove_from_index(&path);
+        }
+    }
+}
+
+#[test]
+fn apply_changes_test() {
+    use lsp_types::{Position, Range};
+
+    let change = |start: (u32, u32), end: (u32, u32), text: &str| TextDocumentContentChangeEvent {
+        range: Some(Range::new(
+            Position::new(start.0, start.1),
+            Position::new(end.0, end.1),
+        )),
+        range_length: None,
+        text: text.to_string(),
+    };
+
+    // Columns are bytes, and the emoji takes up four
+    let mut text = "# 🐸 box\non_tick() {\n}\n".to_string();
+    apply_changes(
+        &mut text,
+        vec![
+            change((0, 7), (0, 10), "frog"),
+            change((1, 11), (1, 11), "\n    print_i32(1)"),
+        ],
+    );
+    assert_eq!(text, "# 🐸 frog\non_tick() {\n    print_i32(1)\n}\n");
+
+    // After the é, which is the sixth character but ends at the seventh byte
+    let mut accented = "# café ok".to_string();
+    apply_changes(&mut accented, vec![change((0, 7), (0, 7), "!")]);
+    assert_eq!(accented, "# café! ok");
+
+    apply_changes(
+        &mut text,
+        vec![TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "on_spawn() {\n}\n".to_string(),
+        }],
+    );
+    assert_eq!(text, "on_spawn() {\n}\n");
+
+    // Columns past the end of a line stop before its \r\n
+    let mut text = "on_spawn() {\r\n}\r\n".to_string();
+    apply_changes(
+        &mut text,
+        vec![change((0, 12), (0, 40), "\r\n    print_i32(1)")],
+    );
+    assert_eq!(text, "on_spawn() {\r\n    print_i32(1)\r\n}\r\n");
+}
+
+#[test]
+fn stale_analysis_test() {
+    use lsp_types::{
+        DocumentFormattingParams, FormattingOptions, TextDocumentIdentifier,
+        notification::{Notification, PublishDiagnostics},
+        request::Formatting,
+    };
+
+    use crate::test_support::{TestClient, uri};
+
+    let mut client = TestClient::new();
+    client.open(
+        "mods/foo/crate-box.grug",
+        "on_spawn() {\n    print_i32(\n}\n",
+    );
+    // All of these arrive before the worker's answer for the opened text is handled
+    client.change(
+        "mods/foo/crate-box.grug",
+        "on_spawn() {\n    print_i32(1\n}\n",
+    );
+    client.change("mods/foo/crate-box.grug", "on_spawn() {\nprint_i32(1)\n}\n");
+
+    // Requests can't wait for the worker, so they parse the newest text themselves
+    let edits = client
+        .expect::<Formatting>(DocumentFormattingParams {
+            text_document: TextDocumentIdentifier::new(uri("mods/foo/crate-box.grug")),
+            options: FormattingOptions::default(),
+            work_done_progress_params: Default::default(),
+        })
+        .unwrap();
+    assert!(edits[0].new_text.contains("    print_i32(1)\n"));
+
+    // The results for the broken texts are dropped instead of flashing their errors
+    assert!(client.diagnostics("mods/foo/crate-box.grug").is_empty());
+    let published = client
+        .notifications()
+        .into_iter()
+        .filter(|notification| {
+            notification.method == PublishDiagnostics::METHOD
+                && notification.params["uri"] == uri("mods/foo/crate-box.grug").as_str()
+        })
+        .count();
+    assert_eq!(published, 0);
 }
@@ -0,0 +1,86 @@
+use std::time::{Duration, Instant};
+
+use lsp_server::{ErrorCode, ResponseError};
+
+use crate::server::{Server, utils::request_error};
+
+use log::warn;
+
+impl Server {
+    pub fn start_deadline(&mut self, started: Instant) {
+        self.request_deadline =
+            Some(started + Duration::from_millis(self.settings.request_timeout));
+    }
+
+    pub fn end_deadline(&mut self, method: &str, started: Instant) {
+        self.request_deadline = None;
+
+        let elapsed = started.elapsed();
+        if elapsed > Duration::from_millis(self.settings.request_timeout) {
+            warn!(
+                "{} took {:?}, past the requestTimeout of {}ms",
+                method, elapsed, self.settings.request_timeout
+            );
+        }
+    }
+
+    // Checked by the handlers that go over the whole workspace, which stop early once it's true
+    pub fn past_deadline(&self) -> bool {
+        self.request_deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    // For the requests that can't send back only part of their answer
+    pub fn timed_out(&self, what: &str) -> ResponseError {
+        request_error(
+            ErrorCode::RequestFailed,
+            format!(
+                "{} took longer than the requestTimeout of {}ms",
+                what, self.settings.request_timeout
+            ),
+        )
+    }
+}
+
+#[test]
+fn request_timeout_test() {
+    use lsp_types::{
+        RenameFilesParams, WorkspaceSymbolParams,
+        request::{Request, WillRenameFiles, WorkspaceSymbolRequest},
+    };
+
+    use crate::{
+        server::settings::Settings,
+        test_support::{TestClient, uri},
+    };
+
+    let mut client = TestClient::with_settings(Settings {
+        request_timeout: 0,
+        ..Default::default()
+    });
+    client.open("mods/foo/crate-box.grug", "on_spawn() {\n}\n");
+    client.open("mods/foo/other-box.grug", "a: string = \"foo:crate\"\n");
+
+    // Workspace symbols can be cut short
+    let symbols = client
+        .request_json(
+            WorkspaceSymbolRequest::METHOD,
+            serde_json::to_value(WorkspaceSymbolParams {
+                query: String::new(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .unwrap(),
+        )
+        .unwrap();
+    assert_eq!(symbols, serde_json::json!([]));
+
+    // Renames have to cover every file, or not happen at all
+    let rename = client.request::<WillRenameFiles>(RenameFilesParams {
+        files: vec![lsp_types::FileRename {
+            old_uri: uri("mods/foo/crate-box.grug").to_string(),
+            new_uri: uri("mods/foo/barrel-box.grug").to_string(),
+        }],
+    });
+    assert_eq!(rename.unwrap_err().code, ErrorCode::RequestFailed as i32);
+}
@@ -0,0 +1,61 @@
+use tree_sitter::Node;
+
+use crate::server::{
+    document::{Document, Type},
+    mod_api::ModApi,
+    utils::{get_inner_expression, get_spot_info},
+};
+
+// None means the type can't be known, either because the expression is broken
+// or because it refers to something that isn't declared
+pub fn infer_type(document: &Document, mod_api: &ModApi, node: &Node) -> Option<Type> {
+    let text = &document.content[node.byte_range()];
+
+    match node.kind() {
+        "number" => Some(if text.contains(&b'.') {
+            Type::F32
+        } else {
+            Type::I32
+        }),
+        "string" => Some(Type::String),
+        "me" => Some(Type::ID),
+        "identifier" => match text {
+            b"true" | b"false" => Some(Type::Bool),
+            _ => get_spot_info(document, node)
+                .variables
+                .into_iter()
+                .find(|var| var.name.as_bytes() == text)
+                .map(|var| var.r#type),
+        },
+        "argument" | "contained_expression" => {
+            infer_type(document, mod_api, &get_inner_expression(node)?)
+        }
+        "unary_expression" => match node.child_by_field_name("operator")?.kind() {
+            "not" => Some(Type::Bool),
+            _ => infer_type(document, mod_api, &node.child_by_field_name("operand")?),
+        },
+        "binary_expression" => match node.child_by_field_name("operator")?.kind() {
+            "==" | "!=" | "<" | ">" | "<=" | ">=" | "and" | "or" => Some(Type::Bool),
+            _ => infer_type(document, mod_api, &node.child_by_field_name("left")?),
+        },
+        "function_call" => {
+            let name = node.child_by_field_name("name")?;
+            let name = str::from_utf8(&document.content[name.byte_range()]).ok()?;
+
+            if let Some(game_function) = mod_api.game_functions.get(name) {
+                return game_function
+                    .return_type
+                    .as_ref()
+                    .map(|ret_type| ret_type.as_type());
+            }
+
+            document.get_helper(name.as_bytes())?.ret_type.clone()
+        }
+        _ => None,
+    }
+}
+
+// Entity types aren't tracked precisely enough yet, so they accept anything
+pub fn is_assignable(expected: &Type, actual: &Type) -> bool {
+    matches!(expected, Type::Entity(_)) || expected == actual
+}
@@ -2,9 +2,12 @@
 #[allow(unused)]
 use std::str::FromStr;
 
+use lsp_server::{ErrorCode, Message, Notification, ResponseError};
 use lsp_types::Position;
 use tree_sitter::Node;
 
+use log::debug;
+
 use crate::server::document::{Document, Variable, parser_utils};
 
 #[derive(PartialEq, Eq, Debug)]
@@ -49,7 +52,7 @@ pub fn get_spot_info(document: &Document, node: &tree_sitter::Node) -> SpotInfo
         variables.push(global_var.clone());
     }
 
-    let mut parent = node.clone();
+    let mut parent = *node;
 
     while let Some(next_parent) = parent.parent() {
         if next_parent.kind() == "source_file" {
@@ -129,7 +132,8 @@ on_spawn(str: string) {
         source.as_bytes().to_vec(),
         "tired-box.grug".to_string(),
         lsp_types::Uri::from_str("some_uri").unwrap(),
-    );
+    )
+    .unwrap();
     assert_eq!(document.entity_type, "box");
 
     let spot_info = get_spot_info(&document, &func_call);
@@ -160,6 +164,43 @@ pub fn treesitter_range_to_lsp(range: &tree_sitter::Range) -> lsp_types::Range {
     }
 }
 
+// Where the line starting at line_start ends, before its `\n` or `\r\n`
+pub fn line_end(content: &[u8], line_start: usize) -> usize {
+    match content[line_start..].iter().position(|byte| *byte == b'\n') {
+        Some(idx) if idx > 0 && content[line_start + idx - 1] == b'\r' => line_start + idx - 1,
+        Some(idx) => line_start + idx,
+        None => content.len(),
+    }
+}
+
+// Files keep the line endings they were written with, going by the first one like editors do
+pub fn line_ending(content: &[u8]) -> &'static str {
+    match content.iter().position(|byte| *byte == b'\n') {
+        Some(idx) if idx > 0 && content[idx - 1] == b'\r' => "\r\n",
+        _ => "\n",
+    }
+}
+
+// Columns are byte offsets into the line, like the ones tree-sitter gives out.
+// Columns past the end of the line point at its end, as the spec asks
+pub fn position_to_byte(content: &[u8], position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match content[line_start..].iter().position(|byte| *byte == b'\n') {
+            Some(idx) => line_start += idx + 1,
+            None => return content.len(),
+        }
+    }
+
+    let mut byte = (line_start + position.character as usize).min(line_end(content, line_start));
+    // A column inside a character would split it, so it starts at its first byte instead
+    while byte > line_start && content.get(byte).is_some_and(|next| next & 0xC0 == 0x80) {
+        byte -= 1;
+    }
+
+    byte
+}
+
 pub fn is_function_call(node: &Node) -> bool {
     let Some(parent) = node.parent() else {
         return false;
@@ -167,3 +208,271 @@ pub fn is_function_call(node: &Node) -> bool {
 
     parent.kind() == "function_call"
 }
+
+/// Escapes the characters that have a special meaning inside a snippet placeholder
+pub fn escape_snippet_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for chr in text.chars() {
+        if matches!(chr, '$' | '}' | '\\') {
+            out.push('\\');
+        }
+        out.push(chr);
+    }
+
+    out
+}
+
+// What a snippet inserts when the client can't expand it: placeholders become their text,
+// choices their first option, and tab stops nothing
+pub fn snippet_to_plain_text(snippet: &str) -> String {
+    fn plain_text(
+        chars: &mut std::iter::Peekable<std::str::Chars>,
+        out: &mut String,
+        nested: bool,
+    ) {
+        while let Some(chr) = chars.next() {
+            match chr {
+                '\\' => out.extend(chars.next()),
+                '}' if nested => return,
+                '$' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                    while chars.next_if(char::is_ascii_digit).is_some() {}
+                }
+                '$' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    while chars.next_if(char::is_ascii_digit).is_some() {}
+
+                    match chars.next() {
+                        Some(':') => plain_text(chars, out, true),
+                        Some('|') => {
+                            let mut first = true;
+                            for chr in chars.by_ref() {
+                                match chr {
+                                    '|' => break,
+                                    ',' => first = false,
+                                    chr if first => out.push(chr),
+                                    _ => {}
+                                }
+                            }
+                            chars.next_if_eq(&'}');
+                        }
+                        _ => {}
+                    }
+                }
+                chr => out.push(chr),
+            }
+        }
+    }
+
+    let mut out = String::with_capacity(snippet.len());
+    plain_text(&mut snippet.chars().peekable(), &mut out, false);
+    out
+}
+
+// Where everything the server sends to the editor goes
+pub type Client = crossbeam_channel::Sender<Message>;
+
+// What request handlers answer with, handle_message turns it into the response
+pub type RequestResult = Result<serde_json::Value, ResponseError>;
+
+pub fn ok<T: serde::Serialize>(value: T) -> RequestResult {
+    Ok(serde_json::to_value(value).unwrap())
+}
+
+pub fn request_error(code: ErrorCode, message: String) -> ResponseError {
+    ResponseError {
+        code: code as i32,
+        message,
+        data: None,
+    }
+}
+
+// A buggy client shouldn't be able to take the server down with a malformed message
+pub fn parse_params<P: serde::de::DeserializeOwned>(
+    method: &str,
+    params: &serde_json::Value,
+) -> Result<P, ResponseError> {
+    P::deserialize(params).map_err(|err| {
+        debug!("Malformed params for {}: {}", method, params);
+        request_error(
+            ErrorCode::InvalidParams,
+            format!("Invalid params for {}: {}", method, err),
+        )
+    })
+}
+
+// Only file:// URIs have a path, others like untitled: are buffers the editor never saved
+pub fn uri_to_path(uri: &str) -> Option<&str> {
+    uri.strip_prefix("file://")
+}
+
+// What requests on documents without a path answer with
+pub fn unsupported_uri(uri: &str) -> ResponseError {
+    request_error(
+        ErrorCode::InvalidParams,
+        format!("Only file:// URIs are supported, got {}", uri),
+    )
+}
+
+// Nobody might be listening, like when grug-ls check drives the server
+pub fn send_message(client: &Client, message: Message) {
+    let _ = client.send(message);
+}
+
+pub fn send_notification<N: lsp_types::notification::Notification>(
+    client: &Client,
+    params: N::Params,
+) {
+    let notification = Notification::new(N::METHOD.to_string(), params);
+
+    send_message(client, Message::Notification(notification));
+}
+
+pub fn show_message(client: &Client, typ: lsp_types::MessageType, message: String) {
+    send_notification::<lsp_types::notification::ShowMessage>(
+        client,
+        lsp_types::ShowMessageParams { typ, message },
+    );
+}
+
+pub fn get_descendants_of_kind<'a>(node: &Node<'a>, kind: &str) -> Vec<Node<'a>> {
+    let mut out: Vec<Node<'a>> = Vec::new();
+    let mut stack: Vec<Node<'a>> = vec![*node];
+
+    while let Some(node) = stack.pop() {
+        if node.kind() == kind {
+            out.push(node);
+        }
+
+        let mut cursor = node.walk();
+        let children: Vec<Node<'a>> = node.children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+
+    out
+}
+
+// The expression wrapped by an argument or parentheses. `me` is an anonymous
+// node, so looking at named children alone would skip it
+pub fn get_inner_expression<'a>(node: &Node<'a>) -> Option<Node<'a>> {
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .find(|child| !matches!(child.kind(), "(" | ")"))
+}
+
+// How tightly each kind of expression binds, from the precedences in the grammar
+pub fn precedence(node: &Node) -> u8 {
+    match node.kind() {
+        "binary_expression" => node
+            .child_by_field_name("operator")
+            .map_or(0, |operator| operator_precedence(operator.kind())),
+        "unary_expression" => 7,
+        _ => 8,
+    }
+}
+
+pub fn operator_precedence(operator: &str) -> u8 {
+    match operator {
+        "or" => 1,
+        "and" => 2,
+        "==" | "!=" => 3,
+        ">=" | ">" | "<=" | "<" => 4,
+        "+" | "-" => 5,
+        _ => 6,
+    }
+}
+
+// Parentheses around an expression don't change what it means
+pub fn strip_parentheses<'a>(mut node: Node<'a>) -> Node<'a> {
+    while node.kind() == "contained_expression" {
+        let Some(inner) = get_inner_expression(&node) else {
+            break;
+        };
+        node = inner;
+    }
+
+    node
+}
+
+// Whether text with this precedence has to be put in parentheses to take the place of the node
+pub fn needs_parentheses(node: &Node, precedence: u8) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+
+    match parent.kind() {
+        "binary_expression" => {
+            let Some(operator) = parent.child_by_field_name("operator") else {
+                return false;
+            };
+            let parent_precedence = operator_precedence(operator.kind());
+            // Operators are left associative, so an equal one on the right needs them too
+            let is_right = parent
+                .child_by_field_name("right")
+                .is_some_and(|right| right.id() == node.id());
+
+            precedence < parent_precedence || (precedence == parent_precedence && is_right)
+        }
+        "unary_expression" => precedence < 7,
+        _ => false,
+    }
+}
+
+// The comment lines right above a declaration, without their `#`
+pub fn doc_comment(content: &[u8], declaration: &Node) -> Option<String> {
+    let mut lines: Vec<String> = Vec::new();
+
+    let mut node = *declaration;
+    while let Some(previous) = node.prev_sibling().filter(|previous| {
+        previous.kind() == "comment"
+            && previous.start_position().row + 1 == node.start_position().row
+    }) {
+        let text = String::from_utf8_lossy(&content[previous.byte_range()]);
+        lines.push(text.trim_start_matches('#').trim().to_string());
+        node = previous;
+    }
+
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+    Some(lines.join("\n"))
+}
+
+// The call an argument is passed to, along with which argument it is
+pub fn get_call_argument<'a>(node: &Node<'a>) -> Option<(Node<'a>, usize)> {
+    let argument = if node.kind() == "argument" {
+        *node
+    } else {
+        node.parent().filter(|parent| parent.kind() == "argument")?
+    };
+    let call = argument
+        .parent()
+        .filter(|parent| parent.kind() == "function_call")?;
+
+    let mut cursor = call.walk();
+    let idx = call
+        .children_by_field_name("argument", &mut cursor)
+        .position(|other| other.id() == argument.id())?;
+
+    Some((call, idx))
+}
+
+#[test]
+fn snippet_to_plain_text_test() {
+    assert_eq!(
+        snippet_to_plain_text("spawn_box(${1:1.0}, ${2:y})$0"),
+        "spawn_box(1.0, y)"
+    );
+    assert_eq!(
+        snippet_to_plain_text("if ${1:condition} {\n\t$0\n}"),
+        "if condition {\n\t\n}"
+    );
+    assert_eq!(
+        snippet_to_plain_text("${1|up,down|} ${2:a ${3:nested}} \\$1"),
+        "up a nested $1"
+    );
+    assert_eq!(
+        snippet_to_plain_text(&escape_snippet_text("costs $5 {}")),
+        "costs $5 {}"
+    );
+}
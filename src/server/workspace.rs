@@ -0,0 +1,564 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use lsp_types::Uri;
+use tree_sitter::Parser;
+
+use crate::server::{
+    Server,
+    document::Document,
+    ignore::{IgnoreFile, is_ignored, read_ignore_files},
+};
+
+use log::error;
+use log::info;
+
+// Excluded and ignored directories aren't walked into at all
+pub fn collect_grug_files_except(
+    dir: &Path,
+    is_excluded: &dyn Fn(&Path) -> bool,
+    respect_ignore_files: bool,
+    out: &mut Vec<PathBuf>,
+) {
+    let mut ignore_files: Vec<IgnoreFile> = Vec::new();
+    walk_grug_files(
+        dir,
+        is_excluded,
+        respect_ignore_files.then_some(&mut ignore_files),
+        out,
+    );
+}
+
+// The ignore files of the directories above are on the stack while walking one
+fn walk_grug_files(
+    dir: &Path,
+    is_excluded: &dyn Fn(&Path) -> bool,
+    mut ignore_files: Option<&mut Vec<IgnoreFile>>,
+    out: &mut Vec<PathBuf>,
+) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        error!("Couldn't read directory: {:?}", dir);
+        return;
+    };
+
+    let pushed = match ignore_files.as_deref_mut() {
+        Some(ignore_files) => {
+            let mut new_files = read_ignore_files(dir);
+            let pushed = new_files.len();
+            ignore_files.append(&mut new_files);
+            pushed
+        }
+        None => 0,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if is_excluded(&path)
+            || ignore_files
+                .as_deref()
+                .is_some_and(|ignore_files| is_ignored(ignore_files, &path, file_type.is_dir()))
+        {
+            continue;
+        }
+
+        if file_type.is_dir() {
+            walk_grug_files(&path, is_excluded, ignore_files.as_deref_mut(), out);
+        } else if path.extension().is_some_and(|ext| ext == "grug") {
+            out.push(path);
+        }
+    }
+
+    if let Some(ignore_files) = ignore_files {
+        ignore_files.truncate(ignore_files.len() - pushed);
+    }
+}
+
+// Runs f on every path across all cores, each thread with its own parser.
+// The results are handed to on_result on the calling thread as they come in
+#[cfg(feature = "native")]
+pub fn map_in_parallel<T: Send>(
+    paths: Vec<PathBuf>,
+    f: impl Fn(&mut Parser, &Path) -> T + Sync,
+    mut on_result: impl FnMut(PathBuf, T),
+) {
+    let threads = std::thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1)
+        .min(paths.len().max(1));
+
+    let (path_send, path_recv) = crossbeam_channel::unbounded::<PathBuf>();
+    for path in paths {
+        path_send.send(path).unwrap();
+    }
+    drop(path_send);
+
+    // Bounded, so workers wait instead of piling up parsed files the caller hasn't taken yet
+    let (result_send, result_recv) = crossbeam_channel::bounded::<(PathBuf, T)>(threads * 2);
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            let path_recv = path_recv.clone();
+            let result_send = result_send.clone();
+            let f = &f;
+
+            scope.spawn(move || {
+                let mut parser = Parser::new();
+                parser
+                    .set_language(&tree_sitter_grug::LANGUAGE.into())
+                    .unwrap();
+
+                for path in path_recv {
+                    let result = f(&mut parser, &path);
+                    if result_send.send((path, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(result_send);
+
+        for (path, result) in result_recv {
+            on_result(path, result);
+        }
+    });
+}
+
+// Parses a grug file from disk
+fn read_document(parser: &mut Parser, path: &Path) -> Option<Document> {
+    let path_str = path.to_str()?;
+    let Ok(content) = std::fs::read(path) else {
+        error!("Couldn't read grug file: {}", path_str);
+        return None;
+    };
+    let uri = Uri::from_str(&format!("file://{}", path_str)).ok()?;
+    let file_name = path.file_name()?.to_str()?.to_string();
+
+    info!("Indexing file: {}", path_str);
+    Document::new(parser, content, file_name, uri)
+}
+
+// The entity a script defines is named by everything before the last '-' in its file name
+pub fn get_entity_name(file_name: &str) -> Option<&str> {
+    let stem = file_name.strip_suffix(".grug")?;
+    stem.rsplit_once('-').map(|(name, _)| name)
+}
+
+// The type of entity a script is for comes after the last '-' in its file name
+pub fn get_entity_type(file_name: &str) -> Option<&str> {
+    let stem = file_name.strip_suffix(".grug")?;
+    stem.split('-').next_back()
+}
+
+// An entity defined by a grug file in the workspace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceEntity {
+    // The grug file that defines it
+    pub path: String,
+    pub mod_name: String,
+    pub name: String,
+    pub entity_type: String,
+}
+
+impl WorkspaceEntity {
+    pub fn reference(&self) -> String {
+        format!("{}:{}", self.mod_name, self.name)
+    }
+}
+
+impl Server {
+    // Scripts live in mods/<modname>/..., falling back to the first directory under the root
+    pub fn get_mod_name(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(self.root_path.as_ref()?).ok()?;
+        let dirs: Vec<&str> = relative
+            .parent()?
+            .components()
+            .filter_map(|component| component.as_os_str().to_str())
+            .collect();
+
+        let mod_name = match dirs.iter().position(|dir| *dir == "mods") {
+            Some(idx) => dirs.get(idx + 1)?,
+            None => dirs.first()?,
+        };
+
+        Some(mod_name.to_string())
+    }
+
+    pub fn get_mod_dir(&self, path: &Path) -> Option<PathBuf> {
+        let root = self.root_path.as_ref()?;
+        let relative = path.strip_prefix(root).ok()?;
+        let dirs: Vec<_> = relative.parent()?.components().collect();
+
+        let depth = match dirs.iter().position(|dir| dir.as_os_str() == "mods") {
+            Some(idx) => idx + 2,
+            None => 1,
+        };
+        (dirs.len() >= depth).then(|| root.join(dirs[..depth].iter().collect::<PathBuf>()))
+    }
+
+    // The `modname:entityname` string other scripts use to refer to this file
+    pub fn get_entity_reference(&self, path: &Path) -> Option<String> {
+        let mod_name = self.get_mod_name(path)?;
+        let entity_name = get_entity_name(path.file_name()?.to_str()?)?;
+
+        Some(format!("{}:{}", mod_name, entity_name))
+    }
+
+    pub fn get_workspace_entities(&self) -> Vec<WorkspaceEntity> {
+        self.entities_at(&self.document_paths())
+    }
+
+    // Entities only depend on where their files are, so the files don't need to be read
+    pub fn entities_at<P: AsRef<Path>>(&self, paths: &[P]) -> Vec<WorkspaceEntity> {
+        let mut entities: Vec<WorkspaceEntity> = paths
+            .iter()
+            .filter_map(|path| {
+                let path = path.as_ref();
+                let file_name = path.file_name()?.to_str()?;
+
+                Some(WorkspaceEntity {
+                    path: path.to_str()?.to_string(),
+                    mod_name: self.get_mod_name(path)?,
+                    name: get_entity_name(file_name)?.to_string(),
+                    entity_type: get_entity_type(file_name)?.to_string(),
+                })
+            })
+            .collect();
+        entities.sort_by_key(|entity| entity.reference());
+
+        entities
+    }
+
+    // Entity strings can leave out the mod name when the entity is in the same mod
+    pub fn entity_string_reference(&self, path: &Path, text: &str) -> Option<String> {
+        if text.contains(':') {
+            Some(text.to_string())
+        } else {
+            Some(format!("{}:{}", self.get_mod_name(path)?, text))
+        }
+    }
+
+    pub fn resolve_entity_string(&self, path: &Path, text: &str) -> Option<WorkspaceEntity> {
+        let reference = self.entity_string_reference(path, text)?;
+
+        self.get_workspace_entities()
+            .into_iter()
+            .find(|entity| entity.reference() == reference)
+    }
+
+    // Every grug file the server knows about, including the ones evicted from memory.
+    // Excluded files the editor opened are left out
+    pub fn document_paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self
+            .document_map
+            .keys()
+            .chain(self.evicted_documents.iter())
+            .filter(|path| !self.is_excluded(Path::new(path)))
+            .cloned()
+            .collect();
+        paths.sort();
+
+        paths
+    }
+
+    // Documents that aren't in memory are parsed from disk for the call, without keeping them around
+    pub fn with_document<R>(&self, path: &str, f: impl FnOnce(&Document) -> R) -> Option<R> {
+        if let Some(document) = self.document_map.get(path) {
+            return Some(f(document));
+        }
+
+        let content = self.file_system.read(path)?;
+        let uri = Uri::from_str(&format!("file://{}", path)).ok()?;
+        let file_name = Path::new(path).file_name()?.to_str()?.to_string();
+
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_grug::LANGUAGE.into())
+            .unwrap();
+
+        Some(f(&Document::new(&mut parser, content, file_name, uri)?))
+    }
+
+    // Brings an evicted document back into memory
+    pub fn load_document(&mut self, path: &str, parser: &mut Parser) -> Option<&Document> {
+        if self.evicted_documents.contains(path) {
+            self.index_file(Path::new(path), parser);
+        }
+
+        self.document_map.get(path)
+    }
+
+    // Drops the closed documents that were loaded first, until the rest fits in the budget
+    pub fn evict_documents(&mut self) {
+        let mut cached: usize = self
+            .document_map
+            .iter()
+            .filter(|(path, _)| !self.open_documents.contains(*path))
+            .map(|(_, document)| document.content.len())
+            .sum();
+
+        // The last loaded document stays, whoever loaded it is about to use it
+        while cached > self.settings.document_cache_size && self.load_order.len() > 1 {
+            let Some(path) = self.load_order.pop_front() else {
+                break;
+            };
+            if self.open_documents.contains(&path) {
+                continue;
+            }
+            let Some(document) = self.document_map.remove(&path) else {
+                continue;
+            };
+
+            info!("Evicting {} from memory", path);
+            cached -= document.content.len();
+            self.evicted_documents.insert(path);
+        }
+    }
+
+    // Stores the document, and lets the file system see the editor's text while it's open
+    pub fn insert_document(&mut self, path: &str, document: Document) {
+        if self.open_documents.contains(path) {
+            self.file_system.write(path, &document.content);
+        } else {
+            self.file_system.remove(path);
+        }
+
+        self.evicted_documents.remove(path);
+        self.load_order.retain(|loaded| loaded != path);
+        self.load_order.push_back(path.to_string());
+        self.document_map.insert(path.to_string(), document);
+    }
+
+    // Everything kept per path moves along, so the editor's version and unparsed edits
+    // carry over. Open documents are checked again and their diagnostics move to the new URI
+    pub fn rename_document(&mut self, old_path: &str, new_path: &str, parser: &mut Parser) {
+        // Evicted documents only need to be read from their new path
+        if self.evicted_documents.remove(old_path) {
+            self.index_file(Path::new(new_path), parser);
+            return;
+        }
+
+        let document = self.document_map.remove(old_path);
+        let is_open = self.open_documents.remove(old_path);
+        if document.is_none() && !is_open {
+            return;
+        }
+        let (Ok(old_uri), Ok(new_uri)) = (
+            Uri::from_str(&format!("file://{}", old_path)),
+            Uri::from_str(&format!("file://{}", new_path)),
+        ) else {
+            return;
+        };
+
+        info!("Moving {} to {}", old_path, new_path);
+        self.file_system.remove(old_path);
+        self.load_order.retain(|loaded| loaded != old_path);
+        if is_open {
+            self.open_documents.insert(new_path.to_string());
+        }
+        if let Some(version) = self.document_versions.remove(old_path) {
+            self.document_versions.insert(new_path.to_string(), version);
+        }
+        if let Some(text) = self.unparsed_changes.remove(old_path) {
+            self.unparsed_changes.insert(new_path.to_string(), text);
+        }
+        self.diagnostics_due.remove(old_path);
+        // Results still on their way for the old path get dropped
+        let generation = self.generations.remove(old_path);
+
+        let file_name = new_path.split("/").last().unwrap().to_string();
+        if let Some(document) = document
+            .and_then(|document| Document::new(parser, document.content, file_name, new_uri))
+        {
+            self.insert_document(new_path, document);
+        }
+
+        if is_open {
+            self.generations
+                .insert(new_path.to_string(), generation.unwrap_or_default() + 1);
+            self.clear_diagnostics(old_uri);
+            self.queue_analysis(new_path);
+        }
+    }
+
+    // Without the native feature, only the files the host opens are known
+    #[cfg(feature = "native")]
+    pub fn index_workspace(&mut self) {
+        let Some(root_path) = &self.root_path else {
+            return;
+        };
+
+        let mut paths: Vec<PathBuf> = Vec::new();
+        collect_grug_files_except(
+            root_path,
+            &|path| self.is_excluded(path),
+            self.settings.respect_ignore_files,
+            &mut paths,
+        );
+
+        info!("Indexing {} grug files", paths.len());
+        map_in_parallel(paths, read_document, |path, document| {
+            let Some(path) = path.to_str() else {
+                return;
+            };
+            if let Some(document) = document
+                && !self.open_documents.contains(path)
+            {
+                self.insert_document(path, document);
+                self.evict_documents();
+            }
+        });
+    }
+
+    // Reads a grug file from disk into the index, unless the editor owns its contents
+    pub fn index_file(&mut self, path: &Path, parser: &mut Parser) {
+        let Some(path_str) = path.to_str() else {
+            return;
+        };
+        if self.open_documents.contains(path_str) {
+            return;
+        }
+
+        let Some(document) = read_document(parser, path) else {
+            return;
+        };
+        self.insert_document(path_str, document);
+        self.evict_documents();
+    }
+
+    // Open documents are the editor's, so a delete or rename on disk leaves them alone.
+    // They are dropped once they are closed, or moved when the editor renames them
+    pub fn remove_from_index(&mut self, path: &Path) -> Option<Document> {
+        let path = path.to_str()?;
+        if self.open_documents.contains(path) {
+            return None;
+        }
+
+        info!("Removing file from the index: {}", path);
+        self.evicted_documents.remove(path);
+        self.load_order.retain(|loaded| loaded != path);
+        self.file_system.remove(path);
+
+        self.document_map.remove(path)
+    }
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn evict_documents_test() {
+    use crate::server::{mod_api::ModApi, settings::Settings};
+
+    let root = std::env::temp_dir().join(format!("grug-ls-eviction-{}", std::process::id()));
+    let dir = root.join("mods").join("foo");
+    std::fs::create_dir_all(&dir).unwrap();
+    for name in ["a-box.grug", "b-box.grug", "c-box.grug"] {
+        std::fs::write(dir.join(name), "on_spawn() {\n}\n").unwrap();
+    }
+
+    let settings = Settings {
+        document_cache_size: 30,
+        ..Default::default()
+    };
+    let mut server = Server::new(
+        Some(root.clone()),
+        settings,
+        Default::default(),
+        ModApi::default(),
+        std::sync::mpsc::channel().1,
+        crossbeam_channel::unbounded().0,
+    );
+
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+    server.index_workspace();
+
+    // Each file is 15 bytes, so only two fit
+    assert_eq!(server.document_map.len(), 2);
+    assert_eq!(server.document_paths().len(), 3);
+    assert_eq!(server.get_workspace_entities().len(), 3);
+
+    let evicted = server.evicted_documents.iter().next().unwrap().clone();
+    assert!(server.with_document(&evicted, |document| document.on_functions.len()) == Some(1));
+    assert!(server.load_document(&evicted, &mut parser).is_some());
+    assert!(!server.evicted_documents.contains(&evicted));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn removed_on_disk_test() {
+    use std::sync::mpsc::channel;
+
+    use serde_json::json;
+
+    use crate::server::{helper::ServerUpdate, mod_api::ModApi, settings::Settings};
+
+    let (watcher, updates) = channel();
+    let mut server = Server::new(
+        Some("/grug-ls-fixture".into()),
+        Settings::default(),
+        Default::default(),
+        ModApi::default(),
+        updates,
+        crossbeam_channel::unbounded().0,
+    );
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_grug::LANGUAGE.into())
+        .unwrap();
+
+    let closed = "/grug-ls-fixture/mods/foo/tired-box.grug";
+    let document = Document::new(
+        &mut parser,
+        b"on_spawn() {\n}\n".to_vec(),
+        "tired-box.grug".to_string(),
+        Uri::from_str(&format!("file://{}", closed)).unwrap(),
+    )
+    .unwrap();
+    server.insert_document(closed, document);
+
+    let open = "/grug-ls-fixture/mods/foo/crate-box.grug";
+    let uri = format!("file://{}", open);
+    server.handle_notification(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": { "uri": uri, "languageId": "grug", "version": 1, "text": "on_spawn() {\n}\n" },
+        }),
+        &mut parser,
+    );
+
+    // The editor renames the open file, and the watcher sees it go before the editor says so
+    for path in [closed, open] {
+        watcher
+            .send(ServerUpdate::GrugFileRemove(path.into()))
+            .unwrap();
+    }
+    server.handle_notification(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "text": "on_spawn() {\n    print_i32(1)\n}\n" }],
+        }),
+        &mut parser,
+    );
+    assert!(!server.document_map.contains_key(closed));
+    assert!(!server.load_order.iter().any(|loaded| loaded == closed));
+    assert!(server.open_documents.contains(open));
+
+    let new_path = "/grug-ls-fixture/mods/foo/big-box.grug";
+    server.handle_notification(
+        "workspace/didRenameFiles",
+        json!({ "files": [{ "oldUri": uri, "newUri": format!("file://{}", new_path) }] }),
+        &mut parser,
+    );
+    assert!(server.open_documents.contains(new_path));
+    assert_eq!(server.document_versions.get(new_path), Some(&2));
+    let content = &server.document_map[new_path].content;
+    assert!(String::from_utf8_lossy(content).contains("print_i32(1)"));
+}
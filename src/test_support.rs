@@ -0,0 +1,290 @@
+// Drives a server in memory, for black-box tests of how it answers an editor.
+// Fixture files live under a made-up workspace root, nothing is read from disk
+use std::{collections::HashMap, path::Path, str::FromStr, time::Duration};
+
+use lsp_server::{Message, Notification, Response, ResponseError};
+use lsp_types::{
+    ClientCapabilities, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, Position, PublishDiagnosticsParams, TextDocumentContentChangeEvent,
+    TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams, Uri,
+    VersionedTextDocumentIdentifier,
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+        PublishDiagnostics,
+    },
+    request::Request,
+};
+use serde_json::Value;
+use tree_sitter::Parser;
+
+use crate::server::{Server, mod_api::ModApi, settings::Settings};
+
+pub const ROOT: &str = "/grug-ls-fixture";
+
+// Marks where the cursor is in a fixture, and is removed from the text the server sees
+pub const CURSOR: &str = "$0";
+
+// How long to wait for the analysis worker before giving up on diagnostics
+const ANALYSIS_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Splits the cursor marker out of a fixture. Columns are bytes, like the rest of the server
+pub fn parse_fixture(fixture: &str) -> (String, Option<Position>) {
+    let Some(offset) = fixture.find(CURSOR) else {
+        return (fixture.to_string(), None);
+    };
+    assert!(
+        !fixture[offset + CURSOR.len()..].contains(CURSOR),
+        "A fixture can only have one cursor"
+    );
+
+    let before = &fixture[..offset];
+    let line = before.matches('\n').count();
+    let character = before.len() - before.rfind('\n').map_or(0, |idx| idx + 1);
+    let text = format!("{}{}", before, &fixture[offset + CURSOR.len()..]);
+
+    (text, Some(Position::new(line as u32, character as u32)))
+}
+
+pub fn uri(path: &str) -> Uri {
+    Uri::from_str(&format!("file://{}/{}", ROOT, path)).unwrap()
+}
+
+pub struct TestClient {
+    server: Server,
+    parser: Parser,
+    messages: crossbeam_channel::Receiver<Message>,
+    // Notifications taken off the channel while waiting for something else
+    notifications: Vec<Notification>,
+    // Requests from the server, like workspace/applyEdit, that haven't been answered
+    requests: Vec<lsp_server::Request>,
+    versions: HashMap<String, i32>,
+    cursors: HashMap<String, Position>,
+}
+
+impl Default for TestClient {
+    fn default() -> TestClient {
+        TestClient::new()
+    }
+}
+
+impl TestClient {
+    pub fn new() -> TestClient {
+        // Diagnostics for edits are checked right away instead of after a pause in typing
+        TestClient::with_settings(Settings {
+            diagnostics_delay: 0,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_settings(settings: Settings) -> TestClient {
+        TestClient::with_capabilities(settings, ClientCapabilities::default())
+    }
+
+    pub fn with_capabilities(settings: Settings, capabilities: ClientCapabilities) -> TestClient {
+        let (client, messages) = crossbeam_channel::unbounded();
+        let server = Server::new(
+            Some(ROOT.into()),
+            settings,
+            capabilities,
+            ModApi::default(),
+            std::sync::mpsc::channel().1,
+            client,
+        );
+
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_grug::LANGUAGE.into())
+            .unwrap();
+
+        TestClient {
+            server,
+            parser,
+            messages,
+            notifications: Vec::new(),
+            requests: Vec::new(),
+            versions: HashMap::new(),
+            cursors: HashMap::new(),
+        }
+    }
+
+    pub fn server(&self) -> &Server {
+        &self.server
+    }
+
+    // Opening the mod API path, mod_api.json by default, loads it like the editor's buffer would
+    pub fn open(&mut self, path: &str, fixture: &str) -> Option<Position> {
+        let (text, cursor) = parse_fixture(fixture);
+        self.set_cursor(path, cursor);
+        self.versions.insert(path.to_string(), 1);
+
+        self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri(path), "grug".to_string(), 1, text),
+        });
+
+        cursor
+    }
+
+    // Replaces the whole text of an open document
+    pub fn change(&mut self, path: &str, fixture: &str) -> Option<Position> {
+        let (text, cursor) = parse_fixture(fixture);
+        self.set_cursor(path, cursor);
+        let version = self.versions.entry(path.to_string()).or_default();
+        *version += 1;
+        let version = *version;
+
+        self.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri(path), version),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text,
+            }],
+        });
+
+        cursor
+    }
+
+    pub fn close(&mut self, path: &str) {
+        self.versions.remove(path);
+        self.cursors.remove(path);
+
+        self.notify::<DidCloseTextDocument>(DidCloseTextDocumentParams {
+            text_document: TextDocumentIdentifier::new(uri(path)),
+        });
+    }
+
+    fn set_cursor(&mut self, path: &str, cursor: Option<Position>) {
+        match cursor {
+            Some(cursor) => self.cursors.insert(path.to_string(), cursor),
+            None => self.cursors.remove(path),
+        };
+    }
+
+    // Where the cursor marker was in the last text given for the document
+    pub fn cursor(&self, path: &str) -> TextDocumentPositionParams {
+        let Some(position) = self.cursors.get(path) else {
+            panic!("{} has no {} marker", path, CURSOR);
+        };
+
+        TextDocumentPositionParams::new(TextDocumentIdentifier::new(uri(path)), *position)
+    }
+
+    pub fn notify<N: lsp_types::notification::Notification>(&mut self, params: N::Params) {
+        let params = serde_json::to_value(params).unwrap();
+        self.server
+            .handle_notification(N::METHOD, params, &mut self.parser);
+    }
+
+    pub fn request<R: Request>(&mut self, params: R::Params) -> Result<R::Result, ResponseError> {
+        let params = serde_json::to_value(params).unwrap();
+        let result = self.request_json(R::METHOD, params)?;
+
+        Ok(serde_json::from_value(result).unwrap())
+    }
+
+    // For requests without lsp_types definitions, like the grug-ls/ ones
+    pub fn request_json(&mut self, method: &str, params: Value) -> Result<Value, ResponseError> {
+        self.server.handle_request(method, params, &mut self.parser)
+    }
+
+    // Like request, but fails the test when the server answers with an error
+    pub fn expect<R: Request>(&mut self, params: R::Params) -> R::Result {
+        match self.request::<R>(params) {
+            Ok(result) => result,
+            Err(err) => panic!("{} failed with {}: {}", R::METHOD, err.code, err.message),
+        }
+    }
+
+    // Every notification the server sent since the last call
+    pub fn notifications(&mut self) -> Vec<Notification> {
+        self.take_messages();
+        std::mem::take(&mut self.notifications)
+    }
+
+    // Every request the server sent since the last call
+    pub fn requests(&mut self) -> Vec<lsp_server::Request> {
+        self.take_messages();
+        std::mem::take(&mut self.requests)
+    }
+
+    // Answers a request from the server, like the editor would
+    pub fn respond<R: Request>(&mut self, request: &lsp_server::Request, result: R::Result) {
+        assert_eq!(request.method, R::METHOD);
+        let response = Response::new_ok(request.id.clone(), result);
+        self.server
+            .handle_message(Message::Response(response), &mut self.parser);
+    }
+
+    fn take_messages(&mut self) {
+        while let Ok(message) = self.messages.try_recv() {
+            match message {
+                Message::Notification(notification) => self.notifications.push(notification),
+                Message::Request(request) => self.requests.push(request),
+                Message::Response(_) => {}
+            }
+        }
+    }
+
+    // Waits for the analysis of the document's latest text, and returns what it found
+    pub fn diagnostics(&mut self, path: &str) -> Vec<lsp_types::Diagnostic> {
+        let uri = uri(path);
+        self.server.analyze_due_documents();
+        #[cfg(not(feature = "native"))]
+        self.server.run_analysis_jobs();
+
+        let results = self.server.analysis_results();
+        loop {
+            self.take_messages();
+            let published = self.notifications.iter().rposition(|notification| {
+                notification.method == PublishDiagnostics::METHOD
+                    && notification.params.get("uri").and_then(|uri| uri.as_str())
+                        == Some(uri.as_str())
+            });
+            if let Some(idx) = published {
+                let notification = self.notifications.remove(idx);
+                let params: PublishDiagnosticsParams =
+                    serde_json::from_value(notification.params).unwrap();
+                return params.diagnostics;
+            }
+
+            match results.recv_timeout(ANALYSIS_TIMEOUT) {
+                Ok(result) => self.server.handle_analysis_result(result),
+                Err(_) => panic!("{} never got diagnostics", Path::new(path).display()),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_client_test() {
+    use lsp_types::{
+        DocumentHighlightParams, PartialResultParams, WorkDoneProgressParams,
+        request::DocumentHighlightRequest,
+    };
+
+    assert_eq!(
+        parse_fixture("on_spawn() {\n    x$0: i32 = 1\n}\n"),
+        (
+            "on_spawn() {\n    x: i32 = 1\n}\n".to_string(),
+            Some(Position::new(1, 5))
+        )
+    );
+
+    let mut client = TestClient::new();
+    client.open(
+        "mods/foo/a-box.grug",
+        "on_spawn() {\n    $0x: i32 = 1\n    print_i32(x)\n}\n",
+    );
+
+    let highlights = client.expect::<DocumentHighlightRequest>(DocumentHighlightParams {
+        text_document_position_params: client.cursor("mods/foo/a-box.grug"),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    });
+    assert_eq!(highlights.map(|highlights| highlights.len()), Some(2));
+
+    client.change("mods/foo/a-box.grug", "on_spawn() {\n    x: i32 = \n}\n");
+    assert!(!client.diagnostics("mods/foo/a-box.grug").is_empty());
+
+    assert!(client.request_json("bogus/request", Value::Null).is_err());
+}